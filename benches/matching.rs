@@ -0,0 +1,88 @@
+// Cost of the comparison core: distances of every algorithm by name length, every process name against every
+// critical one as process counts and critical lists grow, and skeletons of look-alike characters. Whole scans,
+// rules included, are timed with the hidden `bonomen scan --bench PROCS`.
+
+#[macro_use]
+extern crate criterion;
+extern crate strsim;
+
+#[allow(dead_code)]
+#[path = "../src/similarity.rs"]
+mod similarity;
+#[allow(dead_code)]
+#[path = "../src/confusables.rs"]
+mod confusables;
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+
+// Names of about `length` characters from a fixed seed, the same on every run
+fn names(count: usize, length: usize, seed: u64) -> Vec<String> {
+    let alphabet  = b"abcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut state = seed;
+    let mut next  = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+
+    (0 .. count)
+        .map(|_| {
+            let length = length - length / 4 + next() % (length / 2 + 1);
+            (0 .. length).map(|_| alphabet[next() % alphabet.len()] as char).collect()
+        })
+        .collect()
+}
+
+fn distances(c: &mut Criterion) {
+    let mut group = c.benchmark_group("distance");
+    for algorithm_name in similarity::ALGORITHMS {
+        let algorithm = similarity::from_name(algorithm_name).unwrap();
+        for &length in &[8, 16, 32, 64] {
+            let pair = names(2, length, 0x5eed + length as u64);
+            group.bench_with_input(BenchmarkId::new(*algorithm_name, length), &pair, |b, pair| {
+                b.iter(|| algorithm.distance(black_box(&pair[0]), black_box(&pair[1])))
+            });
+        }
+    }
+    group.finish();
+}
+
+// Every process against every critical process, as scans do before any pruning
+fn matching(c: &mut Criterion) {
+    let algorithm = similarity::from_name("damerau-levenshtein").unwrap();
+    let mut group = c.benchmark_group("matching");
+    for &critical in &[10, 100] {
+        let crit_names = names(critical, 12, 0xc417);
+        for &processes in &[100, 1000, 5000] {
+            let proc_names = names(processes, 12, 0x9e0c);
+            group.throughput(Throughput::Elements(processes as u64));
+            group.bench_with_input(BenchmarkId::new(format!("{} critical", critical), processes), &proc_names,
+                                   |b, proc_names| {
+                b.iter(|| {
+                    proc_names.iter()
+                        .flat_map(|name| crit_names.iter().map(move |crit_name| (name, crit_name)))
+                        .filter(|&(name, crit_name)| algorithm.distance(name, crit_name) <= 2.0)
+                        .count()
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn skeletons(c: &mut Criterion) {
+    let mut group = c.benchmark_group("skeleton");
+    let samples = [("latin", "svchost.exe"), ("cyrillic", "svсhоst.exe"), ("invisible", "svc\u{200b}host.exe")];
+    for &(label, name) in &samples {
+        group.bench_with_input(BenchmarkId::from_parameter(label), name, |b, name| {
+            b.iter(|| confusables::skeleton(black_box(name)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, distances, matching, skeletons);
+criterion_main!(benches);
@@ -0,0 +1,226 @@
+// JSON API of the monitor mode with `--api PATH`, for local tools that trigger scans or follow detections
+// without running the CLI each time. A Unix socket, or a named pipe on Windows, taking one JSON request per line:
+//
+//   {"request": "scan"}                    scan every process, answered with the report
+//   {"request": "scan", "pids": [PID...]}  scan these processes only
+//   {"request": "status"}                  answered with what `bonomen status --json` prints
+//   {"request": "subscribe"}               receive every detection from then on, as the JSON Lines events
+//
+// Scans run on the monitor, in between its own scans.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[cfg(windows)]
+use std::ffi::OsStr;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+
+use serde_json::Value;
+
+#[cfg(windows)]
+use winapi::um::winbase::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX};
+
+use control::{self, Status};
+use jsonl;
+use report;
+use types::ScanReport;
+
+// A scan requested by a client, answered once the monitor ran it
+pub struct Request {
+    // Only these processes, every one when None
+    pub pids: Option<Vec<u32>>,
+    reply   : Sender<Value>,
+}
+
+impl Request {
+    pub fn answer(self, answer: Value) {
+        // The client may be gone already
+        let _ = self.reply.send(answer);
+    }
+}
+
+// What the threads of the clients share
+#[derive(Clone)]
+struct Clients {
+    requests   : Sender<Request>,
+    subscribers: Arc<Mutex<Vec<Sender<Value>>>>,
+    status     : Arc<Mutex<Status>>,
+}
+
+pub struct Api {
+    requests   : Receiver<Request>,
+    subscribers: Arc<Mutex<Vec<Sender<Value>>>>,
+    host       : String,
+}
+
+impl Api {
+    // Next scan requested within `wait`
+    pub fn next(&self, wait: Duration) -> Option<Request> {
+        match self.requests.recv_timeout(wait) {
+            Ok(request)                         => Some(request),
+            Err(RecvTimeoutError::Timeout)      => None,
+            // The listening thread holds a sender as long as it runs
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(wait);
+                None
+            },
+        }
+    }
+
+    // Send the detections of `report` to the subscribers, those gone are dropped
+    pub fn publish(&self, report: &ScanReport, finished: bool) {
+        let mut events: Vec<Value> = report.detections.iter()
+            .map(|detection| {
+                jsonl::line("detection", &self.host, json!({ "detection": report::comparison_json(detection) }))
+            })
+            .collect();
+        if finished {
+            events.push(jsonl::line("scan_finished", &self.host, json!({ "summary": report::summary_json(report) })));
+        }
+
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|subscriber| events.iter().all(|event| subscriber.send(event.clone()).is_ok()));
+        }
+    }
+}
+
+// Take requests on `path` from a background thread, a thread per client
+#[cfg(unix)]
+pub fn serve(path: &str, status: Arc<Mutex<Status>>) -> Result<Api, String> {
+    let listener       = control::listen(path)?;
+    let (api, clients) = channels(status);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|s| s.ok()) {
+            let clients = clients.clone();
+            match stream.try_clone() {
+                Ok(reader) => {
+                    thread::spawn(move || converse(reader, stream, &clients));
+                },
+                Err(why)   => debug!("API client dropped: {}", why),
+            }
+        }
+    });
+
+    Ok(api)
+}
+
+#[cfg(windows)]
+pub fn serve(path: &str, status: Arc<Mutex<Status>>) -> Result<Api, String> {
+    let name: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+    // Creating the first instance fails when another monitor owns the pipe
+    let mut pipe = control::create_pipe(&name, PIPE_ACCESS_DUPLEX | FILE_FLAG_FIRST_PIPE_INSTANCE)
+        .map_err(|why| format!("couldn't create {}: {}", path, why))? as usize;
+    let (api, clients) = channels(status);
+    let path = path.to_string();
+
+    thread::spawn(move || loop {
+        // Handles travel between threads as numbers
+        match control::accept(pipe as _).and_then(|file| file.try_clone().map(|reader| (reader, file))) {
+            Ok((reader, file)) => {
+                let clients = clients.clone();
+                thread::spawn(move || converse(reader, file, &clients));
+            },
+            Err(why)           => debug!("API client dropped: {}", why),
+        }
+        pipe = match control::create_pipe(&name, PIPE_ACCESS_DUPLEX) {
+            Ok(pipe) => pipe as usize,
+            Err(why) => {
+                warn!("API requests aren't answered anymore, couldn't create {}: {}", path, why);
+                return;
+            },
+        };
+    });
+
+    Ok(api)
+}
+
+fn channels(status: Arc<Mutex<Status>>) -> (Api, Clients) {
+    let (sender, receiver) = mpsc::channel();
+    let subscribers        = Arc::new(Mutex::new(Vec::new()));
+
+    (Api { requests: receiver, subscribers: subscribers.clone(), host: report::hostname() },
+     Clients { requests: sender, subscribers: subscribers, status: status })
+}
+
+// Answer the requests of a client until it disconnects or subscribes
+fn converse<R: Read, W: Write>(reader: R, mut writer: W, clients: &Clients) {
+    for line in BufReader::new(reader).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(why) => {
+                debug!("API client dropped: {}", why);
+                return;
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let answer = match ::serde_json::from_str::<Value>(&line) {
+            Ok(request) => match request["request"].as_str() {
+                Some("scan")      => scan(&request, clients),
+                Some("status")    => control::snapshot(&clients.status),
+                Some("subscribe") => {
+                    subscribe(&mut writer, clients);
+                    return;
+                },
+                _                 => json!({ "error": "unknown request, expected scan, status or subscribe" }),
+            },
+            Err(why)    => json!({ "error": format!("invalid JSON: {}", why) }),
+        };
+        if let Err(why) = send(&mut writer, &answer) {
+            debug!("API client dropped: {}", why);
+            return;
+        }
+    }
+}
+
+// Have the monitor scan the processes of the request, and wait for its report
+fn scan(request: &Value, clients: &Clients) -> Value {
+    let pids = match request.get("pids") {
+        None       => None,
+        Some(pids) => {
+            let pids: Option<Vec<u32>> = pids.as_array().and_then(|pids| {
+                pids.iter().map(|pid| pid.as_u64().filter(|&pid| pid <= u32::MAX as u64).map(|pid| pid as u32))
+                    .collect()
+            });
+            match pids {
+                Some(pids) => Some(pids),
+                None       => return json!({ "error": "pids must be an array of process IDs" }),
+            }
+        },
+    };
+
+    let (reply, answer) = mpsc::channel();
+    if clients.requests.send(Request { pids: pids, reply: reply }).is_err() {
+        return json!({ "error": "the monitor no longer takes requests" });
+    }
+    answer.recv().unwrap_or_else(|_| json!({ "error": "the monitor stopped before scanning" }))
+}
+
+// Stream the events published from now on, until the client disconnects
+fn subscribe<W: Write>(writer: &mut W, clients: &Clients) {
+    let (sender, events) = mpsc::channel();
+    if let Ok(mut subscribers) = clients.subscribers.lock() {
+        subscribers.push(sender);
+    }
+    if send(writer, &json!({ "subscribed": true })).is_err() {
+        return;
+    }
+
+    for event in events {
+        if let Err(why) = send(writer, &event) {
+            debug!("API subscriber dropped: {}", why);
+            return;
+        }
+    }
+}
+
+fn send<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
+    writeln!(writer, "{}", value).and_then(|_| writer.flush())
+}
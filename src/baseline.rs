@@ -0,0 +1,97 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+
+use chrono::Local;
+use serde_json::Value;
+
+use report::hostname;
+use types::BaselineEntry;
+
+// Difference between a baseline and the running processes
+pub enum Change<'a> {
+    // No process of that name was running when the baseline was saved
+    Appeared(&'a BaselineEntry),
+    // No process of that name is running anymore
+    Disappeared(&'a BaselineEntry),
+    // The process runs from an executable path not seen in the baseline
+    PathChanged(&'a BaselineEntry, Vec<&'a str>),
+    // Same executable path, but its content changed
+    HashChanged(&'a BaselineEntry),
+}
+
+// Write the running processes to a baseline file
+pub fn save(path: &str, procs: &[BaselineEntry]) -> io::Result<()> {
+    let doc = json!({
+        "timestamp": Local::now().to_rfc3339(),
+        "host":      hostname(),
+        "processes": procs.iter().map(|p| json!({
+            "pid":      p.pid,
+            "name":     p.name,
+            "exe_path": p.exe_path,
+            "sha256":   p.hash,
+        })).collect::<Vec<_>>(),
+    });
+
+    let mut file = File::create(path)?;
+    serde_json::to_writer_pretty(&mut file, &doc)?;
+    writeln!(file)
+}
+
+// Read the processes of a baseline file
+pub fn load(path: &str) -> io::Result<Vec<BaselineEntry>> {
+    let doc: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let procs = match doc.get("processes").and_then(|p| p.as_array()) {
+        Some(procs) => procs,
+        None        => return Err(io::Error::new(io::ErrorKind::InvalidData, "no processes in baseline")),
+    };
+
+    Ok(procs.iter().map(|p| BaselineEntry {
+        pid:      p.get("pid").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        name:     p.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        exe_path: p.get("exe_path").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        hash:     p.get("sha256").and_then(|v| v.as_str()).map(|h| h.to_string()),
+    }).collect())
+}
+
+// Compare running processes against a baseline, processes are matched by name
+pub fn diff<'a>(baseline: &'a [BaselineEntry], current: &'a [BaselineEntry]) -> Vec<Change<'a>> {
+    let mut known: HashMap<&str, Vec<&BaselineEntry>> = HashMap::new();
+    for p in baseline.iter() {
+        known.entry(p.name.as_str()).or_insert_with(Vec::new).push(p);
+    }
+    let running: HashSet<&str> = current.iter().map(|p| p.name.as_str()).collect();
+
+    let mut changes = Vec::new();
+    for p in current.iter() {
+        let seen = match known.get(p.name.as_str()) {
+            Some(seen) => seen,
+            None       => {
+                changes.push(Change::Appeared(p));
+                continue;
+            },
+        };
+
+        match seen.iter().find(|b| b.exe_path == p.exe_path) {
+            Some(b) => if b.hash.is_some() && p.hash.is_some() && b.hash != p.hash {
+                changes.push(Change::HashChanged(p));
+            },
+            None    => {
+                let mut paths: Vec<&str> = seen.iter().map(|b| b.exe_path.as_str()).collect();
+                paths.sort();
+                paths.dedup();
+                changes.push(Change::PathChanged(p, paths));
+            },
+        }
+    }
+
+    // Report each vanished name once
+    let mut gone = HashSet::new();
+    for p in baseline.iter() {
+        if !running.contains(p.name.as_str()) && gone.insert(p.name.as_str()) {
+            changes.push(Change::Disappeared(p));
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,125 @@
+// Synthetic processes of the hidden `--bench PROCS` mode, measuring what whole scans cost, the rules included, as
+// the number of processes, of critical processes and the length of names grow. Names come from a fixed seed,
+// every run checks the same processes.
+
+use types::{ProcProps, ProcessInfo, ScanReport, Severity, Threshold};
+use whitelist;
+
+// Where the synthetic critical processes are whitelisted, and where the other processes run from
+#[cfg(unix)]
+const SYSTEM_DIR: &'static str = "/usr/lib/bench/";
+#[cfg(unix)]
+const OTHER_DIR : &'static str = "/opt/bench/";
+#[cfg(windows)]
+const SYSTEM_DIR: &'static str = "C:\\Bench\\System\\";
+#[cfg(windows)]
+const OTHER_DIR : &'static str = "C:\\Bench\\Programs\\";
+
+// Average length of the names, when not given
+pub const NAME_LENGTH: usize = 12;
+
+const ALPHABET: &'static [u8] = b"abcdefghijklmnopqrstuvwxyz0123456789-_";
+
+// xorshift64*, enough to spread names out
+struct Generator(u64);
+
+impl Generator {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    fn letter(&mut self) -> char {
+        ALPHABET[self.below(ALPHABET.len())] as char
+    }
+
+    // Around `length` characters, a quarter more or less
+    fn name(&mut self, length: usize) -> String {
+        let spread = length / 4;
+        let length = (length - spread + self.below(2 * spread + 1)).max(1);
+
+        (0 .. length).map(|_| self.letter()).collect()
+    }
+
+    // `name` with one character substituted, inserted, deleted or swapped with the next one
+    fn misspell(&mut self, name: &str) -> String {
+        let mut chars: Vec<char> = name.chars().collect();
+        let at = self.below(chars.len());
+        match self.below(4) {
+            0                     => chars[at] = self.letter(),
+            1                     => chars.insert(at, self.letter()),
+            2 if chars.len() > 1  => { chars.remove(at); },
+            _ if at + 1 < chars.len() => chars.swap(at, at + 1),
+            _                     => chars.push(self.letter()),
+        }
+
+        chars.into_iter().collect()
+    }
+}
+
+// `count` critical processes named with about `length` characters, whitelisted in the system directory
+pub fn critical(count: usize, length: usize) -> Vec<ProcProps> {
+    let mut generator = Generator(0x5eed_0c1a_55e5_0001);
+    let whitelist     = whitelist::Entry::parse(SYSTEM_DIR).ok().into_iter().collect::<Vec<_>>();
+
+    (0 .. count)
+        .map(|_| ProcProps {
+            name:          generator.name(length),
+            threshold:     Threshold::Absolute(1),
+            whitelist:     whitelist.clone(),
+            in_containers: Vec::new(),
+            severity:      Severity::Medium,
+            instances:     None,
+            users:         Vec::new(),
+            threads:       None,
+            handles:       None,
+        })
+        .collect()
+}
+
+// `count` processes: one in ten misspells a critical process, one in twenty is named as one but runs from
+// elsewhere, the others have random names of about `length` characters
+pub fn processes(count: usize, crit_procs: &[ProcProps], length: usize) -> Vec<ProcessInfo> {
+    let mut generator = Generator(0x5eed_9e0c_e55e_0002);
+
+    (0 .. count)
+        .map(|index| {
+            let imitated = crit_procs.get(generator.below(crit_procs.len()));
+            let name     = match (generator.below(20), imitated) {
+                (0, Some(crit_proc))     => crit_proc.name.clone(),
+                (1, Some(crit_proc)) |
+                (2, Some(crit_proc))     => generator.misspell(&crit_proc.name),
+                _                        => generator.name(length),
+            };
+
+            ProcessInfo {
+                pid:         1000 + index as u32,
+                exe_path:    format!("{}{}", OTHER_DIR, name),
+                name:        name,
+                exe_problem: None,
+                exe_denied:  false,
+                ppid:        Some(1),
+                user:        None,
+                owner:       None,
+                threads:     None,
+                handles:     None,
+                cmdline:     None,
+                started:     None,
+            }
+        })
+        .collect()
+}
+
+pub fn print(report: &ScanReport) {
+    let seconds = report.elapsed.as_secs() as f64 + report.elapsed.subsec_nanos() as f64 / 1e9;
+    let rate    = if seconds > 0.0 { report.scanned as f64 / seconds } else { 0.0 };
+
+    println!("Benchmark: {} processes, {} name comparisons in {:.3}s, {:.0} processes/s, {} detections",
+             report.scanned, report.compared, seconds, rate, report.detections.len());
+}
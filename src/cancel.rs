@@ -0,0 +1,80 @@
+// Scans that stop short instead of hanging. Operations on a single process that may block, reading
+// /proc or hashing an executable on an unresponsive network mount, are given up after a timeout, and
+// Ctrl-C ends a scan with the processes checked so far.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// Exit code of interrupted scans, the one shells give processes ended by SIGINT
+pub const EXIT_CODE: i32 = 130;
+// Seconds an operation may take, unless `--op-timeout` says otherwise
+pub const DEFAULT_TIMEOUT: u64 = 30;
+
+static TIMEOUT_MS: AtomicU64   = AtomicU64::new(DEFAULT_TIMEOUT * 1000);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// A zero `timeout` lets operations take as long as they need
+pub fn set_timeout(timeout: Duration) {
+    TIMEOUT_MS.store(timeout.as_secs() * 1000 + u64::from(timeout.subsec_millis()), Ordering::SeqCst);
+}
+
+// Result of `operation`, None when it takes longer than the timeout. The thread running it is left
+// behind, blocked in the kernel where nothing can end it.
+pub fn with_timeout<T, F>(operation: F) -> Option<T>
+    where T: Send + 'static,
+          F: FnOnce() -> T + Send + 'static {
+    let timeout = TIMEOUT_MS.load(Ordering::SeqCst);
+    if timeout == 0 {
+        return Some(operation());
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(operation());
+    });
+
+    receiver.recv_timeout(Duration::from_millis(timeout)).ok()
+}
+
+// Whether Ctrl-C was pressed since `stop_on_interrupt`
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// Ctrl-C stops the scan at the next process, a second one exits right away
+#[cfg(unix)]
+pub fn stop_on_interrupt() {
+    extern "C" fn on_interrupt(_signal: ::libc::c_int) {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            unsafe { ::libc::_exit(EXIT_CODE) }
+        }
+    }
+
+    unsafe {
+        ::libc::signal(::libc::SIGINT, on_interrupt as extern "C" fn(::libc::c_int) as ::libc::sighandler_t);
+    }
+}
+
+#[cfg(windows)]
+pub fn stop_on_interrupt() {
+    use winapi::shared::minwindef::{BOOL, DWORD, FALSE, TRUE};
+    use winapi::um::consoleapi::SetConsoleCtrlHandler;
+    use winapi::um::wincon::CTRL_C_EVENT;
+
+    // Called on a thread of its own
+    unsafe extern "system" fn on_control(event: DWORD) -> BOOL {
+        if event != CTRL_C_EVENT {
+            return FALSE;
+        }
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            ::std::process::exit(EXIT_CODE);
+        }
+        TRUE
+    }
+
+    unsafe {
+        SetConsoleCtrlHandler(Some(on_control), TRUE);
+    }
+}
@@ -0,0 +1,867 @@
+use clap::{Arg, ArgGroup, App, AppSettings, Shell, SubCommand};
+
+use similarity;
+
+// Options shared by every mode running scans
+fn scan_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    let mut args = vec![
+        Arg::with_name("file")
+            .short("f")
+            .long("file")
+            .value_name("FILE")
+            .help("File containing critical processes path, threshold, whitelist, can be repeated to layer files")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        profile_arg(),
+        verbose_arg(),
+        Arg::with_name("report")
+            .long("report")
+            .value_name("PATH")
+            .help("Write a full scan report to PATH")
+            .takes_value(true),
+        Arg::with_name("report-format")
+            .long("report-format")
+            .value_name("FORMAT")
+            .help("Report format, deduced from the report file extension by default")
+            .possible_values(&["json", "csv", "sarif", "stix", "misp"])
+            .takes_value(true),
+        Arg::with_name("report-distance")
+            .long("report-distance")
+            .value_name("DISTANCE")
+            .help("Also include in the report every comparison with a distance up to DISTANCE")
+            .takes_value(true),
+        Arg::with_name("top")
+            .long("top")
+            .value_name("N")
+            .help("Number of closest critical processes shown per process with -vv, 0 for all, 3 by default")
+            .takes_value(true),
+        Arg::with_name("algorithm")
+            .short("a")
+            .long("algorithm")
+            .value_name("ALGORITHM")
+            .help("String similarity algorithm, overrides the critical processes file setting")
+            .possible_values(similarity::ALGORITHMS)
+            .takes_value(true),
+        Arg::with_name("threads")
+            .short("j")
+            .long("threads")
+            .value_name("N")
+            .help("Number of threads used to compare processes, defaults to the number of CPUs")
+            .takes_value(true),
+        Arg::with_name("op-timeout")
+            .long("op-timeout")
+            .value_name("SECONDS")
+            .help("Give up reading or hashing a single process after this many seconds, 0 waits forever, 30 by default")
+            .takes_value(true),
+        Arg::with_name("ignore")
+            .long("ignore")
+            .value_name("NAME")
+            .help("Leave running processes with this name, glob or re: regex out of the scan, can be repeated")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("connections")
+            .long("connections")
+            .help("List the network connections of suspicious processes"),
+        Arg::with_name("template")
+            .long("template")
+            .value_name("FORMAT")
+            .help("Print each detection as FORMAT, e.g. \"{pid} {name} {path} matched {crit} (d={distance})\", \
+                   placeholders: pid, name, path, crit, distance, threshold, reason, severity, score, module, owner, \
+                   container, started, age")
+            .takes_value(true),
+        Arg::with_name("env")
+            .long("env")
+            .value_name("NAME")
+            .help("Show these environment variables of suspicious processes, globs and re: regexes allowed, \
+                   LD_PRELOAD, LD_LIBRARY_PATH, LD_AUDIT, DYLD_INSERT_LIBRARIES, PATH, PROMPT and COMSPEC by default")
+            .min_values(0)
+            .multiple(true)
+            .use_delimiter(true)
+            .takes_value(true),
+        Arg::with_name("env-redact")
+            .long("env-redact")
+            .value_name("NAME")
+            .help("Redact the values of --env variables with these names, globs and re: regexes allowed, \
+                   *PASSWORD*, *PASSWD*, *SECRET*, *TOKEN*, *KEY* and *CREDENTIAL* by default")
+            .requires("env")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("modules")
+            .long("modules")
+            .help("Flag modules loaded from temporary or user directories into critical processes"),
+        Arg::with_name("suspend")
+            .long("suspend")
+            .help("Suspend suspicious processes instead of leaving them running, to preserve them for forensics"),
+        Arg::with_name("explain")
+            .long("explain")
+            .help("Tell why each detection is suspicious: rule, distance, threshold and whitelist entries consulted"),
+        Arg::with_name("path-hijack")
+            .long("path-hijack")
+            .help("Also flag commands in PATH shadowing or imitating critical processes"),
+        Arg::with_name("persistence")
+            .long("persistence")
+            .help("Also flag autostart entries (services, cron, Run keys, tasks) imitating critical processes"),
+        Arg::with_name("services")
+            .long("services")
+            .help("Also flag installed services (systemd units, Windows services) imitating critical processes"),
+        Arg::with_name("kernel-modules")
+            .long("kernel-modules")
+            .help("Also flag loaded kernel modules and drivers imitating the expected ones of kernel_module.NAME"),
+        Arg::with_name("min-severity")
+            .long("min-severity")
+            .value_name("LEVEL")
+            .help("Only report detections of at least this severity")
+            .possible_values(::types::SEVERITIES)
+            .takes_value(true),
+        Arg::with_name("alert-score")
+            .long("alert-score")
+            .value_name("SCORE")
+            .help("Only report processes whose detection rule weights add up to this score [default: 50]")
+            .takes_value(true),
+        Arg::with_name("match")
+            .long("match")
+            .value_name("NAME")
+            .help("Only check running processes with this name, glob or re: regex, can be repeated")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("pid-range")
+            .long("pid-range")
+            .value_name("MIN-MAX")
+            .help("Only check processes with a PID in this inclusive range, either bound may be omitted")
+            .allow_hyphen_values(true)
+            .takes_value(true),
+        Arg::with_name("webhook")
+            .long("webhook")
+            .value_name("URL")
+            .help("POST each detection as JSON to URL")
+            .takes_value(true),
+        Arg::with_name("webhook-header")
+            .long("webhook-header")
+            .value_name("HEADER")
+            .help("Extra \"Name: value\" header for webhook requests, e.g. for auth tokens, can be repeated")
+            .requires("webhook")
+            .multiple(true)
+            .number_of_values(1)
+            .takes_value(true),
+        Arg::with_name("webhook-batch")
+            .long("webhook-batch")
+            .help("Send all detections in a single webhook request at the end of the scan")
+            .requires("webhook"),
+        Arg::with_name("input-procs")
+            .long("input-procs")
+            .value_name("FILE")
+            .help("Check a captured process listing (ps -eo comm,pid,args output, CSV or JSON) instead of the live system")
+            .takes_value(true),
+        Arg::with_name("db")
+            .long("db")
+            .value_name("PATH")
+            .help("Record scans and their detections in the SQLite database PATH, for bonomen history")
+            .takes_value(true),
+        Arg::with_name("push")
+            .long("push")
+            .value_name("URL")
+            .help("Send the report of each scan to the bonomen server at URL, e.g. https://bonomen-server:8443")
+            .takes_value(true),
+        Arg::with_name("push-token")
+            .long("push-token")
+            .value_name("TOKEN")
+            .help("Bearer token the server was started with")
+            .requires("push")
+            .takes_value(true),
+        Arg::with_name("agent-id")
+            .long("agent-id")
+            .value_name("NAME")
+            .help("Name the server files the reports of this host under [default: the host name]")
+            .requires("push")
+            .takes_value(true),
+        Arg::with_name("push-ca")
+            .long("push-ca")
+            .value_name("PATH")
+            .help("PEM CA certificates the server certificate is issued by, instead of the public roots")
+            .requires("push")
+            .takes_value(true),
+        Arg::with_name("push-cert")
+            .long("push-cert")
+            .value_name("PATH")
+            .help("PEM client certificate to present to the server")
+            .requires_all(&["push", "push-key"])
+            .takes_value(true),
+        Arg::with_name("push-key")
+            .long("push-key")
+            .value_name("PATH")
+            .help("PEM private key of the client certificate")
+            .requires("push-cert")
+            .takes_value(true),
+        Arg::with_name("spool")
+            .long("spool")
+            .value_name("DIR")
+            .help("Keep the reports the server couldn't be reached for in DIR, and send them with the next one")
+            .requires("push")
+            .takes_value(true),
+    ];
+
+    if cfg!(unix) {
+        args.push(Arg::with_name("user")
+                  .short("u")
+                  .long("user")
+                  .value_name("USER")
+                  .help("Only check processes owned by this user name or UID, can be repeated")
+                  .multiple(true)
+                  .number_of_values(1)
+                  .takes_value(true));
+    }
+
+    if cfg!(target_os = "linux") {
+        args.push(Arg::with_name("trust-packages")
+                  .long("trust-packages")
+                  .help("Consider executables installed by dpkg or rpm whitelisted, as long as they match the package"));
+    }
+
+    args.push(Arg::with_name("vt-api-key")
+              .long("vt-api-key")
+              .value_name("KEY")
+              .help("Look up the SHA-256 of the executables of detections on VirusTotal with this API key")
+              .takes_value(true));
+    args.push(Arg::with_name("vt-rate")
+              .long("vt-rate")
+              .value_name("PER_MINUTE")
+              .help("VirusTotal requests per minute the API key allows [default: 4]")
+              .requires("vt-api-key")
+              .takes_value(true));
+    args.push(Arg::with_name("vt-cache")
+              .long("vt-cache")
+              .value_name("PATH")
+              .help("File caching VirusTotal answers for a day [default: bonomen/virustotal.json in the user cache]")
+              .requires("vt-api-key")
+              .takes_value(true));
+
+    if cfg!(feature = "yara") {
+        args.push(Arg::with_name("yara")
+                  .long("yara")
+                  .value_name("PATH")
+                  .help("Match the executables of detections against the YARA rules of a file or .yar directory")
+                  .takes_value(true));
+    }
+
+    if cfg!(windows) {
+        args.push(Arg::with_name("verify-signatures")
+                  .long("verify-signatures")
+                  .help("Flag critical-named processes whose executable isn't validly Authenticode signed"));
+        args.push(Arg::with_name("check-hollowing")
+                  .long("check-hollowing")
+                  .help("Flag critical processes whose image in memory doesn't match their executable on disk"));
+    }
+
+    args
+}
+
+// Continuous scans report a running process once, unless told to remind
+fn realert_after_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("realert-after")
+        .long("realert-after")
+        .value_name("DURATION")
+        .help("Alert again about a suspicious process still running after DURATION, e.g. 90s, 30m, 1h or 1d")
+        .takes_value(true)
+}
+
+// Output of single scans, a SARIF log replaces the text
+fn output_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("output")
+        .long("output")
+        .value_name("FORMAT")
+        .help("Output format, sarif prints a SARIF 2.1.0 log of the detections instead of text")
+        .possible_values(&["text", "sarif"])
+        .default_value("text")
+        .takes_value(true)
+}
+
+// Critical processes files whose whitelisted executables are watched
+fn fim_file_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("file")
+        .short("f")
+        .long("file")
+        .value_name("FILE")
+        .help("Critical processes files, layered in order, can be repeated")
+        .multiple(true)
+        .number_of_values(1)
+        .takes_value(true)
+}
+
+// -v, -vv and -vvv, the level of the log messages shown
+fn verbose_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("verbose")
+        .short("v")
+        .long("verbose")
+        .multiple(true)
+        .help("Verbose mode, repeat for more detail: -v info, -vv debug, -vvv trace")
+}
+
+// Which `[profile.NAME]` sections of the critical processes files apply
+fn profile_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("profile")
+        .long("profile")
+        .value_name("NAME")
+        .help("Also apply the [profile.NAME] sections of the critical processes files")
+        .takes_value(true)
+}
+
+// Where the monitor answers `bonomen status`
+fn control_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("control")
+        .long("control")
+        .value_name("PATH")
+        .help(if cfg!(windows) {
+            "Control named pipe of the monitor, \\\\.\\pipe\\bonomen by default"
+        } else {
+            "Control socket of the monitor, /run/bonomen.sock by default"
+        })
+        .takes_value(true)
+}
+
+// Checking processes as they start needs the kernel's process events, only built in on request
+fn exec_events_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    if cfg!(all(any(target_os = "linux", windows), feature = "exec-events")) {
+        vec![Arg::with_name("exec-events")
+             .long("exec-events")
+             .help("Also check every process the moment it starts, from the kernel's process events")]
+    } else {
+        Vec::new()
+    }
+}
+
+// Scans of synthetic processes, timing the detection rules without the cost of listing processes. Hidden, a
+// development tool.
+fn bench_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![Arg::with_name("bench")
+         .long("bench")
+         .value_name("PROCS")
+         .help("Scan PROCS synthetic processes instead of the running ones and print how long it took")
+         .conflicts_with("input-procs")
+         .hidden(true)
+         .takes_value(true),
+         Arg::with_name("bench-critical")
+         .long("bench-critical")
+         .value_name("COUNT")
+         .help("Compare against COUNT synthetic critical processes instead of the configured ones")
+         .requires("bench")
+         .hidden(true)
+         .takes_value(true),
+         Arg::with_name("bench-name-length")
+         .long("bench-name-length")
+         .value_name("CHARS")
+         .help("Average length of the synthetic names [default: 12]")
+         .requires("bench")
+         .hidden(true)
+         .takes_value(true)]
+}
+
+// The database of `bonomen history` and `bonomen trends`, and which of its detections they look at
+fn history_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![Arg::with_name("db")
+         .long("db")
+         .value_name("PATH")
+         .help("SQLite database scans recorded their detections in")
+         .required(true)
+         .takes_value(true),
+         Arg::with_name("since")
+         .long("since")
+         .value_name("TIME")
+         .help("Only detections from TIME on: a date, an RFC 3339 time, or a duration ago such as 7d")
+         .takes_value(true),
+         Arg::with_name("until")
+         .long("until")
+         .value_name("TIME")
+         .help("Only detections before TIME, given as for --since")
+         .takes_value(true),
+         Arg::with_name("host")
+         .long("host")
+         .value_name("HOST")
+         .help("Only detections on HOST")
+         .takes_value(true),
+         Arg::with_name("name")
+         .long("name")
+         .value_name("NAME")
+         .help("Only detections of processes with this name, glob or re: regex")
+         .takes_value(true),
+         Arg::with_name("rule")
+         .long("rule")
+         .value_name("REASON")
+         .help("Only detections for this reason, e.g. wrong-path or distance")
+         .takes_value(true)]
+}
+
+// Without a subcommand BONOMEN runs a single scan, as `bonomen scan` does
+pub fn build<'a, 'b>(banner: &'a str) -> App<'a, 'b> {
+    let app = App::new(banner)
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Detect critical process impersonation")
+        .args(&scan_args())
+        .args(&bench_args())
+        .arg(output_arg())
+        .arg(Arg::with_name("quiet")
+             .short("q")
+             .long("quiet")
+             .global(true)
+             .help("Only print detections, one tab separated line each, without banner or colors"))
+        .arg(Arg::with_name("no-color")
+             .long("no-color")
+             .global(true)
+             .help("Don't use colors, also disabled by the NO_COLOR environment variable or when stdout isn't a terminal"))
+        .arg(Arg::with_name("lang")
+             .long("lang")
+             .value_name("LANG")
+             .global(true)
+             .help("Language of the console output: en (default), de or a message catalog file, reports stay English")
+             .takes_value(true))
+        .arg(Arg::with_name("config-key")
+             .long("config-key")
+             .value_name("KEY")
+             .global(true)
+             .help("Hex encoded ed25519 public key, or a file holding it, every critical processes file must be \
+                    signed with (FILE.sig), BONOMEN_CONFIG_KEY by default")
+             .takes_value(true))
+        .arg(Arg::with_name("config-signature")
+             .long("config-signature")
+             .value_name("POLICY")
+             .global(true)
+             .help("What a critical processes file failing verification does: refuse (default) or warn and load it")
+             .possible_values(::configsig::POLICIES)
+             .takes_value(true))
+        .arg(Arg::with_name("self-sha256")
+             .long("self-sha256")
+             .value_name("SHA256")
+             .global(true)
+             .help("Refuse to run unless the bonomen binary has this SHA-256, BONOMEN_SELF_SHA256 by default")
+             .takes_value(true))
+        .arg(Arg::with_name("dump-defaults")
+             .long("dump-defaults")
+             .help("Print the embedded critical processes list and exit"))
+        .subcommand(SubCommand::with_name("scan")
+                    .about("Scan running processes once")
+                    .args(&scan_args())
+                    .args(&bench_args())
+                    .arg(output_arg()))
+        .subcommand(SubCommand::with_name("check")
+                    .about("Explain what every detection rule makes of specific processes")
+                    .args(&scan_args())
+                    .arg(Arg::with_name("pid")
+                         .short("p")
+                         .long("pid")
+                         .value_name("PID")
+                         .help("Process to check, can be repeated")
+                         .multiple(true)
+                         .number_of_values(1)
+                         .takes_value(true))
+                    .arg(Arg::with_name("name")
+                         .short("n")
+                         .long("name")
+                         .value_name("NAME")
+                         .help("Check every process with this name, glob or re: regex, can be repeated")
+                         .multiple(true)
+                         .number_of_values(1)
+                         .takes_value(true))
+                    .group(ArgGroup::with_name("process")
+                           .args(&["pid", "name"])
+                           .multiple(true)
+                           .required(true)))
+        .subcommand(SubCommand::with_name("remote")
+                    .about("Scan the processes of a remote host over SSH")
+                    .args(&scan_args())
+                    .arg(output_arg())
+                    .arg(Arg::with_name("host")
+                         .short("H")
+                         .long("host")
+                         .value_name("[USER@]HOST")
+                         .help("Host to collect the process list from, as given to ssh")
+                         .required(true)
+                         .takes_value(true))
+                    .arg(Arg::with_name("ssh-option")
+                         .short("o")
+                         .long("ssh-option")
+                         .value_name("OPTION")
+                         .help("Extra ssh option, e.g. Port=2222, can be repeated")
+                         .multiple(true)
+                         .number_of_values(1)
+                         .takes_value(true)))
+        .subcommand(SubCommand::with_name("monitor")
+                    .about("Scan running processes continuously")
+                    .args(&scan_args())
+                    .arg(Arg::with_name("interval")
+                         .short("i")
+                         .long("interval")
+                         .value_name("SECONDS")
+                         .help("Seconds between two scans")
+                         .default_value("60")
+                         .takes_value(true))
+                    .arg(Arg::with_name("schedule")
+                         .long("schedule")
+                         .value_name("CRON")
+                         .help("Scan on a cron expression instead of every --interval, e.g. \"*/15 * * * *\"")
+                         .takes_value(true))
+                    .arg(Arg::with_name("jitter")
+                         .long("jitter")
+                         .value_name("SECONDS")
+                         .help("Delay scheduled scans by up to SECONDS, the same on each host, to spread a fleet")
+                         .requires("schedule")
+                         .takes_value(true))
+                    .arg(Arg::with_name("metrics-addr")
+                         .long("metrics-addr")
+                         .value_name("ADDR")
+                         .help("Serve Prometheus metrics on ADDR, e.g. 127.0.0.1:9617")
+                         .takes_value(true))
+                    .arg(control_arg())
+                    .arg(Arg::with_name("api")
+                         .long("api")
+                         .value_name("PATH")
+                         .help(if cfg!(windows) {
+                             "Take JSON scan and subscribe requests on the named pipe PATH"
+                         } else {
+                             "Take JSON scan and subscribe requests on the Unix socket PATH"
+                         })
+                         .takes_value(true))
+                    .arg(realert_after_arg())
+                    .arg(Arg::with_name("output")
+                         .long("output")
+                         .value_name("FORMAT")
+                         .help("Output format, jsonl emits one JSON object per scan start, detection and scan end")
+                         .possible_values(&["text", "jsonl"])
+                         .default_value("text")
+                         .takes_value(true))
+                    .arg(Arg::with_name("output-file")
+                         .long("output-file")
+                         .value_name("PATH")
+                         .help("Append the JSON Lines events to PATH instead of stdout, text output stays on stdout")
+                         .takes_value(true))
+                    .args(&exec_events_args()))
+        .subcommand(SubCommand::with_name("status")
+                    .about("Show the state of the running monitor")
+                    .arg(control_arg())
+                    .arg(Arg::with_name("json")
+                         .long("json")
+                         .help("Print the status as JSON")))
+        .subcommand(SubCommand::with_name("tui")
+                    .about("Interactive dashboard of the running processes and detections")
+                    .args(&scan_args())
+                    .arg(Arg::with_name("interval")
+                         .short("i")
+                         .long("interval")
+                         .value_name("SECONDS")
+                         .help("Seconds between two scans")
+                         .default_value("5")
+                         .takes_value(true)))
+        .subcommand(SubCommand::with_name("whitelist")
+                    .about("Edit the whitelists of critical processes files")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("add")
+                                .about("Whitelist the executables of the detections of a JSON report")
+                                .arg(Arg::with_name("from-report")
+                                     .long("from-report")
+                                     .value_name("REPORT")
+                                     .help("JSON report of the reviewed detections, written with --report")
+                                     .required(true)
+                                     .takes_value(true))
+                                .arg(Arg::with_name("interactive")
+                                     .short("i")
+                                     .long("interactive")
+                                     .help("Ask before whitelisting each executable"))
+                                .arg(Arg::with_name("file")
+                                     .short("f")
+                                     .long("file")
+                                     .value_name("FILE")
+                                     .help("Critical processes files the scan used, can be repeated")
+                                     .multiple(true)
+                                     .number_of_values(1)
+                                     .takes_value(true))
+                                .arg(profile_arg())))
+        .subcommand(SubCommand::with_name("completions")
+                    .about("Print the completion script of a shell")
+                    .arg(Arg::with_name("SHELL")
+                         .help("Shell to complete for")
+                         .possible_values(&Shell::variants())
+                         .required(true)
+                         .index(1)))
+        .subcommand(SubCommand::with_name("history")
+                    .about("Query the detections recorded with --db, oldest first")
+                    .args(&history_args())
+                    .arg(Arg::with_name("json")
+                         .long("json")
+                         .help("Print the detections as JSON")))
+        .subcommand(SubCommand::with_name("trends")
+                    .about("Count the detections recorded with --db by reason, critical process and day or week")
+                    .args(&history_args())
+                    .arg(Arg::with_name("by")
+                         .long("by")
+                         .value_name("PERIOD")
+                         .help("Count the detections of every day or of every week")
+                         .possible_values(::trends::PERIODS)
+                         .default_value("day")
+                         .takes_value(true))
+                    .arg(Arg::with_name("json")
+                         .long("json")
+                         .help("Print the counts as JSON")))
+        .subcommand(SubCommand::with_name("server")
+                    .about("Collect the reports of remote agents over HTTPS and serve them as JSON")
+                    .arg(Arg::with_name("listen")
+                         .long("listen")
+                         .value_name("ADDRESS:PORT")
+                         .help("Address to accept reports on [default: 0.0.0.0:8443]")
+                         .takes_value(true))
+                    .arg(Arg::with_name("cert")
+                         .long("cert")
+                         .value_name("PATH")
+                         .help("PEM certificate chain of the server")
+                         .required(true)
+                         .takes_value(true))
+                    .arg(Arg::with_name("key")
+                         .long("key")
+                         .value_name("PATH")
+                         .help("PEM private key of the server certificate")
+                         .required(true)
+                         .takes_value(true))
+                    .arg(Arg::with_name("client-ca")
+                         .long("client-ca")
+                         .value_name("PATH")
+                         .help("PEM CA certificates agents' client certificates must be issued by")
+                         .takes_value(true))
+                    .arg(Arg::with_name("token")
+                         .long("token")
+                         .value_name("TOKEN")
+                         .help("Only answer requests sent with this bearer token, as reports are with --push-token")
+                         .takes_value(true))
+                    .arg(verbose_arg())
+                    .arg(Arg::with_name("db")
+                         .long("db")
+                         .value_name("PATH")
+                         .help("SQLite database to record the reports in, created when missing")
+                         .required(true)
+                         .takes_value(true)))
+        .subcommand(SubCommand::with_name("resume")
+                    .about("Resume processes suspended by --suspend")
+                    .arg(Arg::with_name("pid")
+                         .value_name("PID")
+                         .help("Process to resume")
+                         .required(true)
+                         .multiple(true)))
+        .subcommand(SubCommand::with_name("config")
+                    .about("Manage critical processes files")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("validate")
+                                .about("Check a critical processes file without scanning")
+                                .arg(Arg::with_name("FILE")
+                                     .help("Critical processes files, layered in order, the default one when absent")
+                                     .multiple(true)
+                                     .index(1)))
+                    .subcommand(SubCommand::with_name("test")
+                                .about("Show what a proposed configuration flags in a captured process listing, \
+                                        next to the current one")
+                                .arg(Arg::with_name("FILE")
+                                     .help("Proposed critical processes files, layered in order")
+                                     .required(true)
+                                     .multiple(true)
+                                     .index(1))
+                                .arg(Arg::with_name("against")
+                                     .long("against")
+                                     .value_name("LISTING")
+                                     .help("Captured process listing, as for --input-procs")
+                                     .required(true)
+                                     .takes_value(true))
+                                .arg(Arg::with_name("current")
+                                     .long("current")
+                                     .value_name("FILE")
+                                     .help("Current critical processes files, those scans load by default when absent")
+                                     .multiple(true)
+                                     .number_of_values(1)
+                                     .takes_value(true))
+                                .arg(profile_arg()))
+                    .subcommand(SubCommand::with_name("sign")
+                                .about("Write the detached signature FILE.sig of critical processes files")
+                                .arg(Arg::with_name("FILE")
+                                     .help("Critical processes files to sign, included ones are signed separately")
+                                     .required(true)
+                                     .multiple(true)
+                                     .index(1))
+                                .arg(Arg::with_name("private-key")
+                                     .short("k")
+                                     .long("private-key")
+                                     .value_name("KEY")
+                                     .help("Hex encoded ed25519 private key, or a file holding it")
+                                     .required(true)
+                                     .takes_value(true))))
+        .subcommand(SubCommand::with_name("baseline")
+                    .about("Record the set of running processes")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("save")
+                                .about("Save the running processes, their paths and hashes to a baseline file")
+                                .alias("create")
+                                .arg(Arg::with_name("output")
+                                     .short("o")
+                                     .long("output")
+                                     .value_name("PATH")
+                                     .help("Baseline file to write")
+                                     .default_value("baseline.json")
+                                     .takes_value(true)))
+                    .subcommand(SubCommand::with_name("diff")
+                                .about("Report processes that appeared, disappeared or changed since the baseline")
+                                .arg(Arg::with_name("baseline")
+                                     .short("b")
+                                     .long("baseline")
+                                     .value_name("PATH")
+                                     .help("Baseline file to compare against")
+                                     .default_value("baseline.json")
+                                     .takes_value(true))))
+        .subcommand(SubCommand::with_name("fim")
+                    .about("Watch the executables critical processes are whitelisted to run from for changes")
+                    .setting(AppSettings::SubcommandRequiredElseHelp)
+                    .subcommand(SubCommand::with_name("init")
+                                .about("Record the hashes and modification times of the whitelisted executables")
+                                .arg(fim_file_arg())
+                                .arg(profile_arg())
+                                .arg(Arg::with_name("output")
+                                     .short("o")
+                                     .long("output")
+                                     .value_name("PATH")
+                                     .help("FIM baseline file to write")
+                                     .default_value("fim.json")
+                                     .takes_value(true)))
+                    .subcommand(SubCommand::with_name("check")
+                                .about("Report whitelisted executables modified, gone or added since the FIM baseline")
+                                .arg(fim_file_arg())
+                                .arg(profile_arg())
+                                .arg(Arg::with_name("baseline")
+                                     .short("b")
+                                     .long("baseline")
+                                     .value_name("PATH")
+                                     .help("FIM baseline file to compare against")
+                                     .default_value("fim.json")
+                                     .takes_value(true))))
+        .subcommand(SubCommand::with_name("update")
+                    .about("Download, verify and install signed critical processes definitions")
+                    .arg(Arg::with_name("url")
+                         .long("url")
+                         .value_name("URL")
+                         .help("Critical processes file to download")
+                         .required(true)
+                         .takes_value(true))
+                    .arg(Arg::with_name("signature-url")
+                         .long("signature-url")
+                         .value_name("URL")
+                         .help("Hex encoded ed25519 signature of the file, URL.sig by default")
+                         .takes_value(true))
+                    .arg(Arg::with_name("public-key")
+                         .short("k")
+                         .long("public-key")
+                         .value_name("KEY")
+                         .help("Hex encoded ed25519 public key, or a file holding it")
+                         .required(true)
+                         .takes_value(true))
+                    .arg(Arg::with_name("output")
+                         .short("o")
+                         .long("output")
+                         .value_name("PATH")
+                         .help("Where to install the file, the system-wide critical processes file by default")
+                         .takes_value(true)))
+        .subcommand(SubCommand::with_name("doctor")
+                    .about("Check that this system supports a full scan")
+                    .arg(Arg::with_name("file")
+                         .short("f")
+                         .long("file")
+                         .value_name("FILE")
+                         .help("Critical processes file to validate, can be repeated")
+                         .multiple(true)
+                         .number_of_values(1)
+                         .takes_value(true))
+                    .arg(profile_arg())
+                    .arg(Arg::with_name("report")
+                         .long("report")
+                         .value_name("PATH")
+                         .help("Report file that must be writable")
+                         .takes_value(true)));
+
+    if cfg!(unix) {
+        app.subcommand(SubCommand::with_name("install-service")
+                       .about("Write a systemd unit running the monitor mode")
+                       .arg(Arg::with_name("file")
+                            .short("f")
+                            .long("file")
+                            .value_name("FILE")
+                            .help("Critical processes files used by the service, can be repeated")
+                            .multiple(true)
+                            .number_of_values(1)
+                            .takes_value(true))
+                       .arg(profile_arg())
+                       .arg(Arg::with_name("interval")
+                            .short("i")
+                            .long("interval")
+                            .value_name("SECONDS")
+                            .help("Seconds between two scans")
+                            .default_value("60")
+                            .takes_value(true))
+                       .arg(Arg::with_name("metrics-addr")
+                            .long("metrics-addr")
+                            .value_name("ADDR")
+                            .help("Serve Prometheus metrics on ADDR")
+                            .takes_value(true))
+                       .arg(realert_after_arg())
+                       .args(&exec_events_args())
+                       .arg(Arg::with_name("watchdog")
+                            .long("watchdog")
+                            .value_name("SECONDS")
+                            .help("Restart the service when it stops responding for this long")
+                            .default_value("300")
+                            .takes_value(true))
+                       .arg(Arg::with_name("output")
+                            .short("o")
+                            .long("output")
+                            .value_name("PATH")
+                            .help("Unit file to write")
+                            .default_value("/etc/systemd/system/bonomen.service")
+                            .takes_value(true)))
+    } else if cfg!(windows) {
+        app.subcommand(SubCommand::with_name("service")
+                       .about("Manage the BONOMEN Windows service, detections are written to the event log")
+                       .setting(AppSettings::SubcommandRequiredElseHelp)
+                       .subcommand(SubCommand::with_name("install")
+                                   .about("Register the service, started at boot")
+                                   .arg(Arg::with_name("file")
+                                        .short("f")
+                                        .long("file")
+                                        .value_name("FILE")
+                                        .help("Critical processes files used by the service, can be repeated")
+                                        .multiple(true)
+                                        .number_of_values(1)
+                                        .takes_value(true))
+                                   .arg(profile_arg())
+                                   .arg(Arg::with_name("interval")
+                                        .short("i")
+                                        .long("interval")
+                                        .value_name("SECONDS")
+                                        .help("Seconds between two scans")
+                                        .default_value("60")
+                                        .takes_value(true))
+                                   .arg(realert_after_arg()))
+                       .subcommand(SubCommand::with_name("uninstall")
+                                   .about("Remove the service"))
+                       .subcommand(SubCommand::with_name("start")
+                                   .about("Start the installed service"))
+                       .subcommand(SubCommand::with_name("stop")
+                                   .about("Stop the running service"))
+                       .subcommand(SubCommand::with_name("run")
+                                   .about("Entry point used by the service control manager")
+                                   .setting(AppSettings::Hidden)
+                                   .args(&scan_args())
+                                   .arg(Arg::with_name("interval")
+                                        .short("i")
+                                        .long("interval")
+                                        .value_name("SECONDS")
+                                        .default_value("60")
+                                        .takes_value(true))
+                                   .arg(realert_after_arg())))
+    } else {
+        app
+    }
+}
@@ -0,0 +1,535 @@
+// Running processes of this system, enumerated the way each platform allows.
+// Processes that vanish or can't be read while enumerating are skipped.
+
+use std::collections::HashMap;
+#[cfg(windows)]
+use std::collections::HashSet;
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::io::ErrorKind;
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(windows)]
+use std::io;
+#[cfg(windows)]
+use std::mem::{size_of, zeroed};
+#[cfg(windows)]
+use std::ptr;
+#[cfg(windows)]
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local, TimeZone};
+
+#[cfg(unix)]
+use psutil::process::Process;
+#[cfg(unix)]
+use rayon::prelude::*;
+
+#[cfg(windows)]
+use winapi::shared::minwindef::{DWORD, FALSE, FILETIME, MAX_PATH};
+#[cfg(windows)]
+use winapi::shared::winerror::ERROR_INSUFFICIENT_BUFFER;
+#[cfg(windows)]
+use winapi::um::fileapi::QueryDosDeviceW;
+#[cfg(windows)]
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+#[cfg(windows)]
+use winapi::um::processthreadsapi::{GetProcessHandleCount, GetProcessTimes, OpenProcess, OpenProcessToken};
+#[cfg(windows)]
+use winapi::um::psapi::K32EnumProcesses;
+#[cfg(windows)]
+use winapi::um::securitybaseapi::GetTokenInformation;
+#[cfg(windows)]
+use winapi::um::sysinfoapi::GetTickCount64;
+#[cfg(windows)]
+use winapi::um::tlhelp32::{CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W,
+                           TH32CS_SNAPPROCESS};
+#[cfg(windows)]
+use winapi::um::winbase::{LookupAccountSidW, QueryFullProcessImageNameW};
+#[cfg(windows)]
+use winapi::um::winnt::{TokenUser, HANDLE, PROCESS_QUERY_LIMITED_INFORMATION, SID_NAME_USE, TOKEN_QUERY, TOKEN_USER};
+
+#[cfg(unix)]
+use cancel;
+use error::ScanError;
+use types::ProcessInfo;
+
+pub trait Collector {
+    // Hand every running process to `visit` as soon as it's read, returns the number of skipped ones.
+    // Only the processes being read are held meanwhile, callers keep what they need of each.
+    fn each(&self, visit: &mut dyn FnMut(ProcessInfo)) -> Result<usize, ScanError>;
+
+    // Every running process, along with the number of skipped ones
+    fn all(&self) -> Result<(Vec<ProcessInfo>, usize), ScanError> {
+        let mut procs = Vec::new();
+        let skipped   = self.each(&mut |process| procs.push(process))?;
+
+        Ok((procs, skipped))
+    }
+
+    // The processes `pids`, those already gone are skipped
+    fn pids(&self, pids: &[u32]) -> (Vec<ProcessInfo>, usize);
+}
+
+// Collector of the platform bonomen runs on
+#[cfg(unix)]
+pub fn local() -> Box<dyn Collector> {
+    Box::new(UnixCollector)
+}
+
+#[cfg(windows)]
+pub fn local() -> Box<dyn Collector> {
+    Box::new(WindowsCollector)
+}
+
+// Processes captured elsewhere, from listings, remote hosts or test fixtures, served as if they were running
+pub struct Listed {
+    procs: Vec<ProcessInfo>,
+}
+
+pub fn listed(procs: Vec<ProcessInfo>) -> Box<dyn Collector> {
+    Box::new(Listed { procs: procs })
+}
+
+impl Collector for Listed {
+    fn each(&self, visit: &mut dyn FnMut(ProcessInfo)) -> Result<usize, ScanError> {
+        for process in &self.procs {
+            visit(process.clone());
+        }
+
+        Ok(0)
+    }
+
+    fn pids(&self, pids: &[u32]) -> (Vec<ProcessInfo>, usize) {
+        let procs: Vec<ProcessInfo> = self.procs.iter()
+            .filter(|process| pids.contains(&process.pid))
+            .cloned()
+            .collect();
+        let skipped = pids.iter().filter(|pid| !procs.iter().any(|process| process.pid == **pid)).count();
+
+        (procs, skipped)
+    }
+}
+
+// Boot time of this system
+#[cfg(unix)]
+pub fn boot_time() -> Option<DateTime<Local>> {
+    let stat  = fs::read_to_string("/proc/stat").ok()?;
+    let btime = stat.lines().find(|line| line.starts_with("btime "))?["btime ".len() ..].trim().parse::<i64>().ok()?;
+
+    Local.timestamp_opt(btime, 0).single()
+}
+
+#[cfg(windows)]
+pub fn boot_time() -> Option<DateTime<Local>> {
+    let uptime = unsafe { GetTickCount64() };
+
+    Some(Local::now() - ::chrono::Duration::milliseconds(uptime as i64))
+}
+
+#[cfg(unix)]
+struct UnixCollector;
+
+// Processes read in parallel at a time, before they are handed over
+#[cfg(unix)]
+const CHUNK: usize = 256;
+
+#[cfg(unix)]
+impl Collector for UnixCollector {
+    fn each(&self, visit: &mut dyn FnMut(ProcessInfo)) -> Result<usize, ScanError> {
+        let proc_dir = match fs::read_dir("/proc") {
+            Ok(dir)  => dir,
+            Err(why) => return Err(ScanError::Enum(format!("couldn't read /proc: {}", why.to_string()))),
+        };
+        let pids: Vec<u32> = proc_dir.filter_map(|e| e.ok())
+            .filter_map(|entry| entry.file_name().to_string_lossy().parse::<u32>().ok())
+            .collect();
+
+        Ok(unix_processes(&pids, visit))
+    }
+
+    fn pids(&self, pids: &[u32]) -> (Vec<ProcessInfo>, usize) {
+        let mut procs = Vec::new();
+        let skipped   = unix_processes(pids, &mut |process| procs.push(process));
+
+        (procs, skipped)
+    }
+}
+
+// Read the processes `pids` a chunk at a time and hand each one to `visit`, returns the number skipped
+#[cfg(unix)]
+fn unix_processes(pids: &[u32], visit: &mut dyn FnMut(ProcessInfo)) -> usize {
+    // Start times are relative to the boot
+    let booted = boot_time();
+    // getpwuid isn't thread safe, and most processes share a few users
+    let mut owners: HashMap<u32, String> = HashMap::new();
+    let mut skipped = 0;
+
+    for chunk in pids.chunks(CHUNK) {
+        // Executable lookups are the expensive part, read them in parallel. Processes are skipped once
+        // the scan is interrupted, or when /proc doesn't answer in time.
+        let procs: Vec<Option<ProcessInfo>> = chunk.par_iter()
+            .map(|&pid| {
+                if cancel::interrupted() {
+                    return None;
+                }
+                let process = cancel::with_timeout(move || {
+                    Process::new(pid as i32).ok().map(|process| unix_process(&process, booted))
+                });
+                process.unwrap_or_else(|| {
+                    warn!("Reading process {} timed out, it was skipped", pid);
+                    None
+                })
+            })
+            .collect();
+
+        for process in procs {
+            let mut process = match process {
+                Some(process) => process,
+                None          => {
+                    skipped += 1;
+                    continue;
+                },
+            };
+            if let Some(uid) = process.user {
+                process.owner = Some(owners.entry(uid).or_insert_with(|| ::fileinfo::user_name(uid)).clone());
+            }
+            visit(process);
+        }
+    }
+
+    skipped
+}
+
+#[cfg(unix)]
+fn unix_process(process: &Process, booted: Option<DateTime<Local>>) -> ProcessInfo {
+    // Kernel threads and zombies have neither a command line nor an executable
+    let cmdline = process.cmdline_vec().ok().and_then(|cmdline| cmdline);
+    let mut exe_denied = false;
+    let (exe_path, exe_problem) = match process.exe() {
+        Ok(path) => {
+            let path = path.to_string_lossy().into_owned();
+            if path.ends_with(::DELETED_SUFFIX) {
+                // Name checks still apply to where the binary used to be
+                (path.trim_end_matches(::DELETED_SUFFIX).to_string(), Some("deleted-exe"))
+            } else {
+                (path, None)
+            }
+        },
+        Err(why) => {
+            debug!("Couldn't read the executable of {} ({}): {}", process.comm, process.pid, why);
+            // Access errors say nothing about the executable
+            let missing = why.kind() == ErrorKind::NotFound;
+            exe_denied  = why.kind() == ErrorKind::PermissionDenied && cmdline.is_some();
+            (String::new(), if missing && cmdline.is_some() { Some("unresolved-exe") } else { None })
+        },
+    };
+
+    // Seconds after the boot
+    let started = booted.map(|booted| booted + ::chrono::Duration::milliseconds((process.starttime * 1000.0) as i64));
+
+    ProcessInfo {
+        pid:         process.pid as u32,
+        name:        process.comm.clone(),
+        exe_path:    exe_path,
+        exe_problem: exe_problem,
+        exe_denied:  exe_denied,
+        ppid:        Some(process.ppid as u32),
+        // /proc/<pid> belongs to the effective user of the process
+        user:        fs::metadata(Path::new("/proc").join(process.pid.to_string())).map(|meta| meta.uid()).ok(),
+        // Named once collected
+        owner:       None,
+        threads:     None,
+        handles:     None,
+        cmdline:     cmdline,
+        started:     started,
+    }
+}
+
+#[cfg(windows)]
+struct WindowsCollector;
+
+#[cfg(windows)]
+impl Collector for WindowsCollector {
+    fn each(&self, visit: &mut dyn FnMut(ProcessInfo)) -> Result<usize, ScanError> {
+        let mut skipped = 0;
+
+        // Protected processes and elevated services can't be opened for reading, the snapshot
+        // still has their names so they aren't left out of the scan
+        let snapshot = toolhelp_procs();
+
+        // A full buffer may have left PIDs out, retry with a larger one
+        let mut pids: Vec<DWORD> = vec![0; 1024];
+        let mut written = 0;
+        loop {
+            let size = (pids.len() * size_of::<DWORD>()) as u32;
+            if unsafe { K32EnumProcesses(pids.as_mut_ptr(), size, &mut written) } == 0 {
+                if snapshot.is_empty() {
+                    return Err(ScanError::Enum(String::from("K32EnumProcesses and CreateToolhelp32Snapshot failed!")));
+                }
+                warn!("K32EnumProcesses failed, using the Toolhelp32 snapshot");
+                written = 0;
+                break;
+            }
+            if written < size {
+                break;
+            }
+            pids = vec![0; pids.len() * 2];
+        }
+        let processes = &pids[..(written / size_of::<DWORD>() as u32) as usize]; // Slice trick thanks to WindowsBunny @ #rust
+
+        for &process_id in processes {
+            match windows_process(process_id, snapshot.get(&process_id)) {
+                Some(process) => visit(process),
+                None          => skipped += 1,
+            }
+        }
+
+        // Processes started after K32EnumProcesses
+        let listed: HashSet<DWORD> = processes.iter().cloned().collect();
+        for (_, entry) in snapshot.iter().filter(|&(pid, _)| !listed.contains(pid)) {
+            if let Some(process) = windows_process(entry.0, Some(entry)) {
+                visit(process);
+            }
+        }
+
+        Ok(skipped)
+    }
+
+    fn pids(&self, pids: &[u32]) -> (Vec<ProcessInfo>, usize) {
+        let mut win_procs = Vec::new();
+        let mut skipped   = 0;
+        // Parents and thread counts
+        let snapshot = toolhelp_procs();
+        for &pid in pids {
+            match windows_process(pid, snapshot.get(&pid)) {
+                Some(process) => win_procs.push(process),
+                None          => skipped += 1,
+            }
+        }
+
+        (win_procs, skipped)
+    }
+}
+
+// A process read through a single handle, closed before returning. Processes that can't be opened are
+// named after `entry`, their entry in the Toolhelp32 snapshot, and skipped without one. Command lines of
+// other processes aren't read on Windows.
+#[cfg(windows)]
+fn windows_process(pid: u32, entry: Option<&ToolhelpEntry>) -> Option<ProcessInfo> {
+    let h_process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid) };
+    let (name, exe_path) = match (image_path(h_process), entry) {
+        (Some(exe_path), _)                => (exe_name(&exe_path), exe_path),
+        (None, Some(&(_, _, ref name, _))) => {
+            debug!("PID: {} can't be opened, using the Toolhelp32 snapshot", pid);
+            (name.clone(), String::new())
+        },
+        (None, None)                       => {
+            debug!("PID: {} can't be opened", pid);
+            if !h_process.is_null() {
+                unsafe { CloseHandle(h_process) };
+            }
+            return None;
+        },
+    };
+
+    let process = ProcessInfo {
+        pid:         pid,
+        name:        name,
+        exe_path:    exe_path,
+        exe_problem: None,
+        exe_denied:  false,
+        ppid:        entry.map(|&(_, ppid, _, _)| ppid),
+        user:        None,
+        owner:       owner(h_process),
+        threads:     entry.map(|&(_, _, _, threads)| threads),
+        handles:     handle_count(h_process),
+        cmdline:     None,
+        started:     creation_time(h_process),
+    };
+    if !h_process.is_null() {
+        unsafe { CloseHandle(h_process) };
+    }
+
+    Some(process)
+}
+
+// File name of an executable path, as the Toolhelp32 snapshot names processes. Snapshot names are cut
+// at MAX_PATH, those of the path aren't.
+#[cfg(windows)]
+fn exe_name(exe_path: &str) -> String {
+    exe_path.rsplit('\\').next().unwrap_or("").to_string()
+}
+
+// PID, parent PID, executable name and thread count of a process
+#[cfg(windows)]
+type ToolhelpEntry = (DWORD, DWORD, String, DWORD);
+
+// Every running process, as a Toolhelp32 snapshot lists it, by PID
+#[cfg(windows)]
+fn toolhelp_procs() -> HashMap<DWORD, ToolhelpEntry> {
+    let mut procs = HashMap::new();
+
+    unsafe {
+        let h_snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0);
+        if h_snapshot == INVALID_HANDLE_VALUE {
+            warn!("CreateToolhelp32Snapshot failed!");
+            return procs;
+        }
+
+        let mut entry: PROCESSENTRY32W = zeroed();
+        entry.dwSize = size_of::<PROCESSENTRY32W>() as DWORD;
+        let mut more = Process32FirstW(h_snapshot, &mut entry) != 0;
+        while more {
+            procs.insert(entry.th32ProcessID, (entry.th32ProcessID, entry.th32ParentProcessID,
+                                               ::from_wide(&entry.szExeFile), entry.cntThreads));
+            more = Process32NextW(h_snapshot, &mut entry) != 0;
+        }
+        CloseHandle(h_snapshot);
+    }
+
+    procs
+}
+
+// Account a process runs as, `DOMAIN\\name` of the user of its token
+#[cfg(windows)]
+fn owner(h_process: HANDLE) -> Option<String> {
+    if h_process.is_null() {
+        return None;
+    }
+
+    unsafe {
+        let mut h_token: HANDLE = ptr::null_mut();
+        if OpenProcessToken(h_process, TOKEN_QUERY, &mut h_token) == 0 {
+            return None;
+        }
+
+        // TOKEN_USER is followed by the SID it points to, ask for the size first
+        let mut size = 0;
+        GetTokenInformation(h_token, TokenUser, ptr::null_mut(), 0, &mut size);
+        let mut token_user = vec![0u64; (size as usize + 7) / 8];
+        let read = size > 0 && GetTokenInformation(h_token, TokenUser, token_user.as_mut_ptr() as *mut _, size,
+                                                   &mut size) != 0;
+        CloseHandle(h_token);
+        if !read {
+            return None;
+        }
+        let sid = (*(token_user.as_ptr() as *const TOKEN_USER)).User.Sid;
+
+        let mut name   = [0u16; 256];
+        let mut domain = [0u16; 256];
+        let mut name_size   = name.len() as DWORD;
+        let mut domain_size = domain.len() as DWORD;
+        let mut kind: SID_NAME_USE = 0;
+        if LookupAccountSidW(ptr::null(), sid, name.as_mut_ptr(), &mut name_size, domain.as_mut_ptr(), &mut domain_size,
+                             &mut kind) == 0 {
+            return None;
+        }
+
+        Some(format!("{}\\{}", ::from_wide(&domain), ::from_wide(&name)))
+    }
+}
+
+// Number of handles a process has open, with the same limited rights as its executable path
+#[cfg(windows)]
+fn handle_count(h_process: HANDLE) -> Option<u32> {
+    let mut count = 0;
+
+    if !h_process.is_null() && unsafe { GetProcessHandleCount(h_process, &mut count) } != 0 {
+        Some(count)
+    } else {
+        None
+    }
+}
+
+// Creation time of a process, with the same limited rights as its executable path
+#[cfg(windows)]
+fn creation_time(h_process: HANDLE) -> Option<DateTime<Local>> {
+    let mut created: FILETIME = unsafe { zeroed() };
+    let mut exited : FILETIME = unsafe { zeroed() };
+    let mut kernel : FILETIME = unsafe { zeroed() };
+    let mut user   : FILETIME = unsafe { zeroed() };
+
+    if h_process.is_null() ||
+       unsafe { GetProcessTimes(h_process, &mut created, &mut exited, &mut kernel, &mut user) } == 0 {
+        return None;
+    }
+
+    // 100 nanosecond intervals since 1601
+    let ticks = (created.dwHighDateTime as u64) << 32 | created.dwLowDateTime as u64;
+    Local.timestamp_opt((ticks / 10_000_000) as i64 - 11_644_473_600, (ticks % 10_000_000) as u32 * 100).single()
+}
+
+// Full executable path of a process, in the Win32 format. Limited rights are enough, which protected
+// processes allow, and unlike the module list it's available for 32-bit processes running under WoW64
+#[cfg(windows)]
+fn image_path(h_process: HANDLE) -> Option<String> {
+    if h_process.is_null() {
+        return None;
+    }
+
+    unsafe {
+        // Long paths may exceed MAX_PATH
+        let exe_path = ::read_wide(|sz_process_path| {
+            let mut size = sz_process_path.len() as DWORD;
+            if QueryFullProcessImageNameW(h_process, 0, sz_process_path.as_mut_ptr(), &mut size) != 0 {
+                Some(size as usize)
+            } else if io::Error::last_os_error().raw_os_error() == Some(ERROR_INSUFFICIENT_BUFFER as i32) {
+                Some(sz_process_path.len())
+            } else {
+                None
+            }
+        });
+
+        exe_path.map(|exe_path| dos_path(&exe_path))
+    }
+}
+
+// Drive letters along with the NT device each stands for, e.g. "C:" and "\Device\HarddiskVolume3"
+#[cfg(windows)]
+static DOS_DEVICES: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+// Drive letter form of an NT device path, which some processes report their executable with, so that
+// whitelists written with drive letters match them. Paths on devices without a drive letter are kept.
+#[cfg(windows)]
+fn dos_path(path: &str) -> String {
+    if path.starts_with("\\??\\") {
+        return path["\\??\\".len() ..].to_string();
+    }
+    if !path.starts_with("\\Device\\") {
+        return path.to_string();
+    }
+
+    for &(ref drive, ref device) in DOS_DEVICES.get_or_init(dos_devices) {
+        let on_device = path.get(.. device.len()).map_or(false, |prefix| prefix.eq_ignore_ascii_case(device));
+        if on_device && path[device.len() ..].starts_with('\\') {
+            return format!("{}{}", drive, &path[device.len() ..]);
+        }
+    }
+
+    path.to_string()
+}
+
+#[cfg(windows)]
+fn dos_devices() -> Vec<(String, String)> {
+    let mut devices = Vec::new();
+    for letter in b'A' ..= b'Z' {
+        let drive = format!("{}:", letter as char);
+        let name: Vec<u16> = drive.encode_utf16().chain(Some(0)).collect();
+        let mut target = vec![0u16; MAX_PATH];
+        // The first of the null separated targets is the current one
+        let len = unsafe { QueryDosDeviceW(name.as_ptr(), target.as_mut_ptr(), target.len() as DWORD) };
+        if len != 0 {
+            devices.push((drive, ::from_wide(&target[.. len as usize])));
+        }
+    }
+    debug!("Drive letters: {}", devices.iter()
+        .map(|&(ref drive, ref device)| format!("{} {}", drive, device))
+        .collect::<Vec<String>>()
+        .join(", "));
+
+    devices
+}
@@ -0,0 +1,100 @@
+// Detached ed25519 signatures of the critical processes files, checked as they're read with `--config-key`, so that
+// whoever can edit the whitelist can't quietly exempt their own process. `FILE.sig` holds the hex encoded signature
+// of FILE, as written by `bonomen config sign`.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+
+use error::ScanError;
+use update::{decode_hex, key_bytes, parse_key};
+
+// Public key used when `--config-key` isn't given
+pub const KEY_VARIABLE: &'static str = "BONOMEN_CONFIG_KEY";
+// What a file failing verification does: stop with an error, or load with a warning
+pub const POLICIES: &'static [&'static str] = &["refuse", "warn"];
+
+struct Check {
+    key   : VerifyingKey,
+    refuse: bool,
+}
+
+static CHECK: OnceLock<Check> = OnceLock::new();
+
+// Verify the files read from now on against `key`, else the key of the environment, when there is one
+pub fn init(key: Option<&str>, policy: Option<&str>) -> Result<(), ScanError> {
+    let key = match key.map(String::from).or_else(|| env::var(KEY_VARIABLE).ok()) {
+        Some(key) => parse_key(&key)?,
+        None      => return Ok(()),
+    };
+    let _ = CHECK.set(Check {
+        key:    key,
+        refuse: policy != Some("warn"),
+    });
+
+    Ok(())
+}
+
+// `FILE.sig` next to `FILE`
+pub fn signature_path(path: &Path) -> PathBuf {
+    let mut signature = path.as_os_str().to_owned();
+    signature.push(".sig");
+
+    PathBuf::from(signature)
+}
+
+// Check `content`, read from `path`, against its signature when a key was given
+pub fn verify(path: &Path, content: &[u8]) -> Result<(), ScanError> {
+    let check = match CHECK.get() {
+        Some(check) => check,
+        None        => return Ok(()),
+    };
+
+    match check_signature(&check.key, &signature_path(path), content) {
+        Ok(())                   => {
+            debug!("Signature of {} verified", path.display());
+            Ok(())
+        },
+        Err(why) if check.refuse => Err(ScanError::Config(format!("{}: {}", path.display(), why))),
+        Err(why)                 => {
+            // Shown even with --quiet
+            error!("{} IS NOT VERIFIED, loaded anyway: {}", path.display(), why);
+            Ok(())
+        },
+    }
+}
+
+// Key files are verified against, when one was given
+pub fn key() -> Option<&'static VerifyingKey> {
+    CHECK.get().map(|check| &check.key)
+}
+
+// Check `content` against the signature in `sig_path`
+pub fn check_signature(key: &VerifyingKey, sig_path: &Path, content: &[u8]) -> Result<(), String> {
+    let text = fs::read_to_string(sig_path)
+        .map_err(|why| format!("couldn't read its signature {}: {}", sig_path.display(), why))?;
+    let bytes = decode_hex(&text, 64)
+        .ok_or_else(|| format!("{} isn't a hex encoded ed25519 signature", sig_path.display()))?;
+    let mut sig_bytes = [0; 64];
+    sig_bytes.copy_from_slice(&bytes);
+
+    key.verify(content, &Signature::from_bytes(&sig_bytes))
+        .map_err(|_| String::from("bad signature, the file was modified or signed with another key"))
+}
+
+// Sign `path` with `private_key`, hex encoded or a file holding it. Returns where the signature was written, and
+// the public key to verify it with.
+pub fn sign(path: &Path, private_key: &str) -> Result<(PathBuf, String), ScanError> {
+    let key      = SigningKey::from_bytes(&key_bytes(private_key, "private key")?);
+    let content  = fs::read(path)
+        .map_err(|why| ScanError::Config(format!("couldn't read {}: {}", path.display(), why)))?;
+    let sig_path = signature_path(path);
+
+    fs::write(&sig_path, format!("{}\n", hex::encode(key.sign(&content).to_bytes())))
+        .map_err(|why| ScanError::Config(format!("couldn't write {}: {}", sig_path.display(), why)))?;
+
+    Ok((sig_path, hex::encode(key.verifying_key().to_bytes())))
+}
@@ -0,0 +1,82 @@
+// Characters that render (almost) identically to a Latin letter, mapped to
+// the Latin letter they impersonate. Mostly Cyrillic and Greek look-alikes,
+// see https://www.unicode.org/Public/security/latest/confusables.txt
+const CONFUSABLES: &'static [(char, char)] = &[
+    // Cyrillic lowercase
+    ('а', 'a'), ('в', 'b'), ('е', 'e'), ('ѐ', 'e'), ('ё', 'e'), ('һ', 'h'),
+    ('і', 'i'), ('ї', 'i'), ('ј', 'j'), ('к', 'k'), ('ӏ', 'l'), ('м', 'm'),
+    ('о', 'o'), ('р', 'p'), ('ԛ', 'q'), ('ѕ', 's'), ('у', 'y'), ('ѵ', 'v'),
+    ('ԝ', 'w'), ('х', 'x'), ('ԁ', 'd'), ('с', 'c'),
+    // Cyrillic uppercase
+    ('А', 'A'), ('В', 'B'), ('С', 'C'), ('Е', 'E'), ('Н', 'H'), ('І', 'I'),
+    ('Ј', 'J'), ('К', 'K'), ('М', 'M'), ('О', 'O'), ('Р', 'P'), ('Ѕ', 'S'),
+    ('Т', 'T'), ('Х', 'X'), ('У', 'Y'),
+    // Greek
+    ('α', 'a'), ('β', 'b'), ('ε', 'e'), ('ι', 'i'), ('κ', 'k'), ('ν', 'v'),
+    ('ο', 'o'), ('ρ', 'p'), ('υ', 'u'), ('χ', 'x'), ('ω', 'w'),
+    ('Α', 'A'), ('Β', 'B'), ('Ε', 'E'), ('Ζ', 'Z'), ('Η', 'H'), ('Ι', 'I'),
+    ('Κ', 'K'), ('Μ', 'M'), ('Ν', 'N'), ('Ο', 'O'), ('Ρ', 'P'), ('Τ', 'T'),
+    ('Υ', 'Y'), ('Χ', 'X'),
+    // Latin extensions and letterlike symbols
+    ('ı', 'i'), ('ȷ', 'j'), ('ℓ', 'l'), ('ɑ', 'a'), ('ɡ', 'g'), ('ǀ', 'l'), ('ߋ', 'o'),
+];
+
+// Precomposed Latin letters with diacritics, mapped to their base letter. Names in composed (NFC) and
+// decomposed (NFD) form compare alike once the combining marks of the latter are dropped.
+const DIACRITICS: &'static [(&'static str, char)] = &[
+    ("àáâãäåāăą", 'a'), ("çćĉċč", 'c'), ("ďđ", 'd'), ("èéêëēĕėęě", 'e'), ("ĝğġģ", 'g'), ("ĥħ", 'h'),
+    ("ìíîïĩīĭį", 'i'), ("ĵ", 'j'), ("ķ", 'k'), ("ĺļľŀł", 'l'), ("ñńņňŉ", 'n'), ("òóôõöøōŏő", 'o'),
+    ("ŕŗř", 'r'), ("śŝşš", 's'), ("ţťŧ", 't'), ("ùúûüũūŭůűų", 'u'), ("ŵ", 'w'), ("ýÿŷ", 'y'), ("źżž", 'z'),
+    ("ÀÁÂÃÄÅĀĂĄ", 'A'), ("ÇĆĈĊČ", 'C'), ("ĎĐ", 'D'), ("ÈÉÊËĒĔĖĘĚ", 'E'), ("ĜĞĠĢ", 'G'), ("ĤĦ", 'H'),
+    ("ÌÍÎÏĨĪĬĮİ", 'I'), ("Ĵ", 'J'), ("Ķ", 'K'), ("ĹĻĽĿŁ", 'L'), ("ÑŃŅŇ", 'N'), ("ÒÓÔÕÖØŌŎŐ", 'O'),
+    ("ŔŖŘ", 'R'), ("ŚŜŞŠ", 'S'), ("ŢŤŦ", 'T'), ("ÙÚÛÜŨŪŬŮŰŲ", 'U'), ("Ŵ", 'W'), ("ÝŸŶ", 'Y'), ("ŹŻŽ", 'Z'),
+];
+
+fn latin_lookalike(c: char) -> Option<char> {
+    match c {
+        // Full-width forms of ASCII, and the ideographic space
+        '\u{ff01}' ..= '\u{ff5e}' => ::std::char::from_u32(c as u32 - 0xfee0),
+        '\u{3000}'               => Some(' '),
+        _                        => CONFUSABLES.iter().find(|&&(from, _)| from == c).map(|&(_, to)| to)
+            .or_else(|| DIACRITICS.iter().find(|&&(from, _)| from.contains(c)).map(|&(_, to)| to)),
+    }
+}
+
+// Combining marks, diacritics of decomposed letters
+fn is_combining(c: char) -> bool {
+    match c {
+        '\u{0300}' ..= '\u{036f}' | '\u{1ab0}' ..= '\u{1aff}' | '\u{1dc0}' ..= '\u{1dff}' |
+        '\u{20d0}' ..= '\u{20ff}' | '\u{fe20}' ..= '\u{fe2f}' => true,
+        _                                                     => false,
+    }
+}
+
+// Characters that render as nothing: zero-width and formatting characters, bidirectional controls
+// reordering the text around them, fillers, and control characters
+pub fn is_invisible(c: char) -> bool {
+    match c {
+        '\u{00ad}' | '\u{034f}' | '\u{061c}' | '\u{115f}' | '\u{1160}' | '\u{17b4}' | '\u{17b5}' | '\u{180e}' |
+        '\u{200b}' ..= '\u{200f}' | '\u{202a}' ..= '\u{202e}' | '\u{2060}' ..= '\u{2064}' |
+        '\u{2066}' ..= '\u{206f}' | '\u{3164}' | '\u{fe00}' ..= '\u{fe0f}' | '\u{feff}' | '\u{ffa0}' => true,
+        _                                                                                            => c.is_control(),
+    }
+}
+
+// Replace every confusable character with the Latin letter it imitates, full-width and accented letters
+// included, and leave invisible characters and combining marks out.
+pub fn skeleton(name: &str) -> String {
+    name.chars()
+        .filter(|&c| !is_invisible(c) && !is_combining(c))
+        .map(|c| latin_lookalike(c).unwrap_or(c))
+        .collect()
+}
+
+// True if the name contains at least one confusable character or combining mark.
+pub fn has_confusables(name: &str) -> bool {
+    name.chars().any(|c| latin_lookalike(c).is_some() || is_combining(c))
+}
+
+// True if the name contains characters that render as nothing
+pub fn has_invisible(name: &str) -> bool {
+    name.chars().any(is_invisible)
+}
@@ -0,0 +1,90 @@
+// Containers processes run in, told apart by the cgroup the runtime puts them in:
+//   /system.slice/docker-<id>.scope
+//   /kubepods.slice/kubepods-burstable.slice/kubepods-burstable-pod<uid>.slice/cri-containerd-<id>.scope
+//   /kubepods/besteffort/pod<uid>/<id>
+
+use std::fs;
+
+use types::Container;
+
+// Container of the process `pid`, None for processes of the host
+pub fn of(pid: u32) -> Option<Container> {
+    let cgroup = fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    // `hierarchy:controllers:path`, cgroup v1 lists one line per hierarchy
+    let (path, (id, runtime)) = cgroup.lines()
+        .filter_map(|line| line.splitn(3, ':').nth(2))
+        .filter_map(|path| container_id(path).map(|container| (path, container)))
+        .next()?;
+    let pod_uid = pod_uid(path);
+
+    // The pod's own view of its name and namespace, through the container's root
+    let (pod, namespace) = if pod_uid.is_some() {
+        (read_trimmed(&format!("/proc/{}/root/etc/hostname", pid)),
+         read_trimmed(&format!("/proc/{}/root/var/run/secrets/kubernetes.io/serviceaccount/namespace", pid)))
+    } else {
+        (None, None)
+    };
+
+    Some(Container {
+        id:        id,
+        runtime:   runtime,
+        pod_uid:   pod_uid,
+        pod:       pod,
+        namespace: namespace,
+    })
+}
+
+// The innermost component of `path` naming a container, with the runtime that created it
+fn container_id(path: &str) -> Option<(String, &'static str)> {
+    const RUNTIMES: [(&str, &str); 5] = [
+        ("docker-",         "docker"),
+        ("cri-containerd-", "containerd"),
+        ("crio-",           "cri-o"),
+        ("libpod-",         "podman"),
+        ("",                ""),
+    ];
+    let components: Vec<&str> = path.split('/').collect();
+
+    for (i, component) in components.iter().enumerate().rev() {
+        let component = component.trim_end_matches(".scope");
+        for &(prefix, runtime) in RUNTIMES.iter() {
+            if !component.starts_with(prefix) || !is_container_id(&component[prefix.len()..]) {
+                continue;
+            }
+            // cgroupfs paths name the runtime in the parent component instead
+            let runtime = match (runtime, i.checked_sub(1).map(|parent| components[parent])) {
+                ("", Some("docker"))                 => "docker",
+                ("", Some("libpod_parent"))          => "podman",
+                ("", _) if path.contains("kubepods") => "kubernetes",
+                ("", _)                              => "container",
+                (runtime, _)                         => runtime,
+            };
+            return Some((component[prefix.len()..].to_string(), runtime));
+        }
+    }
+
+    None
+}
+
+// Runtimes name containers by 64 hexadecimal digits
+fn is_container_id(id: &str) -> bool {
+    id.len() == 64 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+// UID of the Kubernetes pod, `pod<uid>` with the cgroupfs driver and
+// `kubepods-<qos>-pod<uid>.slice` with dashes turned to underscores with the systemd driver
+fn pod_uid(path: &str) -> Option<String> {
+    path.split('/')
+        .filter(|component| component.starts_with("pod") || component.starts_with("kubepods-"))
+        .filter_map(|component| {
+            let component = component.trim_end_matches(".slice");
+            component.rfind("pod").map(|pos| component[pos + 3..].replace('_', "-"))
+        })
+        .find(|uid| uid.len() == 36)
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path).ok()
+        .map(|content| content.trim().to_string())
+        .filter(|content| !content.is_empty())
+}
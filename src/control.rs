@@ -0,0 +1,247 @@
+// Control socket of the monitor mode, answering `bonomen status` with the state of the running monitor.
+// A Unix socket, or a named pipe on Windows, only reachable from this machine.
+
+use std::io::{self, Read, Write};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(windows)]
+use std::ffi::OsStr;
+#[cfg(windows)]
+use std::fs::File;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+#[cfg(windows)]
+use std::os::windows::io::{FromRawHandle, RawHandle};
+#[cfg(windows)]
+use std::ptr;
+
+use chrono::{DateTime, Local};
+use serde_json::Value;
+
+#[cfg(windows)]
+use winapi::shared::winerror::ERROR_PIPE_CONNECTED;
+#[cfg(windows)]
+use winapi::um::fileapi::FlushFileBuffers;
+#[cfg(windows)]
+use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+#[cfg(windows)]
+use winapi::um::namedpipeapi::{ConnectNamedPipe, CreateNamedPipeW, DisconnectNamedPipe};
+#[cfg(windows)]
+use winapi::um::winbase::{FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_OUTBOUND, PIPE_REJECT_REMOTE_CLIENTS,
+                          PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT};
+#[cfg(windows)]
+use winapi::um::winnt::HANDLE;
+
+use types::ScanReport;
+
+#[cfg(unix)]
+pub const DEFAULT_PATH: &'static str = "/run/bonomen.sock";
+#[cfg(windows)]
+pub const DEFAULT_PATH: &'static str = r"\\.\pipe\bonomen";
+
+pub struct Status {
+    started   : DateTime<Local>,
+    // Critical processes files in use
+    config    : String,
+    scans     : u64,
+    last_scan : Option<DateTime<Local>>,
+    next_scan : Option<DateTime<Local>>,
+    // Detections of the last scan, new and already alerted on
+    detections: usize,
+    repeated  : usize,
+    // New detections since start
+    alerts    : u64,
+}
+
+impl Status {
+    pub fn new(config: String) -> Status {
+        Status {
+            started:    Local::now(),
+            config:     config,
+            scans:      0,
+            last_scan:  None,
+            next_scan:  None,
+            detections: 0,
+            repeated:   0,
+            alerts:     0,
+        }
+    }
+
+    pub fn set_config(&mut self, config: String) {
+        self.config = config;
+    }
+
+    pub fn record(&mut self, report: &ScanReport, next_scan: DateTime<Local>) {
+        self.scans     += 1;
+        self.last_scan  = Some(Local::now());
+        self.next_scan  = Some(next_scan);
+        self.detections = report.detections.len();
+        self.repeated   = report.repeated.len();
+        self.alerts    += report.detections.len() as u64;
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "pid":        process::id(),
+            "started":    self.started.to_rfc3339(),
+            "uptime":     (Local::now() - self.started).num_seconds(),
+            "config":     self.config,
+            "scans":      self.scans,
+            "last_scan":  self.last_scan.map(|time| time.to_rfc3339()),
+            "next_scan":  self.next_scan.map(|time| time.to_rfc3339()),
+            "detections": self.detections,
+            "repeated":   self.repeated,
+            "alerts":     self.alerts,
+        })
+    }
+}
+
+// Answer every connection with the current status, one line of JSON, from a background thread
+#[cfg(unix)]
+pub fn serve(path: &str, status: Arc<Mutex<Status>>) -> Result<(), String> {
+    let listener = listen(path)?;
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().filter_map(|s| s.ok()) {
+            if let Err(why) = stream.write_all(&status_line(&status)) {
+                debug!("status request failed: {}", why);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+// Socket at `path` only the user running the monitor can connect to
+#[cfg(unix)]
+pub fn listen(path: &str) -> Result<UnixListener, String> {
+    // A socket left behind by a monitor that didn't exit cleanly
+    if Path::new(path).exists() {
+        if UnixStream::connect(path).is_ok() {
+            return Err(format!("another monitor is listening on {}", path));
+        }
+        fs::remove_file(path).map_err(|why| format!("couldn't remove the stale socket {}: {}", path, why))?;
+    }
+    // Created for the owner alone rather than restricted once others could have connected. The umask is the
+    // process's, the monitor binds its sockets before starting any thread that creates files.
+    let umask    = unsafe { ::libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    unsafe { ::libc::umask(umask) };
+    let listener = listener.map_err(|why| format!("couldn't listen on {}: {}", path, why))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .map_err(|why| format!("couldn't restrict access to {}: {}", path, why))?;
+
+    Ok(listener)
+}
+
+#[cfg(windows)]
+pub fn serve(path: &str, status: Arc<Mutex<Status>>) -> Result<(), String> {
+    let name: Vec<u16> = OsStr::new(path).encode_wide().chain(Some(0)).collect();
+    // Creating the first instance fails when another monitor owns the pipe
+    let mut pipe = create_pipe(&name, PIPE_ACCESS_OUTBOUND | FILE_FLAG_FIRST_PIPE_INSTANCE)
+        .map_err(|why| format!("couldn't create {}: {}", path, why))?;
+    let path = path.to_string();
+
+    thread::spawn(move || loop {
+        if let Err(why) = respond(pipe, &status) {
+            debug!("status request failed: {}", why);
+        }
+        pipe = match create_pipe(&name, PIPE_ACCESS_OUTBOUND) {
+            Ok(pipe) => pipe,
+            Err(why) => {
+                warn!("Status requests aren't answered anymore, couldn't create {}: {}", path, why);
+                return;
+            },
+        };
+    });
+
+    Ok(())
+}
+
+// Instance of the named pipe `name`, `mode` gives its direction and flags
+#[cfg(windows)]
+pub fn create_pipe(name: &[u16], mode: u32) -> io::Result<HANDLE> {
+    let pipe = unsafe {
+        CreateNamedPipeW(name.as_ptr(), mode,
+                         PIPE_TYPE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS, PIPE_UNLIMITED_INSTANCES,
+                         4096, 0, 0, ptr::null_mut())
+    };
+    if pipe == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(pipe)
+}
+
+// Wait for a client on `pipe`, the pipe is closed with the file
+#[cfg(windows)]
+pub fn accept(pipe: HANDLE) -> io::Result<File> {
+    // Clients may connect between CreateNamedPipeW and ConnectNamedPipe
+    let connected = unsafe { ConnectNamedPipe(pipe, ptr::null_mut()) } != 0;
+    let error     = io::Error::last_os_error();
+    let file      = unsafe { File::from_raw_handle(pipe as RawHandle) };
+    if !connected && error.raw_os_error() != Some(ERROR_PIPE_CONNECTED as i32) {
+        return Err(error);
+    }
+
+    Ok(file)
+}
+
+// Answer a client on `pipe`, closed afterwards
+#[cfg(windows)]
+fn respond(pipe: HANDLE, status: &Arc<Mutex<Status>>) -> io::Result<()> {
+    let mut file = accept(pipe)?;
+    file.write_all(&status_line(status))?;
+    unsafe {
+        FlushFileBuffers(pipe);
+        DisconnectNamedPipe(pipe);
+    }
+
+    Ok(())
+}
+
+fn status_line(status: &Arc<Mutex<Status>>) -> Vec<u8> {
+    format!("{}\n", snapshot(status)).into_bytes()
+}
+
+// The status as JSON, as `bonomen status --json` prints it
+pub fn snapshot(status: &Arc<Mutex<Status>>) -> Value {
+    match status.lock() {
+        Ok(status) => status.to_json(),
+        Err(_)     => json!({ "error": "status unavailable" }),
+    }
+}
+
+// Status of the monitor listening on `path`
+pub fn query(path: &str) -> Result<Value, String> {
+    let mut answer = String::new();
+    connect(path).and_then(|mut stream| stream.read_to_string(&mut answer))
+        .map_err(|why| format!("no monitor answered on {}: {}", path, why))?;
+
+    ::serde_json::from_str(&answer).map_err(|why| format!("invalid status from {}: {}", path, why))
+}
+
+#[cfg(unix)]
+fn connect(path: &str) -> io::Result<UnixStream> {
+    let stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    Ok(stream)
+}
+
+#[cfg(windows)]
+fn connect(path: &str) -> io::Result<File> {
+    File::open(path)
+}
@@ -0,0 +1,64 @@
+// Detections already alerted on by a continuous scan, so a long running impersonator
+// isn't reported again at every interval
+
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+use types::{Comparison, ScanReport};
+
+// pid, name, executable path
+pub type Key = (u32, String, String);
+
+pub fn key(comparison: &Comparison) -> Key {
+    (comparison.pid, comparison.name.clone(), comparison.exe_path.clone())
+}
+
+pub struct Alerted {
+    // Alert again about a process still running after this long, never when absent
+    realert_after: Option<Duration>,
+    // When each process still detected was last alerted on
+    alerted      : HashMap<Key, Instant>,
+}
+
+impl Alerted {
+    pub fn new(realert_after: Option<Duration>) -> Alerted {
+        Alerted {
+            realert_after: realert_after,
+            alerted:       HashMap::new(),
+        }
+    }
+
+    // Processes whose detections the next scan must not alert on
+    pub fn suppressed(&self) -> HashSet<Key> {
+        self.alerted.iter()
+            .filter(|&(_, at)| self.realert_after.map_or(true, |after| at.elapsed() < after))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    // Remember the processes alerted on by a scan, and forget those no longer detected
+    pub fn update(&mut self, report: &ScanReport) {
+        let now         = Instant::now();
+        let mut alerted = HashMap::new();
+
+        for detection in &report.detections {
+            alerted.insert(key(detection), now);
+        }
+        for detection in &report.repeated {
+            let key = key(detection);
+            let at  = self.alerted.get(&key).cloned().unwrap_or(now);
+            alerted.entry(key).or_insert(at);
+        }
+
+        self.alerted = alerted;
+    }
+
+    // Remember the processes alerted on by a check of some processes only, the others
+    // weren't looked at
+    pub fn add(&mut self, report: &ScanReport) {
+        let now = Instant::now();
+        for detection in &report.detections {
+            self.alerted.insert(key(detection), now);
+        }
+    }
+}
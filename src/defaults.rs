@@ -0,0 +1,35 @@
+// Critical processes lists compiled into the binary, used when no file is available.
+
+#[cfg(windows)]
+use std::env;
+#[cfg(windows)]
+use std::ffi::OsString;
+#[cfg(windows)]
+use std::path::Path;
+use std::path::PathBuf;
+
+#[cfg(target_os = "macos")]
+pub const DEFAULT_PROCS: &'static str = include_str!("defaults/macos.txt");
+#[cfg(windows)]
+pub const DEFAULT_PROCS: &'static str = include_str!("defaults/windows.txt");
+#[cfg(all(unix, not(target_os = "macos")))]
+pub const DEFAULT_PROCS: &'static str = include_str!("defaults/linux.txt");
+
+// System-wide critical processes file, as installed by `bonomen update`
+#[cfg(unix)]
+pub fn installed_path() -> PathBuf {
+    PathBuf::from("/etc/bonomen/default_procs.txt")
+}
+
+#[cfg(windows)]
+pub fn installed_path() -> PathBuf {
+    let program_data = env::var_os("ProgramData").unwrap_or_else(|| OsString::from("C:\\ProgramData"));
+    Path::new(&program_data).join("bonomen").join("default_procs.txt")
+}
+
+#[cfg(target_os = "macos")]
+pub const DEFAULT_NAME: &'static str = "<embedded macOS defaults>";
+#[cfg(windows)]
+pub const DEFAULT_NAME: &'static str = "<embedded Windows defaults>";
+#[cfg(all(unix, not(target_os = "macos")))]
+pub const DEFAULT_NAME: &'static str = "<embedded Linux defaults>";
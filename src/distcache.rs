@@ -0,0 +1,59 @@
+// Distances of process names to the critical processes, kept between the scans of the monitor
+// mode: the names running on a host barely change from one scan to the next
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, RwLock};
+
+use types::{NameMatching, ProcProps};
+
+// Whether the name contained look-alike characters, and its distance to every critical process
+pub type Distances = (bool, Vec<f64>);
+
+// Algorithm, name normalization and critical processes distances are measured against, with their
+// threshold: those out of reach aren't measured
+type Against = (String, NameMatching, Vec<(String, f64)>);
+
+pub struct DistanceCache {
+    against  : Mutex<Option<Against>>,
+    distances: RwLock<HashMap<String, Distances>>,
+}
+
+impl DistanceCache {
+    pub fn new() -> DistanceCache {
+        DistanceCache {
+            against:   Mutex::new(None),
+            distances: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // Forget every distance when the critical processes or the way they're compared changed,
+    // e.g. after the critical processes file was edited
+    pub fn measure_against(&self, algorithm: &str, names: NameMatching, crit_procs: &[ProcProps]) {
+        let thresholds = crit_procs.iter()
+            .map(|crit_proc| (crit_proc.name.clone(), crit_proc.threshold.limit(&crit_proc.name, false)))
+            .collect();
+        let against = Some((algorithm.to_string(), names, thresholds));
+        let mut current = self.against.lock().unwrap();
+        if *current != against {
+            debug!("Critical processes changed, measuring distances again");
+            self.distances.write().unwrap().clear();
+            *current = against;
+        }
+    }
+
+    pub fn get_or_measure<F>(&self, name: &str, measure: F) -> Distances
+        where F: FnOnce() -> Distances {
+        if let Some(distances) = self.distances.read().unwrap().get(name) {
+            return distances.clone();
+        }
+
+        let distances = measure();
+        self.distances.write().unwrap().insert(name.to_string(), distances.clone());
+        distances
+    }
+
+    // Drop the names no longer running, after a scan of every process
+    pub fn retain(&self, names: &HashSet<&str>) {
+        self.distances.write().unwrap().retain(|name, _| names.contains(name.as_str()));
+    }
+}
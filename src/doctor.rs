@@ -0,0 +1,236 @@
+use std::fs::{self, OpenOptions};
+use std::path::Path;
+
+use output::Output;
+
+#[cfg(unix)]
+use psutil;
+#[cfg(windows)]
+use std::mem::size_of;
+#[cfg(windows)]
+use winapi::shared::minwindef::DWORD;
+#[cfg(windows)]
+use winapi::um::psapi::K32EnumProcesses;
+
+use term;
+
+enum Status {
+    Pass,
+    Fail,
+    Skip,
+}
+
+struct Check {
+    name:    &'static str,
+    status:  Status,
+    details: String,
+}
+
+// Run every pre-flight check and print a pass/fail matrix.
+// Returns false if at least one check failed.
+pub fn run(file_names : &[&str],
+           profile    : Option<&str>,
+           report_path: Option<&str>,
+           terminal   : &mut Output) -> bool {
+    let mut checks = Vec::new();
+
+    platform_checks(&mut checks);
+    checks.push(check_event_source());
+    checks.push(check_sink(report_path));
+    checks.push(check_rules(file_names, profile));
+    checks.push(check_integrity());
+
+    let mut ok = true;
+    for check in checks.iter() {
+        let (label, color) = match check.status {
+            Status::Pass => ("PASS", term::color::GREEN),
+            Status::Fail => ("FAIL", term::color::RED),
+            Status::Skip => ("SKIP", term::color::YELLOW),
+        };
+        if let Status::Fail = check.status {
+            ok = false;
+        }
+
+        print!("{:<22}", check.name);
+        terminal.fg(color);
+        print!("{:<6}", label);
+        terminal.reset();
+        println!("{}", check.details);
+    }
+
+    ok
+}
+
+#[cfg(unix)]
+fn platform_checks(checks: &mut Vec<Check>) {
+    let euid = unsafe { ::libc::geteuid() };
+    checks.push(Check {
+        name:    "privileges",
+        status:  if ::privileged() { Status::Pass } else { Status::Fail },
+        details: match (::privileged(), euid) {
+            (true, 0)  => String::from("running as root"),
+            (true, _)  => format!("running as euid {} with CAP_SYS_PTRACE", euid),
+            (false, 0) => String::from("running as root without CAP_SYS_PTRACE, processes of other users are only \
+                                        checked by name"),
+            (false, _) => format!("running as euid {} without {}, processes of other users are only checked by name",
+                                  euid, ::PRIVILEGES),
+        },
+    });
+
+    // Count the processes listed in /proc and how many executable links we may follow
+    let mut total      = 0;
+    let mut unreadable = 0;
+    let proc_dir = match fs::read_dir("/proc") {
+        Ok(dir)  => Some(dir),
+        Err(why) => {
+            checks.push(Check {
+                name:    "/proc visibility",
+                status:  Status::Fail,
+                details: format!("couldn't read /proc: {}", why.to_string()),
+            });
+            None
+        }
+    };
+    if let Some(dir) = proc_dir {
+        for entry in dir.filter_map(|e| e.ok()) {
+            if entry.file_name().to_string_lossy().parse::<u32>().is_err() {
+                continue;
+            }
+            total += 1;
+            if fs::read_link(entry.path().join("exe")).is_err() {
+                unreadable += 1;
+            }
+        }
+        checks.push(Check {
+            name:    "/proc visibility",
+            status:  if total > 0 && unreadable < total { Status::Pass } else { Status::Fail },
+            details: format!("{} processes, {} executable links unreadable", total, unreadable),
+        });
+    }
+
+    checks.push(match psutil::process::all() {
+        Ok(procs) => Check {
+            name:    "process enumeration",
+            status:  Status::Pass,
+            details: format!("{} processes enumerated", procs.len()),
+        },
+        Err(why)  => Check {
+            name:    "process enumeration",
+            status:  Status::Fail,
+            details: why.to_string(),
+        },
+    });
+}
+
+#[cfg(windows)]
+fn platform_checks(checks: &mut Vec<Check>) {
+    checks.push(Check {
+        name:    "privileges",
+        status:  Status::Skip,
+        details: String::from("not checked on Windows, run from an elevated prompt"),
+    });
+
+    const SIZE: usize = 1024;
+    let mut pids = [0; SIZE];
+    let mut written = 0;
+    let ret = unsafe {
+        K32EnumProcesses(pids.as_mut_ptr(), (pids.len() * size_of::<DWORD>()) as u32, &mut written)
+    };
+    checks.push(Check {
+        name:    "K32EnumProcesses",
+        status:  if ret != 0 { Status::Pass } else { Status::Fail },
+        details: if ret != 0 {
+            format!("{} processes enumerated", written / size_of::<DWORD>() as u32)
+        } else {
+            String::from("call failed")
+        },
+    });
+}
+
+// Real-time process events for `monitor --exec-events`
+#[cfg(all(any(target_os = "linux", windows), feature = "exec-events"))]
+fn check_event_source() -> Check {
+    match ::procevents::probe() {
+        Ok(())   => Check {
+            name:    "event source",
+            status:  Status::Pass,
+            details: String::from("kernel exec events available"),
+        },
+        Err(why) => Check {
+            name:    "event source",
+            status:  Status::Skip,
+            details: format!("polling only, exec events unavailable: {}", why),
+        },
+    }
+}
+
+#[cfg(not(all(any(target_os = "linux", windows), feature = "exec-events")))]
+fn check_event_source() -> Check {
+    Check {
+        name:    "event source",
+        status:  Status::Skip,
+        details: String::from("polling only, no real-time process events"),
+    }
+}
+
+// Make sure the report file can be created, without leaving it behind
+fn check_sink(report_path: Option<&str>) -> Check {
+    let path = match report_path {
+        Some(path) => path,
+        None       => return Check {
+            name:    "report sink",
+            status:  Status::Skip,
+            details: String::from("no --report given"),
+        },
+    };
+
+    let existed = Path::new(path).exists();
+    let result  = OpenOptions::new().append(true).create(true).open(path);
+    if !existed && result.is_ok() {
+        let _ = fs::remove_file(path);
+    }
+
+    Check {
+        name:    "report sink",
+        status:  if result.is_ok() { Status::Pass } else { Status::Fail },
+        details: match result {
+            Ok(_)    => format!("{} is writable", path),
+            Err(why) => format!("{}: {}", path, why.to_string()),
+        },
+    }
+}
+
+fn check_integrity() -> Check {
+    match ::integrity::check(None) {
+        Ok(Some(verified)) => Check {
+            name:    "self integrity",
+            status:  Status::Pass,
+            details: verified,
+        },
+        Ok(None)           => Check {
+            name:    "self integrity",
+            status:  Status::Skip,
+            details: String::from("no expected SHA-256 or signature to verify the binary against"),
+        },
+        Err(why)           => Check {
+            name:    "self integrity",
+            status:  Status::Fail,
+            details: why,
+        },
+    }
+}
+
+fn check_rules(file_names: &[&str], profile: Option<&str>) -> Check {
+    match ::load_config(file_names, profile) {
+        Ok((source, config)) => Check {
+            name:    "rule file",
+            status:  if config.procs.is_empty() { Status::Fail } else { Status::Pass },
+            details: format!("{}: {} critical processes", source, config.procs.len()),
+        },
+        Err(why)             => Check {
+            name:    "rule file",
+            status:  Status::Fail,
+            details: why.to_string(),
+        },
+    }
+}
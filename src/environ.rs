@@ -0,0 +1,127 @@
+// Environment variables of detected processes with `--env`, read from /proc/<pid>/environ on Unix and
+// from the process parameters the PEB points to on Windows. Values of variables named like secrets are
+// redacted before they reach the output.
+
+use std::io;
+
+use whitelist::Entry;
+
+// Variables shown by `--env` when no name is given: loader hijacking, and where commands come from
+pub const DEFAULT_NAMES: &'static [&'static str] = &["LD_PRELOAD", "LD_LIBRARY_PATH", "LD_AUDIT",
+                                                      "DYLD_INSERT_LIBRARIES", "PATH", "PROMPT", "COMSPEC"];
+// Variables whose values are redacted, unless `--env-redact` gives other patterns
+pub const DEFAULT_REDACT: &'static [&'static str] = &["*PASSWORD*", "*PASSWD*", "*SECRET*", "*TOKEN*", "*KEY*",
+                                                       "*CREDENTIAL*"];
+
+const REDACTED: &'static str = "[redacted]";
+
+pub struct Settings {
+    // Names of the variables shown, exact, globs or `re:` regular expressions
+    pub names : Vec<Entry>,
+    // Names of the variables whose values are replaced
+    pub redact: Vec<Entry>,
+}
+
+impl Settings {
+    // Entries of `names` and `redact`, the defaults for those left out
+    pub fn new(names: &[&str], redact: &[&str]) -> Result<Settings, String> {
+        let names  = if names.is_empty() { DEFAULT_NAMES } else { names };
+        let redact = if redact.is_empty() { DEFAULT_REDACT } else { redact };
+
+        Ok(Settings {
+            names:  names.iter().map(|name| Entry::parse(name)).collect::<Result<_, _>>()?,
+            redact: redact.iter().map(|name| Entry::parse(name)).collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+// Variables of the process `pid` selected by `settings`, in the order of its environment
+pub fn selected(pid: u32, settings: &Settings) -> io::Result<Vec<(String, String)>> {
+    // Windows names are case insensitive, `Path` is PATH
+    let matching = |entries: &[Entry], name: &str| {
+        entries.iter().any(|entry| entry.matches(name) || entry.matches(&name.to_uppercase()))
+    };
+
+    Ok(read(pid)?.into_iter()
+        .filter(|&(ref name, _)| matching(&settings.names, name))
+        .map(|(name, value)| {
+            let value = if matching(&settings.redact, &name) { REDACTED.to_string() } else { value };
+            (name, value)
+        })
+        .collect())
+}
+
+// `NAME=value` entries separated by nulls. Windows keeps the current directory of each drive in
+// entries named `=C:`, the first `=` is part of their name.
+fn parse(block: &str) -> Vec<(String, String)> {
+    block.split('\0')
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let (split, _) = entry.char_indices().skip(1).find(|&(_, c)| c == '=')?;
+            Some((entry[.. split].to_string(), entry[split + 1 ..].to_string()))
+        })
+        .collect()
+}
+
+// Every variable of the process `pid`
+#[cfg(unix)]
+pub fn read(pid: u32) -> io::Result<Vec<(String, String)>> {
+    let block = ::std::fs::read(format!("/proc/{}/environ", pid))?;
+
+    Ok(parse(&String::from_utf8_lossy(&block)))
+}
+
+// The environment block is read with the size recorded next to it, bounded in case it's bogus
+#[cfg(windows)]
+const MAX_BLOCK: usize = 1 << 20;
+
+#[cfg(windows)]
+pub fn read(pid: u32) -> io::Result<Vec<(String, String)>> {
+    use winapi::shared::minwindef::FALSE;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winnt::{PROCESS_QUERY_INFORMATION, PROCESS_VM_READ};
+
+    unsafe {
+        let h_process = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, FALSE, pid);
+        if h_process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let block = read_block(h_process);
+        CloseHandle(h_process);
+
+        Ok(parse(&String::from_utf16_lossy(&block?)))
+    }
+}
+
+#[cfg(windows)]
+unsafe fn read_block(h_process: ::winapi::um::winnt::HANDLE) -> io::Result<Vec<u16>> {
+    use std::cmp::min;
+    use std::mem::size_of;
+
+    use winapi::shared::minwindef::LPVOID;
+
+    use hollowing::{peb_address, read_memory};
+
+    // Offsets of PEB.ProcessParameters, then of Environment and EnvironmentSize in the parameters
+    #[cfg(target_pointer_width = "64")]
+    const OFFSETS: (usize, usize, usize) = (0x20, 0x80, 0x3f0);
+    #[cfg(target_pointer_width = "32")]
+    const OFFSETS: (usize, usize, usize) = (0x10, 0x48, 0x290);
+    let (parameters_at, environment_at, size_at) = OFFSETS;
+
+    let read_pointer = |address: usize| -> io::Result<usize> {
+        let mut value: usize = 0;
+        read_memory(h_process, address, &mut value as *mut _ as LPVOID, size_of::<usize>())?;
+        Ok(value)
+    };
+    let parameters  = read_pointer(peb_address(h_process)? + parameters_at)?;
+    let environment = read_pointer(parameters + environment_at)?;
+    let size        = read_pointer(parameters + size_at)?;
+
+    let mut block = vec![0u16; min(size, MAX_BLOCK) / 2];
+    let read = read_memory(h_process, environment, block.as_mut_ptr() as LPVOID, block.len() * 2)?;
+    block.truncate(read / 2);
+
+    Ok(block)
+}
@@ -0,0 +1,56 @@
+use std::error::Error;
+use std::fmt;
+
+// Failures that stop BONOMEN from completing a scan
+#[derive(Debug)]
+pub enum ScanError {
+    // The critical processes file or a command line option is invalid
+    Config(String),
+    // Running processes couldn't be enumerated
+    Enum(String),
+    // Missing privileges to inspect processes
+    Permission(String),
+    // Critical processes definitions couldn't be downloaded, verified or installed
+    Update(String),
+    // No monitor answered on the control socket
+    Control(String),
+    // The history database couldn't be read
+    History(String),
+    // The fleet server couldn't start
+    Server(String),
+    // BONOMEN's own binary doesn't match its expected hash or signature
+    Integrity(String),
+}
+
+impl ScanError {
+    // Process exit code reported for this error
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            ScanError::Config(_)     => 2,
+            ScanError::Enum(_)       => 3,
+            ScanError::Permission(_) => 4,
+            ScanError::Update(_)     => 5,
+            ScanError::Control(_)    => 6,
+            ScanError::History(_)    => 7,
+            ScanError::Server(_)     => 8,
+            ScanError::Integrity(_)  => 9,
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScanError::Config(ref msg)     => write!(f, "configuration error: {}", msg),
+            ScanError::Enum(ref msg)       => write!(f, "process enumeration error: {}", msg),
+            ScanError::Permission(ref msg) => write!(f, "permission error: {}", msg),
+            ScanError::Update(ref msg)     => write!(f, "update error: {}", msg),
+            ScanError::Control(ref msg)    => write!(f, "status error: {}", msg),
+            ScanError::History(ref msg)    => write!(f, "history error: {}", msg),
+            ScanError::Server(ref msg)     => write!(f, "server error: {}", msg),
+            ScanError::Integrity(ref msg)  => write!(f, "integrity error: {}", msg),
+        }
+    }
+}
+
+impl Error for ScanError {}
@@ -0,0 +1,302 @@
+// Why processes are suspicious or not: the verdict of every detection rule on a single process
+// for `bonomen check`, and the reasoning behind each detection with `--explain`.
+
+use chrono::Local;
+use term;
+
+use output::Output;
+use rules;
+use types::{Comparison, ProcProps, ProcessInfo, Threshold};
+
+// Print what each rule found about `process` and its score, true when it would be reported.
+// Comparisons beyond their threshold are only counted, a process is measured against every critical process.
+pub fn process(setup   : &::ScanSetup,
+               context : &rules::Context,
+               process : &ProcessInfo,
+               subject : &rules::Subject,
+               terminal: &mut Output) -> bool {
+    terminal.bold();
+    match (subject.ppid, subject.parent) {
+        (Some(ppid), Some(parent)) => {
+            println!("{} (PID {}), started by {} ({})", subject.name, subject.pid, parent, ppid)
+        },
+        (Some(ppid), None)         => println!("{} (PID {}), started by {}", subject.name, subject.pid, ppid),
+        (None, _)                  => println!("{} (PID {})", subject.name, subject.pid),
+    }
+    terminal.reset();
+    println!("\texecutable: {}", if subject.exe_known { subject.exe_path } else { "unknown" });
+    if let Some(started) = subject.started {
+        println!("\tstarted: {}", started.format("%Y-%m-%d %H:%M:%S"));
+    }
+    if let Some(argv0) = subject.argv0 {
+        println!("\targv[0]: {}", argv0);
+    }
+    if let Some(container) = subject.container {
+        println!("\tin {}", container);
+    }
+    if subject.homoglyph {
+        println!("\tname spelled with look-alike characters");
+    }
+    println!("\tclosest critical processes: {}",
+             ::closest(&setup.crit_procs, subject.distances, setup.options.top).join(", "));
+    if let Some(why) = left_out(setup, process) {
+        println!("\tleft out of scans: {}", why);
+    }
+
+    let (mut found, hits) = ::run_rules(setup, subject, context);
+    let score      = hits.iter().map(|&(_, weight)| weight).sum::<u32>();
+    let alerting   = score >= setup.alert_score;
+    let mut suspicious = false;
+    for rule in rules::registry() {
+        let comparisons = match found.iter().position(|&(name, _)| name == rule.name()) {
+            Some(index) => found.remove(index).1,
+            None        => {
+                println!("  {}: disabled by rule.{} = off", rule.name(), rule.name());
+                continue;
+            },
+        };
+        let (close, far): (Vec<Comparison>, Vec<Comparison>) = comparisons.into_iter()
+            .partition(|comparison| comparison.suspicious || comparison.distance <= comparison.threshold);
+        if close.is_empty() && far.is_empty() {
+            println!("  {}: nothing found", rule.name());
+        }
+        for comparison in close {
+            suspicious |= comparison.suspicious && comparison.severity >= setup.options.min_severity && alerting;
+            verdict(rule.name(), &comparison, subject, setup, alerting, terminal);
+        }
+        if !far.is_empty() {
+            println!("  {}: {} critical processes beyond their threshold", rule.name(), far.len());
+        }
+    }
+    println!("  {}", score_line(&hits, setup));
+    println!();
+
+    suspicious
+}
+
+fn verdict(rule      : &str,
+           comparison: &Comparison,
+           subject   : &rules::Subject,
+           setup     : &::ScanSetup,
+           alerting  : bool,
+           terminal  : &mut Output) {
+    let against = if comparison.crit_name.is_empty() {
+        comparison.reason.to_string()
+    } else if comparison.reason == "duplicate" {
+        format!("{} instances of {} running, {} expected", comparison.distance, comparison.crit_name,
+                comparison.threshold)
+    } else {
+        format!("{} at distance {}, threshold {}", comparison.crit_name, comparison.distance, comparison.threshold)
+    };
+    let module = if comparison.module.is_empty() { String::new() } else { format!(", loads {}", comparison.module) };
+
+    if comparison.suspicious && comparison.severity >= setup.options.min_severity && alerting {
+        terminal.fg(term::color::RED);
+        println!("  {}: {}{}: suspicious [{}]", rule, against, module, comparison.severity.name());
+        terminal.reset();
+    } else if comparison.suspicious && comparison.severity >= setup.options.min_severity {
+        println!("  {}: {}{}: suspicious [{}], score below the alert score",
+                 rule, against, module, comparison.severity.name());
+    } else if comparison.suspicious {
+        println!("  {}: {}{}: suspicious [{}], below --min-severity",
+                 rule, against, module, comparison.severity.name());
+    } else if !subject.exe_known {
+        println!("  {}: {}: executable unknown", rule, against);
+    } else {
+        terminal.fg(term::color::GREEN);
+        println!("  {}: {}: whitelisted executable", rule, against);
+        terminal.reset();
+    }
+}
+
+// Why scans with the same options would skip the process
+fn left_out(setup: &::ScanSetup, process: &ProcessInfo) -> Option<&'static str> {
+    if ::is_ignored(&process.name, &setup.options) {
+        Some("ignored by an ignore setting or --ignore")
+    } else if !setup.options.only.is_empty() && !setup.options.only.iter().any(|entry| entry.matches(&process.name)) {
+        Some("outside --match")
+    } else if !::in_scope(&process.name, process.pid, &setup.options) {
+        Some("outside --pid-range")
+    } else if !::owned_by_users(process, &setup.options) {
+        Some("not owned by --user")
+    } else {
+        None
+    }
+}
+
+// Rule, distance and threshold behind a detection, and why whitelisting didn't apply
+pub fn detection(rule      : &str,
+                 comparison: &Comparison,
+                 subject   : &rules::Subject,
+                 setup     : &::ScanSetup,
+                 hits      : &[(&str, u32)]) -> Vec<String> {
+    let mut lines = vec![format!("rule {}, reason {}", rule, comparison.reason), score_line(hits, setup)];
+    let crit_proc = match setup.crit_procs.iter().find(|crit_proc| crit_proc.name == comparison.crit_name) {
+        Some(crit_proc) => crit_proc,
+        None            => {
+            lines.push(String::from(if comparison.reason == "deleted-exe" {
+                "executable deleted while the process runs"
+            } else {
+                "executable gone, the process still has a command line"
+            }));
+            return lines;
+        },
+    };
+
+    match comparison.reason {
+        // Found whatever the whitelist says
+        "module"        => {
+            lines.push(format!("{} is loaded from outside the system directories", comparison.module));
+            return lines;
+        },
+        "injection"     => {
+            lines.push(format!("{} makes the loader map a library the executable doesn't ask for", comparison.module));
+            return lines;
+        },
+        "exe-mismatch"  => {
+            lines.push(format!("{} isn't mapped executable in the process, /proc/{}/maps shows another image",
+                               subject.exe_path, subject.pid));
+            return lines;
+        },
+        #[cfg(windows)]
+        "hollowed"      => {
+            match ::hollowing::differences(subject.pid, subject.exe_path) {
+                Ok(differences) => lines.extend(differences),
+                Err(why)        => lines.push(format!("image no longer readable: {}", why)),
+            }
+            lines.push(format!("PE headers in memory differ from {} on disk", subject.exe_path));
+            return lines;
+        },
+        "unsigned"      => {
+            lines.push(format!("whitelisted for {}, but not signed", crit_proc.name));
+            return lines;
+        },
+        "bad-signature" => {
+            lines.push(format!("whitelisted for {}, but the signature doesn't verify", crit_proc.name));
+            return lines;
+        },
+        "duplicate"     => {
+            lines.push(format!("{} instances of {} running, instances.{} = {}", comparison.distance,
+                               crit_proc.name, crit_proc.name, comparison.threshold));
+            lines.push(String::from("whitelisted instances, then those of the lowest PIDs, are the expected ones"));
+            return lines;
+        },
+        "recent"        => {
+            if let (Some(started), Some(booted)) = (subject.started, setup.options.booted) {
+                lines.push(format!("started {} ago, {} after boot",
+                                   ::format_age((Local::now() - started).num_seconds()),
+                                   ::format_age((started - booted).num_seconds())));
+            }
+            let (within, after_boot) = setup.options.recent;
+            lines.push(format!("recent_start = {}s, recent_uptime = {}s, {} usually starts at boot",
+                               within.num_seconds(), after_boot.num_seconds(), crit_proc.name));
+            return lines;
+        },
+        "odd-parent"    => {
+            lines.push(format!("started by {}, {} isn't started from a shell or script interpreter",
+                               subject.parent.unwrap_or("its parent"), crit_proc.name));
+            return lines;
+        },
+        "wrong-user"    => {
+            lines.push(format!("runs as {}, user.{} = {}", subject.owner.unwrap_or("?"), crit_proc.name,
+                               crit_proc.users.join(", ")));
+            return lines;
+        },
+        "thread-count"  => {
+            lines.push(format!("{} threads, threads.{} = {}", comparison.distance, crit_proc.name,
+                               range(crit_proc.threads)));
+            return lines;
+        },
+        "handle-count"  => {
+            lines.push(format!("{} handles open, handles.{} = {}", comparison.distance, crit_proc.name,
+                               range(crit_proc.handles)));
+            return lines;
+        },
+        "wrong-path"    => lines.push(format!("same name as the critical process {}", crit_proc.name)),
+        "argv0"         => lines.push(format!("argv[0] names the critical process {}", crit_proc.name)),
+        _               => {
+            lines.push(format!("distance {} to {}, threshold {}{}", comparison.distance, crit_proc.name,
+                               comparison.threshold, threshold_basis(crit_proc, setup)));
+            if subject.homoglyph {
+                lines.push(String::from("name spelled with look-alike characters, compared as plain letters"));
+            }
+            if ::confusables::has_invisible(subject.name) {
+                lines.push(format!("name {} hides characters that render as nothing, compared as {}",
+                                   subject.name.escape_debug(), ::confusables::skeleton(subject.name)));
+            }
+            match comparison.reason {
+                "temp-path"      => lines.push(format!("{} is a temporary or download location", subject.exe_path)),
+                "removable-path" => lines.push(format!("{} is on removable media", subject.exe_path)),
+                _                => (),
+            }
+            // Found whatever the whitelist says
+            if comparison.reason == "invisible-chars" {
+                return lines;
+            }
+        },
+    }
+
+    lines.push(whitelist(subject.exe_path, crit_proc));
+    if !crit_proc.in_containers.is_empty() {
+        let entries: Vec<String> = crit_proc.in_containers.iter().map(|entry| entry.to_string()).collect();
+        match subject.container {
+            Some(container) => lines.push(format!("container entries {} don't match in {}",
+                                                  entries.join(", "), container)),
+            None            => lines.push(format!("container entries {} only apply inside containers",
+                                                  entries.join(", "))),
+        }
+    }
+    #[cfg(target_os = "linux")] {
+        if setup.options.packages.is_some() && subject.exe_known {
+            lines.push(String::from("not installed by a package, or modified since, with --trust-packages"));
+        }
+    }
+
+    lines
+}
+
+// Rules adding up to the score of a process, and the score it needs to be reported
+fn score_line(hits: &[(&str, u32)], setup: &::ScanSetup) -> String {
+    let score = hits.iter().map(|&(_, weight)| weight).sum::<u32>();
+    let rules: Vec<String> = hits.iter().map(|&(rule, weight)| format!("{} {}", rule, weight)).collect();
+
+    if rules.is_empty() {
+        format!("score 0, alert score {}", setup.alert_score)
+    } else {
+        format!("score {} ({}), alert score {}", score, rules.join(" + "), setup.alert_score)
+    }
+}
+
+// Whitelist entries of `crit_proc` checked against `exe_path`, none of them matching
+pub fn whitelist(exe_path: &str, crit_proc: &ProcProps) -> String {
+    let entries: Vec<String> = crit_proc.whitelist.iter().map(|entry| entry.to_string()).collect();
+    if exe_path.is_empty() {
+        format!("executable unknown, the whitelist of {} can't apply", crit_proc.name)
+    } else if entries.is_empty() {
+        format!("{} whitelists no executable", crit_proc.name)
+    } else {
+        format!("{} matches none of the entries whitelisted for {}: {}",
+                exe_path, crit_proc.name, entries.join(", "))
+    }
+}
+
+// How a relative threshold was turned into a distance
+fn threshold_basis(crit_proc: &ProcProps, setup: &::ScanSetup) -> String {
+    match crit_proc.threshold {
+        Threshold::Percent(p) if !setup.algorithm.normalized() => {
+            format!(" ({}% of {} characters)", p, crit_proc.name.chars().count())
+        },
+        Threshold::Percent(_)                                  => String::from(" (percent)"),
+        Threshold::Absolute(_)                                 => String::new(),
+    }
+}
+
+// A `threads.NAME` or `handles.NAME` range as written, unbounded ends left out
+fn range(range: Option<(u32, u32)>) -> String {
+    match range {
+        Some((min, max)) if max == u32::max_value() => format!("{}-", min),
+        Some((0, max))                              => format!("-{}", max),
+        Some((min, max))                            => format!("{}-{}", min, max),
+        None                                        => String::from("unset"),
+    }
+}
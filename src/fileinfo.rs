@@ -0,0 +1,135 @@
+// Metadata of the executables backing detections
+
+use std::fs;
+
+use chrono::{DateTime, Local};
+
+use types::FileInfo;
+
+// None when `path` can't be read, e.g. an executable that was deleted
+#[cfg(unix)]
+pub fn stat(path: &str) -> Option<FileInfo> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = fs::metadata(path).ok()?;
+
+    Some(FileInfo {
+        modified: meta.modified().ok().map(DateTime::<Local>::from),
+        owner:    Some(user_name(meta.uid())),
+        group:    Some(group_name(meta.gid())),
+        mode:     Some(meta.mode()),
+        version:  None,
+        company:  None,
+    })
+}
+
+#[cfg(windows)]
+pub fn stat(path: &str) -> Option<FileInfo> {
+    let meta = fs::metadata(path).ok()?;
+    let (version, company) = version_info(path);
+
+    Some(FileInfo {
+        modified: meta.modified().ok().map(DateTime::<Local>::from),
+        owner:    None,
+        group:    None,
+        mode:     None,
+        version:  version,
+        company:  company,
+    })
+}
+
+// Name of the user `uid`, the number itself when it has no entry
+#[cfg(unix)]
+pub fn user_name(uid: u32) -> String {
+    unsafe {
+        let passwd = ::libc::getpwuid(uid);
+        if passwd.is_null() {
+            return uid.to_string();
+        }
+        ::std::ffi::CStr::from_ptr((*passwd).pw_name).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> String {
+    unsafe {
+        let group = ::libc::getgrgid(gid);
+        if group.is_null() {
+            return gid.to_string();
+        }
+        ::std::ffi::CStr::from_ptr((*group).gr_name).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(windows)]
+mod ffi {
+    use std::os::raw::c_void;
+
+    use winapi::shared::minwindef::{BOOL, DWORD, UINT};
+    use winapi::um::winnt::LPCWSTR;
+
+    #[link(name = "version")]
+    extern "system" {
+        pub fn GetFileVersionInfoSizeW(filename: LPCWSTR, handle: *mut DWORD) -> DWORD;
+        pub fn GetFileVersionInfoW(filename: LPCWSTR, handle: DWORD, len: DWORD, data: *mut c_void) -> BOOL;
+        pub fn VerQueryValueW(block: *const c_void, sub_block: LPCWSTR,
+                              buffer: *mut *mut c_void, len: *mut UINT) -> BOOL;
+    }
+}
+
+// FileVersion and CompanyName of the version resource, in its first language
+#[cfg(windows)]
+fn version_info(path: &str) -> (Option<String>, Option<String>) {
+    use std::os::raw::c_void;
+    use std::ptr;
+
+    let wide_path = wide(path);
+    let mut handle = 0;
+    let size = unsafe { ffi::GetFileVersionInfoSizeW(wide_path.as_ptr(), &mut handle) };
+    if size == 0 {
+        return (None, None);
+    }
+    let mut block = vec![0u8; size as usize];
+    if unsafe { ffi::GetFileVersionInfoW(wide_path.as_ptr(), 0, size, block.as_mut_ptr() as *mut c_void) } == 0 {
+        return (None, None);
+    }
+
+    let query = |sub_block: &str| -> Option<(*const c_void, usize)> {
+        let sub_block = wide(sub_block);
+        let mut buffer = ptr::null_mut();
+        let mut len    = 0;
+        let found = unsafe {
+            ffi::VerQueryValueW(block.as_ptr() as *const c_void, sub_block.as_ptr(), &mut buffer, &mut len)
+        };
+        if found == 0 || buffer.is_null() || len == 0 {
+            None
+        } else {
+            Some((buffer as *const c_void, len as usize))
+        }
+    };
+
+    // Pairs of language and code page identifiers
+    let (lang, codepage) = match query("\\VarFileInfo\\Translation") {
+        Some((translation, len)) if len >= 4 => unsafe {
+            let translation = translation as *const u16;
+            (*translation, *translation.offset(1))
+        },
+        _                                    => return (None, None),
+    };
+    let string = |name: &str| query(&format!("\\StringFileInfo\\{:04x}{:04x}\\{}", lang, codepage, name))
+        .map(|(value, len)| {
+            // The length is in characters, terminating NUL included
+            let value = unsafe { ::std::slice::from_raw_parts(value as *const u16, len) };
+            String::from_utf16_lossy(value).trim_end_matches('\0').trim().to_string()
+        })
+        .filter(|value| !value.is_empty());
+
+    (string("FileVersion"), string("CompanyName"))
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+
+    ::std::ffi::OsStr::new(s).encode_wide().chain(::std::iter::once(0)).collect()
+}
@@ -0,0 +1,168 @@
+// File integrity of the executables critical processes are whitelisted to run from. Their hashes and
+// modification times are recorded once and compared on later runs, whether the processes run or not:
+// a binary replaced on disk only runs at the next restart of its service.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use glob::glob;
+use serde_json::Value;
+
+use hash;
+use report::hostname;
+use types::ProcProps;
+use whitelist::Entry;
+
+// State of a whitelisted executable
+pub struct Record {
+    pub path    : String,
+    // Critical processes whitelisted to run from it
+    pub names   : Vec<String>,
+    // None when it couldn't be read
+    pub sha256  : Option<String>,
+    pub size    : Option<u64>,
+    pub modified: Option<DateTime<Local>>,
+}
+
+// Difference between the recorded executables and those on disk
+pub enum Change<'a> {
+    // Same path, different content
+    Modified(&'a Record, &'a Record),
+    // Same content, the modification time changed
+    Touched(&'a Record, &'a Record),
+    // Recorded, gone from disk
+    Missing(&'a Record),
+    // Whitelisted by a pattern or a new entry, not recorded
+    Added(&'a Record),
+}
+
+// Executables whitelisted by `crit_procs`, along with the critical processes they belong to. Exact
+// entries are taken as is, directories for the file named like the process, and globs expanded.
+// Regular expressions can't be listed, they're left out. The `recorded` files still whitelisted are
+// kept, so that those a glob no longer expands to are found missing.
+pub fn files(crit_procs: &[ProcProps], recorded: &[Record]) -> BTreeMap<String, Vec<String>> {
+    let mut files: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for crit_proc in crit_procs {
+        let mut paths = Vec::new();
+        for entry in &crit_proc.whitelist {
+            match *entry {
+                Entry::Exact { ref path, .. }     => paths.push(path.clone()),
+                Entry::Directory { ref path, .. } => {
+                    paths.push(Path::new(path).join(&crit_proc.name).to_string_lossy().into_owned());
+                },
+                Entry::Glob(ref pattern)          => match glob(pattern.as_str()) {
+                    Ok(matched) => paths.extend(matched.filter_map(|path| path.ok())
+                                                .map(|path| path.to_string_lossy().into_owned())),
+                    Err(why)    => debug!("couldn't expand {}: {}", pattern.as_str(), why),
+                },
+                Entry::Regex(ref regex)           => {
+                    debug!("{} is whitelisted by the regex {}, its files can't be listed", crit_proc.name, regex);
+                },
+            }
+        }
+        paths.extend(recorded.iter()
+            .filter(|r| crit_proc.whitelist.iter().any(|entry| entry.matches(&r.path)))
+            .map(|r| r.path.clone()));
+
+        for path in paths {
+            let names = files.entry(path).or_insert_with(Vec::new);
+            if !names.contains(&crit_proc.name) {
+                names.push(crit_proc.name.clone());
+            }
+        }
+    }
+
+    files
+}
+
+// Current state of the file at `path`, files that are gone have neither hash nor size
+pub fn record(path: &str, names: &[String]) -> Record {
+    let meta = fs::metadata(path).ok();
+    let sha256 = match meta {
+        Some(_) => hash::sha256_file(path).map_err(|why| warn!("couldn't hash {}: {}", path, why)).ok(),
+        None    => None,
+    };
+
+    Record {
+        path:     path.to_string(),
+        names:    names.to_vec(),
+        sha256:   sha256,
+        size:     meta.as_ref().map(|meta| meta.len()),
+        modified: meta.and_then(|meta| meta.modified().ok()).map(DateTime::<Local>::from),
+    }
+}
+
+// Write the records to a FIM baseline file
+pub fn save(path: &str, records: &[Record]) -> io::Result<()> {
+    let doc = json!({
+        "timestamp": Local::now().to_rfc3339(),
+        "host":      hostname(),
+        "files":     records.iter().map(|r| json!({
+            "path":     r.path,
+            "names":    r.names,
+            "sha256":   r.sha256,
+            "size":     r.size,
+            "modified": r.modified.map(|modified| modified.to_rfc3339()),
+        })).collect::<Vec<_>>(),
+    });
+
+    let mut file = File::create(path)?;
+    serde_json::to_writer_pretty(&mut file, &doc)?;
+    writeln!(file)
+}
+
+// Read the records of a FIM baseline file
+pub fn load(path: &str) -> io::Result<Vec<Record>> {
+    let doc: Value = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+    let files = match doc.get("files").and_then(|f| f.as_array()) {
+        Some(files) => files,
+        None        => return Err(io::Error::new(io::ErrorKind::InvalidData, "no files in FIM baseline")),
+    };
+
+    Ok(files.iter().map(|f| Record {
+        path:     f.get("path").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        names:    f.get("names").and_then(|v| v.as_array()).into_iter().flat_map(|names| names.iter())
+            .filter_map(|name| name.as_str().map(String::from))
+            .collect(),
+        sha256:   f.get("sha256").and_then(|v| v.as_str()).map(String::from),
+        size:     f.get("size").and_then(|v| v.as_u64()),
+        modified: f.get("modified").and_then(|v| v.as_str())
+            .and_then(|modified| DateTime::parse_from_rfc3339(modified).ok())
+            .map(|modified| modified.with_timezone(&Local)),
+    }).collect())
+}
+
+// Compare the files on disk against the recorded ones, by path. Files unreadable on either side are
+// only compared by presence, recorded files no entry whitelists anymore aren't watched.
+pub fn diff<'a>(saved: &'a [Record], current: &'a [Record]) -> Vec<Change<'a>> {
+    let known: HashMap<&str, &Record> = saved.iter().map(|r| (r.path.as_str(), r)).collect();
+
+    let mut changes = Vec::new();
+    for now in current.iter() {
+        let before = match known.get(now.path.as_str()) {
+            Some(before) => before,
+            None         => {
+                if now.size.is_some() {
+                    changes.push(Change::Added(now));
+                }
+                continue;
+            },
+        };
+
+        if before.size.is_some() && now.size.is_none() {
+            changes.push(Change::Missing(before));
+        } else if before.size.is_none() && now.size.is_some() {
+            changes.push(Change::Added(now));
+        } else if before.sha256.is_some() && now.sha256.is_some() && before.sha256 != now.sha256 {
+            changes.push(Change::Modified(before, now));
+        } else if before.sha256.is_some() && before.sha256 == now.sha256 && before.modified != now.modified {
+            changes.push(Change::Touched(before, now));
+        }
+    }
+
+    changes
+}
@@ -0,0 +1,34 @@
+use std::fs::File;
+use std::io;
+
+#[cfg(target_os = "linux")]
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use cancel;
+
+// Hex encoded SHA-256 of a file
+pub fn sha256_file(path: &str) -> io::Result<String> {
+    digest_file::<Sha256>(path)
+}
+
+// Hex encoded MD5 of a file, as recorded by dpkg
+#[cfg(target_os = "linux")]
+pub fn md5_file(path: &str) -> io::Result<String> {
+    digest_file::<Md5>(path)
+}
+
+// Files on unresponsive network mounts never finish reading, they time out
+fn digest_file<D>(path: &str) -> io::Result<String>
+    where D: Digest + io::Write + Send + 'static {
+    let owned = path.to_string();
+    let hashed = cancel::with_timeout(move || -> io::Result<String> {
+        let mut file   = File::open(&owned)?;
+        let mut hasher = D::new();
+        io::copy(&mut file, &mut hasher)?;
+
+        Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+    });
+
+    hashed.unwrap_or_else(|| Err(io::Error::new(io::ErrorKind::TimedOut, format!("hashing {} timed out", path))))
+}
@@ -0,0 +1,226 @@
+// Scans and their detections kept in an SQLite database with `--db`, so that `bonomen history` can tell
+// when a suspicious process first appeared, and on which hosts. `bonomen server` keeps those of its
+// agents the same way.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
+use rusqlite::{self, Connection, Row};
+use rusqlite::types::Type;
+use serde_json::Value;
+
+const SCHEMA: &'static str = "
+    CREATE TABLE IF NOT EXISTS scans (
+        id         INTEGER PRIMARY KEY,
+        time       TEXT NOT NULL,
+        host       TEXT NOT NULL,
+        scanned    INTEGER NOT NULL,
+        skipped    INTEGER NOT NULL,
+        detections INTEGER NOT NULL,
+        elapsed    REAL NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS detections (
+        id        INTEGER PRIMARY KEY,
+        scan      INTEGER NOT NULL REFERENCES scans (id),
+        time      TEXT NOT NULL,
+        host      TEXT NOT NULL,
+        pid       INTEGER NOT NULL,
+        name      TEXT NOT NULL,
+        exe_path  TEXT NOT NULL,
+        critical  TEXT NOT NULL,
+        reason    TEXT NOT NULL,
+        severity  TEXT NOT NULL,
+        distance  REAL NOT NULL,
+        threshold REAL NOT NULL,
+        module    TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS detections_time ON detections (time);
+    CREATE INDEX IF NOT EXISTS detections_name ON detections (name);
+";
+
+// UTC, so that times sort as text
+const TIME_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%SZ";
+
+// A detection as recorded
+pub struct Record {
+    pub time     : DateTime<Local>,
+    pub host     : String,
+    pub pid      : u32,
+    pub name     : String,
+    pub exe_path : String,
+    pub crit_name: String,
+    pub reason   : String,
+    pub severity : String,
+    pub distance : f64,
+    pub threshold: f64,
+    pub module   : String,
+}
+
+// Scans of a host
+pub struct Host {
+    pub host           : String,
+    pub scans          : u64,
+    pub last_scan      : DateTime<Local>,
+    pub last_detections: u64,
+    pub detections     : u64,
+}
+
+impl Record {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "time":      self.time.to_rfc3339(),
+            "host":      self.host,
+            "pid":       self.pid,
+            "name":      self.name,
+            "exe_path":  self.exe_path,
+            "critical":  self.crit_name,
+            "reason":    self.reason,
+            "severity":  self.severity,
+            "distance":  self.distance,
+            "threshold": self.threshold,
+            "module":    self.module,
+        })
+    }
+}
+
+impl Host {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "host":            self.host,
+            "scans":           self.scans,
+            "last_scan":       self.last_scan.to_rfc3339(),
+            "last_detections": self.last_detections,
+            "detections":      self.detections,
+        })
+    }
+}
+
+// Detections `bonomen history` looks for, all of them when nothing is set
+#[derive(Default)]
+pub struct Filter {
+    pub since : Option<DateTime<Local>>,
+    pub until : Option<DateTime<Local>>,
+    pub host  : Option<String>,
+    pub reason: Option<String>,
+}
+
+fn open(path: &str) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    // The server records the reports of several agents at once
+    conn.busy_timeout(Duration::from_secs(10))?;
+    conn.execute_batch(SCHEMA)?;
+
+    Ok(conn)
+}
+
+fn utc(time: DateTime<Local>) -> String {
+    time.with_timezone(&Utc).format(TIME_FORMAT).to_string()
+}
+
+fn local(row: &Row, index: usize) -> rusqlite::Result<DateTime<Local>> {
+    let time: String = row.get(index)?;
+
+    DateTime::parse_from_rfc3339(&time)
+        .map(|time| time.with_timezone(&Local))
+        .map_err(|why| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(why)))
+}
+
+// Add a scan and its detections, from its JSON report, to the database at `path`, created when missing.
+// Returns the number of detections.
+pub fn record(path: &str, report: &Value) -> rusqlite::Result<usize> {
+    let mut conn = open(path)?;
+    let tx   = conn.transaction()?;
+    let time = report["timestamp"].as_str()
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .map_or_else(|| utc(Local::now()), |time| utc(time.with_timezone(&Local)));
+    let host = report["host"].as_str().unwrap_or("unknown");
+    let detections = report["detections"].as_array().map_or(&[][..], |detections| detections.as_slice());
+
+    tx.execute("INSERT INTO scans (time, host, scanned, skipped, detections, elapsed)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+               params![time, host, report["scanned"].as_i64().unwrap_or(0),
+                       report["summary"]["skipped"].as_i64().unwrap_or(0), detections.len() as i64,
+                       report["summary"]["elapsed_seconds"].as_f64().unwrap_or(0.0)])?;
+    let scan = tx.last_insert_rowid();
+    for d in detections {
+        let text = |key: &str| d[key].as_str().unwrap_or("").to_string();
+        tx.execute("INSERT INTO detections (scan, time, host, pid, name, exe_path, critical, reason, severity,
+                                            distance, threshold, module)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                   params![scan, time, host, d["pid"].as_i64().unwrap_or(0), text("name"), text("exe_path"),
+                           text("critical"), text("reason"), text("severity"), d["distance"].as_f64().unwrap_or(0.0),
+                           d["threshold"].as_f64().unwrap_or(0.0), text("module")])?;
+    }
+    tx.commit()?;
+
+    Ok(detections.len())
+}
+
+// Every host of the database at `path`, with its last scan
+pub fn hosts(path: &str) -> rusqlite::Result<Vec<Host>> {
+    let conn = open(path)?;
+
+    let mut stmt = conn.prepare("SELECT host, COUNT(*), MAX(time),
+                                        (SELECT detections FROM scans AS last WHERE last.host = scans.host
+                                         ORDER BY time DESC, id DESC LIMIT 1),
+                                        SUM(detections)
+                                 FROM scans GROUP BY host ORDER BY host")?;
+    let hosts = stmt.query_map(params![], |row| {
+        Ok(Host {
+            host:            row.get(0)?,
+            scans:           row.get::<_, i64>(1)? as u64,
+            last_scan:       local(row, 2)?,
+            last_detections: row.get::<_, i64>(3)? as u64,
+            detections:      row.get::<_, i64>(4)? as u64,
+        })
+    })?;
+
+    hosts.collect()
+}
+
+// Detections of the database at `path` matching `filter`, oldest first
+pub fn query(path: &str, filter: &Filter) -> rusqlite::Result<Vec<Record>> {
+    let conn = open(path)?;
+
+    let mut conditions = Vec::new();
+    let mut values     = Vec::new();
+    if let Some(since) = filter.since {
+        conditions.push("time >= ?");
+        values.push(utc(since));
+    }
+    if let Some(until) = filter.until {
+        conditions.push("time < ?");
+        values.push(utc(until));
+    }
+    if let Some(ref host) = filter.host {
+        conditions.push("host = ?");
+        values.push(host.clone());
+    }
+    if let Some(ref reason) = filter.reason {
+        conditions.push("reason = ?");
+        values.push(reason.clone());
+    }
+    let filters = if conditions.is_empty() { String::new() } else { format!("WHERE {}", conditions.join(" AND ")) };
+    let sql     = format!("SELECT time, host, pid, name, exe_path, critical, reason, severity, distance, threshold,
+                                  module
+                           FROM detections {} ORDER BY time, id", filters);
+
+    let mut stmt = conn.prepare(&sql)?;
+    let records  = stmt.query_map(rusqlite::params_from_iter(values.iter()), |row| {
+        Ok(Record {
+            time:      local(row, 0)?,
+            host:      row.get(1)?,
+            pid:       row.get(2)?,
+            name:      row.get(3)?,
+            exe_path:  row.get(4)?,
+            crit_name: row.get(5)?,
+            reason:    row.get(6)?,
+            severity:  row.get(7)?,
+            distance:  row.get(8)?,
+            threshold: row.get(9)?,
+            module:    row.get(10)?,
+        })
+    })?;
+
+    records.collect()
+}
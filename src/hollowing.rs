@@ -0,0 +1,148 @@
+// Process hollowing: the image a process runs is replaced in memory after it started from a legitimate
+// executable, its name and path still look right. The PE headers of the image the PEB points to are
+// compared with those of the executable on disk.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem::size_of;
+use std::ptr;
+
+use winapi::shared::minwindef::{FALSE, LPVOID, ULONG};
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::memoryapi::ReadProcessMemory;
+use winapi::um::processthreadsapi::OpenProcess;
+use winapi::um::winnt::{HANDLE, LONG, PROCESS_QUERY_INFORMATION, PROCESS_VM_READ};
+
+// Enough for the DOS, NT and optional headers
+const HEADERS_SZ: usize = 4096;
+
+const PROCESS_BASIC_INFORMATION_CLASS: ULONG = 0;
+
+#[repr(C)]
+struct ProcessBasicInformation {
+    exit_status:       LONG,
+    peb_base_address:  LPVOID,
+    affinity_mask:     usize,
+    base_priority:     i32,
+    unique_process_id: usize,
+    parent_process_id: usize,
+}
+
+#[link(name = "ntdll")]
+extern "system" {
+    fn NtQueryInformationProcess(process: HANDLE, class: ULONG, information: LPVOID, length: ULONG,
+                                 return_length: *mut ULONG) -> LONG;
+}
+
+// Fields of the PE headers that hollowing changes
+struct Headers {
+    size_of_image: u32,
+    entry_point  : u32,
+    timestamp    : u32,
+}
+
+// How the image mapped in the process `pid` differs from `exe_path`, empty when it matches
+pub fn differences(pid: u32, exe_path: &str) -> io::Result<Vec<String>> {
+    let mut on_disk = vec![0; HEADERS_SZ];
+    let read = File::open(exe_path)?.read(&mut on_disk)?;
+    let on_disk = parse(&on_disk[.. read])
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("{} isn't a PE file", exe_path)))?;
+
+    let in_memory = match parse(&image_headers(pid)?) {
+        Some(headers) => headers,
+        // Wiped or overwritten headers
+        None          => return Ok(vec![String::from("no PE headers at the image base")]),
+    };
+
+    let mut found = Vec::new();
+    if in_memory.size_of_image != on_disk.size_of_image {
+        found.push(format!("image size {:#x} in memory, {:#x} on disk", in_memory.size_of_image,
+                           on_disk.size_of_image));
+    }
+    if in_memory.entry_point != on_disk.entry_point {
+        found.push(format!("entry point {:#x} in memory, {:#x} on disk", in_memory.entry_point,
+                           on_disk.entry_point));
+    }
+    if in_memory.timestamp != on_disk.timestamp {
+        found.push(format!("link timestamp {:#x} in memory, {:#x} on disk", in_memory.timestamp,
+                           on_disk.timestamp));
+    }
+
+    Ok(found)
+}
+
+// First bytes of the image the PEB of the process names as its own
+fn image_headers(pid: u32) -> io::Result<Vec<u8>> {
+    unsafe {
+        let h_process = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, FALSE, pid);
+        if h_process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let headers = read_image_headers(h_process);
+        CloseHandle(h_process);
+
+        headers
+    }
+}
+
+unsafe fn read_image_headers(h_process: HANDLE) -> io::Result<Vec<u8>> {
+    // PEB.ImageBaseAddress follows four flag bytes and the Mutant handle, both pointer aligned
+    let mut image_base: usize = 0;
+    read_memory(h_process, peb_address(h_process)? + 2 * size_of::<usize>(),
+                &mut image_base as *mut _ as LPVOID, size_of::<usize>())?;
+
+    let mut headers = vec![0u8; HEADERS_SZ];
+    let read = read_memory(h_process, image_base, headers.as_mut_ptr() as LPVOID, HEADERS_SZ)?;
+    headers.truncate(read);
+
+    Ok(headers)
+}
+
+// Address of the PEB of a process opened with PROCESS_QUERY_INFORMATION
+pub unsafe fn peb_address(h_process: HANDLE) -> io::Result<usize> {
+    let mut info: ProcessBasicInformation = ::std::mem::zeroed();
+    let status = NtQueryInformationProcess(h_process, PROCESS_BASIC_INFORMATION_CLASS,
+                                           &mut info as *mut _ as LPVOID,
+                                           size_of::<ProcessBasicInformation>() as ULONG, ptr::null_mut());
+    if status < 0 {
+        return Err(io::Error::new(io::ErrorKind::Other, format!("NtQueryInformationProcess failed: {:#x}", status)));
+    }
+
+    Ok(info.peb_base_address as usize)
+}
+
+// Copy `size` bytes at `address` in the process, opened with PROCESS_VM_READ, into `buffer`
+pub unsafe fn read_memory(h_process: HANDLE, address: usize, buffer: LPVOID, size: usize) -> io::Result<usize> {
+    let mut read = 0;
+    if ReadProcessMemory(h_process, address as LPVOID, buffer, size, &mut read) == 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(read)
+}
+
+// Headers of a PE image, the same offsets on disk and once mapped
+fn parse(image: &[u8]) -> Option<Headers> {
+    if image.get(.. 2) != Some(&b"MZ"[..]) {
+        return None;
+    }
+    let nt = read_u32(image, 0x3c)? as usize;
+    if image.get(nt .. nt + 4) != Some(&b"PE\0\0"[..]) {
+        return None;
+    }
+    // IMAGE_FILE_HEADER, then the optional header at the same offsets for PE32 and PE32+
+    let file_header     = nt + 4;
+    let optional_header = file_header + 20;
+
+    Some(Headers {
+        size_of_image: read_u32(image, optional_header + 56)?,
+        entry_point:   read_u32(image, optional_header + 16)?,
+        timestamp:     read_u32(image, file_header + 4)?,
+    })
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset .. offset + 4)?;
+
+    Some(u32::from(bytes[0]) | u32::from(bytes[1]) << 8 | u32::from(bytes[2]) << 16 | u32::from(bytes[3]) << 24)
+}
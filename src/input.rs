@@ -0,0 +1,142 @@
+// Process listings captured on another machine, scanned instead of the live system
+
+use std::fs::File;
+use std::io::Read;
+
+use serde_json::Value;
+
+use error::ScanError;
+use types::ProcessInfo;
+
+// Read a captured process listing. Supported formats are
+// - JSON: an array of objects, or an object with a "processes" array (as written by `baseline save`)
+// - CSV: with a header row naming the pid, name and exe_path columns
+// - the output of `ps -eo comm,pid,args`
+// The format is taken from the file extension, else guessed from the content.
+pub fn read_listing(path: &str) -> Result<Vec<ProcessInfo>, ScanError> {
+    let mut content = String::new();
+    if let Err(why) = File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+        return Err(ScanError::Config(format!("couldn't read {}: {}", path, why.to_string())));
+    }
+
+    let lower   = path.to_lowercase();
+    let trimmed = content.trim_start();
+    if lower.ends_with(".json") || trimmed.starts_with('[') || trimmed.starts_with('{') {
+        parse_json(&content).map_err(|why| ScanError::Config(format!("{}: {}", path, why)))
+    } else if lower.ends_with(".csv") || trimmed.lines().next().map_or(false, |l| l.contains(',')) {
+        parse_csv(&content).map_err(|why| ScanError::Config(format!("{}: {}", path, why)))
+    } else {
+        Ok(parse_ps(&content))
+    }
+}
+
+// Accept a few spellings for each field
+fn field<'a>(obj: &'a Value, names: &[&str]) -> Option<&'a Value> {
+    names.iter().filter_map(|n| obj.get(*n)).next()
+}
+
+fn parse_json(content: &str) -> Result<Vec<ProcessInfo>, String> {
+    let doc: Value = serde_json::from_str(content).map_err(|why| why.to_string())?;
+    let procs = match doc.get("processes").unwrap_or(&doc).as_array() {
+        Some(procs) => procs,
+        None        => return Err(String::from("expected an array of processes")),
+    };
+
+    Ok(procs.iter().map(|p| ProcessInfo {
+        pid:         field(p, &["pid", "PID", "Id"]).and_then(|v| v.as_u64()).unwrap_or(0) as u32,
+        name:        field(p, &["name", "comm", "Name"]).and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        exe_path:    field(p, &["exe_path", "exe", "path", "Path"]).and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        exe_problem: None,
+        exe_denied:  false,
+        ppid:        field(p, &["ppid", "PPID", "ParentProcessId"]).and_then(|v| v.as_u64()).map(|ppid| ppid as u32),
+        user:        None,
+        owner:       field(p, &["owner", "user", "UserName"]).and_then(|v| v.as_str()).map(String::from),
+        // Get-Process lists the threads themselves
+        threads:     field(p, &["threads", "Threads", "ThreadCount"])
+            .and_then(|v| v.as_u64().or_else(|| v.as_array().map(|threads| threads.len() as u64)))
+            .map(|threads| threads as u32),
+        handles:     field(p, &["handles", "Handles", "HandleCount"]).and_then(|v| v.as_u64()).map(|n| n as u32),
+        cmdline:     None,
+        started:     None,
+    }).collect())
+}
+
+fn parse_csv(content: &str) -> Result<Vec<ProcessInfo>, String> {
+    let mut lines = content.lines();
+    let header: Vec<String> = match lines.next() {
+        Some(header) => header.split(',').map(|h| h.trim().to_lowercase()).collect(),
+        None         => return Ok(Vec::new()),
+    };
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+
+    let name_col = match column(&["name", "comm", "process"]) {
+        Some(col) => col,
+        None      => return Err(String::from("no name column in CSV header")),
+    };
+    let pid_col     = column(&["pid"]);
+    let ppid_col    = column(&["ppid"]);
+    let path_col    = column(&["exe_path", "exe", "path"]);
+    let user_col    = column(&["owner", "user", "username"]);
+    let threads_col = column(&["threads", "threadcount"]);
+    let handles_col = column(&["handles", "handlecount"]);
+
+    Ok(lines
+       .filter(|l| !l.trim().is_empty())
+       .map(|line| {
+           let cols: Vec<&str> = line.split(',').map(|c| c.trim().trim_matches('"')).collect();
+           let get = |col: Option<usize>| col.and_then(|c| cols.get(c)).cloned().unwrap_or("");
+
+           ProcessInfo {
+               pid:         get(pid_col).parse::<u32>().unwrap_or(0),
+               name:        get(Some(name_col)).to_string(),
+               exe_path:    get(path_col).to_string(),
+               exe_problem: None,
+               exe_denied:  false,
+               ppid:        get(ppid_col).parse::<u32>().ok(),
+               user:        None,
+               owner:       Some(get(user_col)).filter(|owner| !owner.is_empty()).map(String::from),
+               threads:     get(threads_col).parse::<u32>().ok(),
+               handles:     get(handles_col).parse::<u32>().ok(),
+               cmdline:     None,
+               started:     None,
+           }
+       })
+       .collect())
+}
+
+// `ps -eo comm,pid,args` output. The command name may contain spaces, the first
+// numeric column is the PID, and argv[0] is the executable when it's an absolute path.
+pub fn parse_ps(content: &str) -> Vec<ProcessInfo> {
+    let mut procs = Vec::new();
+
+    for line in content.lines() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let pid_idx = match tokens.iter().position(|t| t.parse::<u32>().is_ok()) {
+            Some(idx) if idx > 0 => idx,
+            // Header or malformed line
+            _                    => continue,
+        };
+
+        let exe_path = tokens.get(pid_idx + 1)
+            .filter(|arg0| arg0.starts_with('/'))
+            .map(|arg0| arg0.to_string())
+            .unwrap_or_default();
+
+        procs.push(ProcessInfo {
+            pid:         tokens[pid_idx].parse::<u32>().unwrap_or(0),
+            name:        tokens[.. pid_idx].join(" "),
+            exe_path:    exe_path,
+            exe_problem: None,
+            exe_denied:  false,
+            ppid:        None,
+            user:        None,
+            owner:       None,
+            threads:     None,
+            handles:     None,
+            cmdline:     None,
+            started:     None,
+        });
+    }
+
+    procs
+}
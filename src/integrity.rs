@@ -0,0 +1,54 @@
+// BONOMEN's own binary checked at startup, a sensor is itself a target: its SHA-256 against the one given with
+// `--self-sha256`, and its detached signature `bonomen.sig` against the key embedded at build time from the
+// BONOMEN_RELEASE_KEY environment variable, else the one of `--config-key`.
+
+use std::env;
+use std::fs;
+
+use sha2::{Digest, Sha256};
+
+use configsig;
+use update::parse_key;
+
+// Expected SHA-256 used when `--self-sha256` isn't given
+pub const HASH_VARIABLE: &'static str = "BONOMEN_SELF_SHA256";
+// Public key release binaries are signed with, their signature is then required
+const RELEASE_KEY: Option<&'static str> = option_env!("BONOMEN_RELEASE_KEY");
+
+// What the binary was verified against, None when there is nothing to verify it against. An error tells how
+// it was tampered with, or why it couldn't be verified.
+pub fn check(expected: Option<&str>) -> Result<Option<String>, String> {
+    let expected = expected.map(String::from).or_else(|| env::var(HASH_VARIABLE).ok());
+    let release  = match RELEASE_KEY {
+        Some(key) => Some(parse_key(key).map_err(|why| format!("invalid embedded release key: {}", why))?),
+        None      => None,
+    };
+    // Nothing to verify against, the binary isn't even looked for
+    if expected.is_none() && release.is_none() && configsig::key().is_none() {
+        return Ok(None);
+    }
+
+    let exe = env::current_exe().map_err(|why| format!("couldn't locate the running binary: {}", why))?;
+    // Without an embedded key, binaries are only verified once signed
+    let sig_path = configsig::signature_path(&exe);
+    let key      = release.or_else(|| configsig::key().filter(|_| sig_path.exists()).cloned());
+    if expected.is_none() && key.is_none() {
+        return Ok(None);
+    }
+
+    let content = fs::read(&exe).map_err(|why| format!("couldn't read {}: {}", exe.display(), why))?;
+    let mut verified = Vec::new();
+    if let Some(expected) = expected {
+        let actual = hex::encode(Sha256::digest(&content));
+        if !actual.eq_ignore_ascii_case(expected.trim()) {
+            return Err(format!("the SHA-256 of {} is {}, {} expected", exe.display(), actual, expected.trim()));
+        }
+        verified.push(String::from("expected SHA-256"));
+    }
+    if let Some(key) = key {
+        configsig::check_signature(&key, &sig_path, &content).map_err(|why| format!("{}: {}", exe.display(), why))?;
+        verified.push(format!("signature {}", sig_path.display()));
+    }
+
+    Ok(if verified.is_empty() { None } else { Some(format!("{}: {}", exe.display(), verified.join(", "))) })
+}
@@ -0,0 +1,65 @@
+// JSON Lines events of the monitor mode, one object per line for log shippers to tail
+
+use std::fs::OpenOptions;
+use std::io::{self, stdout, Write};
+
+use chrono::Local;
+use serde_json::Value;
+
+use report;
+use types::ScanReport;
+
+pub struct EventStream {
+    out : Box<dyn Write>,
+    host: String,
+}
+
+impl EventStream {
+    // Events are appended to `path`, or written to stdout
+    pub fn open(path: Option<&str>) -> io::Result<EventStream> {
+        let out: Box<dyn Write> = match path {
+            Some(path) => Box::new(OpenOptions::new().create(true).append(true).open(path)?),
+            None       => Box::new(stdout()),
+        };
+
+        Ok(EventStream { out: out, host: report::hostname() })
+    }
+
+    pub fn scan_started(&mut self) {
+        self.emit("scan_started", json!({}));
+    }
+
+    // Detections not alerted on yet, of a full scan or of the processes just started
+    pub fn detections(&mut self, report: &ScanReport) {
+        for detection in &report.detections {
+            self.emit("detection", json!({ "detection": report::comparison_json(detection) }));
+        }
+    }
+
+    pub fn scan_finished(&mut self, report: &ScanReport) {
+        self.emit("scan_finished", json!({ "summary": report::summary_json(report) }));
+    }
+
+    fn emit(&mut self, event: &str, fields: Value) {
+        let line = line(event, &self.host, fields);
+
+        // A reader tailing the stream sees every event as soon as it happens
+        if let Err(why) = writeln!(self.out, "{}", line).and_then(|_| self.out.flush()) {
+            warn!("Couldn't write the {} event: {}", event, why);
+        }
+    }
+}
+
+// An event of `host` happening now, with its `fields`
+pub fn line(event: &str, host: &str, fields: Value) -> Value {
+    let mut line = json!({
+        "event":     event,
+        "timestamp": Local::now().to_rfc3339(),
+        "host":      host,
+    });
+    if let (Some(line), Value::Object(fields)) = (line.as_object_mut(), fields) {
+        line.extend(fields);
+    }
+
+    line
+}
@@ -0,0 +1,91 @@
+// Kernel modules loaded with `--kernel-modules`, compared against the expected ones of `kernel_module.NAME`
+// settings: read from /proc/modules on Linux and with EnumDeviceDrivers on Windows.
+
+use std::io;
+
+// A loaded kernel module
+pub struct Module {
+    pub name: String,
+    // File it was loaded from, when known
+    pub path: String,
+}
+
+// Names are compared the way the kernel does, `snd-hda` and `snd_hda` are the same module
+#[cfg(unix)]
+pub fn canonical(name: &str) -> String {
+    name.trim_end_matches(".ko").replace('-', "_")
+}
+
+#[cfg(windows)]
+pub fn canonical(name: &str) -> String {
+    name.to_lowercase()
+}
+
+#[cfg(target_os = "linux")]
+pub fn loaded() -> io::Result<Vec<Module>> {
+    let text = ::std::fs::read_to_string("/proc/modules")?;
+
+    // `name size refcount dependencies state address`
+    Ok(text.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|name| Module {
+            name: name.to_string(),
+            path: String::new(),
+        })
+        .collect())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn loaded() -> io::Result<Vec<Module>> {
+    Err(io::Error::new(io::ErrorKind::Other, "kernel modules are only listed on Linux and Windows"))
+}
+
+#[cfg(windows)]
+pub fn loaded() -> io::Result<Vec<Module>> {
+    use std::mem::size_of;
+    use std::ptr;
+
+    use winapi::shared::minwindef::LPVOID;
+    use winapi::um::psapi::{K32EnumDeviceDrivers, K32GetDeviceDriverBaseNameW, K32GetDeviceDriverFileNameW};
+
+    unsafe {
+        // Drivers may be loaded between two calls, retry with the size needed
+        let mut drivers: Vec<LPVOID> = Vec::new();
+        let mut cb_needed            = 0;
+        loop {
+            if K32EnumDeviceDrivers(drivers.as_mut_ptr(), (drivers.len() * size_of::<LPVOID>()) as u32,
+                                    &mut cb_needed) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let count = cb_needed as usize / size_of::<LPVOID>();
+            if count <= drivers.len() {
+                drivers.truncate(count);
+                break;
+            }
+            drivers = vec![ptr::null_mut(); count];
+        }
+
+        Ok(drivers.into_iter()
+            .filter_map(|base| {
+                let name = ::read_wide(|buf| {
+                    match K32GetDeviceDriverBaseNameW(base, buf.as_mut_ptr(), buf.len() as u32) {
+                        0   => None,
+                        len => Some(len as usize),
+                    }
+                })?;
+                // `\SystemRoot\system32\drivers\...`, the way the kernel sees it
+                let path = ::read_wide(|buf| {
+                    match K32GetDeviceDriverFileNameW(base, buf.as_mut_ptr(), buf.len() as u32) {
+                        0   => None,
+                        len => Some(len as usize),
+                    }
+                }).unwrap_or_default();
+
+                Some(Module {
+                    name: name,
+                    path: path,
+                })
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,83 @@
+// Critical process names bucketed by their length in characters. An edit distance is never smaller than
+// the length difference of two names, a process name is only measured against the critical processes
+// whose length is within their threshold of its own.
+
+use std::collections::HashMap;
+use std::f64;
+
+use similarity::Similarity;
+use types::{NameMatching, ProcProps};
+
+pub struct LengthBuckets {
+    // Names as compared, in the order of the critical processes
+    names  : Vec<String>,
+    // Distance up to which each critical process is measured
+    bounds : Vec<f64>,
+    // Indexes of the critical processes by name length
+    buckets: HashMap<usize, Vec<usize>>,
+    longest: usize,
+    // Largest length difference measured, None to measure every critical process
+    reach  : Option<usize>,
+}
+
+impl LengthBuckets {
+    // Distances within the threshold of each critical process are measured, and within `report_dist`
+    // when given. Normalized algorithms measure every critical process, a length difference is no bound
+    // to a percentage.
+    pub fn new(crit_procs : &[ProcProps],
+               algorithm  : &dyn Similarity,
+               names      : NameMatching,
+               report_dist: Option<f64>) -> LengthBuckets {
+        let normalized  = algorithm.normalized();
+        let mut buckets = HashMap::new();
+        let mut longest = 0;
+        let mut bounds  = Vec::new();
+        let mut crit_names = Vec::new();
+
+        for (index, crit_proc) in crit_procs.iter().enumerate() {
+            let crit_name = names.normalize(&crit_proc.name);
+            let length    = crit_name.chars().count();
+            let limit     = crit_proc.threshold.limit(&crit_proc.name, normalized);
+            buckets.entry(length).or_insert_with(Vec::new).push(index);
+            longest = longest.max(length);
+            bounds.push(report_dist.map_or(limit, |report_dist| limit.max(report_dist)));
+            crit_names.push(crit_name);
+        }
+        let widest = bounds.iter().cloned().fold(0.0, f64::max);
+        let reach  = if normalized || !widest.is_finite() { None } else { Some(widest.floor() as usize) };
+
+        LengthBuckets {
+            names:   crit_names,
+            bounds:  bounds,
+            buckets: buckets,
+            longest: longest,
+            reach:   reach,
+        }
+    }
+
+    // Every critical process measured, however far
+    pub fn all(crit_procs: &[ProcProps], algorithm: &dyn Similarity, names: NameMatching) -> LengthBuckets {
+        LengthBuckets::new(crit_procs, algorithm, names, Some(f64::INFINITY))
+    }
+
+    // Distance between `name`, as compared, and every critical process. Those out of reach are infinitely far.
+    pub fn distances(&self, name: &str, algorithm: &dyn Similarity) -> Vec<f64> {
+        let reach = match self.reach {
+            Some(reach) => reach,
+            None        => return self.names.iter().map(|crit_name| algorithm.distance(name, crit_name)).collect(),
+        };
+
+        let length        = name.chars().count();
+        let mut distances = vec![f64::INFINITY; self.names.len()];
+        for crit_length in length.saturating_sub(reach) ..= (length + reach).min(self.longest) {
+            let gap = (crit_length as f64 - length as f64).abs();
+            for &index in self.buckets.get(&crit_length).into_iter().flatten() {
+                if gap <= self.bounds[index] {
+                    distances[index] = algorithm.distance(name, &self.names[index]);
+                }
+            }
+        }
+
+        distances
+    }
+}
@@ -1,22 +1,39 @@
-use log::{LogRecord, LogLevel, LogMetadata};
+use std::io::{stderr, Write};
 
-struct BonomenLogger;
+use log::{LogRecord, LogLevelFilter, LogMetadata};
+
+// Diagnostics go to stderr, stdout is kept for detections
+struct BonomenLogger {
+    level: LogLevelFilter,
+}
 
 impl ::log::Log for BonomenLogger {
     fn enabled(&self, metadata: &LogMetadata) -> bool {
-        metadata.level() <= LogLevel::Info
+        metadata.level() <= self.level
     }
 
     fn log(&self, record: &LogRecord) {
         if self.enabled(record.metadata()) {
-            println!("{} - {}", record.level(), record.args());
+            let _ = writeln!(stderr(), "{} - {}", record.level(), record.args());
         }
     }
 }
 
-pub fn init() -> Result<(), ::log::SetLoggerError> {
+// Map the number of -v flags to a level: warnings by default, then info, debug and trace.
+// Quiet mode only keeps errors.
+pub fn level(verbosity: u64, quiet: bool) -> LogLevelFilter {
+    match (quiet, verbosity) {
+        (true, _) => LogLevelFilter::Error,
+        (_, 0)    => LogLevelFilter::Warn,
+        (_, 1)    => LogLevelFilter::Info,
+        (_, 2)    => LogLevelFilter::Debug,
+        _         => LogLevelFilter::Trace,
+    }
+}
+
+pub fn init(level: LogLevelFilter) -> Result<(), ::log::SetLoggerError> {
     ::log::set_logger(|max_log_level| {
-        max_log_level.set(::log::LogLevelFilter::Info);
-        Box::new(BonomenLogger)
+        max_log_level.set(level);
+        Box::new(BonomenLogger { level: level })
     })
 }
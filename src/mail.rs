@@ -0,0 +1,279 @@
+// Digest emails of the detections of a scan, sent over SMTP to whoever has no SIEM to watch
+
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::Local;
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use report;
+use types::ScanReport;
+
+const TIMEOUT_SECS: u64 = 30;
+
+// How the connection to the server is secured
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tls {
+    // Plain text, only for relays on the local network
+    None,
+    // Upgraded with STARTTLS after connecting, port 587 by default
+    StartTls,
+    // TLS from the start, port 465 by default
+    Implicit,
+}
+
+impl Tls {
+    pub fn from_name(name: &str) -> Option<Tls> {
+        match name {
+            "none"     => Some(Tls::None),
+            "starttls" => Some(Tls::StartTls),
+            "tls"      => Some(Tls::Implicit),
+            _          => None,
+        }
+    }
+
+    fn default_port(&self) -> u16 {
+        match *self {
+            Tls::None     => 25,
+            Tls::StartTls => 587,
+            Tls::Implicit => 465,
+        }
+    }
+}
+
+// `smtp.*` settings of the critical processes file
+#[derive(Clone)]
+pub struct Settings {
+    // HOST or HOST:PORT
+    pub server  : Option<String>,
+    pub tls     : Tls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from    : Option<String>,
+    pub to      : Vec<String>,
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        Settings {
+            server:   None,
+            tls:      Tls::StartTls,
+            username: None,
+            password: None,
+            from:     None,
+            to:       Vec::new(),
+        }
+    }
+
+    // Parse the `smtp.NAME = VALUE` setting
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "server"   => self.server   = Some(value.to_string()),
+            "username" => self.username = Some(value.to_string()),
+            "password" => self.password = Some(value.to_string()),
+            "from"     => self.from     = Some(value.to_string()),
+            "to"       => self.to.push(value.to_string()),
+            "tls"      => match Tls::from_name(value) {
+                Some(tls) => self.tls = tls,
+                None      => return Err(format!("unknown TLS mode {}, expected none, starttls or tls", value)),
+            },
+            _          => return Err(format!("unknown SMTP setting: smtp.{}", name)),
+        }
+
+        Ok(())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.server.is_some()
+    }
+}
+
+// Email the detections of `report` to every recipient, in a single message
+pub fn send(settings: &Settings, report: &ScanReport) -> Result<(), String> {
+    let server = settings.server.as_ref().ok_or_else(|| String::from("no SMTP server"))?;
+    let from   = settings.from.as_ref().ok_or_else(|| String::from("smtp.from isn't set"))?;
+    if settings.to.is_empty() {
+        return Err(String::from("smtp.to isn't set"));
+    }
+    let (host, port) = match server.rfind(':') {
+        Some(pos) => (&server[.. pos], server[pos + 1 ..].parse::<u16>()
+                      .map_err(|_| format!("invalid SMTP server port: {}", server))?),
+        None      => (server.as_str(), settings.tls.default_port()),
+    };
+
+    if settings.tls == Tls::None && settings.username.is_some() {
+        warn!("SMTP credentials are sent in plain text, smtp.tls = none");
+    }
+
+    let mut session = Session::connect(host, port, settings.tls).map_err(|why| format!("{}: {}", server, why))?;
+    session.deliver(settings, from, &message(from, &settings.to, report))
+        .map_err(|why| format!("{}: {}", server, why))
+}
+
+// Headers and a plain text body listing the detections
+fn message(from: &str, to: &[String], report: &ScanReport) -> String {
+    let host = report.host.clone().unwrap_or_else(report::hostname);
+
+    let mut body = format!("BONOMEN found {} suspicious processes on {}.\r\n\r\n", report.detections.len(), host);
+    for detection in &report.detections {
+        body.push_str(&format!("[{}] {}: {} (PID {}) running from {}, imitates {} at distance {}\r\n",
+                               detection.severity.name(), detection.reason, detection.name, detection.pid,
+                               detection.exe_path, detection.crit_name, detection.distance));
+        if !detection.module.is_empty() {
+            body.push_str(&format!("    module: {}\r\n", detection.module));
+        }
+        if let Some(ref container) = detection.container {
+            body.push_str(&format!("    in {}\r\n", container));
+        }
+        if let Some(ref exe_info) = detection.exe_info {
+            body.push_str(&format!("    executable: {}\r\n", exe_info));
+        }
+        for &(ref name, ref value) in &detection.environment {
+            body.push_str(&format!("    environment: {}={}\r\n", name, value));
+        }
+        if !detection.yara.is_empty() {
+            body.push_str(&format!("    YARA rules: {}\r\n", detection.yara.join(", ")));
+        }
+        if let Some(ref verdict) = detection.virustotal {
+            body.push_str(&format!("    VirusTotal: {}/{} engines\r\n", verdict.malicious, verdict.engines));
+        }
+    }
+    body.push_str(&format!("\r\nProcesses scanned: {}, comparisons: {}, elapsed: {:.3}s\r\n",
+                           report.scanned, report.compared, report::seconds(report.elapsed)));
+
+    format!("From: {}\r\nTo: {}\r\nSubject: BONOMEN: {} suspicious processes on {}\r\nDate: {}\r\n\
+             MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+            from, to.join(", "), report.detections.len(), host, Local::now().to_rfc2822(), body)
+}
+
+trait Stream: Read + Write {}
+impl<T: Read + Write> Stream for T {}
+
+// A conversation with an SMTP server, RFC 5321
+struct Session {
+    stream: Box<dyn Stream>,
+    host  : String,
+}
+
+impl Session {
+    fn connect(host: &str, port: u16, tls: Tls) -> io::Result<Session> {
+        let tcp = TcpStream::connect((host, port))?;
+        tcp.set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECS)))?;
+        tcp.set_write_timeout(Some(Duration::from_secs(TIMEOUT_SECS)))?;
+
+        if tls == Tls::Implicit {
+            let mut session = Session { stream: Box::new(secure(host, tcp)?), host: host.to_string() };
+            session.greet()?;
+            return Ok(session);
+        }
+
+        // Replies are read byte by byte, nothing is left behind in the plain text stream
+        let mut session = Session { stream: Box::new(tcp.try_clone()?), host: host.to_string() };
+        session.greet()?;
+        if tls == Tls::StartTls {
+            session.command("STARTTLS", 220)?;
+            session.stream = Box::new(secure(host, tcp)?);
+            session.command(&format!("EHLO {}", report::hostname()), 250)?;
+        }
+
+        Ok(session)
+    }
+
+    fn greet(&mut self) -> io::Result<()> {
+        self.expect(220)?;
+        self.command(&format!("EHLO {}", report::hostname()), 250)
+    }
+
+    fn deliver(&mut self, settings: &Settings, from: &str, message: &str) -> io::Result<()> {
+        if let (&Some(ref username), &Some(ref password)) = (&settings.username, &settings.password) {
+            let credentials = BASE64.encode(format!("\0{}\0{}", username, password));
+            self.command(&format!("AUTH PLAIN {}", credentials), 235)?;
+        }
+
+        self.command(&format!("MAIL FROM:<{}>", from), 250)?;
+        for to in &settings.to {
+            self.command(&format!("RCPT TO:<{}>", to), 250)?;
+        }
+        self.command("DATA", 354)?;
+
+        // Lines starting with a dot would end the message early
+        let mut data = String::new();
+        for line in message.split("\r\n") {
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push('.');
+        self.command(&data, 250)?;
+
+        self.command("QUIT", 221)
+    }
+
+    fn command(&mut self, command: &str, expected: u16) -> io::Result<()> {
+        if command.starts_with("AUTH") {
+            debug!("SMTP > AUTH PLAIN ...");
+        } else {
+            debug!("SMTP > {}", command.lines().next().unwrap_or(""));
+        }
+        self.stream.write_all(command.as_bytes())?;
+        self.stream.write_all(b"\r\n")?;
+        self.stream.flush()?;
+
+        self.expect(expected)
+    }
+
+    // Replies span lines `CODE-text` up to the last one, `CODE text`
+    fn expect(&mut self, expected: u16) -> io::Result<()> {
+        loop {
+            let line = self.read_line()?;
+            debug!("SMTP < {}", line);
+            let code = line.get(.. 3).and_then(|code| code.parse::<u16>().ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("invalid reply: {}", line)))?;
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+            // 251, the recipient will be forwarded, is as good as 250
+            if code == expected || (expected == 250 && code == 251) {
+                return Ok(());
+            }
+
+            return Err(io::Error::new(io::ErrorKind::Other, format!("{} replied: {}", self.host, line)));
+        }
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = Vec::new();
+        let mut byte = [0u8; 1];
+        loop {
+            if self.stream.read(&mut byte)? == 0 {
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed"));
+            }
+            if byte[0] == b'\n' {
+                break;
+            }
+            line.push(byte[0]);
+        }
+
+        Ok(String::from_utf8_lossy(&line).trim_end_matches('\r').to_string())
+    }
+}
+
+// TLS over `tcp`, verified against the Mozilla root certificates
+fn secure(host: &str, tcp: TcpStream) -> io::Result<StreamOwned<ClientConnection, TcpStream>> {
+    let roots  = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+    let config = ClientConfig::builder().with_root_certificates(roots).with_no_client_auth();
+    let name   = ServerName::try_from(host.to_string())
+        .map_err(|why| io::Error::new(io::ErrorKind::InvalidInput, why.to_string()))?;
+    let conn   = ClientConnection::new(Arc::new(config), name)
+        .map_err(|why| io::Error::new(io::ErrorKind::Other, why.to_string()))?;
+
+    Ok(StreamOwned::new(conn, tcp))
+}
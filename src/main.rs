@@ -1,61 +1,140 @@
 #[macro_use]
 extern crate clap;
+#[macro_use]
 extern crate log;
+#[macro_use]
+extern crate serde_json;
+#[macro_use]
+extern crate rusqlite;
+extern crate chrono;
+extern crate rayon;
+extern crate glob;
+extern crate regex;
+extern crate sha2;
+extern crate ureq;
+extern crate ed25519_dalek;
+extern crate hex;
+extern crate ratatui;
+extern crate rustls;
+extern crate webpki_roots;
+extern crate base64;
+extern crate uuid;
+#[cfg(feature = "yara")]
+extern crate yara;
 extern crate strsim;
 extern crate term;
 
 #[cfg(windows)]
 extern crate winapi;
 #[cfg(windows)]
-extern crate kernel32;
+#[macro_use]
+extern crate windows_service;
 
 #[cfg(unix)]
 extern crate psutil;
 #[cfg(unix)]
 extern crate libc;
+#[cfg(target_os = "linux")]
+extern crate md5;
 
-use clap::{Arg, App};
+use chrono::{DateTime, Local};
+use clap::ArgMatches;
+use rayon::prelude::*;
 
+use std::cmp::{max, min};
+use std::collections::HashMap;
 #[cfg(unix)]
-use psutil::process::Process;
-use strsim::damerau_levenshtein;
-use std::fs::File;
+use std::env;
+use std::fs;
 use std::path::Path;
-#[cfg(unix)]
 use std::path::PathBuf;
-use std::io::{BufRead, BufReader, Write, stdout};
+use std::io::{BufRead, BufReader, Write, stdout, stderr};
 #[cfg(unix)]
-use std::process::exit;
-#[cfg(windows)]
-use std::mem::size_of;
-#[cfg(windows)]
-use std::ptr;
-
-#[cfg(windows)]
-use winapi::winnt::PROCESS_QUERY_INFORMATION;
-#[cfg(windows)]
-use winapi::winnt::PROCESS_VM_READ;
-#[cfg(windows)]
-use winapi::minwindef::HMODULE;
+use std::ffi::CString;
 #[cfg(windows)]
-use winapi::minwindef::DWORD;
-#[cfg(windows)]
-use winapi::minwindef::FALSE;
-#[cfg(windows)]
-use winapi::psapi::LIST_MODULES_ALL;
+use std::ffi::OsString;
+use std::process::exit;
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+mod api;
+mod baseline;
+mod bench;
+mod cancel;
+mod cli;
+mod collect;
+mod configsig;
+mod confusables;
+#[cfg(unix)]
+mod container;
+mod control;
+mod dedup;
+mod distcache;
+mod defaults;
+mod doctor;
+mod environ;
+mod error;
+mod explain;
+mod fileinfo;
+mod fim;
+mod hash;
+mod history;
 #[cfg(windows)]
-use kernel32::OpenProcess;
-#[cfg(windows)]
-use kernel32::K32EnumProcessModulesEx;
-#[cfg(windows)]
-use kernel32::K32GetModuleBaseNameW;
+mod hollowing;
+mod input;
+mod integrity;
+mod kernelmods;
+mod jsonl;
+mod lengths;
+mod logger;
+mod mail;
+mod messages;
+mod metrics;
+mod misp;
+mod modules;
+mod netconn;
+mod output;
+#[cfg(target_os = "linux")]
+mod packages;
+mod pathenv;
+mod persistence;
+#[cfg(all(any(target_os = "linux", windows), feature = "exec-events"))]
+mod procevents;
+mod progress;
+mod push;
+mod reload;
+mod remote;
+mod report;
+mod response;
+mod review;
+mod rules;
+mod sarif;
+mod schedule;
+mod server;
 #[cfg(windows)]
-use kernel32::K32EnumProcesses;
+mod signature;
+mod similarity;
+mod staging;
+mod stix;
+#[cfg(unix)]
+mod systemd;
+mod template;
+mod trends;
+mod tui;
+mod types;
+mod update;
+mod virustotal;
+mod webhook;
+mod whitelist;
+#[cfg(feature = "yara")]
+mod yarascan;
 #[cfg(windows)]
-use kernel32::K32GetModuleFileNameExW;
+mod winservice;
 
-mod types;
+use error::ScanError;
+use output::Output;
+use similarity::Similarity;
 
 const BONOMEN_BANNER: &'static str = r"
       =======  ======= ==    == ======= ========== ====== ==    ==
@@ -66,289 +145,3091 @@ const BONOMEN_BANNER: &'static str = r"
 
 const DEFAULT_FILE: &'static str = "default_procs.txt";
 
+// Appended by the kernel to /proc/<pid>/exe once the executable is removed
+#[cfg(unix)]
+const DELETED_SUFFIX: &'static str = " (deleted)";
+
 fn main() {
+    if let Err(why) = run() {
+        let _ = stdout().flush();
+        let _ = writeln!(stderr(), "{}", why);
+
+        exit(why.exit_code());
+    }
+}
+
+fn run() -> Result<(), ScanError> {
     // Handle command line arguments
-    let matches = App::new(BONOMEN_BANNER)
-        .version(crate_version!())
-        .author(crate_authors!())
-        .about("Detect critical process impersonation")
-        .arg(Arg::with_name("file")
-             .short("f")
-             .long("file")
-             .value_name("FILE")
-             .help("File containing critical processes path, threshold, whitelist")
-             .takes_value(true))
-        .arg(Arg::with_name("verbose")
-             .short("v")
-             .long("verbose")
-             .help("Verbose mode"))
-        .get_matches();
-
-    let mut terminal = term::stdout().unwrap();
-    if terminal.supports_attr(term::Attr::Bold) {
-        match terminal.attr(term::Attr::Bold) {
-            Ok(ok)   => ok,
-            Err(why) => println!("{}", why.to_string()),
-        }
-    }
-
-    println!("{}\n\tAuthor(s):{} Version:{}\n",
-             BONOMEN_BANNER, crate_authors!(), crate_version!());
-    terminal.reset().unwrap();
+    let matches = cli::build(BONOMEN_BANNER).get_matches();
 
-    #[cfg(unix)]
-    unsafe {
-       	if libc::geteuid() != 0 {
-            terminal.attr(term::Attr::Bold).unwrap();
-            terminal.fg(term::color::RED).unwrap();
-            println!("{}", "BONOMEN needs root privileges to read process executable path!");
-            terminal.reset().unwrap();
-            let _ = stdout().flush();
-            
-            exit(0);
-        }
-    };
+    if matches.is_present("dump-defaults") {
+        print!("{}", defaults::DEFAULT_PROCS);
+        let _ = stdout().flush();
 
-    let file_name = matches.value_of("file").unwrap_or(DEFAULT_FILE);
-    let verb_mode = if matches.is_present("verbose") { true } else { false };
+        return Ok(());
+    }
 
-    // Load known standard system processes
-    terminal.fg(term::color::GREEN).unwrap();
-    println!("Standard processes file: {}", file_name);
-    terminal.reset().unwrap();
-    let crit_proc_vec = read_procs_file(&file_name);
+    let quiet = matches.is_present("quiet");
+    let mut terminal = Output::new(!quiet && !matches.is_present("no-color"));
 
-    let r;
+    let sub_matches = matches.subcommand().1.unwrap_or(&matches);
+    let verbosity   = sub_matches.occurrences_of("verbose");
+    if let Err(why) = logger::init(logger::level(verbosity, quiet)) {
+        let _ = writeln!(stderr(), "couldn't set up logging: {}", why.to_string());
+    }
+    messages::init(matches.value_of("lang")).map_err(ScanError::Config)?;
+    configsig::init(matches.value_of("config-key"), matches.value_of("config-signature"))?;
+    // Before anything the binary does could be trusted
+    match integrity::check(matches.value_of("self-sha256")) {
+        Ok(Some(verified)) => debug!("Binary verified, {}", verified),
+        Ok(None)           => debug!("No expected hash or signature to verify the binary against"),
+        Err(why)           => return Err(ScanError::Integrity(why)),
+    }
 
-    #[cfg(unix)] {
-        // Read current active processes
-        let sys_procs_vec = read_unix_system_procs();
-        // Check for process name impersonation
-        r = unix_check_procs_impers(&crit_proc_vec, &sys_procs_vec, &verb_mode, &mut terminal);
+    // JSON Lines events, SARIF logs and completion scripts on stdout are meant for programs, not a banner
+    let program_stdout = match sub_matches.value_of("output") {
+        Some("jsonl") => !sub_matches.is_present("output-file"),
+        Some("sarif") => true,
+        _             => matches.subcommand_name() == Some("completions"),
+    };
+    if !quiet && !program_stdout {
+        terminal.bold();
+        println!("{}\n\tAuthor(s):{} Version:{}\n",
+                 BONOMEN_BANNER, crate_authors!(), crate_version!());
+        terminal.reset();
     }
 
-    #[cfg(windows)] {
-        let sys_procs_vec = read_win_system_procs(&mut terminal);
+    let result = match matches.subcommand() {
+        ("doctor", Some(sub_m))   => {
+            let files: Vec<&str> = sub_m.values_of("file").map(|v| v.collect()).unwrap_or_default();
+            if !doctor::run(&files, sub_m.value_of("profile"), sub_m.value_of("report"), &mut terminal) {
+                let _ = stdout().flush();
+                exit(1);
+            }
+            Ok(())
+        },
+        ("config", Some(sub_m))   => config_command(sub_m, &mut terminal),
+        ("baseline", Some(sub_m)) => baseline_command(sub_m, &mut terminal),
+        ("fim", Some(sub_m))      => fim_command(sub_m, &mut terminal),
+        ("monitor", Some(sub_m))  => monitor(sub_m, &mut terminal),
+        ("status", Some(sub_m))   => status_command(sub_m, &mut terminal),
+        ("history", Some(sub_m))  => history_command(sub_m, &mut terminal),
+        ("trends", Some(sub_m))   => trends_command(sub_m, &mut terminal),
+        ("server", Some(sub_m))   => server_command(sub_m),
+        ("scan", Some(sub_m)) if sub_m.is_present("bench") => bench_command(sub_m, &mut terminal),
+        ("scan", Some(sub_m))     => scan(sub_m, &mut terminal).map(exit_on_detections),
+        ("check", Some(sub_m))    => check_command(sub_m, &mut terminal),
+        ("remote", Some(sub_m))   => remote_scan(sub_m, &mut terminal).map(exit_on_detections),
+        ("tui", Some(sub_m))      => tui_command(sub_m),
+        ("update", Some(sub_m))   => update_command(sub_m, &mut terminal),
+        ("resume", Some(sub_m))   => resume_command(sub_m, &mut terminal),
+        ("completions", Some(sub_m)) => completions_command(sub_m),
+        ("whitelist", Some(sub_m))   => whitelist_command(sub_m, &mut terminal),
+        #[cfg(unix)]
+        ("install-service", Some(sub_m)) => install_service_command(sub_m, &mut terminal),
+        #[cfg(windows)]
+        ("service", Some(sub_m))  => service_command(sub_m, &mut terminal),
+        _ if matches.is_present("bench") => bench_command(&matches, &mut terminal),
+        _                         => scan(&matches, &mut terminal).map(exit_on_detections),
+    };
+    let _ = stdout().flush();
+
+    result
+}
 
-        r = win_check_procs_impers(&crit_proc_vec, &sys_procs_vec, &verb_mode, &mut terminal);
+// A scan that reported detections exits with status 1, one interrupted with Ctrl-C with status 130
+fn exit_on_detections(r: types::ScanReport) {
+    if r.interrupted {
+        let _ = stdout().flush();
+        exit(cancel::EXIT_CODE);
+    }
+    if r.detections.len() > 0 {
+        let _ = stdout().flush();
+        exit(1);
     }
+}
 
-    if r > 0 {
-        terminal.fg(term::color::RED).unwrap();
-    } else {
-        terminal.fg(term::color::GREEN).unwrap();
+// What reading the executable of other users' processes takes
+#[cfg(target_os = "linux")]
+const PRIVILEGES: &'static str = "root or CAP_SYS_PTRACE";
+#[cfg(all(unix, not(target_os = "linux")))]
+const PRIVILEGES: &'static str = "root";
+
+// On Linux the capability is what matters: root may have dropped it, and setcap can grant it alone
+#[cfg(target_os = "linux")]
+fn privileged() -> bool {
+    const CAP_SYS_PTRACE: u32 = 19;
+
+    match effective_capabilities() {
+        Some(caps) => caps & (1 << CAP_SYS_PTRACE) != 0,
+        None       => unsafe { libc::geteuid() == 0 },
     }
-    println!("Found {} suspicious processes.\n{}", r, "Done!");
-    terminal.reset().unwrap();
-    let _ = stdout().flush();
 }
 
-// Read standard system processes from a file.
-// Each line in the file is of the format:
-// <process name>:<threshold value>:<process absolute path>
-fn read_procs_file(file_name: &str) -> Vec<types::ProcProps> {
-    let path    = Path::new(file_name);
-    let display = path.display();
+#[cfg(all(unix, not(target_os = "linux")))]
+fn privileged() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
 
-    let file = match File::open(&path) {
-        Err(why) => panic!("couldn't open {}: {}", display, why.to_string()),
-        Ok(file) => file,
-    };
+// Effective capability set of this process, as /proc/self/status shows it
+#[cfg(target_os = "linux")]
+fn effective_capabilities() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let caps   = status.lines().find(|line| line.starts_with("CapEff:"))?["CapEff:".len() ..].trim();
+
+    u64::from_str_radix(caps, 16).ok()
+}
+
+// Unprivileged, the executables of other users' processes can't be read, those are only checked by name
+#[cfg(unix)]
+fn warn_unprivileged() {
+    if !privileged() {
+        warn!("Running without {}, processes of other users are only checked by name", PRIVILEGES);
+    }
+}
 
-    let mut procs = Vec::new();
+#[cfg(windows)]
+fn warn_unprivileged() {
+}
+
+#[cfg(unix)]
+fn check_privileges() -> Result<(), ScanError> {
+    if !privileged() {
+        return Err(ScanError::Permission(
+            format!("BONOMEN needs {} to read process executable path!", PRIVILEGES)));
+    }
 
-    // Read whole file line by line, and unwrap each line
-    let reader = BufReader::new(file);
-    let lines  = reader.lines().map(|l| l.unwrap());
+    Ok(())
+}
 
-    for line in lines {
-        // Split each line into a vector
-        let v: Vec<_> = line.split(';').map(|s| s.to_string()).collect();
-        assert!(v.len() >= 3, "Invalid format, line: {}", line);
-        let mut wl    = Vec::new();
+#[cfg(windows)]
+fn check_privileges() -> Result<(), ScanError> {
+    Ok(())
+}
 
-        // Push process absolute path, may be more than 1 path
-        for i in 2 .. v.len() {
-            wl.push(v[i].to_string());
+// Size the global thread pool used for comparisons, may only be done once, and give operations on
+// single processes their timeout
+fn setup_threads(matches: &ArgMatches) -> Result<(), ScanError> {
+    if let Some(timeout) = matches.value_of("op-timeout") {
+        match timeout.parse::<u64>() {
+            Ok(timeout) => cancel::set_timeout(Duration::from_secs(timeout)),
+            Err(_)      => return Err(ScanError::Config(format!("invalid operation timeout: {}", timeout))),
         }
+    }
+    if let Some(threads) = matches.value_of("threads") {
+        let threads = match threads.parse::<usize>() {
+            Ok(threads) => threads,
+            Err(_)      => return Err(ScanError::Config(format!("invalid number of threads: {}", threads))),
+        };
+        if let Err(why) = rayon::ThreadPoolBuilder::new().num_threads(threads).build_global() {
+            return Err(ScanError::Config(format!("couldn't start {} threads: {}", threads, why)));
+        }
+    }
 
-        procs.push(types::ProcProps {
-            name:      v[0].to_string(),
-            threshold: v[1].parse::<u32>().unwrap(),
-            whitelist: wl
-        });
+    Ok(())
+}
+
+// Run a single scan
+fn scan(matches: &ArgMatches, terminal: &mut Output) -> Result<types::ScanReport, ScanError> {
+    // A captured listing can be checked without any privileges
+    if !matches.is_present("input-procs") {
+        warn_unprivileged();
     }
+    setup_threads(matches)?;
+    cancel::stop_on_interrupt();
 
-    procs
+    scan_once(matches, terminal, None, None, None)
 }
 
-fn is_whitelisted(proc_path: &str, whitelist: &Vec<std::string::String>) -> bool {
-    whitelist.iter().any(|p| p == proc_path)
+// A scan of synthetic processes, timed. Detections are what it measures and don't change the exit status.
+fn bench_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    setup_threads(matches)?;
+    cancel::stop_on_interrupt();
+
+    let r = scan_once(matches, terminal, None, None, None)?;
+    bench::print(&r);
+
+    Ok(())
 }
 
-// Read running processes
-#[cfg(unix)]
-fn read_unix_system_procs() -> Vec<Process> {
-    psutil::process::all().unwrap()
+// Detection runs locally on the remote process list, no local privileges are needed
+fn remote_scan(matches: &ArgMatches, terminal: &mut Output) -> Result<types::ScanReport, ScanError> {
+    setup_threads(matches)?;
+    cancel::stop_on_interrupt();
+
+    if !matches.is_present("quiet") && matches.value_of("output") != Some("sarif") {
+        terminal.fg(term::color::GREEN);
+        println!("{}", messages::text("scan.remote_host", &[&matches.value_of("host").unwrap_or("")]));
+        terminal.reset();
+    }
+
+    scan_once(matches, terminal, None, None, None)
 }
 
-#[cfg(windows)]
-fn read_win_system_procs(terminal: &mut Box<term::StdoutTerminal>) -> Vec<types::WinProc> {
-    let mut win_procs = Vec::new();
-
-    const SIZE: usize = 1024;
-    let mut pids = [0; SIZE];
-    let mut written = 0;
-    unsafe {
-        if K32EnumProcesses(pids.as_mut_ptr(), (pids.len() * size_of::<DWORD>()) as u32, &mut written) == 0 {
-            terminal.fg(term::color::RED).unwrap();
-            println!("{}", "K32EnumProcesses failed!");
-            terminal.reset().unwrap();
-
-            return win_procs;
-        }
-    }
-    let processes = &pids[..(written / size_of::<DWORD>() as u32) as usize]; // Slice trick thanks to WindowsBunny @ #rust
-
-    const NAME_SZ: usize = 64;
-    let mut sz_process_name = [0; NAME_SZ];
-    const PATH_SZ: usize = 254;
-    let mut sz_process_path = [0; PATH_SZ];
-    
-    for i in 0 .. processes.len() {
-        let process_id: DWORD = processes[i];
-        unsafe {
-            let h_process = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, FALSE, process_id);
-	    
-            
-            if !h_process.is_null() {
-                let h_mod     = ptr::null_mut();
-                let cb_needed = ptr::null_mut();
-	        
-                if K32EnumProcessModulesEx(h_process, h_mod, size_of::<HMODULE>() as u32, cb_needed, LIST_MODULES_ALL) > 0 {
-                    terminal.fg(term::color::RED).unwrap();
-                    println!("PID: {} {}", process_id, "K32EnumProcessModules failed!");
-                    terminal.reset().unwrap();
+// Scan again every `--interval` seconds, until interrupted
+fn monitor(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    if !matches.is_present("input-procs") {
+        warn_unprivileged();
+    }
+    setup_threads(matches)?;
 
-                    continue;
-                } else {
-                    if K32GetModuleBaseNameW(h_process, *h_mod, sz_process_name.as_mut_ptr(), NAME_SZ as u32) == 0 {
-                        terminal.fg(term::color::RED).unwrap();
-                        println!("PID: {} {}", process_id, "K32GetModuleBaseNameW failed!");
-                        terminal.reset().unwrap();
-
-                        continue;
-                    } else {
-                        if K32GetModuleFileNameExW(h_process, *h_mod, sz_process_path.as_mut_ptr(), PATH_SZ as u32) == 0 {
-                            terminal.fg(term::color::RED).unwrap();
-                            println!("PID: {} {}", process_id, "K32GetModuleFileNameExW failed!");
-                            terminal.reset().unwrap();
-
-                            continue;
-                        }
+    let interval = match matches.value_of("interval").unwrap_or("60").parse::<u64>() {
+        Ok(interval) => Duration::from_secs(interval),
+        Err(_)       => return Err(ScanError::Config(String::from("invalid interval"))),
+    };
+    let schedule = match matches.value_of("schedule") {
+        Some(expression) => Some(schedule::Schedule::parse(expression)
+                                 .map_err(|why| ScanError::Config(format!("invalid schedule: {}", why)))?),
+        None             => None,
+    };
+    let jitter   = match matches.value_of("jitter").unwrap_or("0").parse::<i64>().ok()
+        .filter(|&jitter| jitter >= 0)
+        .and_then(::chrono::Duration::try_seconds) {
+        Some(jitter) => schedule::host_jitter(&report::hostname(), jitter),
+        None         => return Err(ScanError::Config(String::from("invalid jitter"))),
+    };
+    if schedule.is_some() && matches.occurrences_of("interval") > 0 {
+        warn!("Scans follow the schedule, --interval is ignored");
+    }
+
+    let metrics = Arc::new(Mutex::new(metrics::Metrics::default()));
+    if let Some(addr) = matches.value_of("metrics-addr") {
+        metrics::serve(addr, metrics.clone()).map_err(ScanError::Config)?;
+        info!("Serving metrics on http://{}/metrics", addr);
+    }
+
+    let files: Vec<&str> = matches.values_of("file").map(|v| v.collect()).unwrap_or_default();
+    let mut config = reload::Reloader::load(&files, matches.value_of("profile"))?;
+    #[cfg(unix)]
+    reload::reload_on_hangup();
+
+    let status = Arc::new(Mutex::new(control::Status::new(config.source().to_string())));
+    let control_path = matches.value_of("control").unwrap_or(control::DEFAULT_PATH);
+    match control::serve(control_path, status.clone()) {
+        Ok(())   => info!("Answering status requests on {}", control_path),
+        Err(why) => warn!("`bonomen status` won't reach this monitor: {}", why),
+    }
+    let api = match matches.value_of("api") {
+        Some(path) => {
+            let api = api::serve(path, status.clone()).map_err(ScanError::Config)?;
+            info!("Taking API requests on {}", path);
+            Some(api)
+        },
+        None       => None,
+    };
+
+    let mut alerted = dedup::Alerted::new(realert_after(matches)?);
+    let distances   = Arc::new(distcache::DistanceCache::new());
+    let mut stream  = match matches.value_of("output") {
+        Some("jsonl") => Some(jsonl::EventStream::open(matches.value_of("output-file"))
+                              .map_err(|why| ScanError::Config(format!("couldn't open the output file: {}", why)))?),
+        _             => None,
+    };
+    if stream.is_none() && matches.is_present("output-file") {
+        warn!("Only JSON Lines events are written to a file, --output-file is ignored without --output jsonl");
+    }
+
+    #[cfg(unix)]
+    let watchdog = systemd::watchdog_interval();
+
+    #[cfg(all(any(target_os = "linux", windows), feature = "exec-events"))]
+    let events = if matches.is_present("exec-events") && (matches.is_present("input-procs") || matches.is_present("host")) {
+        warn!("Process events only come from the local system, --exec-events is ignored");
+        None
+    } else if matches.is_present("exec-events") {
+        let events = procevents::exec_events()
+            .map_err(|why| ScanError::Enum(format!("couldn't subscribe to process events: {}", why)))?;
+        info!("Checking processes as they start");
+        Some(events)
+    } else {
+        None
+    };
+
+    loop {
+        let start = Instant::now();
+        if let Some(ref mut stream) = stream {
+            stream.scan_started();
+        }
+        let r     = scan_once(matches, terminal, Some(&alerted), Some(&distances), Some(config.current()))?;
+        if let Some(ref mut stream) = stream {
+            stream.detections(&r);
+            stream.scan_finished(&r);
+        }
+        if let Some(ref api) = api {
+            api.publish(&r, true);
+        }
+        alerted.update(&r);
+        if let Ok(mut metrics) = metrics.lock() {
+            metrics.record(&r, start.elapsed());
+        }
+        let _ = stdout().flush();
+
+        let (wait, next_scan) = match schedule {
+            Some(ref schedule) => scheduled_wait(schedule, jitter)?,
+            None               => (interval, Local::now() + ::chrono::Duration::seconds(interval.as_secs() as i64)),
+        };
+        info!("Next scan at {}", next_scan.format("%Y-%m-%d %H:%M:%S"));
+        if let Ok(mut status) = status.lock() {
+            status.record(&r, next_scan);
+        }
+
+        #[cfg(unix)]
+        systemd::notify(&format!("READY=1\nSTATUS=Last scan found {} suspicious processes, next scan at {}",
+                                 r.detections.len() + r.repeated.len(), next_scan.format("%H:%M:%S")));
+
+        // Wait for the next scan, scanning right away once the configuration changed
+        let deadline = Instant::now() + wait;
+        #[cfg(unix)]
+        let mut pinged: Option<Instant> = None;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            let wait = ::std::cmp::min(deadline - now, reload::POLL);
+
+            // Keep the watchdog fed
+            #[cfg(unix)]
+            let wait = match watchdog {
+                Some(ping) => {
+                    if pinged.map_or(true, |pinged| now - pinged >= ping) {
+                        systemd::notify("WATCHDOG=1");
+                        pinged = Some(now);
                     }
+                    ::std::cmp::min(wait, ping)
+                },
+                None       => wait,
+            };
+
+            #[cfg(all(any(target_os = "linux", windows), feature = "exec-events"))] {
+                match events {
+                    Some(ref events) => {
+                        check_started_procs(matches, terminal, &mut alerted, &distances, &config, &mut stream,
+                                            api.as_ref(), events, wait)?;
+                        answer_requests(matches, terminal, &mut alerted, &distances, &config, &mut stream,
+                                        api.as_ref(), Duration::from_secs(0));
+                    },
+                    None             => answer_requests(matches, terminal, &mut alerted, &distances, &config,
+                                                        &mut stream, api.as_ref(), wait),
                 }
             }
-	}
-
-        let name_str = String::from_utf16(&sz_process_name[..])
-            .unwrap()
-            .split('\u{0}')
-            .next()
-            .unwrap_or("")
-            .to_string();
-        let path_str = String::from_utf16(&sz_process_path[..])
-            .unwrap()
-            .split('\u{0}')
-            .next()
-            .unwrap_or("")
-            .to_string();
+            #[cfg(not(all(any(target_os = "linux", windows), feature = "exec-events")))]
+            answer_requests(matches, terminal, &mut alerted, &distances, &config, &mut stream, api.as_ref(), wait);
 
-        if name_str != "" && path_str != "" {
-            win_procs.push(types::WinProc {
-                name    : name_str,
-                exe_path: path_str
-            });
+            if config.reload_if_changed() {
+                #[cfg(unix)]
+                systemd::notify("RELOADING=1");
+                if let Ok(mut status) = status.lock() {
+                    status.set_config(config.source().to_string());
+                }
+                break;
+            }
         }
     }
+}
+
+// Time until the next run of the schedule, delayed by this host's jitter, and when that is
+fn scheduled_wait(schedule: &schedule::Schedule,
+                  jitter  : ::chrono::Duration) -> Result<(Duration, DateTime<Local>), ScanError> {
+    let now = Local::now();
+    // Runs delayed past the following one still keep the pace of the schedule
+    let next_scan = match now.checked_sub_signed(jitter).and_then(|start| schedule.next_after(start)) {
+        Some(next) => next.checked_add_signed(jitter)
+            .ok_or_else(|| ScanError::Config(String::from("invalid jitter, the next scan would never come")))?,
+        None       => return Err(ScanError::Config(String::from("the schedule never runs"))),
+    };
 
-    win_procs
+    Ok(((next_scan - now).to_std().unwrap_or_default(), next_scan))
 }
 
-#[cfg(windows)]
-fn win_check_procs_impers(crit_procs_vec: &Vec<types::ProcProps>,
-                          sys_procs_vec : &Vec<types::WinProc>,
-                          verb_mode     : &bool,
-                          terminal      : &mut Box<term::StdoutTerminal>) -> u32 {
-    let mut susp_procs: u32 = 0;
+// Check the processes started within `wait`, as their exec events come in
+#[cfg(all(any(target_os = "linux", windows), feature = "exec-events"))]
+fn check_started_procs(matches  : &ArgMatches,
+                       terminal : &mut Output,
+                       alerted  : &mut dedup::Alerted,
+                       distances: &Arc<distcache::DistanceCache>,
+                       config   : &reload::Reloader,
+                       stream   : &mut Option<jsonl::EventStream>,
+                       api      : Option<&api::Api>,
+                       events   : &::std::sync::mpsc::Receiver<u32>,
+                       wait     : Duration) -> Result<(), ScanError> {
+    use std::sync::mpsc::RecvTimeoutError;
 
-    for sys_proc in sys_procs_vec.iter() {
-        if *verb_mode {
-            terminal.fg(term::color::BRIGHT_GREEN).unwrap();
-            println!("> Checking system process: {}", sys_proc.name);
-            println!("> system process executable absolute path: {}", sys_proc.exe_path);
+    let deadline = Instant::now() + wait;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(());
         }
 
-        for crit_proc in crit_procs_vec.iter() {
-            let threshold = damerau_levenshtein(&sys_proc.name, &crit_proc.name);
-            if *verb_mode {
-                terminal.fg(term::color::CYAN).unwrap();
-                println!( "\tagainst critical process: {}, distance: {}", crit_proc.name, threshold);
-                terminal.reset().unwrap();
-            }
+        let mut pids = match events.recv_timeout(deadline - now) {
+            Ok(pid)                             => vec![pid],
+            Err(RecvTimeoutError::Timeout)      => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(ScanError::Enum(String::from("process events are no longer received")));
+            },
+        };
+        // Processes started together are checked together
+        pids.extend(events.try_iter());
 
-            if threshold > 0 && threshold <= crit_proc.threshold as usize &&
-                !is_whitelisted(&sys_proc.exe_path, &crit_proc.whitelist) {
-                    terminal.fg(term::color::RED).unwrap();
-                    println!("Suspicious: {} <-> {} : distance {}", sys_proc.name, crit_proc.name, threshold);
-                    terminal.reset().unwrap();
+        let r = scan_pids(matches, terminal, Some(alerted), Some(distances), Some(config.current()), Some(pids))?;
+        if let Some(ref mut stream) = *stream {
+            stream.detections(&r);
+        }
+        if let Some(api) = api {
+            api.publish(&r, false);
+        }
+        alerted.add(&r);
+        let _ = stdout().flush();
+    }
+}
 
-                    susp_procs += 1;
-            }
+// Wait up to `wait`, running the scans requested through the API meanwhile. Their report also lists the
+// detections already alerted on, which other subscribers aren't sent again.
+#[allow(clippy::too_many_arguments)]
+fn answer_requests(matches  : &ArgMatches,
+                   terminal : &mut Output,
+                   alerted  : &mut dedup::Alerted,
+                   distances: &Arc<distcache::DistanceCache>,
+                   config   : &reload::Reloader,
+                   stream   : &mut Option<jsonl::EventStream>,
+                   api      : Option<&api::Api>,
+                   wait     : Duration) {
+    let api = match api {
+        Some(api) => api,
+        None      => return thread::sleep(wait),
+    };
+
+    let deadline = Instant::now() + wait;
+    while let Some(request) = api.next(deadline.saturating_duration_since(Instant::now())) {
+        let pids = request.pids.clone();
+        let full = pids.is_none();
+        let r    = match scan_pids(matches, terminal, Some(alerted), Some(distances), Some(config.current()), pids) {
+            Ok(r)    => r,
+            Err(why) => {
+                request.answer(json!({ "error": why.to_string() }));
+                continue;
+            },
+        };
+
+        let mut answer = report::document(&r);
+        answer["repeated"] = json!(r.repeated.iter().map(report::comparison_json).collect::<Vec<_>>());
+        request.answer(answer);
+        if let Some(ref mut stream) = *stream {
+            stream.detections(&r);
+        }
+        api.publish(&r, full);
+        if full { alerted.update(&r) } else { alerted.add(&r) }
+        let _ = stdout().flush();
+    }
+}
+
+// Explain what every detection rule makes of the processes given by `--pid` and `--name`.
+// Exits with status 1 when one of them is suspicious, as a scan does.
+fn check_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    setup_threads(matches)?;
+    let mut setup = scan_setup(matches, None, None, None, None)?;
+    // The closest critical processes are listed however far they are
+    setup.buckets = lengths::LengthBuckets::all(&setup.crit_procs, &*setup.algorithm, setup.options.names);
+
+    let mut pids = Vec::new();
+    for pid in matches.values_of("pid").into_iter().flat_map(|v| v) {
+        match pid.parse::<u32>() {
+            Ok(pid) => pids.push(pid),
+            Err(_)  => return Err(ScanError::Config(format!("invalid PID: {}", pid))),
         }
     }
+    let mut names = Vec::new();
+    for name in matches.values_of("name").into_iter().flat_map(|v| v) {
+        names.push(whitelist::Entry::parse(name).map_err(ScanError::Config)?);
+    }
+
+    let collector = match matches.value_of("input-procs") {
+        Some(listing) => collect::listed(input::read_listing(listing)?),
+        None          => collect::local(),
+    };
+    let (sys_procs_vec, parents) = if names.is_empty() {
+        let sys_procs_vec = collector.pids(&pids).0;
+        let parents       = parent_names(&*collector, &sys_procs_vec);
+        (sys_procs_vec, parents)
+    } else {
+        let sys_procs_vec = collector.all()?.0;
+        let parents       = process_names(&sys_procs_vec);
+        (sys_procs_vec, parents)
+    };
+    let targets: Vec<&types::ProcessInfo> = sys_procs_vec.iter()
+        .filter(|sys_proc| pids.contains(&sys_proc.pid) || names.iter().any(|name| name.matches(&sys_proc.name)))
+        .collect();
+    if targets.is_empty() {
+        return Err(ScanError::Enum(String::from("no running process matches --pid or --name")));
+    }
+
+    let measured: Vec<Measured> = targets.iter().map(|target| measure(&setup, target)).collect();
+    let subjects: Vec<rules::Subject> = targets.iter().zip(measured.iter())
+        .map(|(target, measured)| subject(target, measured, &parents))
+        .collect();
+    // Instances are counted among the targets
+    let context = rule_context(&setup, &subjects);
+
+    let mut suspicious = false;
+    for (target, subject) in targets.iter().zip(subjects.iter()) {
+        suspicious |= explain::process(&setup, &context, target, subject, terminal);
+    }
+
+    if suspicious {
+        let _ = stdout().flush();
+        exit(1);
+    }
+
+    Ok(())
+}
+
+// Interactive dashboard, rescanning every `--interval` seconds
+fn tui_command(matches: &ArgMatches) -> Result<(), ScanError> {
+    if !matches.is_present("input-procs") {
+        warn_unprivileged();
+    }
+    setup_threads(matches)?;
+
+    let interval = match matches.value_of("interval").unwrap_or("5").parse::<u64>() {
+        Ok(interval) if interval > 0 => Duration::from_secs(interval),
+        _                            => return Err(ScanError::Config(String::from("invalid interval"))),
+    };
 
-    susp_procs
+    tui::run(matches, interval)
 }
 
+// Write a systemd unit running `monitor` with the given options
 #[cfg(unix)]
-fn unix_check_procs_impers(crit_procs_vec: &Vec<types::ProcProps>,
-                           sys_procs_vec : &Vec<Process>,
-                           verb_mode     : &bool,
-                           terminal      : &mut Box<term::StdoutTerminal>) -> u32 {
-    // Number of suspicious processes
-    let mut susp_procs: u32 = 0;
-
-    for sys_proc in sys_procs_vec.iter() {
-        let exe_path = match sys_proc.exe() {
-            Ok(path) => path,
-            Err(why) => PathBuf::from(why.to_string()),
-        };
+fn install_service_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let exe = match env::current_exe() {
+        Ok(exe)  => exe,
+        Err(why) => return Err(ScanError::Config(format!("couldn't locate the bonomen executable: {}", why))),
+    };
+
+    let mut monitor_args = vec![String::from("--interval"), matches.value_of("interval").unwrap_or("60").to_string()];
+    for file in matches.values_of("file").into_iter().flat_map(|v| v) {
+        // The service doesn't run from the current directory
+        let file = fs::canonicalize(file)
+            .map_err(|why| ScanError::Config(format!("couldn't find {}: {}", file, why)))?;
+        monitor_args.push(String::from("--file"));
+        monitor_args.push(file.to_string_lossy().into_owned());
+    }
+    if let Some(profile) = matches.value_of("profile") {
+        monitor_args.push(String::from("--profile"));
+        monitor_args.push(profile.to_string());
+    }
+    if let Some(addr) = matches.value_of("metrics-addr") {
+        monitor_args.push(String::from("--metrics-addr"));
+        monitor_args.push(addr.to_string());
+    }
+    if let Some(after) = matches.value_of("realert-after") {
+        monitor_args.push(String::from("--realert-after"));
+        monitor_args.push(after.to_string());
+    }
+    if matches.is_present("exec-events") {
+        monitor_args.push(String::from("--exec-events"));
+    }
+
+    let watchdog = match matches.value_of("watchdog").unwrap_or("300").parse::<u64>() {
+        Ok(watchdog) => watchdog,
+        Err(_)       => return Err(ScanError::Config(String::from("invalid watchdog timeout"))),
+    };
+
+    let path = matches.value_of("output").unwrap_or("/etc/systemd/system/bonomen.service");
+    systemd::install(path, &systemd::unit(&exe, &monitor_args, watchdog))
+        .map_err(|why| ScanError::Config(format!("couldn't write {}: {}", path, why)))?;
+
+    if !matches.is_present("quiet") {
+        terminal.fg(term::color::GREEN);
+        println!("Service unit written to: {}", path);
+        terminal.reset();
+        println!("Enable it with: systemctl daemon-reload && systemctl enable --now bonomen");
+    }
+
+    Ok(())
+}
+
+// Show the state of the monitor listening on the control socket
+fn status_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let path   = matches.value_of("control").unwrap_or(control::DEFAULT_PATH);
+    let status = control::query(path).map_err(ScanError::Control)?;
+    if matches.is_present("json") {
+        println!("{}", status);
+        return Ok(());
+    }
+
+    let time = |name: &str| {
+        status[name].as_str()
+            .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+            .map_or(String::from("-"), |time| time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string())
+    };
+    terminal.bold();
+    println!("Monitor running, PID {}", status["pid"]);
+    terminal.reset();
+    println!("Uptime: {}", format_age(status["uptime"].as_i64().unwrap_or(0)));
+    println!("Config: {}", status["config"].as_str().unwrap_or("-"));
+    println!("Scans: {}", status["scans"]);
+    println!("Last scan: {}", time("last_scan"));
+    println!("Next scan: {}", time("next_scan"));
+    println!("Last scan detections: {} new, {} already reported", status["detections"], status["repeated"]);
+    println!("Detections since start: {}", status["alerts"]);
+
+    Ok(())
+}
+
+// Detections recorded with --db the filters of `bonomen history` and `bonomen trends` select, oldest first
+fn filtered_history(matches: &ArgMatches) -> Result<Vec<history::Record>, ScanError> {
+    let db = matches.value_of("db").unwrap_or("");
+    // Querying would create an empty database
+    if !Path::new(db).exists() {
+        return Err(ScanError::History(format!("no database at {}, scans record one with --db", db)));
+    }
+    let filter = history::Filter {
+        since:  matches.value_of("since").map(history_time).transpose()?,
+        until:  matches.value_of("until").map(history_time).transpose()?,
+        host:   matches.value_of("host").map(String::from),
+        reason: matches.value_of("rule").map(String::from),
+    };
+    let name = match matches.value_of("name") {
+        Some(name) => Some(whitelist::Entry::parse(name).map_err(ScanError::Config)?),
+        None       => None,
+    };
+
+    Ok(history::query(db, &filter)
+        .map_err(|why| ScanError::History(format!("{}: {}", db, why)))?
+        .into_iter()
+        .filter(|record| name.as_ref().map_or(true, |name| name.matches(&record.name)))
+        .collect())
+}
 
-        if *verb_mode {
-            terminal.fg(term::color::BRIGHT_GREEN).unwrap();
-            println!("> Checking system process: {}", sys_proc.comm);
-            println!("> system process executable absolute path: {}", exe_path.to_str().unwrap());
+// Detections recorded with --db, oldest first, with when each process was first and last found
+fn history_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let records = filtered_history(matches)?;
+
+    if matches.is_present("json") {
+        let records: Vec<serde_json::Value> = records.iter().map(history::Record::to_json).collect();
+        println!("{}", serde_json::to_string_pretty(&records).unwrap_or_default());
+        return Ok(());
+    }
+
+    for record in &records {
+        let against = if record.crit_name.is_empty() { String::new() } else { format!(", {}", record.crit_name) };
+        print!("{}  {}  ", record.time.format("%Y-%m-%d %H:%M:%S"), record.host);
+        terminal.bold();
+        print!("{} ({})", record.name, record.pid);
+        terminal.reset();
+        println!(" {} [{}{}, {}]", record.exe_path, record.reason, against, record.severity);
+        if !record.module.is_empty() {
+            println!("\tloads {}", record.module);
         }
+    }
+    match (records.first(), records.last()) {
+        (Some(first), Some(last)) => println!("\n{} detections, first on {}, last on {}", records.len(),
+                                              first.time.format("%Y-%m-%d %H:%M:%S"),
+                                              last.time.format("%Y-%m-%d %H:%M:%S")),
+        _                         => println!("No detections recorded."),
+    }
+
+    Ok(())
+}
+
+// Longest bar of `bonomen trends`, the most detections of a period fill it
+const TREND_BAR: usize = 40;
+
+// What the detections recorded with --db are about, and how many there were over time
+fn trends_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let trends = trends::count(&filtered_history(matches)?, matches.value_of("by") == Some("week"));
+
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&trends.to_json()).unwrap_or_default());
+        return Ok(());
+    }
+    if trends.detections == 0 {
+        println!("No detections recorded.");
+        return Ok(());
+    }
 
-        for crit_proc in crit_procs_vec.iter() {
-            let threshold = damerau_levenshtein(&sys_proc.comm, &crit_proc.name);
-            if *verb_mode {
-                terminal.fg(term::color::CYAN).unwrap();
-                println!( "\tagainst critical process: {}, distance: {}", crit_proc.name, threshold);
-                terminal.reset().unwrap();
+    let width = trends.reasons.iter().map(|&(ref reason, _)| reason.len())
+        .chain(trends.critical.iter().map(|&(ref crit_name, _)| crit_name.as_ref().map_or(1, |name| name.len())))
+        .max()
+        .unwrap_or(0);
+    terminal.bold();
+    println!("By reason");
+    terminal.reset();
+    for &(ref reason, count) in &trends.reasons {
+        println!("  {:<width$}  {:>6}", reason, count, width = width);
+    }
+    terminal.bold();
+    println!("\nBy critical process");
+    terminal.reset();
+    for &(ref crit_name, count) in &trends.critical {
+        println!("  {:<width$}  {:>6}", crit_name.as_ref().map_or("-", |name| name.as_str()), count, width = width);
+    }
+    terminal.bold();
+    println!("\nBy {}", if trends.weekly { "week" } else { "day" });
+    terminal.reset();
+    let most = trends.periods.iter().map(|&(_, count)| count).max().unwrap_or(1);
+    for &(start, count) in &trends.periods {
+        let bar = (count * TREND_BAR + most - 1) / most;
+        println!("  {}  {:>6}  {}", start.format("%Y-%m-%d"), count, "#".repeat(bar));
+    }
+    println!("\n{} detections on {} hosts", trends.detections, trends.hosts);
+
+    Ok(())
+}
+
+fn server_command(matches: &ArgMatches) -> Result<(), ScanError> {
+    let settings = server::Settings {
+        listen:    matches.value_of("listen").unwrap_or(server::DEFAULT_LISTEN).to_string(),
+        cert:      matches.value_of("cert").unwrap_or("").to_string(),
+        key:       matches.value_of("key").unwrap_or("").to_string(),
+        client_ca: matches.value_of("client-ca").map(String::from),
+        token:     matches.value_of("token").map(String::from),
+        db:        matches.value_of("db").unwrap_or("").to_string(),
+    };
+
+    server::run(settings).map_err(ScanError::Server)
+}
+
+// A date, taken at midnight, an RFC 3339 time, or a duration before now such as "7d"
+fn history_time(time: &str) -> Result<DateTime<Local>, ScanError> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+        return Ok(time.with_timezone(&Local));
+    }
+    if let Some(midnight) = ::chrono::NaiveDate::parse_from_str(time, "%Y-%m-%d").ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest()) {
+        return Ok(midnight);
+    }
+    match parse_duration(time)
+        .and_then(|ago| ::chrono::Duration::from_std(ago).ok())
+        .and_then(|ago| Local::now().checked_sub_signed(ago)) {
+        Some(time) => Ok(time),
+        None       => Err(ScanError::Config(format!("invalid time: {}, expected a date, an RFC 3339 time or a \
+                                                     duration", time))),
+    }
+}
+
+// Manage the Windows service, or run as one when started by the service control manager
+#[cfg(windows)]
+fn service_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let done = match matches.subcommand() {
+        ("install", Some(sub_m)) => {
+            let mut run_args = vec![OsString::from("--interval"),
+                                    OsString::from(sub_m.value_of("interval").unwrap_or("60"))];
+            for file in sub_m.values_of("file").into_iter().flat_map(|v| v) {
+                // The service runs from the system directory
+                let file = fs::canonicalize(file)
+                    .map_err(|why| ScanError::Config(format!("couldn't find {}: {}", file, why)))?;
+                run_args.push(OsString::from("--file"));
+                run_args.push(file.into_os_string());
             }
+            if let Some(profile) = sub_m.value_of("profile") {
+                run_args.push(OsString::from("--profile"));
+                run_args.push(OsString::from(profile));
+            }
+            if let Some(after) = sub_m.value_of("realert-after") {
+                run_args.push(OsString::from("--realert-after"));
+                run_args.push(OsString::from(after));
+            }
+            winservice::install(run_args)?;
+            "installed"
+        },
+        ("uninstall", Some(_))   => { winservice::uninstall()?; "uninstalled" },
+        ("start", Some(_))       => { winservice::start()?; "started" },
+        ("stop", Some(_))        => { winservice::stop()?; "stopped" },
+        ("run", Some(_))         => return winservice::run(),
+        _                        => return Ok(()),
+    };
+
+    if !matches.is_present("quiet") {
+        terminal.fg(term::color::GREEN);
+        println!("Service {}: {}", winservice::SERVICE_NAME, done);
+        terminal.reset();
+    }
+
+    Ok(())
+}
+
+// `--realert-after`, repeated detections are never alerted on again when absent
+fn realert_after(matches: &ArgMatches) -> Result<Option<Duration>, ScanError> {
+    match matches.value_of("realert-after") {
+        Some(after) => match parse_duration(after) {
+            Some(after) => Ok(Some(after)),
+            None        => Err(ScanError::Config(format!("invalid duration: {}", after))),
+        },
+        None        => Ok(None),
+    }
+}
+
+// Days, hours, minutes and seconds, e.g. "3d 04:05:06"
+fn format_age(seconds: i64) -> String {
+    format!("{}d {:02}:{:02}:{:02}", seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60, seconds % 60)
+}
+
+// Seconds, or a number followed by one of the s, m, h or d units, e.g. "90", "30m", "1d"
+fn parse_duration(duration: &str) -> Option<Duration> {
+    let duration = duration.trim();
+    let (number, unit) = match duration.chars().last() {
+        Some('s') => (&duration[.. duration.len() - 1], 1),
+        Some('m') => (&duration[.. duration.len() - 1], 60),
+        Some('h') => (&duration[.. duration.len() - 1], 60 * 60),
+        Some('d') => (&duration[.. duration.len() - 1], 24 * 60 * 60),
+        _         => (duration, 1),
+    };
+
+    // Too long to count in seconds is invalid too
+    number.parse::<u64>().ok().and_then(|n| n.checked_mul(unit)).map(Duration::from_secs)
+}
 
-            if threshold > 0 && threshold <= crit_proc.threshold as usize &&
-                !is_whitelisted(&(exe_path.to_str().unwrap()), &crit_proc.whitelist) {
-                    terminal.fg(term::color::RED).unwrap();
-                    println!("Suspicious: {} <-> {} : distance {}", sys_proc.comm, crit_proc.name, threshold);
-                    terminal.reset().unwrap();
+// Run a single scan, without alerting again on the processes in `alerted`.
+// Scans repeated by the monitor mode reuse the `distances` measured by the previous ones.
+fn scan_once(matches  : &ArgMatches,
+             terminal : &mut Output,
+             alerted  : Option<&dedup::Alerted>,
+             distances: Option<&Arc<distcache::DistanceCache>>,
+             config   : Option<(String, types::Config)>) -> Result<types::ScanReport, ScanError> {
+    scan_pids(matches, terminal, alerted, distances, config, None)
+}
+
+// Scan the running processes, or only `pids` when checking newly started ones.
+// Checks of new processes only print and send their detections.
+fn scan_pids(matches  : &ArgMatches,
+             terminal : &mut Output,
+             alerted  : Option<&dedup::Alerted>,
+             distances: Option<&Arc<distcache::DistanceCache>>,
+             config   : Option<(String, types::Config)>,
+             pids     : Option<Vec<u32>>) -> Result<types::ScanReport, ScanError> {
+    let full  = pids.is_none();
+    let start = Instant::now();
+    let setup = scan_setup(matches, alerted, distances, config, pids)?;
+    if !setup.options.quiet && !setup.options.silent && full {
+        terminal.fg(term::color::GREEN);
+        println!("{}", messages::text("scan.file", &[&setup.source]));
+        terminal.reset();
+    }
+
+    let mut r = check_processes(matches, &setup, terminal)?;
+    r.elapsed = start.elapsed();
+    if r.interrupted {
+        warn!("Scan interrupted, only {} processes were checked", r.scanned);
+    }
 
-                    susp_procs += 1;
+    send_webhook(matches, &r);
+    send_mail(&setup.mail, &r);
+    // Checks of new processes only matter when they found something
+    if let Some(db) = matches.value_of("db") {
+        if full || !r.detections.is_empty() {
+            if let Err(why) = history::record(db, &report::document(&r)) {
+                error!("couldn't record the scan in {}: {}", db, why);
             }
         }
     }
+    if full || !r.detections.is_empty() {
+        push_report(matches, &r);
+    }
+    if !full {
+        return Ok(r);
+    }
+
+    if r.skipped > 0 {
+        warn!("Skipped {} processes that couldn't be read", r.skipped);
+    }
+    if !r.denied.is_empty() {
+        warn!("Executables of {} processes can't be read without privileges, they were only checked by name",
+              r.denied.len());
+        let denied: Vec<String> = r.denied.iter().map(|&(pid, ref name)| format!("{} ({})", name, pid)).collect();
+        info!("Only checked by name: {}", denied.join(", "));
+    }
+
+    write_scan_report(matches, &r, setup.options.quiet || setup.options.silent);
+    if matches.value_of("output") == Some("sarif") {
+        if let Err(why) = sarif::write(&mut stdout(), &r) {
+            error!("couldn't write the SARIF log: {}", why);
+        }
+    }
+
+    if setup.options.quiet || setup.options.silent {
+        return Ok(r);
+    }
+
+    print_summary(&r);
+
+    if r.detections.len() > 0 {
+        terminal.fg(term::color::RED);
+    } else {
+        terminal.fg(term::color::GREEN);
+    }
+    println!("{}\n{}", messages::text("scan.found", &[&r.detections.len()]), messages::text("scan.done", &[]));
+    terminal.reset();
+
+    Ok(r)
+}
+
+// What a scan checks and how, from the command line and the critical processes files
+struct ScanSetup {
+    source    : String,
+    crit_procs: Vec<types::ProcProps>,
+    algorithm : Box<dyn Similarity>,
+    // Critical processes a name is measured against
+    buckets   : lengths::LengthBuckets,
+    options   : types::ScanOptions,
+    rules     : Vec<Box<dyn rules::DetectionRule>>,
+    // Weight of each enabled rule, in order
+    weights   : Vec<u32>,
+    // Score from which a process is reported
+    alert_score: u32,
+    mail      : mail::Settings,
+}
+
+// `config` is the one the monitor keeps, else the critical processes files are read
+fn scan_setup(matches  : &ArgMatches,
+              alerted  : Option<&dedup::Alerted>,
+              distances: Option<&Arc<distcache::DistanceCache>>,
+              config   : Option<(String, types::Config)>,
+              pids     : Option<Vec<u32>>) -> Result<ScanSetup, ScanError> {
+    let report_dist = match matches.value_of("report-distance") {
+        Some(d) => match d.parse::<f64>() {
+            Ok(d)  => Some(d),
+            Err(_) => return Err(ScanError::Config(format!("invalid report distance: {}", d))),
+        },
+        None    => None,
+    };
+    let mut options = types::ScanOptions {
+        quiet:        matches.is_present("quiet"),
+        // JSON Lines events or a SARIF log on stdout replace the text output
+        silent:       match matches.value_of("output") {
+            Some("jsonl") => !matches.is_present("output-file"),
+            Some("sarif") => true,
+            _             => false,
+        },
+        report_dist:  report_dist,
+        ignore:       Vec::new(),
+        min_severity: types::Severity::from_name(matches.value_of("min-severity").unwrap_or("low"))
+            .unwrap_or(types::Severity::Low),
+        only:         Vec::new(),
+        pid_range:    match matches.value_of("pid-range") {
+            Some(range) => match parse_range(range) {
+                Some(range) => Some(range),
+                None        => return Err(ScanError::Config(format!("invalid PID range: {}", range))),
+            },
+            None        => None,
+        },
+        #[cfg(unix)]
+        users:        Vec::new(),
+        connections:  matches.is_present("connections"),
+        environ:      None,
+        template:     match matches.value_of("template") {
+            Some(template) => Some(template::Template::parse(template)
+                                   .map_err(|why| ScanError::Config(format!("invalid template: {}", why)))?),
+            None           => None,
+        },
+        modules:      matches.is_present("modules"),
+        path_hijack:  matches.is_present("path-hijack"),
+        persistence:  matches.is_present("persistence"),
+        services:     matches.is_present("services"),
+        kernel_modules: None,
+        local:        !matches.is_present("input-procs") && !matches.is_present("host") && !matches.is_present("bench"),
+        suspend:      matches.is_present("suspend"),
+        explain:      matches.is_present("explain"),
+        #[cfg(target_os = "linux")]
+        packages:     None,
+        #[cfg(feature = "yara")]
+        yara:         None,
+        virustotal:   None,
+        names:        types::NameMatching::platform_default(),
+        paths:        types::PathMatching::platform_default(),
+        suppress:     alerted.map(|alerted| alerted.suppressed()).unwrap_or_default(),
+        pids:         pids,
+        distances:    None,
+        top:          match matches.value_of("top").unwrap_or("3").parse::<usize>() {
+            Ok(top) => top,
+            Err(_)  => return Err(ScanError::Config(String::from("invalid number of closest critical processes"))),
+        },
+        booted:       None,
+        recent:       (::chrono::Duration::hours(1), ::chrono::Duration::days(7)),
+        #[cfg(windows)]
+        verify_sigs:  matches.is_present("verify-signatures"),
+        #[cfg(windows)]
+        hollowing:    matches.is_present("check-hollowing"),
+    };
+
+    // Load known standard system processes
+    let (source, mut config) = match config {
+        Some(config) => config,
+        None         => {
+            let files: Vec<&str> = matches.values_of("file").map(|v| v.collect()).unwrap_or_default();
+            let (source, config) = load_config(&files, matches.value_of("profile"))?;
+            info!("{} critical processes loaded from {}", config.procs.len(), source);
+            (source, config)
+        },
+    };
 
-    susp_procs
+    if matches.is_present("bench") {
+        let (procs, critical, length) = bench_sizes(matches)?;
+        if let Some(critical) = critical {
+            config.procs = bench::critical(critical, length);
+        }
+        // Printing thousands of detections would be most of what gets timed
+        options.silent = true;
+        info!("Benchmarking {} synthetic processes against {} critical processes", procs, config.procs.len());
+    }
+    options.ignore = config.ignore;
+    if matches.is_present("kernel-modules") {
+        if config.kernel_modules.is_empty() {
+            warn!("No kernel_module.NAME settings in {}, --kernel-modules has nothing to compare against", source);
+        }
+        options.kernel_modules = Some(config.kernel_modules);
+    }
+    options.names  = config.names.unwrap_or(options.names);
+    options.paths  = types::PathMatching {
+        ignore_case: config.ignore_path_case.unwrap_or(options.paths.ignore_case),
+        distance:    config.path_distance.unwrap_or(options.paths.distance),
+    };
+    // Start times of listings and remote hosts can't be put against their boot
+    if options.local {
+        options.booted = collect::boot_time();
+    }
+    if let Some(start) = config.recent_start.and_then(|start| ::chrono::Duration::from_std(start).ok()) {
+        options.recent.0 = start;
+    }
+    if let Some(uptime) = config.recent_uptime.and_then(|uptime| ::chrono::Duration::from_std(uptime).ok()) {
+        options.recent.1 = uptime;
+    }
+    for pattern in matches.values_of("ignore").into_iter().flat_map(|v| v) {
+        match whitelist::Entry::parse(pattern) {
+            Ok(entry) => options.ignore.push(entry),
+            Err(why)  => return Err(ScanError::Config(why)),
+        }
+    }
+    for pattern in matches.values_of("match").into_iter().flat_map(|v| v) {
+        match whitelist::Entry::parse(pattern) {
+            Ok(entry) => options.only.push(entry),
+            Err(why)  => return Err(ScanError::Config(why)),
+        }
+    }
+    #[cfg(unix)]
+    for user in matches.values_of("user").into_iter().flat_map(|v| v) {
+        options.users.push(resolve_user(user)?);
+    }
+    #[cfg(unix)]
+    if !options.users.is_empty() && (matches.is_present("input-procs") || matches.is_present("host")) {
+        warn!("Process listings carry no owner, --user is ignored");
+        options.users.clear();
+    }
+    if options.connections && (matches.is_present("input-procs") || matches.is_present("host")) {
+        warn!("Connections can only be listed for local processes, --connections is ignored");
+        options.connections = false;
+    }
+    if matches.is_present("env") && !options.local {
+        warn!("Environments can only be read for local processes, --env is ignored");
+    } else if matches.is_present("env") {
+        let names:  Vec<&str> = matches.values_of("env").map(|v| v.collect()).unwrap_or_default();
+        let redact: Vec<&str> = matches.values_of("env-redact").map(|v| v.collect()).unwrap_or_default();
+        options.environ = Some(environ::Settings::new(&names, &redact).map_err(ScanError::Config)?);
+    }
+    if options.modules && (matches.is_present("input-procs") || matches.is_present("host")) {
+        warn!("Modules can only be inspected for local processes, --modules is ignored");
+        options.modules = false;
+    }
+    if options.suspend && !options.local {
+        warn!("Only local processes can be suspended, --suspend is ignored");
+        options.suspend = false;
+    }
+    if options.path_hijack && (matches.is_present("input-procs") || matches.is_present("host")) {
+        warn!("Only the local PATH can be inspected, --path-hijack is ignored");
+        options.path_hijack = false;
+    }
+    if options.persistence && (matches.is_present("input-procs") || matches.is_present("host")) {
+        warn!("Only the local autostart entries can be inspected, --persistence is ignored");
+        options.persistence = false;
+    }
+    if options.services && (matches.is_present("input-procs") || matches.is_present("host")) {
+        warn!("Only the local services can be inspected, --services is ignored");
+        options.services = false;
+    }
+    if options.kernel_modules.is_some() && !options.local {
+        warn!("Only the local kernel modules can be inspected, --kernel-modules is ignored");
+        options.kernel_modules = None;
+    }
+    #[cfg(target_os = "linux")]
+    if matches.is_present("trust-packages") && !options.local {
+        warn!("Package databases only cover local executables, --trust-packages is ignored");
+    } else if matches.is_present("trust-packages") {
+        let packages = packages::Database::load()
+            .map_err(|why| ScanError::Config(format!("couldn't read the package database: {}", why)))?;
+        info!("{} files installed by {} are trusted", packages.len(), packages.manager);
+        options.packages = Some(packages);
+    }
+    #[cfg(feature = "yara")]
+    if let Some(path) = matches.value_of("yara") {
+        if options.local {
+            options.yara = Some(yarascan::load(path).map_err(ScanError::Config)?);
+        } else {
+            warn!("YARA rules only apply to local executables, --yara is ignored");
+        }
+    }
+    if let Some(key) = matches.value_of("vt-api-key") {
+        let rate = match matches.value_of("vt-rate").map(str::parse::<u32>) {
+            Some(Ok(rate)) if rate > 0 => rate,
+            Some(_)                    => return Err(ScanError::Config(String::from("invalid --vt-rate"))),
+            None                       => virustotal::DEFAULT_RATE,
+        };
+        let cache = matches.value_of("vt-cache").map(PathBuf::from).or_else(virustotal::default_cache);
+        if options.local {
+            options.virustotal = Some(virustotal::Client::new(key, cache, rate));
+        } else {
+            warn!("Only local executables can be looked up, --vt-api-key is ignored");
+        }
+    }
+
+    let algorithm_name = matches.value_of("algorithm")
+        .or(config.algorithm.as_ref().map(|a| a.as_str()))
+        .unwrap_or("damerau-levenshtein");
+    let algorithm = match similarity::from_name(algorithm_name) {
+        Some(algorithm) => algorithm,
+        None            => return Err(ScanError::Config(format!("unknown algorithm: {}", algorithm_name))),
+    };
+    info!("Comparing names with {}", algorithm_name);
+    let rules = rules::enabled(&config.rules);
+    for rule in rules::registry() {
+        if !rules.iter().any(|enabled| enabled.name() == rule.name()) {
+            info!("Detection rule {} is disabled", rule.name());
+        }
+    }
+    let weights     = rules::weights(&rules, &config.weights);
+    let alert_score = match matches.value_of("alert-score") {
+        Some(score) => match score.parse::<u32>() {
+            Ok(score) => score,
+            Err(_)    => return Err(ScanError::Config(format!("invalid alert score: {}", score))),
+        },
+        None        => config.alert_score.unwrap_or(rules::ALERT_SCORE),
+    };
+    let buckets = lengths::LengthBuckets::new(&config.procs, &*algorithm, options.names, options.report_dist);
+    if let Some(distances) = distances {
+        distances.measure_against(algorithm_name, options.names, &config.procs);
+        options.distances = Some(distances.clone());
+    }
+
+    Ok(ScanSetup {
+        source:     source,
+        crit_procs: config.procs,
+        algorithm:  algorithm,
+        buckets:    buckets,
+        options:    options,
+        rules:      rules,
+        weights:    weights,
+        alert_score: alert_score,
+        mail:       config.mail,
+    })
+}
+
+// Synthetic processes, critical processes when replacing the configured ones, and length of their names
+fn bench_sizes(matches: &ArgMatches) -> Result<(usize, Option<usize>, usize), ScanError> {
+    let count = |arg: &str, what: &str| match matches.value_of(arg).map(str::parse::<usize>) {
+        Some(Ok(count)) if count > 0 => Ok(Some(count)),
+        Some(_)                      => Err(ScanError::Config(format!("invalid number of {}", what))),
+        None                         => Ok(None),
+    };
+
+    Ok((count("bench", "synthetic processes")?.unwrap_or(0),
+        count("bench-critical", "synthetic critical processes")?,
+        count("bench-name-length", "characters")?.unwrap_or(bench::NAME_LENGTH)))
+}
+
+// Check the processes of the listing, the remote host or this system
+fn check_processes(matches : &ArgMatches,
+                   setup   : &ScanSetup,
+                   terminal: &mut Output) -> Result<types::ScanReport, ScanError> {
+    let (crit_proc_vec, algorithm, options) = (&setup.crit_procs, &*setup.algorithm, &setup.options);
+
+    let collector = match (matches.value_of("input-procs"), matches.value_of("host")) {
+        // Offline check of a listing captured elsewhere
+        (Some(listing), _) => collect::listed(input::read_listing(listing)?),
+        (None, None) if matches.is_present("bench") => {
+            let (count, _, length) = bench_sizes(matches)?;
+            collect::listed(bench::processes(count, crit_proc_vec, length))
+        },
+        (None, Some(host)) => {
+            let ssh_opts: Vec<&str> = matches.values_of("ssh-option").map(|v| v.collect()).unwrap_or_default();
+            collect::listed(remote::collect(host, &ssh_opts)?)
+        },
+        (None, None)       => collect::local(),
+    };
+    let mut r = scan_procs(setup, &*collector, terminal)?;
+    // Commands don't change with the processes started
+    if options.path_hijack && options.pids.is_none() {
+        check_path_hijack(&mut r, crit_proc_vec, &setup.buckets, algorithm, options, terminal);
+    }
+    if options.persistence && options.pids.is_none() {
+        check_persistence(&mut r, crit_proc_vec, &setup.buckets, algorithm, options, terminal);
+    }
+    if options.services && options.pids.is_none() {
+        check_services(&mut r, crit_proc_vec, &setup.buckets, algorithm, options, terminal);
+    }
+    if let (Some(expected), true) = (options.kernel_modules.as_ref(), options.pids.is_none()) {
+        check_kernel_modules(&mut r, expected, algorithm, options, terminal);
+    }
+    r.host = matches.value_of("host").map(String::from);
+
+    Ok(r)
+}
+
+// What the scan covered, to tell a clean run from one that inspected nothing
+fn print_summary(r: &types::ScanReport) {
+    let by_reason: Vec<String> = r.detections_by_reason().iter()
+        .map(|(reason, count)| format!("{}: {}", reason, count))
+        .collect();
+
+    println!("\n{}", messages::text("summary.title", &[]));
+    println!("\t{}", messages::text("summary.scanned", &[&r.scanned]));
+    println!("\t{}", messages::text("summary.compared", &[&r.compared]));
+    println!("\t{}", messages::text("summary.skipped", &[&r.skipped]));
+    if !r.denied.is_empty() {
+        println!("\t{}", messages::text("summary.denied", &[&r.denied.len()]));
+    }
+    if by_reason.is_empty() {
+        println!("\t{}", messages::text("summary.detections", &[&0]));
+    } else {
+        println!("\t{}", messages::text("summary.by_reason", &[&r.detections.len(), &by_reason.join(", ")]));
+    }
+    if r.repeated.len() > 0 {
+        println!("\t{}", messages::text("summary.repeated", &[&r.repeated.len()]));
+    }
+    if r.interrupted {
+        println!("\t{}", messages::text("summary.interrupted", &[]));
+    }
+    println!("\t{}\n", messages::text("summary.elapsed", &[&format!("{:.3}", report::seconds(r.elapsed))]));
+}
+
+// Enumerate and check the processes of `collector`. Full scans keep only the name of processes
+// out of their scope, to name parents, and only argv[0] of command lines.
+fn scan_procs(setup    : &ScanSetup,
+              collector: &dyn collect::Collector,
+              terminal : &mut Output) -> Result<types::ScanReport, ScanError> {
+    let (sys_procs_vec, skipped, parents) = match setup.options.pids {
+        Some(ref pids) => {
+            let (sys_procs_vec, skipped) = collector.pids(pids);
+            let parents = parent_names(collector, &sys_procs_vec);
+            (sys_procs_vec, skipped, parents)
+        },
+        None           => {
+            let options = &setup.options;
+            let mut sys_procs_vec = Vec::new();
+            let mut parents       = HashMap::new();
+            let skipped = collector.each(&mut |mut sys_proc| {
+                parents.insert(sys_proc.pid, sys_proc.name.clone());
+                if !is_ignored(&sys_proc.name, options) && in_scope(&sys_proc.name, sys_proc.pid, options) &&
+                   owned_by_users(&sys_proc, options) {
+                    if let Some(ref mut cmdline) = sys_proc.cmdline {
+                        cmdline.truncate(1);
+                        cmdline.shrink_to_fit();
+                    }
+                    sys_procs_vec.push(sys_proc);
+                }
+            })?;
+            (sys_procs_vec, skipped, parents)
+        },
+    };
+
+    // Check for process name impersonation
+    let mut r = check_procs_impers(setup, &sys_procs_vec, &parents, terminal);
+    r.skipped = skipped;
+
+    Ok(r)
+}
+
+// Alert the --webhook endpoint about detections, failures don't abort the scan
+fn send_webhook(matches: &ArgMatches, r: &types::ScanReport) {
+    let url = match matches.value_of("webhook") {
+        Some(url) if r.detections.len() > 0 => url,
+        _                                   => return,
+    };
+    let headers: Vec<&str> = matches.values_of("webhook-header").map(|v| v.collect()).unwrap_or_default();
+
+    if let Err((failed, why)) = webhook::send(url, &headers, matches.is_present("webhook-batch"), r) {
+        error!("{} webhook request(s) to {} failed: {}", failed, url, why);
+    }
+}
+
+// Send the report to the --push server, failures don't abort the scan
+fn push_report(matches: &ArgMatches, r: &types::ScanReport) {
+    let url = match matches.value_of("push") {
+        Some(url) => url,
+        None      => return,
+    };
+    let settings = push::Settings {
+        url:      url.to_string(),
+        token:    matches.value_of("push-token").map(String::from),
+        agent_id: matches.value_of("agent-id").map(String::from),
+        ca:       matches.value_of("push-ca").map(String::from),
+        cert:     matches.value_of("push-cert").map(String::from),
+        key:      matches.value_of("push-key").map(String::from),
+        spool:    matches.value_of("spool").map(PathBuf::from),
+    };
+
+    if let Err(why) = push::send(&settings, report::document(r)) {
+        error!("couldn't push the report: {}", why);
+    }
+}
+
+// Email the detections with the smtp.* settings, failures are only logged
+fn send_mail(settings: &mail::Settings, r: &types::ScanReport) {
+    if !settings.enabled() || r.detections.is_empty() {
+        return;
+    }
+
+    match mail::send(settings, r) {
+        Ok(())   => info!("Detections emailed to {}", settings.to.join(", ")),
+        Err(why) => error!("couldn't email the detections: {}", why),
+    }
+}
+
+// Write the --report file, if one was asked for, failures don't abort the scan
+fn write_scan_report(matches: &ArgMatches, r: &types::ScanReport, quiet: bool) {
+    if let Some(report_path) = matches.value_of("report") {
+        match report::write_report(report_path, matches.value_of("report-format"), r) {
+            Ok(())   => if !quiet {
+                println!("{}", messages::text("scan.report_written", &[&report_path]));
+            },
+            Err(why) => error!("couldn't write report {}: {}", report_path, why.to_string()),
+        }
+    }
+}
+
+// bonomen config <subcommand>
+fn config_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    if let Some(sub_m) = matches.subcommand_matches("validate") {
+        let files: Vec<&str> = sub_m.values_of("FILE").map(|v| v.collect()).unwrap_or_default();
+        let paths            = config_paths(&files);
+
+        let mut validation = Validation {
+            config:   empty_config(),
+            errors:   0,
+            warnings: 0,
+            terminal: terminal,
+        };
+        if paths.is_empty() {
+            validation.check_lines(defaults::DEFAULT_PROCS, defaults::DEFAULT_NAME, None, 0);
+        }
+        for path in paths.iter() {
+            validation.check_file(Path::new(path), 0);
+        }
+        if let Err(why) = apply_process_settings(&mut validation.config) {
+            validation.report("", 0, true, why);
+        }
+
+        let source = if paths.is_empty() { defaults::DEFAULT_NAME.to_string() } else { paths.join(", ") };
+        if validation.errors > 0 {
+            return Err(ScanError::Config(format!("{}: {} errors, {} warnings",
+                                                 source, validation.errors, validation.warnings)));
+        }
+
+        let procs = layer_procs(validation.config.procs).len();
+        validation.terminal.fg(term::color::GREEN);
+        println!("{}: {} critical processes, no errors, {} warnings", source, procs, validation.warnings);
+        validation.terminal.reset();
+    } else if let Some(sub_m) = matches.subcommand_matches("test") {
+        return config_test(sub_m, terminal);
+    } else if let Some(sub_m) = matches.subcommand_matches("sign") {
+        let private_key = sub_m.value_of("private-key").unwrap_or("");
+        for file in sub_m.values_of("FILE").into_iter().flat_map(|v| v) {
+            let (sig_path, public_key) = configsig::sign(Path::new(file), private_key)?;
+            println!("{} signed: {}", file, sig_path.display());
+            if !sub_m.is_present("quiet") {
+                println!("Verify with --config-key {}", public_key);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Detections of a captured listing under the proposed configuration, compared to those of the current one
+fn config_test(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let proposed: Vec<&str> = matches.values_of("FILE").map(|v| v.collect()).unwrap_or_default();
+    let current: Vec<&str>  = matches.values_of("current").map(|v| v.collect()).unwrap_or_default();
+    let listing       = matches.value_of("against").unwrap_or("");
+    let sys_procs_vec = input::read_listing(listing)?;
+    let parents       = process_names(&sys_procs_vec);
+
+    let proposed = load_config(&proposed, matches.value_of("profile"))?;
+    let current  = load_config(&current, matches.value_of("profile"))?;
+    println!("{} processes of {}, proposed {}, current {}\n", sys_procs_vec.len(), listing, proposed.0, current.0);
+    let before = listing_detections(matches, current, &sys_procs_vec, &parents, terminal)?;
+    let after  = listing_detections(matches, proposed, &sys_procs_vec, &parents, terminal)?;
+
+    let key = |c: &types::Comparison| (c.pid, c.name.clone(), c.reason, c.crit_name.clone());
+    let added: Vec<&types::Comparison> = after.iter().filter(|c| !before.iter().any(|b| key(b) == key(c))).collect();
+    let gone: Vec<&types::Comparison>  = before.iter().filter(|c| !after.iter().any(|a| key(a) == key(c))).collect();
+    for &(sign, color, comparisons) in &[("+", term::color::RED, &added), ("-", term::color::GREEN, &gone)] {
+        for c in comparisons {
+            terminal.fg(color);
+            print!("{} {} ({})", sign, c.name, c.pid);
+            terminal.reset();
+            let exe_path = if c.exe_path.is_empty() { String::new() } else { format!(" {}", c.exe_path) };
+            let against  = if c.crit_name.is_empty() { String::new() } else { format!(", {}", c.crit_name) };
+            println!("{} [{}{}, {}]", exe_path, c.reason, against, c.severity.name());
+        }
+    }
+    if !added.is_empty() || !gone.is_empty() {
+        println!();
+    }
+    println!("{} new detections, {} no longer detected, {} unchanged",
+             added.len(), gone.len(), after.len() - added.len());
+
+    Ok(())
+}
+
+// Detections among the processes of a listing, nothing printed
+fn listing_detections(matches      : &ArgMatches,
+                      config       : (String, types::Config),
+                      sys_procs_vec: &[types::ProcessInfo],
+                      parents      : &HashMap<u32, String>,
+                      terminal     : &mut Output) -> Result<Vec<types::Comparison>, ScanError> {
+    let mut setup = scan_setup(matches, None, None, Some(config), None)?;
+    // The processes aren't those of this system
+    setup.options.silent = true;
+    setup.options.local  = false;
+    setup.options.booted = None;
+
+    Ok(check_procs_impers(&setup, sys_procs_vec, parents, terminal).detections)
+}
+
+// Checks every line of critical processes files, unlike parsing it doesn't stop at the first error
+struct Validation<'a> {
+    config  : types::Config,
+    errors  : usize,
+    warnings: usize,
+    terminal: &'a mut Output,
+}
+
+impl<'a> Validation<'a> {
+    fn report(&mut self, source: &str, number: usize, error: bool, message: String) {
+        self.terminal.fg(if error { term::color::RED } else { term::color::YELLOW });
+        if number > 0 {
+            print!("{}:{}: ", source, number);
+        } else if !source.is_empty() {
+            print!("{}: ", source);
+        }
+        print!("{}", if error { "error" } else { "warning" });
+        self.terminal.reset();
+        println!(": {}", message);
+
+        if error { self.errors += 1 } else { self.warnings += 1 }
+    }
+
+    fn check_file(&mut self, path: &Path, depth: usize) {
+        let source = path.display().to_string();
+        if depth > MAX_INCLUDE_DEPTH {
+            self.report(&source, 0, true, String::from("includes nested too deeply"));
+            return;
+        }
+
+        match fs::read_to_string(path) {
+            Ok(content) => self.check_lines(&content, &source, Some(path), depth),
+            Err(why)    => self.report(&source, 0, true, format!("couldn't read: {}", why)),
+        }
+    }
+
+    // `path` is the file holding `content`, if any, to resolve includes
+    // Every profile section is checked, on its own
+    fn check_lines(&mut self, content: &str, source: &str, path: Option<&Path>, depth: usize) {
+        let mut lines   = HashMap::new();
+        let mut profile = None;
+        let mut skipped = empty_config();
+
+        for (number, line) in content.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+            if let Some(section) = profile_section(line) {
+                match section {
+                    Ok(name) => profile = Some(name.to_string()),
+                    Err(why) => self.report(source, number, true, why),
+                }
+                continue;
+            }
+            if let (Some(included), Some(path)) = (include_target(line), path) {
+                self.check_file(&included_path(path, included), depth + 1);
+                continue;
+            }
+
+            let config = if profile.is_some() { &mut skipped } else { &mut self.config };
+            let known  = config.procs.len();
+            if let Err(why) = parse_line(config, line) {
+                self.report(source, number, true, why);
+                continue;
+            }
+            if config.procs.len() == known {
+                continue;
+            }
+
+            // Layered files and profiles may redefine a process on purpose, a single section shouldn't
+            let mut diagnostics = Vec::new();
+            {
+                let crit_proc = &config.procs[known];
+                if let Some(&first) = lines.get(&(profile.clone(), crit_proc.name.clone())) {
+                    diagnostics.push((true, format!("duplicate critical process {}, first defined on line {}",
+                                                    crit_proc.name, first)));
+                } else {
+                    lines.insert((profile.clone(), crit_proc.name.clone()), number);
+                }
+                if let types::Threshold::Absolute(n) = crit_proc.threshold {
+                    if n as usize >= crit_proc.name.chars().count() {
+                        diagnostics.push((false, format!("threshold {} is at least the length of {}, any short name matches",
+                                                         n, crit_proc.name)));
+                    }
+                }
+                // Lists usually name the alternative paths of several distributions, one is enough
+                let paths: Vec<&str> = crit_proc.whitelist.iter().filter_map(|entry| entry.path()).collect();
+                if !paths.is_empty() && paths.len() == crit_proc.whitelist.len() && !paths.iter().any(|path| Path::new(path).exists()) {
+                    diagnostics.push((false, format!("no whitelisted path of {} exists on this system: {}",
+                                                     crit_proc.name, paths.join(", "))));
+                }
+            }
+            for (error, message) in diagnostics {
+                self.report(source, number, error, message);
+            }
+        }
+    }
+}
+
+// bonomen whitelist add --from-report REPORT, whitelists the executables of reviewed detections
+fn whitelist_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let sub_m = match matches.subcommand_matches("add") {
+        Some(sub_m) => sub_m,
+        None        => return Ok(()),
+    };
+    let report_path = sub_m.value_of("from-report").unwrap_or("");
+    let (candidates, others) = review::candidates(report_path)
+        .map_err(|why| ScanError::Config(format!("couldn't read report {}: {}", report_path, why)))?;
+
+    let files: Vec<&str> = sub_m.values_of("file").map(|v| v.collect()).unwrap_or_default();
+    let profile          = sub_m.value_of("profile");
+    // Files a scan would refuse aren't edited
+    let (source, _) = load_config(&files, profile)?;
+    let paths       = config_paths(&files);
+    if paths.is_empty() {
+        return Err(ScanError::Config(format!("{} can't be edited, write it to a file with --dump-defaults first",
+                                             source)));
+    }
+
+    let stdin = std::io::stdin();
+    let mut added = 0;
+    for candidate in &candidates {
+        if sub_m.is_present("interactive") {
+            print!("{} ({}) flagged as {} for {}, whitelist {}? [y/N] ", candidate.name, candidate.pid,
+                   candidate.reason, candidate.crit_name, candidate.exe_path);
+            let _ = stdout().flush();
+            let mut answer = String::new();
+            if stdin.lock().read_line(&mut answer).is_err() || !answer.trim().eq_ignore_ascii_case("y") {
+                continue;
+            }
+        }
+
+        let outcome = review::add(&paths, profile, &candidate.crit_name, &candidate.exe_path)
+            .map_err(|why| ScanError::Config(format!("couldn't whitelist {}: {}", candidate.exe_path, why)))?;
+        match outcome {
+            review::Outcome::Added(file, line)   => {
+                added += 1;
+                terminal.fg(term::color::GREEN);
+                println!("Whitelisted {} for {} on {}:{}", candidate.exe_path, candidate.crit_name, file.display(),
+                         line);
+                terminal.reset();
+            },
+            review::Outcome::Already(file, line) => {
+                println!("{} is already whitelisted for {} on {}:{}", candidate.exe_path, candidate.crit_name,
+                         file.display(), line);
+            },
+            review::Outcome::Undefined           => {
+                warn!("{} isn't defined in {}, it comes from the embedded defaults", candidate.crit_name, source);
+            },
+        }
+    }
+    if others > 0 {
+        info!("{} detections can't be whitelisted by path, e.g. deleted executables or modules", others);
+    }
+    println!("Whitelisted {} executables.", added);
+
+    Ok(())
+}
+
+// bonomen completions SHELL, the script is generated from the command line definition
+fn completions_command(matches: &ArgMatches) -> Result<(), ScanError> {
+    let shell = matches.value_of("SHELL").unwrap_or("").parse::<clap::Shell>().map_err(ScanError::Config)?;
+    cli::build(BONOMEN_BANNER).gen_completions_to("bonomen", shell, &mut stdout());
+
+    Ok(())
+}
+
+// bonomen resume PID..., undoes --suspend
+fn resume_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    for pid in matches.values_of("pid").into_iter().flat_map(|v| v) {
+        let pid = pid.parse::<u32>().map_err(|_| ScanError::Config(format!("invalid PID: {}", pid)))?;
+        response::resume(pid).map_err(|why| ScanError::Permission(format!("couldn't resume {}: {}", pid, why)))?;
+
+        terminal.fg(term::color::GREEN);
+        println!("Resumed {}", pid);
+        terminal.reset();
+    }
+
+    Ok(())
+}
+
+// bonomen update
+fn update_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let url     = matches.value_of("url").unwrap_or("");
+    let sig_url = matches.value_of("signature-url").map(String::from).unwrap_or_else(|| format!("{}.sig", url));
+    let dest    = matches.value_of("output").map(PathBuf::from).unwrap_or_else(defaults::installed_path);
+
+    let count = update::run(url, &sig_url, matches.value_of("public-key").unwrap_or(""), &dest)?;
+    if !matches.is_present("quiet") {
+        terminal.fg(term::color::GREEN);
+        println!("Installed {} critical processes to: {}", count, dest.display());
+        terminal.reset();
+    }
+
+    Ok(())
+}
+
+// bonomen baseline <subcommand>
+fn baseline_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    check_privileges()?;
+
+    if let Some(sub_m) = matches.subcommand_matches("save") {
+        let path  = sub_m.value_of("output").unwrap_or("baseline.json");
+        let procs = snapshot_procs()?;
+
+        if let Err(why) = baseline::save(path, &procs) {
+            return Err(ScanError::Config(format!("couldn't write baseline {}: {}", path, why.to_string())));
+        }
+
+        terminal.fg(term::color::GREEN);
+        println!("Baseline of {} processes written to: {}", procs.len(), path);
+        terminal.reset();
+    }
+
+    if let Some(sub_m) = matches.subcommand_matches("diff") {
+        let path = sub_m.value_of("baseline").unwrap_or("baseline.json");
+        let saved = match baseline::load(path) {
+            Ok(saved) => saved,
+            Err(why)  => return Err(ScanError::Config(format!("couldn't read baseline {}: {}", path, why.to_string()))),
+        };
+        let procs   = snapshot_procs()?;
+        let changes = baseline::diff(&saved, &procs);
+
+        for change in changes.iter() {
+            match *change {
+                baseline::Change::Appeared(p)               => {
+                    terminal.fg(term::color::YELLOW);
+                    println!("Appeared: {} (PID {}) {}", p.name, p.pid, p.exe_path);
+                },
+                baseline::Change::Disappeared(p)            => {
+                    terminal.fg(term::color::CYAN);
+                    println!("Disappeared: {} {}", p.name, p.exe_path);
+                },
+                baseline::Change::PathChanged(p, ref paths) => {
+                    terminal.fg(term::color::RED);
+                    println!("Path changed: {} (PID {}) {}, baseline: {}", p.name, p.pid, p.exe_path, paths.join(", "));
+                },
+                baseline::Change::HashChanged(p)            => {
+                    terminal.fg(term::color::RED);
+                    println!("Executable changed: {} (PID {}) {}", p.name, p.pid, p.exe_path);
+                },
+            }
+            terminal.reset();
+        }
+
+        println!("Found {} differences with baseline {}.", changes.len(), path);
+    }
+
+    Ok(())
+}
+
+// Record the whitelisted executables, or compare them against their record. Changes exit with status 1.
+fn fim_command(matches: &ArgMatches, terminal: &mut Output) -> Result<(), ScanError> {
+    let (init, sub_m) = match matches.subcommand() {
+        ("init", Some(sub_m)) => (true, sub_m),
+        (_, Some(sub_m))      => (false, sub_m),
+        _                     => return Ok(()),
+    };
+    let files: Vec<&str> = sub_m.values_of("file").map(|v| v.collect()).unwrap_or_default();
+    let (source, config) = load_config(&files, sub_m.value_of("profile"))?;
+    let record = |recorded: &[fim::Record]| -> Vec<fim::Record> {
+        fim::files(&config.procs, recorded).iter().map(|(path, names)| fim::record(path, names)).collect()
+    };
+
+    if init {
+        let path    = sub_m.value_of("output").unwrap_or("fim.json");
+        let records = record(&[]);
+        if let Err(why) = fim::save(path, &records) {
+            return Err(ScanError::Config(format!("couldn't write FIM baseline {}: {}", path, why)));
+        }
+
+        let present = records.iter().filter(|r| r.size.is_some()).count();
+        terminal.fg(term::color::GREEN);
+        println!("FIM baseline of {} executables whitelisted in {} written to: {}", present, source, path);
+        terminal.reset();
+        return Ok(());
+    }
+
+    let path  = sub_m.value_of("baseline").unwrap_or("fim.json");
+    let saved = fim::load(path)
+        .map_err(|why| ScanError::Config(format!("couldn't read FIM baseline {}: {}", path, why)))?;
+    let records = record(&saved);
+    let changes = fim::diff(&saved, &records);
+
+    for change in changes.iter() {
+        match *change {
+            fim::Change::Modified(before, now) => {
+                terminal.fg(term::color::RED);
+                println!("Modified: {} ({}), sha256 {} -> {}", now.path, now.names.join(", "),
+                         before.sha256.as_ref().map_or("?", |h| h.as_str()),
+                         now.sha256.as_ref().map_or("?", |h| h.as_str()));
+            },
+            fim::Change::Touched(before, now)  => {
+                terminal.fg(term::color::YELLOW);
+                println!("Touched: {} ({}), same content, modified {} -> {}", now.path, now.names.join(", "),
+                         before.modified.map_or(String::from("?"), |m| m.format("%Y-%m-%d %H:%M:%S").to_string()),
+                         now.modified.map_or(String::from("?"), |m| m.format("%Y-%m-%d %H:%M:%S").to_string()));
+            },
+            fim::Change::Missing(before)       => {
+                terminal.fg(term::color::RED);
+                println!("Missing: {} ({})", before.path, before.names.join(", "));
+            },
+            fim::Change::Added(now)            => {
+                terminal.fg(term::color::YELLOW);
+                println!("Added: {} ({})", now.path, now.names.join(", "));
+            },
+        }
+        terminal.reset();
+    }
+
+    println!("Found {} changes to the executables recorded in {}.", changes.len(), path);
+    if !changes.is_empty() {
+        let _ = stdout().flush();
+        exit(1);
+    }
+
+    Ok(())
+}
+
+// Name, executable and executable hash of every running process
+fn snapshot_procs() -> Result<Vec<types::BaselineEntry>, ScanError> {
+    let (procs, _) = collect::local().all()?;
+
+    // Many processes share an executable, hash each one once
+    let mut hashes: HashMap<String, Option<String>> = HashMap::new();
+
+    Ok(procs.into_iter().map(|process| {
+        let hash = hashes.entry(process.exe_path.clone())
+            .or_insert_with(|| hash::sha256_file(&process.exe_path).ok())
+            .clone();
+
+        types::BaselineEntry {
+            pid:      process.pid,
+            name:     process.name,
+            exe_path: process.exe_path,
+            hash:     hash,
+        }
+    }).collect())
+}
+
+// Load standard system processes from `file_names` or, when no file is given,
+// from DEFAULT_FILE if it exists, else from the list embedded in the binary.
+// Files are layered in order, a later definition of a critical process replaces an earlier one.
+// Returns the name of the source along with its contents.
+// With a `profile`, the `[profile.NAME]` sections of that name apply too.
+fn load_config(file_names: &[&str], profile: Option<&str>) -> Result<(String, types::Config), ScanError> {
+    let paths = config_paths(file_names);
+    let (source, config) = if paths.is_empty() {
+        (defaults::DEFAULT_NAME.to_string(), parse_procs(defaults::DEFAULT_PROCS.as_bytes(), defaults::DEFAULT_NAME)?)
+    } else {
+        let mut config = empty_config();
+        config.profile = profile.map(String::from);
+        for path in paths.iter() {
+            read_procs_file(&mut config, Path::new(path), 0)?;
+        }
+        config.procs = layer_procs(config.procs);
+        apply_process_settings(&mut config).map_err(ScanError::Config)?;
+        (paths.join(", "), config)
+    };
+
+    match profile {
+        // A typo would silently fall back to the shared lines
+        Some(profile) if !config.profiles.iter().any(|defined| defined == profile) => {
+            Err(ScanError::Config(format!("profile {} isn't defined in {}", profile, source)))
+        },
+        Some(profile)                                                              => {
+            Ok((format!("{} (profile {})", source, profile), config))
+        },
+        None                                                                       => Ok((source, config)),
+    }
+}
+
+// Critical processes files to use: the given ones, ./default_procs.txt, the installed one,
+// or none for the embedded defaults
+fn config_paths(file_names: &[&str]) -> Vec<String> {
+    if !file_names.is_empty() {
+        file_names.iter().map(|file_name| file_name.to_string()).collect()
+    } else if Path::new(DEFAULT_FILE).exists() {
+        vec![DEFAULT_FILE.to_string()]
+    } else if defaults::installed_path().exists() {
+        vec![defaults::installed_path().to_string_lossy().into_owned()]
+    } else {
+        Vec::new()
+    }
+}
+
+// Includes deeper than this are most likely a loop
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// Add the standard system processes of a file, and of the files it includes, to `config`.
+fn read_procs_file(config: &mut types::Config, path: &Path, depth: usize) -> Result<(), ScanError> {
+    let display = path.display();
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(ScanError::Config(format!("{}: includes nested too deeply", display)));
+    }
+
+    config.files.push(path.to_path_buf());
+    let content = match fs::read(&path) {
+        Err(why)    => return Err(ScanError::Config(format!("couldn't open {}: {}", display, why.to_string()))),
+        Ok(content) => content,
+    };
+    // Included files carry their own signatures
+    configsig::verify(path, &content)?;
+
+    // Lines of other profiles are still checked, every host shares the file
+    let mut active  = true;
+    let mut skipped = empty_config();
+    for (number, line) in BufReader::new(&content[..]).lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(why) => return Err(ScanError::Config(format!("couldn't read {}: {}", display, why.to_string()))),
+        };
+
+        let result = if let Some(section) = profile_section(&line) {
+            enter_profile(config, section).map(|applies| active = applies)
+        } else if let Some(included) = include_target(&line) {
+            if active {
+                read_procs_file(config, &included_path(path, included), depth + 1)?;
+            }
+            Ok(())
+        } else {
+            parse_line(if active { &mut *config } else { &mut skipped }, &line)
+        };
+        if let Err(why) = result {
+            return Err(ScanError::Config(format!("{}:{}: {}", display, number + 1, why)));
+        }
+    }
+
+    Ok(())
+}
+
+// The profile named by a `[profile.NAME]` section header
+fn profile_section(line: &str) -> Option<Result<&str, String>> {
+    let trimmed = line.trim();
+    if !trimmed.starts_with('[') || !trimmed.ends_with(']') || trimmed.contains(';') {
+        return None;
+    }
+
+    let section = trimmed[1 .. trimmed.len() - 1].trim();
+    Some(match section.find('.').map(|pos| (&section[.. pos], section[pos + 1 ..].trim())) {
+        Some(("profile", name)) if !name.is_empty() => Ok(name),
+        _                                           => Err(format!("unknown section, expected [profile.NAME], line: {}",
+                                                                   line)),
+    })
+}
+
+// Record a `[profile.NAME]` section, true when its lines apply. A section runs to the next one or the end of the file.
+fn enter_profile(config: &mut types::Config, section: Result<&str, String>) -> Result<bool, String> {
+    let name = section?;
+    if !config.profiles.iter().any(|known| known == name) {
+        config.profiles.push(name.to_string());
+    }
+
+    Ok(config.profile.as_ref().map_or(false, |selected| selected == name))
+}
+
+// The file named by an `include = FILE` line
+fn include_target(line: &str) -> Option<&str> {
+    if line.contains(';') {
+        return None;
+    }
+
+    let mut kv = line.splitn(2, '=');
+    match (kv.next().map(str::trim), kv.next().map(str::trim)) {
+        (Some("include"), Some(file)) => Some(file),
+        _                             => None,
+    }
+}
+
+// Included files are relative to the including one
+fn included_path(including: &Path, included: &str) -> PathBuf {
+    including.parent().unwrap_or(Path::new("")).join(included)
+}
+
+// Keep the last definition of each critical process, in the order they were first defined
+fn layer_procs(procs: Vec<types::ProcProps>) -> Vec<types::ProcProps> {
+    let mut layered: Vec<types::ProcProps> = Vec::new();
+    for crit_proc in procs {
+        match layered.iter().position(|known| known.name == crit_proc.name) {
+            Some(i) => layered[i] = crit_proc,
+            None    => layered.push(crit_proc),
+        }
+    }
+
+    layered
+}
+
+fn empty_config() -> types::Config {
+    types::Config {
+        procs:      Vec::new(),
+        algorithm:  None,
+        ignore:     Vec::new(),
+        severities: Vec::new(),
+        instances:  Vec::new(),
+        users:      Vec::new(),
+        threads:    Vec::new(),
+        handles:    Vec::new(),
+        kernel_modules: Vec::new(),
+        names:      None,
+        ignore_path_case: None,
+        path_distance:    None,
+        rules:      Vec::new(),
+        weights:    Vec::new(),
+        alert_score: None,
+        recent_start: None,
+        recent_uptime: None,
+        mail:       mail::Settings::new(),
+        files:      Vec::new(),
+        profile:    None,
+        profiles:   Vec::new(),
+    }
+}
+
+// Parse standard system processes, stopping at the first invalid line.
+// Profile sections are only checked, no profile is selected.
+fn parse_procs<R: BufRead>(reader: R, display: &str) -> Result<types::Config, ScanError> {
+    let mut config  = empty_config();
+    let mut active  = true;
+    let mut skipped = empty_config();
+
+    // Read whole file line by line
+    for (number, line) in reader.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(why) => return Err(ScanError::Config(format!("couldn't read {}: {}", display, why.to_string()))),
+        };
+
+        let result = match profile_section(&line) {
+            Some(section) => enter_profile(&mut config, section).map(|applies| active = applies),
+            None          => parse_line(if active { &mut config } else { &mut skipped }, &line),
+        };
+        if let Err(why) = result {
+            return Err(ScanError::Config(format!("{}:{}: {}", display, number + 1, why)));
+        }
+    }
+
+    if let Err(why) = apply_process_settings(&mut config) {
+        return Err(ScanError::Config(format!("{}: {}", display, why)));
+    }
+
+    Ok(config)
+}
+
+// Each line is of the format:
+// <process name>;<threshold value>;<process absolute path>[;<process absolute path>...]
+// Paths may be glob patterns or, when prefixed with `re:`, regular expressions.
+// The threshold is either absolute, e.g. `2`, or a percentage of the name length, e.g. `15%`.
+// Lines of the format <setting> = <value> configure the scan,
+// empty lines and lines starting with '#' are ignored.
+fn parse_line(config: &mut types::Config, line: &str) -> Result<(), String> {
+    if line.trim().is_empty() || line.trim_start().starts_with('#') {
+        return Ok(());
+    }
+
+    if !line.contains(';') && line.contains('=') {
+        return parse_setting(config, line);
+    }
+
+    // Split each line into a vector
+    let v: Vec<_> = line.split(';').map(|s| s.to_string()).collect();
+    if v.len() < 3 {
+        return Err(format!("Invalid format, expected NAME;THRESHOLD;PATH[;PATH...], line: {}", line));
+    }
+    if v[0].trim().is_empty() {
+        return Err(format!("Missing process name, line: {}", line));
+    }
+    let mut wl    = Vec::new();
+    let mut in_containers = Vec::new();
+
+    // Push process absolute path, may be more than 1 path
+    for i in 2 .. v.len() {
+        if let Some(entry) = whitelist::ContainerEntry::parse(&v[i]) {
+            match entry {
+                Ok(entry) => in_containers.push(entry),
+                Err(why)  => return Err(format!("{}, line: {}", why, line)),
+            }
+            continue;
+        }
+        match whitelist::Entry::parse(&v[i]) {
+            Ok(entry) => wl.push(entry),
+            Err(why)  => return Err(format!("{}, line: {}", why, line)),
+        }
+    }
+
+    let threshold = match parse_threshold(&v[1]) {
+        Some(threshold) => threshold,
+        None            => return Err(format!("Invalid threshold {}, expected a number or a percentage, line: {}",
+                                              v[1], line)),
+    };
+
+    config.procs.push(types::ProcProps {
+        name:          v[0].to_string(),
+        threshold:     threshold,
+        whitelist:     wl,
+        in_containers: in_containers,
+        severity:      types::Severity::Medium,
+        instances:     None,
+        users:         Vec::new(),
+        threads:       None,
+        handles:       None,
+    });
+
+    Ok(())
+}
+
+// Settings of a critical process, e.g. `severity.NAME`, may come before the process they apply to
+fn apply_process_settings(config: &mut types::Config) -> Result<(), String> {
+    for &(ref name, severity) in config.severities.iter() {
+        match config.procs.iter_mut().find(|crit_proc| &crit_proc.name == name) {
+            Some(crit_proc) => crit_proc.severity = severity,
+            None            => return Err(format!("severity of unknown critical process: {}", name)),
+        }
+    }
+    for &(ref name, instances) in config.instances.iter() {
+        match config.procs.iter_mut().find(|crit_proc| &crit_proc.name == name) {
+            Some(crit_proc) => crit_proc.instances = Some(instances),
+            None            => return Err(format!("instances of unknown critical process: {}", name)),
+        }
+    }
+    for &(ref name, ref users) in config.users.iter() {
+        match config.procs.iter_mut().find(|crit_proc| &crit_proc.name == name) {
+            Some(crit_proc) => crit_proc.users = users.clone(),
+            None            => return Err(format!("user of unknown critical process: {}", name)),
+        }
+    }
+    for &(ref name, range) in config.threads.iter() {
+        match config.procs.iter_mut().find(|crit_proc| &crit_proc.name == name) {
+            Some(crit_proc) => crit_proc.threads = Some(range),
+            None            => return Err(format!("threads of unknown critical process: {}", name)),
+        }
+    }
+    for &(ref name, range) in config.handles.iter() {
+        match config.procs.iter_mut().find(|crit_proc| &crit_proc.name == name) {
+            Some(crit_proc) => crit_proc.handles = Some(range),
+            None            => return Err(format!("handles of unknown critical process: {}", name)),
+        }
+    }
+
+    Ok(())
+}
+
+// Parse an absolute threshold `2` or a relative one `15%`
+fn parse_threshold(value: &str) -> Option<types::Threshold> {
+    let value = value.trim();
+    if value.ends_with('%') {
+        value[.. value.len() - 1].parse::<u32>().ok().map(types::Threshold::Percent)
+    } else {
+        value.parse::<u32>().ok().map(types::Threshold::Absolute)
+    }
+}
+
+// Apply a <setting> = <value> line
+fn parse_setting(config: &mut types::Config, line: &str) -> Result<(), String> {
+    let mut kv    = line.splitn(2, '=');
+    let key       = kv.next().unwrap_or("").trim();
+    let value     = kv.next().unwrap_or("").trim();
+
+    match key {
+        "algorithm" => {
+            if !similarity::ALGORITHMS.contains(&value) {
+                return Err(format!("unknown algorithm: {}", value));
+            }
+            config.algorithm = Some(value.to_string());
+        },
+        "ignore"    => match whitelist::Entry::parse(value) {
+            Ok(entry) => config.ignore.push(entry),
+            Err(why)  => return Err(format!("{}, line: {}", why, line)),
+        },
+        "names"     => match types::NameMatching::from_name(value) {
+            Some(names) => config.names = Some(names),
+            None        => return Err(format!("unknown name matching, line: {}", line)),
+        },
+        "paths"     => match value {
+            "exact"       => config.ignore_path_case = Some(false),
+            "ignore-case" => config.ignore_path_case = Some(true),
+            _             => return Err(format!("unknown path matching, line: {}", line)),
+        },
+        "path_distance" => match value.parse::<u32>() {
+            Ok(distance) => config.path_distance = Some(distance),
+            Err(_)       => return Err(format!("invalid path distance, line: {}", line)),
+        },
+        "alert_score" => match value.parse::<u32>() {
+            Ok(score) => config.alert_score = Some(score),
+            Err(_)    => return Err(format!("invalid alert score, line: {}", line)),
+        },
+        "recent_start" | "recent_uptime" => match parse_duration(value) {
+            Some(duration) if key == "recent_start" => config.recent_start  = Some(duration),
+            Some(duration)                          => config.recent_uptime = Some(duration),
+            None                                    => return Err(format!("invalid duration, line: {}", line)),
+        },
+        "include"   => return Err(format!("includes are only supported in files, line: {}", line)),
+        _ if key.starts_with("smtp.")      => if let Err(why) = config.mail.set(&key["smtp.".len() ..], value) {
+            return Err(format!("{}, line: {}", why, line));
+        },
+        _ if key.starts_with("rule.")      => {
+            let name = &key["rule.".len() ..];
+            if !rules::exists(name) {
+                return Err(format!("unknown detection rule {}, line: {}", name, line));
+            }
+            match value {
+                "on"  => config.rules.push((name.to_string(), true)),
+                "off" => config.rules.push((name.to_string(), false)),
+                _     => return Err(format!("expected on or off, line: {}", line)),
+            }
+        },
+        _ if key.starts_with("weight.")    => {
+            let name = &key["weight.".len() ..];
+            if !rules::exists(name) {
+                return Err(format!("unknown detection rule {}, line: {}", name, line));
+            }
+            match value.parse::<u32>() {
+                Ok(weight) => config.weights.push((name.to_string(), weight)),
+                Err(_)     => return Err(format!("invalid weight, line: {}", line)),
+            }
+        },
+        _ if key.starts_with("severity.")  => match types::Severity::from_name(value) {
+            Some(severity) => config.severities.push((key["severity.".len() ..].to_string(), severity)),
+            None           => return Err(format!("unknown severity, line: {}", line)),
+        },
+        _ if key.starts_with("instances.") => match value.parse::<usize>() {
+            Ok(instances) if instances > 0 => {
+                config.instances.push((key["instances.".len() ..].to_string(), instances))
+            },
+            _                              => return Err(format!("expected a number of instances, line: {}", line)),
+        },
+        _ if key.starts_with("user.")      => {
+            let users: Vec<String> = value.split(',').map(|user| user.trim().to_string())
+                .filter(|user| !user.is_empty())
+                .collect();
+            if users.is_empty() {
+                return Err(format!("expected user names, line: {}", line));
+            }
+            config.users.push((key["user.".len() ..].to_string(), users))
+        },
+        _ if key.starts_with("kernel_module.") => match parse_threshold(value) {
+            Some(threshold) => {
+                config.kernel_modules.push((kernelmods::canonical(&key["kernel_module.".len() ..]), threshold))
+            },
+            None            => return Err(format!("invalid threshold, line: {}", line)),
+        },
+        _ if key.starts_with("threads.") || key.starts_with("handles.") => {
+            let range = match parse_range(value) {
+                Some(range) => range,
+                None        => return Err(format!("expected a range MIN-MAX, line: {}", line)),
+            };
+            // Both prefixes are as long
+            let name = key["threads.".len() ..].to_string();
+            if key.starts_with("threads.") {
+                config.threads.push((name, range));
+            } else {
+                config.handles.push((name, range));
+            }
+        },
+        _ => return Err(format!("unknown setting, line: {}", line)),
+    }
+
+    Ok(())
+}
+
+// Ignored processes are left out of the scan entirely
+fn is_ignored(name: &str, options: &types::ScanOptions) -> bool {
+    options.ignore.iter().any(|entry| entry.matches(name))
+}
+
+// Whether a running process falls within the --match and --pid-range scope
+fn in_scope(name: &str, pid: u32, options: &types::ScanOptions) -> bool {
+    (options.only.is_empty() || options.only.iter().any(|entry| entry.matches(name))) &&
+        options.pid_range.map_or(true, |(min, max)| min <= pid && pid <= max) &&
+        options.pids.as_ref().map_or(true, |pids| pids.contains(&pid))
+}
+
+// "MIN-MAX", "MIN-" or "-MAX"
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    let mut bounds = range.splitn(2, '-');
+    let min        = bounds.next()?.trim();
+    let max        = bounds.next()?.trim();
+
+    let min = if min.is_empty() { 0 } else { min.parse::<u32>().ok()? };
+    let max = if max.is_empty() { u32::max_value() } else { max.parse::<u32>().ok()? };
+
+    if min <= max { Some((min, max)) } else { None }
+}
+
+// UID of a user name, numeric UIDs are taken as is
+#[cfg(unix)]
+fn resolve_user(user: &str) -> Result<u32, ScanError> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(uid);
+    }
+
+    let name = match CString::new(user) {
+        Ok(name) => name,
+        Err(_)   => return Err(ScanError::Config(format!("invalid user name: {}", user))),
+    };
+    let passwd = unsafe { libc::getpwnam(name.as_ptr()) };
+    if passwd.is_null() {
+        return Err(ScanError::Config(format!("unknown user: {}", user)));
+    }
+
+    Ok(unsafe { (*passwd).pw_uid })
+}
+
+#[cfg(unix)]
+fn owned_by_users(process: &types::ProcessInfo, options: &types::ScanOptions) -> bool {
+    options.users.is_empty() || process.user.map_or(false, |uid| options.users.contains(&uid))
+}
+
+#[cfg(windows)]
+fn owned_by_users(_process: &types::ProcessInfo, _options: &types::ScanOptions) -> bool {
+    true
+}
+
+fn is_whitelisted(proc_path: &str, whitelist: &Vec<whitelist::Entry>, paths: types::PathMatching) -> bool {
+    whitelist.iter().any(|entry| entry.matches_with(proc_path, paths))
+}
+
+// Whitelisted anywhere, in the container the process runs in, or installed by a package with `--trust-packages`.
+// Symbolic links are followed for local processes outside containers, `/usr/bin/../tmp/evil` is in /tmp.
+fn is_whitelisted_in(proc_path: &str,
+                     container: Option<&types::Container>,
+                     crit_proc: &types::ProcProps,
+                     options  : &types::ScanOptions) -> bool {
+    let proc_path = if options.local && container.is_none() {
+        whitelist::canonical(proc_path)
+    } else {
+        whitelist::normalize(proc_path)
+    };
+
+    is_whitelisted(&proc_path, &crit_proc.whitelist, options.paths) || container.map_or(false, |container| {
+        crit_proc.in_containers.iter().any(|entry| entry.matches(&proc_path, container))
+    }) || is_packaged(&proc_path, options)
+}
+
+#[cfg(target_os = "linux")]
+fn is_packaged(proc_path: &str, options: &types::ScanOptions) -> bool {
+    match options.packages.as_ref().and_then(|packages| packages.verify(proc_path)) {
+        Some(package) => {
+            debug!("{} is installed by package {}", proc_path, package);
+            true
+        },
+        None          => false,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_packaged(_proc_path: &str, _options: &types::ScanOptions) -> bool {
+    false
+}
+
+// Severity of a detection: the critical process' own severity, raised for clear signs of
+// impersonation and lowered to low for names that are only loosely similar
+fn detection_severity(base      : types::Severity,
+                      reason    : &str,
+                      distance  : f64,
+                      limit     : f64,
+                      normalized: bool) -> types::Severity {
+    let close = if normalized { distance <= limit / 2.0 } else { distance < 2.0 };
+
+    match reason {
+        "wrong-path" | "bad-signature"                        => types::Severity::Critical,
+        "homoglyph" | "argv0" | "unsigned" | "deleted-exe" | "unresolved-exe" | "module" | "duplicate" |
+        "hollowed" | "exe-mismatch" | "wrong-user" | "injection" | "impersonation-at-rest" |
+        "persistence" | "service" | "kernel-module" |
+        "invisible-chars" | "temp-path" | "removable-path"    => max(base, types::Severity::High),
+        "odd-parent" | "recent" | "thread-count" |
+        "handle-count"                                        => min(base, types::Severity::Medium),
+        _ if close                                            => base,
+        _                                                     => types::Severity::Low,
+    }
+}
+
+// Suffix describing why a process was flagged, printed after "Suspicious"
+fn detection_reason(reason: &str) -> String {
+    if reason == "distance" { String::new() } else { format!(" ({})", reason) }
+}
+
+// Unpaired surrogates, which Windows names may hold, become U+FFFD
+#[cfg(windows)]
+fn from_wide(buf: &[u16]) -> String {
+    String::from_utf16_lossy(buf)
+        .split('\u{0}')
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+// Longest Windows path, in UTF-16 units along with the terminating null
+#[cfg(windows)]
+const WIDE_MAX: usize = 32768;
+
+// A string Windows copies into a buffer, read again with a buffer twice as large while it fills it up.
+// `read` returns the number of units copied, the buffer size when the string didn't fit, None on failure.
+#[cfg(windows)]
+fn read_wide<F>(mut read: F) -> Option<String>
+    where F: FnMut(&mut [u16]) -> Option<usize> {
+    let mut buf = vec![0u16; ::winapi::shared::minwindef::MAX_PATH];
+    loop {
+        let len = read(&mut buf[..])?;
+        // Truncated strings fill the buffer, with or without their terminating null
+        if len + 1 < buf.len() || buf.len() >= WIDE_MAX {
+            return Some(from_wide(&buf[.. min(len, buf.len())]));
+        }
+        buf = vec![0; min(buf.len() * 2, WIDE_MAX)];
+    }
+}
+
+// Check processes collected on this system or read from a captured listing
+fn check_procs_impers(setup        : &ScanSetup,
+                      sys_procs_vec: &[types::ProcessInfo],
+                      parents      : &HashMap<u32, String>,
+                      terminal     : &mut Output) -> types::ScanReport {
+    let (crit_procs_vec, options) = (&setup.crit_procs, &setup.options);
+    let sys_procs_vec: Vec<&types::ProcessInfo> = sys_procs_vec.iter()
+        .filter(|sys_proc| !is_ignored(&sys_proc.name, options) && in_scope(&sys_proc.name, sys_proc.pid, options) &&
+                owned_by_users(sys_proc, options))
+        .collect();
+    let mut report = types::ScanReport {
+        host:        None,
+        scanned:     sys_procs_vec.len(),
+        skipped:     0,
+        denied:      sys_procs_vec.iter()
+            .filter(|sys_proc| sys_proc.exe_denied)
+            .map(|sys_proc| (sys_proc.pid, sys_proc.name.clone()))
+            .collect(),
+        compared:    sys_procs_vec.len() * crit_procs_vec.len(),
+        elapsed:     Duration::default(),
+        comparisons: Vec::new(),
+        detections:  Vec::new(),
+        repeated:    Vec::new(),
+        interrupted: false,
+    };
+
+    // Distances and container lookups are the expensive part,
+    // compute them in parallel and report in order
+    let measured: Vec<Measured> = sys_procs_vec.par_iter().map(|sys_proc| measure(setup, sys_proc)).collect();
+    if let (&Some(ref distances), None) = (&options.distances, &options.pids) {
+        distances.retain(&sys_procs_vec.iter().map(|sys_proc| sys_proc.name.as_str()).collect());
+    }
+
+    let subjects: Vec<rules::Subject> = sys_procs_vec.iter().zip(measured.iter())
+        .map(|(sys_proc, measured)| subject(sys_proc, measured, parents))
+        .collect();
+    apply_rules(&mut report, setup, &subjects, terminal);
+
+    report
+}
+
+// What the rules need to know about a process beyond what was collected:
+// its argv[0] name, container, and whether it uses homoglyphs along with its distances
+type Measured = (Option<String>, Option<types::Container>, distcache::Distances);
+
+fn measure(setup: &ScanSetup, sys_proc: &types::ProcessInfo) -> Measured {
+    (argv0_name(&sys_proc.name, sys_proc.cmdline.as_ref()), local_container(sys_proc.pid, &setup.options),
+     cached_distances(&sys_proc.name, &setup.buckets, &*setup.algorithm, &setup.options))
+}
+
+fn subject<'a>(sys_proc: &'a types::ProcessInfo,
+               measured: &'a Measured,
+               parents : &'a HashMap<u32, String>) -> rules::Subject<'a> {
+    let &(ref argv0, ref container, (homoglyph, ref distances)) = measured;
+
+    rules::Subject {
+        pid:         sys_proc.pid,
+        ppid:        sys_proc.ppid,
+        parent:      sys_proc.ppid.and_then(|ppid| parents.get(&ppid)).map(|parent| parent.as_str()),
+        started:     sys_proc.started,
+        name:        &sys_proc.name,
+        exe_path:    &sys_proc.exe_path,
+        exe_known:   !sys_proc.exe_path.is_empty(),
+        exe_problem: sys_proc.exe_problem,
+        argv0:       argv0.as_ref().map(|argv0| argv0.as_str()),
+        owner:       sys_proc.owner.as_ref().map(|owner| owner.as_str()),
+        threads:     sys_proc.threads,
+        handles:     sys_proc.handles,
+        container:   container.as_ref(),
+        homoglyph:   homoglyph,
+        distances:   distances,
+    }
+}
+
+// Names of the processes by PID, to name the parent of the processes checked
+fn process_names(sys_procs_vec: &[types::ProcessInfo]) -> HashMap<u32, String> {
+    sys_procs_vec.iter().map(|sys_proc| (sys_proc.pid, sys_proc.name.clone())).collect()
+}
+
+// Names of `sys_procs_vec` and of their parents, when only some processes were collected
+fn parent_names(collector: &dyn collect::Collector, sys_procs_vec: &[types::ProcessInfo]) -> HashMap<u32, String> {
+    let mut names = process_names(sys_procs_vec);
+    let ppids: Vec<u32> = sys_procs_vec.iter()
+        .filter_map(|sys_proc| sys_proc.ppid)
+        .filter(|ppid| !names.contains_key(ppid))
+        .collect();
+    names.extend(process_names(&collector.pids(&ppids).0));
+
+    names
+}
+
+// Container of a process of this system, listings and remote hosts can't be looked into
+#[cfg(unix)]
+fn local_container(pid: u32, options: &types::ScanOptions) -> Option<types::Container> {
+    if options.local { container::of(pid) } else { None }
+}
+
+#[cfg(windows)]
+fn local_container(_pid: u32, _options: &types::ScanOptions) -> Option<types::Container> {
+    None
+}
+
+// Run every enabled detection rule against the processes, reporting in order
+fn apply_rules(report  : &mut types::ScanReport,
+               setup   : &ScanSetup,
+               subjects: &[rules::Subject],
+               terminal: &mut Output) {
+    let context      = rule_context(setup, subjects);
+    let mut progress = progress::Progress::new(subjects.len(), !setup.options.quiet && !setup.options.silent);
+
+    for (checked, subject) in subjects.iter().enumerate() {
+        // Ctrl-C keeps what the processes checked so far found
+        if cancel::interrupted() {
+            progress.clear();
+            report.interrupted = true;
+            report.scanned     = checked;
+            report.compared    = checked * setup.crit_procs.len();
+            break;
+        }
+        match subject.ppid {
+            Some(ppid) => debug!("Checking system process: {} ({}), started by {}",
+                                 subject.name, subject.exe_path, ppid),
+            None       => debug!("Checking system process: {} ({})", subject.name, subject.exe_path),
+        }
+        log_closest(&setup.crit_procs, subject.distances, setup.options.top);
+
+        // Every rule has its say before the process is scored
+        let (found, hits) = run_rules(setup, subject, &context);
+        let score = hits.iter().map(|&(_, weight)| weight).sum::<u32>();
+        for (rule, comparisons) in found {
+            for mut comparison in comparisons {
+                if comparison.suspicious {
+                    comparison.score = Some(score);
+                }
+                if comparison.suspicious && score < setup.alert_score {
+                    debug!("{} ({}) found by {} scores {}, below the alert score {}",
+                           subject.name, subject.pid, rule, score, setup.alert_score);
+                    comparison.suspicious = false;
+                } else if setup.options.explain && comparison.suspicious {
+                    comparison.explanation = explain::detection(rule, &comparison, subject, setup, &hits);
+                }
+                if comparison.suspicious {
+                    progress.clear();
+                }
+                record_comparison(report, &setup.options, comparison, terminal);
+            }
+        }
+        progress.tick();
+    }
+}
+
+// What the rules check `subjects` against, the instances of critical processes are counted among them
+fn rule_context<'a>(setup: &'a ScanSetup, subjects: &[rules::Subject]) -> rules::Context<'a> {
+    let mut context = rules::Context {
+        crit_procs: &setup.crit_procs,
+        algorithm:  &*setup.algorithm,
+        options:    &setup.options,
+        surplus:    HashMap::new(),
+    };
+    context.surplus = rules::surplus(subjects, &context);
+
+    context
+}
+
+// What each enabled rule found about a process, and the weight of those finding it suspicious
+fn run_rules(setup  : &ScanSetup,
+             subject: &rules::Subject,
+             context: &rules::Context) -> (Vec<(&'static str, Vec<types::Comparison>)>, Vec<(&'static str, u32)>) {
+    let mut found = Vec::new();
+    let mut hits  = Vec::new();
+
+    for (rule, &weight) in setup.rules.iter().zip(&setup.weights) {
+        let comparisons = rule.check(subject, context);
+        if comparisons.iter().any(|comparison| comparison.suspicious) {
+            hits.push((rule.name(), weight));
+        }
+        found.push((rule.name(), comparisons));
+    }
+
+    (found, hits)
+}
+
+// Commands named like a critical process, found in PATH before its whitelisted copy or in a
+// directory users can write to. They run whenever the critical name is typed or spawned.
+fn check_path_hijack(report        : &mut types::ScanReport,
+                     crit_procs_vec: &[types::ProcProps],
+                     buckets       : &lengths::LengthBuckets,
+                     algorithm     : &dyn Similarity,
+                     options       : &types::ScanOptions,
+                     terminal      : &mut Output) {
+    let dirs = pathenv::dirs();
+
+    for (index, dir) in dirs.iter().enumerate() {
+        if dir.is_relative() {
+            warn!("PATH contains the relative directory \"{}\", commands are looked up in the current directory",
+                  dir.display());
+            continue;
+        }
+        let writable = pathenv::writable_by_users(dir);
+
+        for file in pathenv::executables(dir) {
+            let name = match file.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None       => continue,
+            };
+            if is_ignored(&name, options) {
+                continue;
+            }
+            let path = file.to_string_lossy().into_owned();
+            trace!("Checking command: {}", path);
+
+            let (homoglyph, distances) = measure_distances(&name, buckets, algorithm, options.names);
+            report.compared += crit_procs_vec.len();
+
+            for (crit_proc, distance) in crit_procs_vec.iter().zip(distances) {
+                let limit = crit_proc.threshold.limit(&crit_proc.name, algorithm.normalized());
+                let canonical = whitelist::canonical(&path);
+                if distance > limit || is_whitelisted(&canonical, &crit_proc.whitelist, options.paths) ||
+                    is_packaged(&canonical, options) {
+                    continue;
+                }
+
+                // Found first, the whitelisted copy further down PATH is never run
+                let shadows = distance == 0.0 && !homoglyph && dirs[index + 1 ..].iter()
+                    .map(|later| later.join(&name))
+                    .filter(|later| later.is_file())
+                    .map(|later| whitelist::canonical(&later.to_string_lossy()))
+                    .any(|later| is_whitelisted(&later, &crit_proc.whitelist, options.paths));
+
+                let mut explanation = Vec::new();
+                if options.explain && (shadows || writable) {
+                    explanation.push(format!("check path-hijack, distance {} to {}, threshold {}",
+                                             distance, crit_proc.name, limit));
+                    if shadows {
+                        explanation.push(format!("found in PATH before the whitelisted {}", crit_proc.name));
+                    }
+                    if writable {
+                        explanation.push(format!("{} is writable by other users", dir.display()));
+                    }
+                    explanation.push(explain::whitelist(&path, crit_proc));
+                }
+                record_comparison(report, options, types::Comparison {
+                    pid:        0,
+                    name:       name.clone(),
+                    exe_path:   path.clone(),
+                    crit_name:  crit_proc.name.clone(),
+                    distance:   distance,
+                    threshold:  limit,
+                    suspicious: shadows || writable,
+                    reason:     "impersonation-at-rest",
+                    severity:   detection_severity(crit_proc.severity, "impersonation-at-rest",
+                                                   distance, limit, algorithm.normalized()),
+                    module:     String::new(),
+                    connections: Vec::new(),
+                    environment: Vec::new(),
+                    container:  None,
+                    owner:      None,
+                    exe_info:   None,
+                    yara:       Vec::new(),
+                    virustotal: None,
+                    suspended:  false,
+                    explanation: explanation,
+                    score:      None,
+                    started:    None,
+                }, terminal);
+            }
+        }
+    }
+}
+
+// Autostart entries whose program is named like a critical process, outside its whitelisted paths
+fn check_persistence(report        : &mut types::ScanReport,
+                     crit_procs_vec: &[types::ProcProps],
+                     buckets       : &lengths::LengthBuckets,
+                     algorithm     : &dyn Similarity,
+                     options       : &types::ScanOptions,
+                     terminal      : &mut Output) {
+    for entry in persistence::entries() {
+        // Looked up in PATH when not given as a path
+        let name = match Path::new(&entry.target).file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None       => continue,
+        };
+        if is_ignored(&name, options) {
+            continue;
+        }
+        trace!("Checking autostart entry: {} in {}", entry.target, entry.location);
+
+        let (_, distances) = measure_distances(&name, buckets, algorithm, options.names);
+        report.compared += crit_procs_vec.len();
+
+        for (crit_proc, distance) in crit_procs_vec.iter().zip(distances) {
+            let limit = crit_proc.threshold.limit(&crit_proc.name, algorithm.normalized());
+            let canonical = whitelist::canonical(&entry.target);
+            if distance > limit || is_whitelisted(&canonical, &crit_proc.whitelist, options.paths) ||
+                is_packaged(&canonical, options) {
+                continue;
+            }
+
+            let mut explanation = Vec::new();
+            if options.explain {
+                explanation.push(format!("check persistence, distance {} to {}, threshold {}",
+                                         distance, crit_proc.name, limit));
+                explanation.push(format!("started from {}", entry.location));
+                explanation.push(explain::whitelist(&entry.target, crit_proc));
+            }
+            record_comparison(report, options, types::Comparison {
+                pid:        0,
+                name:       name.clone(),
+                exe_path:   entry.target.clone(),
+                crit_name:  crit_proc.name.clone(),
+                distance:   distance,
+                threshold:  limit,
+                suspicious: true,
+                reason:     "persistence",
+                severity:   detection_severity(crit_proc.severity, "persistence",
+                                               distance, limit, algorithm.normalized()),
+                module:     entry.location.clone(),
+                connections: Vec::new(),
+                environment: Vec::new(),
+                container:  None,
+                owner:      None,
+                exe_info:   None,
+                yara:       Vec::new(),
+                virustotal: None,
+                suspended:  false,
+                explanation: explanation,
+                score:      None,
+                started:    None,
+            }, terminal);
+        }
+    }
+}
+
+// Installed services named like a critical process, or running a program named like one, from outside its
+// whitelisted paths
+fn check_services(report        : &mut types::ScanReport,
+                  crit_procs_vec: &[types::ProcProps],
+                  buckets       : &lengths::LengthBuckets,
+                  algorithm     : &dyn Similarity,
+                  options       : &types::ScanOptions,
+                  terminal      : &mut Output) {
+    for service in persistence::services() {
+        if is_ignored(&service.name, options) {
+            continue;
+        }
+        trace!("Checking service: {} ({})", service.name, service.binary);
+
+        // Windows services run as the executable their name would have
+        let process = if cfg!(windows) { format!("{}.exe", service.name) } else { service.name.clone() };
+        let program = Path::new(&service.binary).file_name().map(|name| name.to_string_lossy().into_owned());
+        let (_, by_name) = measure_distances(&process, buckets, algorithm, options.names);
+        let by_program = match program {
+            Some(ref program) => measure_distances(program, buckets, algorithm, options.names).1,
+            None              => vec![f64::INFINITY; crit_procs_vec.len()],
+        };
+        report.compared += crit_procs_vec.len();
+
+        for ((crit_proc, name_distance), program_distance) in crit_procs_vec.iter().zip(by_name).zip(by_program) {
+            let limit    = crit_proc.threshold.limit(&crit_proc.name, algorithm.normalized());
+            let distance = name_distance.min(program_distance);
+            let canonical = whitelist::canonical(&service.binary);
+            let trusted   = !service.binary.is_empty() &&
+                (is_whitelisted(&canonical, &crit_proc.whitelist, options.paths) || is_packaged(&canonical, options));
+            if distance > limit || trusted {
+                continue;
+            }
+
+            let mut explanation = Vec::new();
+            if options.explain {
+                explanation.push(format!("check services, distance {} to {}, threshold {}",
+                                         distance, crit_proc.name, limit));
+                explanation.push(format!("service \"{}\" defined in {}", service.display, service.location));
+                explanation.push(if service.binary.is_empty() {
+                    String::from("the service names no program")
+                } else {
+                    explain::whitelist(&service.binary, crit_proc)
+                });
+            }
+            record_comparison(report, options, types::Comparison {
+                pid:        0,
+                name:       service.name.clone(),
+                exe_path:   service.binary.clone(),
+                crit_name:  crit_proc.name.clone(),
+                distance:   distance,
+                threshold:  limit,
+                suspicious: true,
+                reason:     "service",
+                severity:   detection_severity(crit_proc.severity, "service",
+                                               distance, limit, algorithm.normalized()),
+                module:     service.location.clone(),
+                connections: Vec::new(),
+                environment: Vec::new(),
+                container:  None,
+                owner:      None,
+                exe_info:   None,
+                yara:       Vec::new(),
+                virustotal: None,
+                suspended:  false,
+                explanation: explanation,
+                score:      None,
+                started:    None,
+            }, terminal);
+        }
+    }
+}
+
+// Loaded kernel modules named like an expected one without being it, e.g. `nvdia` next to `nvidia`
+fn check_kernel_modules(report   : &mut types::ScanReport,
+                        expected : &[(String, types::Threshold)],
+                        algorithm: &dyn Similarity,
+                        options  : &types::ScanOptions,
+                        terminal : &mut Output) {
+    let loaded = match kernelmods::loaded() {
+        Ok(loaded) => loaded,
+        Err(why)   => {
+            warn!("Couldn't list the loaded kernel modules: {}", why);
+            return;
+        },
+    };
+
+    for module in loaded {
+        let name = kernelmods::canonical(&module.name);
+        // Expected modules may be named like each other
+        if expected.iter().any(|&(ref expected, _)| *expected == name) {
+            continue;
+        }
+        trace!("Checking kernel module: {}", name);
+        report.compared += expected.len();
+
+        for &(ref expected, ref threshold) in expected {
+            let distance = algorithm.distance(&options.names.normalize(&name), &options.names.normalize(expected));
+            let limit    = threshold.limit(expected, algorithm.normalized());
+            if distance > limit {
+                continue;
+            }
+
+            let mut explanation = Vec::new();
+            if options.explain {
+                explanation.push(format!("check kernel-modules, distance {} to {}, threshold {}",
+                                         distance, expected, limit));
+                explanation.push(format!("{} isn't among the expected kernel modules", name));
+            }
+            record_comparison(report, options, types::Comparison {
+                pid:        0,
+                name:       module.name.clone(),
+                exe_path:   module.path.clone(),
+                crit_name:  expected.clone(),
+                distance:   distance,
+                threshold:  limit,
+                suspicious: true,
+                reason:     "kernel-module",
+                severity:   detection_severity(types::Severity::Medium, "kernel-module",
+                                               distance, limit, algorithm.normalized()),
+                module:     String::new(),
+                connections: Vec::new(),
+                environment: Vec::new(),
+                container:  None,
+                owner:      None,
+                exe_info:   None,
+                yara:       Vec::new(),
+                virustotal: None,
+                suspended:  false,
+                explanation: explanation,
+                score:      None,
+                started:    None,
+            }, terminal);
+        }
+    }
+}
+
+// Name a process claims through argv[0], when it differs from its comm
+fn argv0_name(comm: &str, cmdline: Option<&Vec<String>>) -> Option<String> {
+    // Process titles may follow the name, login shells prefix it with '-'
+    let arg0 = cmdline.and_then(|cmdline| cmdline.first()).and_then(|arg0| arg0.split_whitespace().next())?;
+    let name = Path::new(arg0).file_name()?.to_string_lossy().trim_start_matches('-').to_string();
+
+    if name.is_empty() || name == comm {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+// Distance between `name` and every critical process, after folding look-alike characters, infinite for
+// those its length puts out of reach. Also tells whether `name` contained look-alike characters.
+fn measure_distances(name     : &str,
+                     buckets  : &lengths::LengthBuckets,
+                     algorithm: &dyn Similarity,
+                     names    : types::NameMatching) -> (bool, Vec<f64>) {
+    let norm_name = names.normalize(&confusables::skeleton(name));
+    let homoglyph = confusables::has_confusables(name);
+    let distances = buckets.distances(&norm_name, algorithm);
+
+    (homoglyph, distances)
+}
+
+// The `top` critical processes closest to the process being checked, all of them for 0
+fn log_closest(crit_procs_vec: &[types::ProcProps], distances: &[f64], top: usize) {
+    if !log_enabled!(::log::LogLevel::Debug) {
+        return;
+    }
+
+    debug!("Closest critical processes: {}", closest(crit_procs_vec, distances, top).join(", "));
+}
+
+// The `top` critical processes nearest to a process name, all of them when 0, as "NAME (DISTANCE)"
+fn closest(crit_procs_vec: &[types::ProcProps], distances: &[f64], top: usize) -> Vec<String> {
+    let mut closest: Vec<(&str, f64)> = crit_procs_vec.iter()
+        .map(|crit_proc| crit_proc.name.as_str())
+        .zip(distances.iter().cloned())
+        // Left unmeasured
+        .filter(|&(_, distance)| distance.is_finite())
+        .collect();
+    closest.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(::std::cmp::Ordering::Equal));
+    if top > 0 {
+        closest.truncate(top);
+    }
+
+    closest.iter().map(|&(name, distance)| format!("{} ({})", name, distance)).collect()
+}
+
+// Distances of `name`, measured by a previous scan of the monitor mode when possible
+fn cached_distances(name     : &str,
+                    buckets  : &lengths::LengthBuckets,
+                    algorithm: &dyn Similarity,
+                    options  : &types::ScanOptions) -> distcache::Distances {
+    match options.distances {
+        Some(ref distances) => distances.get_or_measure(name, || {
+            measure_distances(name, buckets, algorithm, options.names)
+        }),
+        None                => measure_distances(name, buckets, algorithm, options.names),
+    }
+}
+
+fn suspend_process(comparison: &types::Comparison) -> bool {
+    if comparison.pid == ::std::process::id() {
+        return false;
+    }
+
+    match response::suspend(comparison.pid) {
+        Ok(())   => true,
+        Err(why) => {
+            error!("couldn't suspend {} ({}): {}", comparison.name, comparison.pid, why);
+            false
+        },
+    }
+}
+
+// Print a detection and keep the comparison if it belongs in the report.
+// Comparisons are kept when suspicious, or when their distance is within `report_dist`.
+fn record_comparison(report    : &mut types::ScanReport,
+                     options   : &types::ScanOptions,
+                     comparison: types::Comparison,
+                     terminal  : &mut Output) {
+    let mut comparison = comparison;
+    let detected = comparison.suspicious && comparison.severity >= options.min_severity;
+
+    // Already alerted on by a previous scan, only kept for the report
+    if detected && options.suppress.contains(&dedup::key(&comparison)) {
+        debug!("Already reported: {} ({}) <-> {}", comparison.name, comparison.pid, comparison.crit_name);
+        report.repeated.push(comparison);
+        return;
+    }
+
+    if detected && options.connections && comparison.pid != 0 {
+        comparison.connections = netconn::connections(comparison.pid);
+    }
+    if let (true, Some(settings)) = (detected && comparison.pid != 0, options.environ.as_ref()) {
+        match environ::selected(comparison.pid, settings) {
+            Ok(environment) => comparison.environment = environment,
+            Err(why)        => debug!("Couldn't read the environment of {} ({}): {}",
+                                      comparison.name, comparison.pid, why),
+        }
+    }
+    if detected && options.local {
+        let exe_path = comparison.exe_path.clone();
+        comparison.exe_info = cancel::with_timeout(move || fileinfo::stat(&exe_path)).and_then(|exe_info| exe_info);
+    }
+    #[cfg(feature = "yara")]
+    if let (true, Some(rules)) = (detected, options.yara.as_ref()) {
+        let path = if comparison.module.is_empty() { &comparison.exe_path } else { &comparison.module };
+        match yarascan::matches(rules, path) {
+            Ok(matched) => comparison.yara = matched,
+            Err(why)    => debug!("couldn't scan {} with the YARA rules: {}", path, why),
+        }
+    }
+    if let (true, Some(client)) = (detected, options.virustotal.as_ref()) {
+        let path = if comparison.module.is_empty() { &comparison.exe_path } else { &comparison.module };
+        match hash::sha256_file(path).map_err(|why| why.to_string()).and_then(|sha256| client.lookup(&sha256)) {
+            Ok(verdict) => comparison.virustotal = Some(verdict),
+            Err(why)    => warn!("couldn't look up {} on VirusTotal: {}", path, why),
+        }
+    }
+    // A process with several detections is suspended once
+    if detected && options.suspend && comparison.pid != 0 {
+        comparison.suspended = report.detections.iter().any(|d| d.pid == comparison.pid && d.suspended) ||
+            suspend_process(&comparison);
+    }
+
+    if detected && options.silent {
+        report.detections.push(comparison);
+    } else if let (true, Some(template)) = (detected, options.template.as_ref()) {
+        println!("{}", template.render(&comparison));
+
+        report.detections.push(comparison);
+    } else if detected && options.quiet {
+        // reason, pid, name, critical name, distance, threshold, executable (or module) path, severity
+        let path = if comparison.module.is_empty() { &comparison.exe_path } else { &comparison.module };
+        print!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}", comparison.reason, comparison.pid, comparison.name,
+               comparison.crit_name, comparison.distance, comparison.threshold, path,
+               comparison.severity.name());
+        // followed by the connections, separated by semicolons
+        if options.connections {
+            let connections: Vec<String> = comparison.connections.iter().map(|c| c.to_string()).collect();
+            print!("\t{}", connections.join("; "));
+        }
+        println!();
+
+        report.detections.push(comparison);
+    } else if detected {
+        let reason = detection_reason(comparison.reason);
+        let prefix = match comparison.score {
+            Some(score) => messages::text("detection.scored", &[&reason, &comparison.severity.name(), &score]),
+            None        => messages::text("detection.suspicious", &[&reason, &comparison.severity.name()]),
+        };
+        let c = &comparison;
+
+        terminal.fg(term::color::RED);
+        println!("{}", match comparison.reason {
+            "wrong-path" | "deleted-exe" | "unresolved-exe" => {
+                messages::text("detection.running_from", &[&prefix, &c.name, &c.exe_path])
+            },
+            "argv0"                                         => {
+                messages::text("detection.argv0", &[&prefix, &c.name, &c.crit_name, &c.exe_path])
+            },
+            "module"                                        => {
+                messages::text("detection.module", &[&prefix, &c.name, &c.pid, &c.module])
+            },
+            "injection"                                     => {
+                messages::text("detection.injection", &[&prefix, &c.name, &c.pid, &c.module])
+            },
+            "duplicate"                                     => {
+                messages::text("detection.duplicate", &[&prefix, &c.name, &c.pid, &c.distance, &c.crit_name,
+                                                        &c.threshold, &c.exe_path])
+            },
+            "recent"                                        => {
+                messages::text("detection.recent", &[&prefix, &c.name, &c.pid, &c.exe_path])
+            },
+            "odd-parent"                                    => {
+                messages::text("detection.odd_parent", &[&prefix, &c.name, &c.pid, &c.exe_path])
+            },
+            "hollowed"                                      => {
+                messages::text("detection.hollowed", &[&prefix, &c.name, &c.pid, &c.exe_path])
+            },
+            "exe-mismatch"                                  => {
+                messages::text("detection.exe_mismatch", &[&prefix, &c.name, &c.pid, &c.exe_path])
+            },
+            "wrong-user"                                    => {
+                let owner = c.owner.as_ref().map_or("?", |owner| owner.as_str());
+                messages::text("detection.wrong_user", &[&prefix, &c.name, &c.pid, &owner, &c.crit_name])
+            },
+            "thread-count"                                  => {
+                messages::text("detection.threads", &[&prefix, &c.name, &c.pid, &c.distance, &c.crit_name])
+            },
+            "handle-count"                                  => {
+                messages::text("detection.handles", &[&prefix, &c.name, &c.pid, &c.distance, &c.crit_name])
+            },
+            "impersonation-at-rest"                         => {
+                messages::text("detection.at_rest", &[&prefix, &c.exe_path, &c.crit_name, &c.distance])
+            },
+            "persistence"                                   => {
+                messages::text("detection.persistence", &[&prefix, &c.module, &c.exe_path, &c.crit_name, &c.distance])
+            },
+            "service"                                       => {
+                messages::text("detection.service", &[&prefix, &c.name, &c.exe_path, &c.crit_name, &c.distance])
+            },
+            "invisible-chars"                               => {
+                // Control characters would reach the terminal
+                messages::text("detection.invisible", &[&prefix, &c.name.escape_debug(), &c.pid, &c.crit_name])
+            },
+            "temp-path"                                     => {
+                messages::text("detection.temp_path", &[&prefix, &c.name, &c.pid, &c.exe_path, &c.crit_name])
+            },
+            "removable-path"                                => {
+                messages::text("detection.removable", &[&prefix, &c.name, &c.pid, &c.exe_path, &c.crit_name])
+            },
+            "kernel-module"                                 => {
+                messages::text("detection.kernel_mod", &[&prefix, &c.name, &c.crit_name, &c.distance])
+            },
+            _                                               => {
+                messages::text("detection.distance", &[&prefix, &c.name, &c.crit_name, &c.distance])
+            },
+        });
+        terminal.reset();
+        if let Some(ref container) = comparison.container {
+            println!("\t{}", messages::text("detection.container", &[container]));
+        }
+        if let Some(ref exe_info) = comparison.exe_info {
+            println!("\t{}", messages::text("detection.executable", &[exe_info]));
+        }
+        for &(ref name, ref value) in &comparison.environment {
+            println!("\t{}", messages::text("detection.environment", &[name, value]));
+        }
+        if !comparison.yara.is_empty() {
+            println!("\t{}", messages::text("detection.yara", &[&comparison.yara.join(", ")]));
+        }
+        match comparison.virustotal {
+            Some(ref verdict) if verdict.engines > 0 => {
+                println!("\t{}", messages::text("detection.virustotal", &[&verdict.malicious, &verdict.engines]))
+            },
+            Some(_)                                  => println!("\t{}", messages::text("detection.vt_unknown", &[])),
+            None                                     => (),
+        }
+        if let Some(started) = comparison.started {
+            let age = format_age((Local::now() - started).num_seconds());
+            println!("\t{}", messages::text("detection.started", &[&started.format("%Y-%m-%d %H:%M:%S"), &age]));
+        }
+        if comparison.suspended {
+            println!("\t{}", messages::text("detection.suspended", &[&comparison.pid]));
+        }
+        for connection in &comparison.connections {
+            println!("\t{}", connection);
+        }
+        for line in &comparison.explanation {
+            println!("\t{}", line);
+        }
+
+        report.detections.push(comparison);
+    } else if options.report_dist.map_or(false, |d| comparison.distance <= d) {
+        report.comparisons.push(comparison);
+    }
 }
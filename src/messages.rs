@@ -0,0 +1,96 @@
+// Console messages in the language chosen with --lang, from a catalog compiled into the binary or
+// read from a file. Reports, JSON and CSV output, and the --quiet lines stay in English.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::sync::OnceLock;
+
+const ENGLISH: &'static str = include_str!("locales/en.txt");
+
+// Languages compiled into the binary
+pub const LANGUAGES: &'static [(&'static str, &'static str)] = &[
+    ("en", ENGLISH),
+    ("de", include_str!("locales/de.txt")),
+];
+
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+// Use the catalog of `lang`, a language code or the path to a catalog file, English when absent
+pub fn init(lang: Option<&str>) -> Result<(), String> {
+    let catalog = match lang {
+        None       => return Ok(()),
+        Some(lang) => match LANGUAGES.iter().find(|&&(code, _)| code == lang) {
+            Some(&(_, contents)) => parse(contents)?,
+            None                 => {
+                let contents = fs::read_to_string(lang)
+                    .map_err(|why| format!("{} is neither a known language ({}) nor a readable catalog: {}",
+                                           lang, codes().join(", "), why))?;
+                parse(&contents).map_err(|why| format!("{}: {}", lang, why))?
+            },
+        },
+    };
+    let _ = CATALOG.set(catalog);
+
+    Ok(())
+}
+
+// Message `key` with its `{}` replaced by `args` in order, in English when the catalog lacks it
+pub fn text(key: &str, args: &[&dyn Display]) -> String {
+    let template = CATALOG.get().and_then(|catalog| catalog.get(key)).cloned()
+        .or_else(|| english(key))
+        .unwrap_or_else(|| key.to_string());
+
+    let mut parts = template.split("{}");
+    let mut text  = parts.next().unwrap_or("").to_string();
+    for (index, part) in parts.enumerate() {
+        match args.get(index) {
+            Some(arg) => text.push_str(&arg.to_string()),
+            None      => text.push_str("{}"),
+        }
+        text.push_str(part);
+    }
+
+    text
+}
+
+fn english(key: &str) -> Option<String> {
+    ENGLISH.lines().filter_map(entry).find(|&(name, _)| name == key).map(|(_, template)| template.to_string())
+}
+
+// `key = text` lines, every key must be one of the English catalog
+fn parse(contents: &str) -> Result<HashMap<String, String>, String> {
+    let mut catalog = HashMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match entry(line) {
+            Some((key, template)) if english(key).is_some() => {
+                catalog.insert(key.to_string(), template.to_string());
+            },
+            Some((key, _))                                  => {
+                return Err(format!("unknown message {} on line {}", key, number + 1));
+            },
+            None                                            => {
+                return Err(format!("expected `key = text` on line {}: {}", number + 1, line));
+            },
+        }
+    }
+
+    Ok(catalog)
+}
+
+fn entry(line: &str) -> Option<(&str, &str)> {
+    if line.trim_start().starts_with('#') {
+        return None;
+    }
+    let pos = line.find('=')?;
+
+    Some((line[.. pos].trim(), line[pos + 1 ..].trim()))
+}
+
+fn codes() -> Vec<&'static str> {
+    LANGUAGES.iter().map(|&(code, _)| code).collect()
+}
@@ -0,0 +1,89 @@
+// Prometheus metrics of the monitor mode, served over plain HTTP
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use report;
+use types::ScanReport;
+
+#[derive(Default)]
+pub struct Metrics {
+    scans     : u64,
+    checked   : usize,
+    duration  : f64,
+    // Detections of the last scan per (critical process, reason)
+    suspicious: BTreeMap<(String, String), usize>,
+}
+
+impl Metrics {
+    pub fn record(&mut self, report: &ScanReport, duration: Duration) {
+        self.scans   += 1;
+        self.checked  = report.scanned;
+        self.duration = report::seconds(duration);
+
+        self.suspicious.clear();
+        for d in report.detections.iter().chain(report.repeated.iter()) {
+            *self.suspicious.entry((d.crit_name.clone(), d.reason.to_string())).or_insert(0) += 1;
+        }
+    }
+
+    // Prometheus text exposition format
+    fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP bonomen_scans_total Scans performed since start.\n");
+        out.push_str("# TYPE bonomen_scans_total counter\n");
+        out.push_str(&format!("bonomen_scans_total {}\n", self.scans));
+        out.push_str("# HELP bonomen_processes_checked Processes checked by the last scan.\n");
+        out.push_str("# TYPE bonomen_processes_checked gauge\n");
+        out.push_str(&format!("bonomen_processes_checked {}\n", self.checked));
+        out.push_str("# HELP bonomen_scan_duration_seconds Duration of the last scan.\n");
+        out.push_str("# TYPE bonomen_scan_duration_seconds gauge\n");
+        out.push_str(&format!("bonomen_scan_duration_seconds {}\n", self.duration));
+        out.push_str("# HELP bonomen_suspicious_processes Suspicious processes found by the last scan.\n");
+        out.push_str("# TYPE bonomen_suspicious_processes gauge\n");
+        for (&(ref critical, ref reason), count) in self.suspicious.iter() {
+            out.push_str(&format!("bonomen_suspicious_processes{{critical=\"{}\",reason=\"{}\"}} {}\n",
+                                  label_value(critical), label_value(reason), count));
+        }
+
+        out
+    }
+}
+
+fn label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Listen on `addr` and answer every request with the current metrics, from a background thread
+pub fn serve(addr: &str, metrics: Arc<Mutex<Metrics>>) -> Result<(), String> {
+    let listener = TcpListener::bind(addr).map_err(|why| format!("couldn't listen on {}: {}", addr, why))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|s| s.ok()) {
+            if let Err(why) = respond(stream, &metrics) {
+                debug!("metrics request failed: {}", why);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, metrics: &Arc<Mutex<Metrics>>) -> ::std::io::Result<()> {
+    // The request itself doesn't matter, every path serves the metrics
+    let mut request = [0; 1024];
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let _ = stream.read(&mut request)?;
+
+    let body = match metrics.lock() {
+        Ok(metrics) => metrics.render(),
+        Err(_)      => String::new(),
+    };
+    write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           body.len(), body)
+}
@@ -0,0 +1,108 @@
+// MISP events of the detections of a scan, ready for import. Each detection is a process object
+// referencing a file object for its executable, the event is tagged with the Masquerading technique.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::Local;
+use serde_json::Value;
+use uuid::Uuid;
+
+use hash;
+use report;
+use sarif;
+use types::{Comparison, ScanReport, Severity};
+
+const MASQUERADING: &'static str = "misp-galaxy:mitre-attack-pattern=\"Masquerading - T1036\"";
+
+pub fn write<W: Write>(out: &mut W, report: &ScanReport) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &event(report))?;
+    writeln!(out)
+}
+
+pub fn event(report: &ScanReport) -> Value {
+    let host = report.host.clone().unwrap_or_else(report::hostname);
+    let now  = Local::now();
+
+    let mut objects = Vec::new();
+    for c in &report.detections {
+        let executable = file(c);
+        let mut attributes = vec![attribute("name", "text", &c.name)];
+        if c.pid != 0 {
+            attributes.push(attribute("pid", "text", &c.pid.to_string()));
+        }
+        if !c.exe_path.is_empty() {
+            attributes.push(attribute("image", "filename", &c.exe_path));
+        }
+        let mut process = json!({
+            "name":          "process",
+            "meta-category": "misc",
+            "uuid":          Uuid::new_v4().to_string(),
+            "comment":       format!("{} [{}, {}]", sarif::message(c), c.reason, c.severity.name()),
+            "Attribute":     attributes,
+        });
+        if let Some(executable) = executable {
+            process["ObjectReference"] = json!([{
+                "referenced_uuid":   executable["uuid"],
+                "relationship_type": "executes",
+            }]);
+            objects.push(process);
+            objects.push(executable);
+        } else {
+            objects.push(process);
+        }
+    }
+
+    json!({
+        "Event": {
+            "uuid":            Uuid::new_v4().to_string(),
+            "info":            format!("BONOMEN: {} suspicious processes on {}", report.detections.len(), host),
+            "date":            now.format("%Y-%m-%d").to_string(),
+            "timestamp":       now.timestamp().to_string(),
+            "threat_level_id": threat_level(report),
+            // Initial analysis, distributed to this organisation only
+            "analysis":        "0",
+            "distribution":    "0",
+            "published":       false,
+            "Tag":             [{ "name": MASQUERADING }],
+            "Object":          objects,
+        },
+    })
+}
+
+// File object of the executable, hashed when it is on this machine
+fn file(c: &Comparison) -> Option<Value> {
+    let name = Path::new(&c.exe_path).file_name()?.to_string_lossy().into_owned();
+    let mut attributes = vec![attribute("filename", "filename", &name), attribute("path", "text", &c.exe_path)];
+    if c.exe_info.is_some() {
+        if let Ok(sha256) = hash::sha256_file(&c.exe_path) {
+            attributes.push(attribute("sha256", "sha256", &sha256));
+        }
+    }
+
+    Some(json!({
+        "name":          "file",
+        "meta-category": "file",
+        "uuid":          Uuid::new_v4().to_string(),
+        "Attribute":     attributes,
+    }))
+}
+
+fn attribute(relation: &str, kind: &str, value: &str) -> Value {
+    json!({
+        "object_relation": relation,
+        "type":            kind,
+        "value":           value,
+        "to_ids":          false,
+    })
+}
+
+// 1 high to 3 low, 4 undefined for scans without detections
+fn threat_level(report: &ScanReport) -> &'static str {
+    match report.detections.iter().map(|c| c.severity).max() {
+        Some(Severity::Critical) | Some(Severity::High) => "1",
+        Some(Severity::Medium)                          => "2",
+        Some(Severity::Low)                             => "3",
+        None                                            => "4",
+    }
+}
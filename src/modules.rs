@@ -0,0 +1,231 @@
+// Modules (shared objects, DLLs) loaded into a process
+
+use std::io;
+
+// Executable file mappings of the process `pid`, the main executable included
+#[cfg(unix)]
+pub fn loaded(pid: u32) -> io::Result<Vec<String>> {
+    let maps = ::std::fs::read_to_string(format!("/proc/{}/maps", pid))?;
+
+    let mut modules: Vec<String> = Vec::new();
+    for line in maps.lines() {
+        // address perms offset dev inode pathname
+        let fields: Vec<&str> = line.splitn(6, ' ').collect();
+        if fields.len() < 6 || !fields[1].contains('x') {
+            continue;
+        }
+        let path = fields[5].trim_start();
+        if path.starts_with('/') && !modules.iter().any(|module| module == path) {
+            modules.push(path.to_string());
+        }
+    }
+
+    Ok(modules)
+}
+
+// Whether the file /proc/<pid>/exe points to is mapped executable in the process, as the kernel maps
+// the executable it starts. An image loaded or swapped in from userland leaves the link behind.
+#[cfg(target_os = "linux")]
+pub fn maps_executable(pid: u32, exe_path: &str) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let exe  = ::std::fs::metadata(format!("/proc/{}/exe", pid))?;
+    let maps = ::std::fs::read_to_string(format!("/proc/{}/maps", pid))?;
+    // Device numbers as glibc encodes them
+    let (major, minor) = ((exe.dev() >> 8) & 0xfff | (exe.dev() >> 32) & !0xfff,
+                          exe.dev() & 0xff | (exe.dev() >> 12) & !0xff);
+
+    Ok(maps.lines().any(|line| {
+        // address perms offset dev inode pathname
+        let fields: Vec<&str> = line.splitn(6, ' ').collect();
+        if fields.len() < 6 || !fields[1].contains('x') {
+            return false;
+        }
+        // Overlay filesystems show the inode of the underlying file, the path still matches
+        let path  = fields[5].trim_start().trim_end_matches(::DELETED_SUFFIX);
+        let inode = fields[4].parse::<u64>().ok() == Some(exe.ino());
+        let dev   = match fields[3].find(':') {
+            Some(pos) => u64::from_str_radix(&fields[3][.. pos], 16).ok() == Some(major) &&
+                         u64::from_str_radix(&fields[3][pos + 1 ..], 16).ok() == Some(minor),
+            None      => false,
+        };
+
+        (inode && dev) || path == exe_path
+    }))
+}
+
+#[cfg(windows)]
+pub fn loaded(pid: u32) -> io::Result<Vec<String>> {
+    use std::mem::size_of;
+    use std::ptr;
+
+    use winapi::shared::minwindef::{FALSE, HMODULE};
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::psapi::{K32EnumProcessModulesEx, K32GetModuleFileNameExW, LIST_MODULES_ALL};
+    use winapi::um::winnt::{PROCESS_QUERY_INFORMATION, PROCESS_VM_READ};
+
+    unsafe {
+        let h_process = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, FALSE, pid);
+        if h_process.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Modules may be loaded between two calls, retry with the size needed
+        let mut h_mods: Vec<HMODULE> = Vec::new();
+        let mut cb_needed            = 0;
+        loop {
+            if K32EnumProcessModulesEx(h_process, h_mods.as_mut_ptr(), (h_mods.len() * size_of::<HMODULE>()) as u32,
+                                       &mut cb_needed, LIST_MODULES_ALL) == 0 {
+                let why = io::Error::last_os_error();
+                CloseHandle(h_process);
+                return Err(why);
+            }
+            let count = cb_needed as usize / size_of::<HMODULE>();
+            if count <= h_mods.len() {
+                h_mods.truncate(count);
+                break;
+            }
+            h_mods = vec![ptr::null_mut(); count];
+        }
+
+        let mut modules = Vec::new();
+        for h_mod in h_mods {
+            // Long paths may exceed MAX_PATH, truncated ones fill the buffer
+            let module = ::read_wide(|sz_module_path| {
+                match K32GetModuleFileNameExW(h_process, h_mod, sz_module_path.as_mut_ptr(),
+                                              sz_module_path.len() as u32) {
+                    0   => None,
+                    len => Some(len as usize),
+                }
+            });
+            if let Some(module) = module {
+                modules.push(module);
+            }
+        }
+        CloseHandle(h_process);
+
+        Ok(modules)
+    }
+}
+
+// Whether a module is the main executable, already checked by name
+#[cfg(unix)]
+pub fn is_executable(module: &str, exe_path: &str) -> bool {
+    module.trim_end_matches(::DELETED_SUFFIX) == exe_path
+}
+
+#[cfg(windows)]
+pub fn is_executable(module: &str, exe_path: &str) -> bool {
+    module.to_lowercase() == exe_path.to_lowercase()
+}
+
+// Temporary and user writable locations system processes never load code from
+#[cfg(unix)]
+pub fn nonstandard_path(path: &str) -> bool {
+    const PREFIXES: &'static [&'static str] = &["/tmp/", "/var/tmp/", "/dev/shm/", "/run/user/", "/home/", "/root/"];
+
+    PREFIXES.iter().any(|prefix| path.starts_with(prefix)) || path.ends_with(::DELETED_SUFFIX)
+}
+
+#[cfg(windows)]
+pub fn nonstandard_path(path: &str) -> bool {
+    const FRAGMENTS: &'static [&'static str] = &["\\users\\", "\\windows\\temp\\", "\\$recycle.bin\\"];
+
+    let path = path.to_lowercase();
+    FRAGMENTS.iter().any(|fragment| path.contains(fragment)) ||
+        ["TEMP", "TMP"].iter()
+            .filter_map(|var| ::std::env::var(var).ok())
+            .any(|dir| !dir.is_empty() && path.starts_with(&dir.to_lowercase()))
+}
+
+// Variables making the loader map libraries into a process before its own
+#[cfg(unix)]
+pub const PRELOAD_VARIABLES: &'static [&'static str] = &["LD_PRELOAD", "LD_AUDIT", "DYLD_INSERT_LIBRARIES"];
+
+// Where the libraries loaded into every process are configured
+#[cfg(unix)]
+pub const PRELOAD_SOURCE: &'static str = "/etc/ld.so.preload";
+#[cfg(windows)]
+pub const PRELOAD_SOURCE: &'static str = "AppInit_DLLs";
+
+// Libraries of a preload list, separated by colons or whitespace
+#[cfg(unix)]
+pub fn library_list(list: &str) -> Vec<String> {
+    list.split(|c: char| c == ':' || c.is_whitespace())
+        .filter(|library| !library.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+// Libraries the loader maps into every dynamically linked process
+#[cfg(unix)]
+pub fn preloaded() -> Vec<String> {
+    match ::std::fs::read_to_string(PRELOAD_SOURCE) {
+        Ok(text) => text.lines()
+            .map(|line| line.split('#').next().unwrap_or(""))
+            .flat_map(|line| library_list(line))
+            .collect(),
+        Err(_)   => Vec::new(),
+    }
+}
+
+// DLLs user32.dll loads into every process using it, when LoadAppInit_DLLs enables them
+#[cfg(windows)]
+pub fn preloaded() -> Vec<String> {
+    use std::mem::size_of;
+    use std::ptr;
+
+    use winapi::shared::minwindef::{DWORD, LPVOID};
+    use winapi::shared::winerror::ERROR_SUCCESS;
+    use winapi::um::winreg::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_DWORD, RRF_RT_REG_SZ};
+
+    let wide = |text: &str| -> Vec<u16> { text.encode_utf16().chain(Some(0)).collect() };
+    let key  = wide("SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion\\Windows");
+
+    unsafe {
+        let mut enabled: DWORD = 0;
+        let mut size           = size_of::<DWORD>() as DWORD;
+        if RegGetValueW(HKEY_LOCAL_MACHINE, key.as_ptr(), wide("LoadAppInit_DLLs").as_ptr(), RRF_RT_REG_DWORD,
+                        ptr::null_mut(), &mut enabled as *mut _ as LPVOID, &mut size) != ERROR_SUCCESS as i32 ||
+           enabled == 0 {
+            return Vec::new();
+        }
+
+        let value = wide("AppInit_DLLs");
+        let mut size: DWORD = 0;
+        if RegGetValueW(HKEY_LOCAL_MACHINE, key.as_ptr(), value.as_ptr(), RRF_RT_REG_SZ, ptr::null_mut(),
+                        ptr::null_mut(), &mut size) != ERROR_SUCCESS as i32 {
+            return Vec::new();
+        }
+        let mut buf = vec![0u16; size as usize / 2 + 1];
+        if RegGetValueW(HKEY_LOCAL_MACHINE, key.as_ptr(), value.as_ptr(), RRF_RT_REG_SZ, ptr::null_mut(),
+                        buf.as_mut_ptr() as LPVOID, &mut size) != ERROR_SUCCESS as i32 {
+            return Vec::new();
+        }
+        let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+
+        // Separated by commas or spaces
+        String::from_utf16_lossy(&buf[.. len]).split(|c: char| c == ',' || c == ' ')
+            .filter(|dll| !dll.is_empty())
+            .map(String::from)
+            .collect()
+    }
+}
+
+// Whether a loaded module is the library of a preload list, given by path or by file name
+#[cfg(unix)]
+pub fn is_library(module: &str, library: &str) -> bool {
+    use std::path::Path;
+
+    let module = module.trim_end_matches(::DELETED_SUFFIX);
+    module == library || (!library.contains('/') && Path::new(module).file_name().map_or(false, |name| name == library))
+}
+
+#[cfg(windows)]
+pub fn is_library(module: &str, library: &str) -> bool {
+    let (module, library) = (module.to_lowercase(), library.to_lowercase());
+    let name = |path: &str| path.rsplit('\\').next().unwrap_or("").to_string();
+
+    module == library || name(&module) == name(&library)
+}
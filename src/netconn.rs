@@ -0,0 +1,239 @@
+// Network connections opened by a process
+
+use std::net::SocketAddr;
+
+use types::Connection;
+
+// Sockets of the process `pid`, empty when they can't be read
+#[cfg(unix)]
+pub fn connections(pid: u32) -> Vec<Connection> {
+    use std::collections::HashSet;
+    use std::fs;
+
+    // Open descriptors link to "socket:[INODE]"
+    let inodes: HashSet<u64> = match fs::read_dir(format!("/proc/{}/fd", pid)) {
+        Ok(fds) => fds.filter_map(|fd| fd.ok())
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .filter_map(|link| {
+                let link = link.to_string_lossy().into_owned();
+                if link.starts_with("socket:[") && link.ends_with(']') {
+                    link[8 .. link.len() - 1].parse::<u64>().ok()
+                } else {
+                    None
+                }
+            })
+            .collect(),
+        Err(_)  => return Vec::new(),
+    };
+    if inodes.is_empty() {
+        return Vec::new();
+    }
+
+    // Read the tables of the process' own network namespace
+    let mut connections = Vec::new();
+    for proto in &["tcp", "tcp6", "udp", "udp6"] {
+        let table = match fs::read_to_string(format!("/proc/{}/net/{}", pid, proto)) {
+            Ok(table) => table,
+            Err(_)    => continue,
+        };
+        connections.extend(table.lines().skip(1)
+            .filter_map(|line| parse_proc_net(proto, line))
+            .filter(|&(inode, _)| inodes.contains(&inode))
+            .map(|(_, connection)| connection));
+    }
+
+    connections
+}
+
+// "sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode ..."
+#[cfg(unix)]
+fn parse_proc_net(proto: &'static str, line: &str) -> Option<(u64, Connection)> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() < 10 {
+        return None;
+    }
+
+    let state = u8::from_str_radix(fields[3], 16).ok()?;
+    let state = if proto.starts_with("udp") {
+        match state {
+            0x01 => "ESTABLISHED",
+            _    => "UNCONN",
+        }
+    } else {
+        match state {
+            0x01 => "ESTABLISHED",
+            0x02 => "SYN_SENT",
+            0x03 => "SYN_RECV",
+            0x04 => "FIN_WAIT1",
+            0x05 => "FIN_WAIT2",
+            0x06 => "TIME_WAIT",
+            0x07 => "CLOSE",
+            0x08 => "CLOSE_WAIT",
+            0x09 => "LAST_ACK",
+            0x0A => "LISTEN",
+            0x0B => "CLOSING",
+            _    => "UNKNOWN",
+        }
+    };
+
+    Some((fields[9].parse().ok()?, Connection {
+        proto:  proto,
+        local:  parse_proc_net_addr(fields[1])?,
+        remote: parse_proc_net_addr(fields[2])?,
+        state:  state,
+    }))
+}
+
+// "0100007F:0035", the address is printed as 32 bit words in host byte order
+#[cfg(unix)]
+fn parse_proc_net_addr(field: &str) -> Option<SocketAddr> {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    let mut parts = field.splitn(2, ':');
+    let addr      = parts.next()?;
+    let port      = u16::from_str_radix(parts.next()?, 16).ok()?;
+
+    let mut bytes = Vec::with_capacity(16);
+    for i in 0 .. addr.len() / 8 {
+        let word = u32::from_str_radix(addr.get(i * 8 .. i * 8 + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+
+    let ip = match bytes.len() {
+        4  => IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&bytes);
+            IpAddr::V6(Ipv6Addr::from(octets))
+        },
+        _  => return None,
+    };
+
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(windows)]
+mod ffi {
+    use winapi::shared::minwindef::{BOOL, DWORD, ULONG};
+
+    pub const AF_INET: ULONG                   = 2;
+    pub const AF_INET6: ULONG                  = 23;
+    pub const TCP_TABLE_OWNER_PID_ALL: DWORD   = 5;
+    pub const NO_ERROR: DWORD                  = 0;
+    pub const ERROR_INSUFFICIENT_BUFFER: DWORD = 122;
+
+    #[repr(C)]
+    pub struct MibTcpRowOwnerPid {
+        pub state:       DWORD,
+        pub local_addr:  DWORD,
+        pub local_port:  DWORD,
+        pub remote_addr: DWORD,
+        pub remote_port: DWORD,
+        pub owning_pid:  DWORD,
+    }
+
+    #[repr(C)]
+    pub struct MibTcp6RowOwnerPid {
+        pub local_addr:      [u8; 16],
+        pub local_scope_id:  DWORD,
+        pub local_port:      DWORD,
+        pub remote_addr:     [u8; 16],
+        pub remote_scope_id: DWORD,
+        pub remote_port:     DWORD,
+        pub state:           DWORD,
+        pub owning_pid:      DWORD,
+    }
+
+    #[link(name = "iphlpapi")]
+    extern "system" {
+        pub fn GetExtendedTcpTable(pTcpTable: *mut ::std::os::raw::c_void, pdwSize: *mut DWORD, bOrder: BOOL,
+                                   ulAf: ULONG, TableClass: DWORD, Reserved: ULONG) -> DWORD;
+    }
+}
+
+// TCP connections of the process `pid`, empty when the tables can't be read
+#[cfg(windows)]
+pub fn connections(pid: u32) -> Vec<Connection> {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    let mut connections = Vec::new();
+
+    if let Some(table) = tcp_table(ffi::AF_INET) {
+        let rows = unsafe { rows::<ffi::MibTcpRowOwnerPid>(&table) };
+        for row in rows.iter().filter(|row| row.owning_pid == pid) {
+            connections.push(Connection {
+                proto:  "tcp",
+                local:  SocketAddr::new(IpAddr::V4(Ipv4Addr::from(u32::from_be(row.local_addr))), port(row.local_port)),
+                remote: SocketAddr::new(IpAddr::V4(Ipv4Addr::from(u32::from_be(row.remote_addr))), port(row.remote_port)),
+                state:  tcp_state(row.state),
+            });
+        }
+    }
+    if let Some(table) = tcp_table(ffi::AF_INET6) {
+        let rows = unsafe { rows::<ffi::MibTcp6RowOwnerPid>(&table) };
+        for row in rows.iter().filter(|row| row.owning_pid == pid) {
+            connections.push(Connection {
+                proto:  "tcp6",
+                local:  SocketAddr::new(IpAddr::V6(Ipv6Addr::from(row.local_addr)), port(row.local_port)),
+                remote: SocketAddr::new(IpAddr::V6(Ipv6Addr::from(row.remote_addr)), port(row.remote_port)),
+                state:  tcp_state(row.state),
+            });
+        }
+    }
+
+    connections
+}
+
+// Owner PID table of the address family, the buffer grows until it fits
+#[cfg(windows)]
+fn tcp_table(family: u32) -> Option<Vec<u32>> {
+    let mut size = 0;
+    unsafe { ffi::GetExtendedTcpTable(::std::ptr::null_mut(), &mut size, 0, family, ffi::TCP_TABLE_OWNER_PID_ALL, 0) };
+
+    // Connections may be opened between two calls
+    for _ in 0 .. 4 {
+        let mut table = vec![0u32; size as usize / 4 + 1];
+        match unsafe { ffi::GetExtendedTcpTable(table.as_mut_ptr() as *mut _, &mut size, 0, family,
+                                                ffi::TCP_TABLE_OWNER_PID_ALL, 0) } {
+            ffi::NO_ERROR                  => return Some(table),
+            ffi::ERROR_INSUFFICIENT_BUFFER => continue,
+            _                              => return None,
+        }
+    }
+
+    None
+}
+
+// The table is a DWORD count followed by the rows
+#[cfg(windows)]
+unsafe fn rows<T>(table: &[u32]) -> &[T] {
+    let count = table[0] as usize;
+    let max   = (table.len() - 1) * 4 / ::std::mem::size_of::<T>();
+
+    ::std::slice::from_raw_parts(table[1 ..].as_ptr() as *const T, ::std::cmp::min(count, max))
+}
+
+// Ports are stored in network byte order in the low 16 bits
+#[cfg(windows)]
+fn port(raw: u32) -> u16 {
+    u16::from_be(raw as u16)
+}
+
+#[cfg(windows)]
+fn tcp_state(state: u32) -> &'static str {
+    match state {
+        1  => "CLOSED",
+        2  => "LISTEN",
+        3  => "SYN_SENT",
+        4  => "SYN_RECV",
+        5  => "ESTABLISHED",
+        6  => "FIN_WAIT1",
+        7  => "FIN_WAIT2",
+        8  => "CLOSE_WAIT",
+        9  => "CLOSING",
+        10 => "LAST_ACK",
+        11 => "TIME_WAIT",
+        12 => "DELETE_TCB",
+        _  => "UNKNOWN",
+    }
+}
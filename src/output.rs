@@ -0,0 +1,72 @@
+// Human-facing terminal output. Colors are cosmetic: they are only used on an
+// interactive terminal, and failing to set them never aborts anything.
+
+use std::env;
+
+use term;
+
+pub struct Output {
+    // None when colors are disabled
+    terminal: Option<Box<term::StdoutTerminal>>,
+}
+
+impl Output {
+    // Colors are used when asked for, NO_COLOR isn't set and stdout is a terminal
+    pub fn new(colors: bool) -> Output {
+        let colors = colors && env::var_os("NO_COLOR").map_or(true, |v| v.is_empty()) && stdout_is_tty();
+
+        Output {
+            terminal: if colors { term::stdout() } else { None },
+        }
+    }
+
+    pub fn fg(&mut self, color: term::color::Color) {
+        if let Some(ref mut terminal) = self.terminal {
+            let _ = terminal.fg(color);
+        }
+    }
+
+    pub fn bold(&mut self) {
+        if let Some(ref mut terminal) = self.terminal {
+            if terminal.supports_attr(term::Attr::Bold) {
+                let _ = terminal.attr(term::Attr::Bold);
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if let Some(ref mut terminal) = self.terminal {
+            let _ = terminal.reset();
+        }
+    }
+}
+
+#[cfg(unix)]
+fn stdout_is_tty() -> bool {
+    unsafe { ::libc::isatty(::libc::STDOUT_FILENO) == 1 }
+}
+
+#[cfg(windows)]
+fn stdout_is_tty() -> bool {
+    use winapi::um::consoleapi::GetConsoleMode;
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_OUTPUT_HANDLE;
+
+    let mut mode = 0;
+    unsafe { GetConsoleMode(GetStdHandle(STD_OUTPUT_HANDLE), &mut mode) != 0 }
+}
+
+#[cfg(unix)]
+pub fn stderr_is_tty() -> bool {
+    unsafe { ::libc::isatty(::libc::STDERR_FILENO) == 1 }
+}
+
+#[cfg(windows)]
+pub fn stderr_is_tty() -> bool {
+    use winapi::um::consoleapi::GetConsoleMode;
+    use winapi::um::processenv::GetStdHandle;
+    use winapi::um::winbase::STD_ERROR_HANDLE;
+
+    let mut mode = 0;
+    unsafe { GetConsoleMode(GetStdHandle(STD_ERROR_HANDLE), &mut mode) != 0 }
+}
@@ -0,0 +1,141 @@
+// Files installed by the system package manager, trusted when they still match the
+// digest recorded in the package database
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use hash;
+
+const DPKG_INFO: &str = "/var/lib/dpkg/info";
+
+// Recorded digest of an installed file, MD5 for dpkg, usually SHA-256 for rpm
+enum Digest {
+    Md5(String),
+    Sha256(String),
+}
+
+pub struct Database {
+    // Package manager the files were read from
+    pub manager: &'static str,
+    files      : HashMap<String, (String, Digest)>,
+}
+
+impl Database {
+    // Read the dpkg database, or the rpm one when dpkg isn't in use
+    pub fn load() -> io::Result<Database> {
+        if Path::new(DPKG_INFO).is_dir() {
+            return load_dpkg();
+        }
+
+        load_rpm()
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    // Package owning `path` when its content matches the package database
+    pub fn verify(&self, path: &str) -> Option<&str> {
+        let (file, &(ref package, ref digest)) = match self.lookup(path) {
+            Some(entry) => entry,
+            None        => return None,
+        };
+        let matches = match *digest {
+            Digest::Md5(ref md5)       => hash::md5_file(path).map(|actual| &actual == md5),
+            Digest::Sha256(ref sha256) => hash::sha256_file(path).map(|actual| &actual == sha256),
+        };
+
+        match matches {
+            Ok(true)  => Some(package),
+            Ok(false) => {
+                warn!("{} differs from the file installed by package {}", file, package);
+                None
+            },
+            Err(why)  => {
+                debug!("Couldn't hash {}: {}", path, why);
+                None
+            },
+        }
+    }
+
+    // Packages may install into /bin or /sbin, merged into /usr on most distributions,
+    // while the kernel reports the resolved path
+    fn lookup(&self, path: &str) -> Option<(&str, &(String, Digest))> {
+        if let Some((file, entry)) = self.files.get_key_value(path) {
+            return Some((file.as_str(), entry));
+        }
+        if !path.starts_with("/usr/") {
+            return None;
+        }
+        let unmerged = &path[4..];
+        match (self.files.get_key_value(unmerged), fs::canonicalize(unmerged)) {
+            (Some((file, entry)), Ok(ref resolved)) if resolved == Path::new(path) => Some((file.as_str(), entry)),
+            _                                                                      => None,
+        }
+    }
+}
+
+// `<package>.md5sums` files list `<md5>  <path relative to />`
+fn load_dpkg() -> io::Result<Database> {
+    let mut files = HashMap::new();
+
+    for entry in fs::read_dir(DPKG_INFO)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |ext| ext != "md5sums") {
+            continue;
+        }
+        let package = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        // Multi-arch packages are suffixed with their architecture
+        let package = package.split(':').next().unwrap_or("").to_string();
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(why)    => {
+                debug!("Couldn't read {}: {}", path.display(), why);
+                continue;
+            },
+        };
+
+        for line in content.lines() {
+            let mut fields = line.splitn(2, "  ");
+            if let (Some(md5), Some(file)) = (fields.next(), fields.next()) {
+                // Documentation is never run
+                if !file.starts_with("usr/share/doc/") && !file.starts_with("usr/share/man/") {
+                    files.insert(format!("/{}", file), (package.clone(), Digest::Md5(md5.to_string())));
+                }
+            }
+        }
+    }
+
+    Ok(Database { manager: "dpkg", files: files })
+}
+
+fn load_rpm() -> io::Result<Database> {
+    let output = Command::new("rpm")
+        .args(&["-qa", "--queryformat", "[%{FILENAMES}\t%{FILEDIGESTS}\t%{NAME}\n]"])
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::new(io::ErrorKind::Other,
+                                  format!("rpm exited with {}: {}", output.status,
+                                          String::from_utf8_lossy(&output.stderr).trim())));
+    }
+
+    let mut files = HashMap::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 3 {
+            continue;
+        }
+        // Directories and symbolic links have no digest, older packages use MD5
+        let digest = match fields[1].len() {
+            32 => Digest::Md5(fields[1].to_string()),
+            64 => Digest::Sha256(fields[1].to_string()),
+            _  => continue,
+        };
+        files.insert(fields[0].to_string(), (fields[2].to_string(), digest));
+    }
+
+    Ok(Database { manager: "rpm", files: files })
+}
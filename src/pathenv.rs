@@ -0,0 +1,61 @@
+// Directories searched for commands, where a planted binary is run in place of the real one
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Entries of the PATH environment variable, in search order
+pub fn dirs() -> Vec<PathBuf> {
+    env::var_os("PATH").map(|path| env::split_paths(&path).collect()).unwrap_or_default()
+}
+
+// Files of `dir` that can be run as commands
+pub fn executables(dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(why)    => {
+            debug!("Couldn't list {}: {}", dir.display(), why);
+            return Vec::new();
+        },
+    };
+
+    entries.filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_executable(path))
+        .collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path).map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+// Extensions listed in PATHEXT
+#[cfg(windows)]
+fn is_executable(path: &Path) -> bool {
+    let pathext   = env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"));
+    let extension = match path.extension() {
+        Some(extension) => format!(".{}", extension.to_string_lossy()).to_lowercase(),
+        None            => return false,
+    };
+
+    path.is_file() && pathext.split(';').any(|ext| ext.to_lowercase() == extension)
+}
+
+// Whether users other than root may drop files into `dir`
+#[cfg(unix)]
+pub fn writable_by_users(dir: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match fs::metadata(dir) {
+        Ok(meta) => meta.uid() != 0 || meta.mode() & 0o002 != 0 || (meta.gid() != 0 && meta.mode() & 0o020 != 0),
+        Err(_)   => false,
+    }
+}
+
+#[cfg(windows)]
+pub fn writable_by_users(dir: &Path) -> bool {
+    ::modules::nonstandard_path(&format!("{}\\", dir.to_string_lossy().trim_end_matches('\\')))
+}
@@ -0,0 +1,476 @@
+// Programs started at boot or logon: the command of each autostart entry with `--persistence` (systemd units,
+// cron tables, rc.local and XDG autostart on Unix, Run keys, scheduled tasks and Startup folders on Windows), and
+// the installed services with `--services`, so that one named like a critical process is caught before it runs.
+
+#[cfg(unix)]
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// An autostart entry
+pub struct Entry {
+    // Where the entry is configured: file, or registry key and value
+    pub location: String,
+    // Program it starts, as written there
+    pub target  : String,
+}
+
+// An installed service: systemd unit or Windows service
+pub struct Service {
+    // Unit name without its suffix, or service key name
+    pub name    : String,
+    // Name shown to users, the description of units
+    pub display : String,
+    // Unit file or registry key defining it
+    pub location: String,
+    // Program it runs, empty when none is given
+    pub binary  : String,
+}
+
+// Files of `dir`, along with those of its `.d` drop-in directories, sorted
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+        Err(why)    => {
+            debug!("Couldn't list {}: {}", dir.display(), why);
+            return Vec::new();
+        },
+    };
+    let dropins: Vec<PathBuf> = files.iter()
+        .filter(|path| path.is_dir() && path.extension().map_or(false, |ext| ext == "d"))
+        .flat_map(|path| fs::read_dir(path).into_iter().flatten())
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    files.extend(dropins);
+    files.retain(|path| path.is_file());
+    files.sort();
+
+    files
+}
+
+// Program of a command line, quoted or up to the first blank
+fn program(command: &str) -> Option<String> {
+    let command = command.trim();
+    let program = if let Some(quoted) = command.strip_prefix('"') {
+        quoted.split('"').next()
+    } else if let (true, Some(end)) = (cfg!(windows), command.to_ascii_lowercase().find(".exe ")) {
+        // Unquoted paths with blanks, `C:\Program Files\...` is still run
+        Some(&command[.. end + ".exe".len()])
+    } else {
+        command.split_whitespace().next()
+    };
+
+    program.filter(|program| !program.is_empty()).map(String::from)
+}
+
+#[cfg(unix)]
+const UNIT_DIRS: &'static [&'static str] = &["/etc/systemd/system", "/run/systemd/system", "/lib/systemd/system",
+                                             "/usr/lib/systemd/system", "/etc/systemd/user", "/usr/lib/systemd/user"];
+// Tables with a user field, and those of single users without one
+#[cfg(unix)]
+const SYSTEM_CRONTABS: &'static [&'static str] = &["/etc/crontab", "/etc/cron.d"];
+#[cfg(unix)]
+const USER_CRONTABS: &'static [&'static str]   = &["/var/spool/cron/crontabs", "/var/spool/cron"];
+// Scripts run as they are
+#[cfg(unix)]
+const CRON_SCRIPTS: &'static [&'static str]    = &["/etc/cron.hourly", "/etc/cron.daily", "/etc/cron.weekly",
+                                                   "/etc/cron.monthly"];
+#[cfg(unix)]
+const RC_LOCAL: &'static str                   = "/etc/rc.local";
+#[cfg(unix)]
+const XDG_AUTOSTART: &'static str              = "/etc/xdg/autostart";
+
+// Every autostart entry of the system
+#[cfg(unix)]
+pub fn entries() -> Vec<Entry> {
+    let mut entries = Vec::new();
+
+    for dir in UNIT_DIRS {
+        for file in files(Path::new(dir)) {
+            // Commands may carry prefixes changing how they run, `-` ignores their failure
+            entries.extend(lines(&file, |line| {
+                let (key, value) = split_assignment(line)?;
+                if key.starts_with("Exec") {
+                    program(value.trim_start_matches(|c| "-@:+!".contains(c)))
+                } else {
+                    None
+                }
+            }));
+        }
+    }
+    for crontab in SYSTEM_CRONTABS {
+        let path = Path::new(crontab);
+        let tables = if path.is_dir() { files(path) } else { vec![path.to_path_buf()] };
+        for table in tables {
+            entries.extend(lines(&table, |line| cron_command(line, true)));
+        }
+    }
+    for dir in USER_CRONTABS {
+        for table in files(Path::new(dir)) {
+            entries.extend(lines(&table, |line| cron_command(line, false)));
+        }
+    }
+    for dir in CRON_SCRIPTS {
+        entries.extend(files(Path::new(dir)).into_iter().map(|script| Entry {
+            location: dir.to_string(),
+            target:   script.to_string_lossy().into_owned(),
+        }));
+    }
+    entries.extend(lines(Path::new(RC_LOCAL), |line| {
+        if line.starts_with("exit") { None } else { program(line) }
+    }));
+    for file in files(Path::new(XDG_AUTOSTART)) {
+        entries.extend(lines(&file, |line| {
+            match split_assignment(line)? {
+                ("Exec", value) => program(value),
+                _               => None,
+            }
+        }));
+    }
+
+    entries
+}
+
+// Service units, those of the first directory of `UNIT_DIRS` overriding the others
+#[cfg(unix)]
+pub fn services() -> Vec<Service> {
+    let mut services = Vec::new();
+    let mut seen     = HashSet::new();
+
+    for dir in UNIT_DIRS {
+        for file in files(Path::new(dir)) {
+            let unit = file.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            // Instances of templates are named `name@instance.service`
+            let name = match unit.strip_suffix(".service") {
+                Some(name) => name.trim_end_matches('@').to_string(),
+                None       => continue,
+            };
+            if !seen.insert(name.clone()) {
+                continue;
+            }
+            let value = |wanted: &str| lines(&file, |line| {
+                match split_assignment(line)? {
+                    (key, value) if key == wanted => Some(value.trim().to_string()),
+                    _                             => None,
+                }
+            }).into_iter().next().map(|entry| entry.target);
+            // Masked units link to /dev/null, and hide the units of the same name
+            let binary = match value("ExecStart") {
+                Some(command) => program(command.trim_start_matches(|c| "-@:+!".contains(c))).unwrap_or_default(),
+                None          => continue,
+            };
+
+            services.push(Service {
+                display:  value("Description").unwrap_or_else(|| name.clone()),
+                name:     name,
+                location: file.to_string_lossy().into_owned(),
+                binary:   binary,
+            });
+        }
+    }
+
+    services
+}
+
+// `Key=value` lines of unit files, desktop entries and cron tables
+#[cfg(unix)]
+fn split_assignment(line: &str) -> Option<(&str, &str)> {
+    let split = line.find('=')?;
+    let key   = line[.. split].trim();
+
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        None
+    } else {
+        Some((key, &line[split + 1 ..]))
+    }
+}
+
+// Entries for the programs found on the lines of `path`, comments left out
+#[cfg(unix)]
+fn lines<F: Fn(&str) -> Option<String>>(path: &Path, target: F) -> Vec<Entry> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(why) => {
+            if path.exists() {
+                debug!("Couldn't read {}: {}", path.display(), why);
+            }
+            return Vec::new();
+        },
+    };
+
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#') && !line.starts_with(';'))
+        .filter_map(&target)
+        .map(|target| Entry {
+            location: path.to_string_lossy().into_owned(),
+            target:   target,
+        })
+        .collect()
+}
+
+// Command of a cron table line: five time fields or an `@` nickname, then the user in system tables
+#[cfg(unix)]
+fn cron_command(line: &str, system: bool) -> Option<String> {
+    // Variables set for the commands
+    if split_assignment(line).is_some() {
+        return None;
+    }
+    let schedule = if line.starts_with('@') { 1 } else { 5 };
+    let skipped  = schedule + if system { 1 } else { 0 };
+
+    program(&line.split_whitespace().skip(skipped).collect::<Vec<_>>().join(" "))
+}
+
+#[cfg(windows)]
+const RUN_KEYS: &'static [&'static str] = &["Software\\Microsoft\\Windows\\CurrentVersion\\Run",
+                                            "Software\\Microsoft\\Windows\\CurrentVersion\\RunOnce",
+                                            "Software\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\Run",
+                                            "Software\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\RunOnce"];
+#[cfg(windows)]
+const STARTUP: &'static str = "Microsoft\\Windows\\Start Menu\\Programs\\Startup";
+
+#[cfg(windows)]
+pub fn entries() -> Vec<Entry> {
+    use std::env;
+
+    use winapi::um::winreg::{HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE};
+
+    let mut entries = Vec::new();
+
+    for &(hive, root) in &[(HKEY_LOCAL_MACHINE, "HKLM"), (HKEY_CURRENT_USER, "HKCU")] {
+        for key in RUN_KEYS {
+            for (name, command) in registry_values(hive, key) {
+                if let Some(target) = program(&expand(&command)) {
+                    entries.push(Entry {
+                        location: format!("{}\\{}\\{}", root, key, name),
+                        target:   target,
+                    });
+                }
+            }
+        }
+    }
+
+    let tasks = env::var("SystemRoot").unwrap_or_else(|_| String::from("C:\\Windows"));
+    let mut dirs = vec![Path::new(&tasks).join("System32").join("Tasks")];
+    while let Some(dir) = dirs.pop() {
+        for path in fs::read_dir(&dir).into_iter().flatten().filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.is_dir() {
+                dirs.push(path);
+            } else if let Some(command) = task_command(&path) {
+                entries.push(Entry {
+                    location: path.to_string_lossy().into_owned(),
+                    target:   expand(&command),
+                });
+            }
+        }
+    }
+
+    for base in ["ProgramData", "APPDATA"].iter().filter_map(|name| env::var_os(name)) {
+        let dir = Path::new(&base).join(STARTUP);
+        for path in files(&dir) {
+            let target = if path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("lnk")) {
+                shortcut_target(&path).unwrap_or_else(|| path.to_string_lossy().into_owned())
+            } else {
+                path.to_string_lossy().into_owned()
+            };
+            entries.push(Entry {
+                location: dir.to_string_lossy().into_owned(),
+                target:   target,
+            });
+        }
+    }
+
+    entries
+}
+
+#[cfg(windows)]
+const SERVICES_KEY: &'static str = "SYSTEM\\CurrentControlSet\\Services";
+
+// Services of the service control manager, drivers left out
+#[cfg(windows)]
+pub fn services() -> Vec<Service> {
+    use std::env;
+
+    use winapi::um::winreg::HKEY_LOCAL_MACHINE;
+
+    let root = env::var("SystemRoot").unwrap_or_else(|_| String::from("C:\\Windows"));
+
+    registry_subkeys(HKEY_LOCAL_MACHINE, SERVICES_KEY).into_iter()
+        .filter_map(|name| {
+            let key    = format!("{}\\{}", SERVICES_KEY, name);
+            let values = registry_values(HKEY_LOCAL_MACHINE, &key);
+            let value  = |wanted: &str| values.iter()
+                .find(|&&(ref name, _)| name.eq_ignore_ascii_case(wanted))
+                .map(|&(_, ref value)| value.clone());
+
+            // Relative to the system root, or written the way the kernel sees it
+            let image  = value("ImagePath")?;
+            let image  = if image.starts_with("\\SystemRoot\\") {
+                format!("{}{}", root, &image["\\SystemRoot".len() ..])
+            } else if image.to_ascii_lowercase().starts_with("system32\\") {
+                format!("{}\\{}", root, image)
+            } else {
+                expand(image.trim_start_matches("\\??\\"))
+            };
+            let binary = program(&image).unwrap_or_default();
+            if binary.to_ascii_lowercase().ends_with(".sys") {
+                return None;
+            }
+
+            Some(Service {
+                display:  value("DisplayName").unwrap_or_else(|| name.clone()),
+                name:     name,
+                location: format!("HKLM\\{}", key),
+                binary:   binary,
+            })
+        })
+        .collect()
+}
+
+// Names of the subkeys of a registry key
+#[cfg(windows)]
+fn registry_subkeys(hive: ::winapi::shared::minwindef::HKEY, key: &str) -> Vec<String> {
+    use std::ptr;
+
+    use winapi::shared::minwindef::{DWORD, HKEY};
+    use winapi::shared::winerror::ERROR_SUCCESS;
+    use winapi::um::winnt::KEY_READ;
+    use winapi::um::winreg::{RegCloseKey, RegEnumKeyExW, RegOpenKeyExW};
+
+    let key: Vec<u16> = key.encode_utf16().chain(Some(0)).collect();
+    let mut subkeys   = Vec::new();
+
+    unsafe {
+        let mut handle: HKEY = ptr::null_mut();
+        if RegOpenKeyExW(hive, key.as_ptr(), 0, KEY_READ, &mut handle) != ERROR_SUCCESS as i32 {
+            return subkeys;
+        }
+        for index in 0 .. {
+            let mut name     = [0u16; 256];
+            let mut name_len = name.len() as DWORD;
+            if RegEnumKeyExW(handle, index, name.as_mut_ptr(), &mut name_len, ptr::null_mut(), ptr::null_mut(),
+                             ptr::null_mut(), ptr::null_mut()) != ERROR_SUCCESS as i32 {
+                break;
+            }
+            subkeys.push(String::from_utf16_lossy(&name[.. name_len as usize]));
+        }
+        RegCloseKey(handle);
+    }
+
+    subkeys
+}
+
+// `%NAME%` references to environment variables replaced by their values, unknown ones left as they are
+#[cfg(windows)]
+fn expand(command: &str) -> String {
+    let mut expanded = String::new();
+    let mut rest     = command;
+
+    while let Some(start) = rest.find('%') {
+        let end = match rest[start + 1 ..].find('%') {
+            Some(end) => start + 1 + end,
+            None      => break,
+        };
+        expanded.push_str(&rest[.. start]);
+        match ::std::env::var(&rest[start + 1 .. end]) {
+            Ok(value) => expanded.push_str(&value),
+            Err(_)    => expanded.push_str(&rest[start .. end + 1]),
+        }
+        rest = &rest[end + 1 ..];
+    }
+    expanded.push_str(rest);
+
+    expanded
+}
+
+// Names and string data of the values of a registry key
+#[cfg(windows)]
+fn registry_values(hive: ::winapi::shared::minwindef::HKEY, key: &str) -> Vec<(String, String)> {
+    use std::ptr;
+
+    use winapi::shared::minwindef::{DWORD, HKEY};
+    use winapi::shared::winerror::ERROR_SUCCESS;
+    use winapi::um::winnt::{KEY_READ, REG_EXPAND_SZ, REG_SZ};
+    use winapi::um::winreg::{RegCloseKey, RegEnumValueW, RegOpenKeyExW};
+
+    let key: Vec<u16> = key.encode_utf16().chain(Some(0)).collect();
+    let mut values    = Vec::new();
+
+    unsafe {
+        let mut handle: HKEY = ptr::null_mut();
+        if RegOpenKeyExW(hive, key.as_ptr(), 0, KEY_READ, &mut handle) != ERROR_SUCCESS as i32 {
+            return values;
+        }
+        for index in 0 .. {
+            let mut name        = [0u16; 16_384];
+            let mut name_len    = name.len() as DWORD;
+            let mut data        = vec![0u16; 16_384];
+            let mut data_len    = (data.len() * 2) as DWORD;
+            let mut kind: DWORD = 0;
+            if RegEnumValueW(handle, index, name.as_mut_ptr(), &mut name_len, ptr::null_mut(), &mut kind,
+                             data.as_mut_ptr() as *mut u8, &mut data_len) != ERROR_SUCCESS as i32 {
+                break;
+            }
+            if kind != REG_SZ && kind != REG_EXPAND_SZ {
+                continue;
+            }
+            let data = &data[.. data_len as usize / 2];
+            let len  = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+            values.push((String::from_utf16_lossy(&name[.. name_len as usize]),
+                         String::from_utf16_lossy(&data[.. len])));
+        }
+        RegCloseKey(handle);
+    }
+
+    values
+}
+
+// Program of the first `Exec` action of a scheduled task, whose XML is saved as UTF-16 with a BOM
+#[cfg(windows)]
+fn task_command(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let xml = if bytes.starts_with(&[0xff, 0xfe]) {
+        let wide: Vec<u16> = bytes[2 ..].chunks(2).filter(|pair| pair.len() == 2)
+            .map(|pair| u16::from(pair[0]) | u16::from(pair[1]) << 8)
+            .collect();
+        String::from_utf16_lossy(&wide)
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+    let start = xml.find("<Command>")? + "<Command>".len();
+    let end   = start + xml[start ..].find("</Command>")?;
+
+    program(&xml[start .. end].replace("&quot;", "\""))
+}
+
+// Local path a shell link points to, from the LinkInfo structure of the .lnk format
+#[cfg(windows)]
+fn shortcut_target(path: &Path) -> Option<String> {
+    const HEADER_SIZE: usize      = 0x4c;
+    const HAS_TARGET_ID_LIST: u32 = 0x1;
+    const HAS_LINK_INFO: u32      = 0x2;
+
+    let bytes = fs::read(path).ok()?;
+    let u16_at = |at: usize| bytes.get(at .. at + 2).map(|b| u16::from(b[0]) | u16::from(b[1]) << 8);
+    let u32_at = |at: usize| bytes.get(at .. at + 4).map(|b| {
+        u32::from(b[0]) | u32::from(b[1]) << 8 | u32::from(b[2]) << 16 | u32::from(b[3]) << 24
+    });
+
+    let flags = u32_at(0x14)?;
+    if flags & HAS_LINK_INFO == 0 {
+        return None;
+    }
+    let mut link_info = HEADER_SIZE;
+    if flags & HAS_TARGET_ID_LIST != 0 {
+        link_info += 2 + usize::from(u16_at(HEADER_SIZE)?);
+    }
+    let base_path = link_info + u32_at(link_info + 0x10)? as usize;
+    let len       = bytes.get(base_path ..)?.iter().position(|&b| b == 0)?;
+
+    if len == 0 {
+        None
+    } else {
+        Some(String::from_utf8_lossy(&bytes[base_path .. base_path + len]).into_owned())
+    }
+}
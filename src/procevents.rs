@@ -0,0 +1,467 @@
+// Process start events delivered by the kernel: the proc connector over netlink on Linux,
+// the Microsoft-Windows-Kernel-Process ETW provider on Windows.
+// Lets the monitor mode check a process the moment it starts instead of at the next scan.
+
+use std::io;
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+#[cfg(target_os = "linux")]
+use std::mem::{size_of, zeroed};
+
+#[cfg(target_os = "linux")]
+use libc;
+
+// linux/netlink.h, linux/connector.h, linux/cn_proc.h
+#[cfg(target_os = "linux")]
+mod ffi {
+    use libc::c_int;
+
+    pub const NETLINK_CONNECTOR: c_int  = 11;
+    pub const NLMSG_DONE: u16           = 3;
+    pub const CN_IDX_PROC: u32          = 1;
+    pub const CN_VAL_PROC: u32          = 1;
+    pub const PROC_CN_MCAST_LISTEN: u32 = 1;
+    pub const PROC_EVENT_EXEC: u32      = 2;
+
+    pub const NLMSG_HDR_LEN: usize = 16;
+    pub const CN_MSG_LEN: usize    = 20;
+}
+
+#[cfg(target_os = "linux")]
+struct Socket(libc::c_int);
+
+#[cfg(target_os = "linux")]
+impl Drop for Socket {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+// Subscribe to process events, needs CAP_NET_ADMIN
+#[cfg(target_os = "linux")]
+fn subscribe() -> io::Result<Socket> {
+    let socket = unsafe { libc::socket(libc::AF_NETLINK, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, ffi::NETLINK_CONNECTOR) };
+    if socket < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = Socket(socket);
+
+    let mut addr: libc::sockaddr_nl = unsafe { zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_pid    = 0;
+    addr.nl_groups = ffi::CN_IDX_PROC;
+    if unsafe { libc::bind(socket.0, &addr as *const _ as *const libc::sockaddr,
+                           size_of::<libc::sockaddr_nl>() as libc::socklen_t) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // nlmsghdr, cn_msg and the PROC_CN_MCAST_LISTEN operation
+    let len = ffi::NLMSG_HDR_LEN + ffi::CN_MSG_LEN + 4;
+    let mut msg = Vec::with_capacity(len);
+    msg.extend_from_slice(&(len as u32).to_ne_bytes());
+    msg.extend_from_slice(&ffi::NLMSG_DONE.to_ne_bytes());
+    msg.extend_from_slice(&0u16.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes());
+    msg.extend_from_slice(&unsafe { libc::getpid() as u32 }.to_ne_bytes());
+    msg.extend_from_slice(&ffi::CN_IDX_PROC.to_ne_bytes());
+    msg.extend_from_slice(&ffi::CN_VAL_PROC.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes());
+    msg.extend_from_slice(&4u16.to_ne_bytes());
+    msg.extend_from_slice(&0u16.to_ne_bytes());
+    msg.extend_from_slice(&ffi::PROC_CN_MCAST_LISTEN.to_ne_bytes());
+
+    if unsafe { libc::send(socket.0, msg.as_ptr() as *const libc::c_void, msg.len(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(socket)
+}
+
+// Whether exec events can be received, for the pre-flight check
+#[cfg(target_os = "linux")]
+pub fn probe() -> io::Result<()> {
+    subscribe().map(|_| ())
+}
+
+// PIDs of the processes executing a new program, as they do.
+// Events are read by a background thread until the receiver is dropped.
+#[cfg(target_os = "linux")]
+pub fn exec_events() -> io::Result<Receiver<u32>> {
+    let socket    = subscribe()?;
+    let (tx, rx)  = channel();
+
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        loop {
+            let len = unsafe { libc::recv(socket.0, buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+            if len < 0 {
+                let why = io::Error::last_os_error();
+                // ENOBUFS: events were dropped under load, the periodic scans catch up
+                if why.raw_os_error() == Some(libc::ENOBUFS) || why.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                error!("couldn't read process events: {}", why);
+                return;
+            }
+
+            if let Some(pid) = exec_pid(&buf[.. len as usize]) {
+                if tx.send(pid).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+// proc_event after the netlink and connector headers: what, cpu, timestamp_ns, then
+// for exec events the thread id and the process id
+#[cfg(target_os = "linux")]
+fn exec_pid(msg: &[u8]) -> Option<u32> {
+    let event = msg.get(ffi::NLMSG_HDR_LEN + ffi::CN_MSG_LEN ..)?;
+    let field = |offset: usize| event.get(offset .. offset + 4).map(|b| u32::from_ne_bytes([b[0], b[1], b[2], b[3]]));
+
+    if field(0)? != ffi::PROC_EVENT_EXEC {
+        return None;
+    }
+    let (pid, tgid) = (field(16)?, field(20)?);
+
+    // Only the main thread, a process shows up once
+    if pid == tgid { Some(tgid) } else { None }
+}
+
+// evntrace.h, evntcons.h
+#[cfg(windows)]
+#[allow(non_snake_case)]
+mod ffi {
+    use winapi::shared::guiddef::GUID;
+    use winapi::shared::minwindef::{UCHAR, ULONG, USHORT};
+    use winapi::um::winnt::{HANDLE, LPCWSTR, LPWSTR, PVOID};
+
+    pub type TRACEHANDLE = u64;
+
+    pub const ERROR_SUCCESS: ULONG                      = 0;
+    pub const ERROR_ALREADY_EXISTS: ULONG               = 183;
+    pub const WNODE_FLAG_TRACED_GUID: ULONG             = 0x0002_0000;
+    pub const EVENT_TRACE_REAL_TIME_MODE: ULONG         = 0x0000_0100;
+    pub const EVENT_TRACE_CONTROL_STOP: ULONG           = 1;
+    pub const EVENT_CONTROL_CODE_ENABLE_PROVIDER: ULONG = 1;
+    pub const TRACE_LEVEL_INFORMATION: UCHAR            = 4;
+    pub const PROCESS_TRACE_MODE_REAL_TIME: ULONG       = 0x0000_0100;
+    pub const PROCESS_TRACE_MODE_EVENT_RECORD: ULONG    = 0x1000_0000;
+
+    #[repr(C)]
+    pub struct WnodeHeader {
+        pub BufferSize:        ULONG,
+        pub ProviderId:        ULONG,
+        pub HistoricalContext: u64,
+        pub TimeStamp:         i64,
+        pub Guid:              GUID,
+        pub ClientContext:     ULONG,
+        pub Flags:             ULONG,
+    }
+
+    #[repr(C)]
+    pub struct EventTraceProperties {
+        pub Wnode:               WnodeHeader,
+        pub BufferSize:          ULONG,
+        pub MinimumBuffers:      ULONG,
+        pub MaximumBuffers:      ULONG,
+        pub MaximumFileSize:     ULONG,
+        pub LogFileMode:         ULONG,
+        pub FlushTimer:          ULONG,
+        pub EnableFlags:         ULONG,
+        pub AgeLimit:            i32,
+        pub NumberOfBuffers:     ULONG,
+        pub FreeBuffers:         ULONG,
+        pub EventsLost:          ULONG,
+        pub BuffersWritten:      ULONG,
+        pub LogBuffersLost:      ULONG,
+        pub RealTimeBuffersLost: ULONG,
+        pub LoggerThreadId:      HANDLE,
+        pub LogFileNameOffset:   ULONG,
+        pub LoggerNameOffset:    ULONG,
+    }
+
+    #[repr(C)]
+    pub struct EventTraceHeader {
+        pub Size:           USHORT,
+        pub FieldTypeFlags: USHORT,
+        pub Version:        ULONG,
+        pub ThreadId:       ULONG,
+        pub ProcessId:      ULONG,
+        pub TimeStamp:      i64,
+        pub Guid:           GUID,
+        pub ProcessorTime:  u64,
+    }
+
+    #[repr(C)]
+    pub struct EventTrace {
+        pub Header:           EventTraceHeader,
+        pub InstanceId:       ULONG,
+        pub ParentInstanceId: ULONG,
+        pub ParentGuid:       GUID,
+        pub MofData:          PVOID,
+        pub MofLength:        ULONG,
+        pub ClientContext:    ULONG,
+    }
+
+    #[repr(C)]
+    pub struct TraceLogfileHeader {
+        pub BufferSize:         ULONG,
+        pub Version:            ULONG,
+        pub ProviderVersion:    ULONG,
+        pub NumberOfProcessors: ULONG,
+        pub EndTime:            i64,
+        pub TimerResolution:    ULONG,
+        pub MaximumFileSize:    ULONG,
+        pub LogFileMode:        ULONG,
+        pub BuffersWritten:     ULONG,
+        pub LogInstanceGuid:    GUID,
+        pub LoggerName:         LPWSTR,
+        pub LogFileName:        LPWSTR,
+        // TIME_ZONE_INFORMATION
+        pub TimeZone:           [u32; 43],
+        pub BootTime:           i64,
+        pub PerfFreq:           i64,
+        pub StartTime:          i64,
+        pub ReservedFlags:      ULONG,
+        pub BuffersLost:        ULONG,
+    }
+
+    #[repr(C)]
+    pub struct EventDescriptor {
+        pub Id:      USHORT,
+        pub Version: UCHAR,
+        pub Channel: UCHAR,
+        pub Level:   UCHAR,
+        pub Opcode:  UCHAR,
+        pub Task:    USHORT,
+        pub Keyword: u64,
+    }
+
+    #[repr(C)]
+    pub struct EventHeader {
+        pub Size:            USHORT,
+        pub HeaderType:      USHORT,
+        pub Flags:           USHORT,
+        pub EventProperty:   USHORT,
+        pub ThreadId:        ULONG,
+        pub ProcessId:       ULONG,
+        pub TimeStamp:       i64,
+        pub ProviderId:      GUID,
+        pub EventDescriptor: EventDescriptor,
+        pub ProcessorTime:   u64,
+        pub ActivityId:      GUID,
+    }
+
+    #[repr(C)]
+    pub struct EventRecord {
+        pub EventHeader:       EventHeader,
+        pub BufferContext:     ULONG,
+        pub ExtendedDataCount: USHORT,
+        pub UserDataLength:    USHORT,
+        pub ExtendedData:      PVOID,
+        pub UserData:          PVOID,
+        pub UserContext:       PVOID,
+    }
+
+    #[repr(C)]
+    pub struct EventTraceLogfile {
+        pub LogFileName:         LPWSTR,
+        pub LoggerName:          LPWSTR,
+        pub CurrentTime:         i64,
+        pub BuffersRead:         ULONG,
+        pub ProcessTraceMode:    ULONG,
+        pub CurrentEvent:        EventTrace,
+        pub LogfileHeader:       TraceLogfileHeader,
+        pub BufferCallback:      PVOID,
+        pub BufferSize:          ULONG,
+        pub Filled:              ULONG,
+        pub EventsLost:          ULONG,
+        pub EventRecordCallback: Option<unsafe extern "system" fn(*mut EventRecord)>,
+        pub IsKernelTrace:       ULONG,
+        pub Context:             PVOID,
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        pub fn StartTraceW(TraceHandle: *mut TRACEHANDLE, InstanceName: LPCWSTR,
+                           Properties: *mut EventTraceProperties) -> ULONG;
+        pub fn ControlTraceW(TraceHandle: TRACEHANDLE, InstanceName: LPCWSTR,
+                             Properties: *mut EventTraceProperties, ControlCode: ULONG) -> ULONG;
+        pub fn EnableTraceEx2(TraceHandle: TRACEHANDLE, ProviderId: *const GUID, ControlCode: ULONG, Level: UCHAR,
+                              MatchAnyKeyword: u64, MatchAllKeyword: u64, Timeout: ULONG, EnableParameters: PVOID) -> ULONG;
+        pub fn OpenTraceW(Logfile: *mut EventTraceLogfile) -> TRACEHANDLE;
+        pub fn ProcessTrace(HandleArray: *mut TRACEHANDLE, HandleCount: ULONG, StartTime: PVOID, EndTime: PVOID) -> ULONG;
+        pub fn CloseTrace(TraceHandle: TRACEHANDLE) -> ULONG;
+    }
+}
+
+// The session outlives BONOMEN when it's killed, the next run takes it over
+#[cfg(windows)]
+const SESSION_NAME: &'static str = "BONOMEN process events";
+
+// Microsoft-Windows-Kernel-Process {22FB2CD6-0E7B-422B-A0C7-2FAD1FD0E716}
+#[cfg(windows)]
+const KERNEL_PROCESS_PROVIDER: ::winapi::shared::guiddef::GUID = ::winapi::shared::guiddef::GUID {
+    Data1: 0x22FB_2CD6,
+    Data2: 0x0E7B,
+    Data3: 0x422B,
+    Data4: [0xA0, 0xC7, 0x2F, 0xAD, 0x1F, 0xD0, 0xE7, 0x16],
+};
+#[cfg(windows)]
+const WINEVENT_KEYWORD_PROCESS: u64 = 0x10;
+#[cfg(windows)]
+const PROCESS_START_EVENT: u16      = 1;
+
+// Properties followed by the session name, as StartTrace expects them
+#[cfg(windows)]
+#[repr(C)]
+struct SessionProperties {
+    properties: ffi::EventTraceProperties,
+    name:       [u16; 64],
+}
+
+#[cfg(windows)]
+fn session_properties() -> SessionProperties {
+    let mut session: SessionProperties = unsafe { ::std::mem::zeroed() };
+    session.properties.Wnode.BufferSize    = ::std::mem::size_of::<SessionProperties>() as u32;
+    session.properties.Wnode.Flags         = ffi::WNODE_FLAG_TRACED_GUID;
+    // Query performance counter timestamps
+    session.properties.Wnode.ClientContext = 1;
+    session.properties.LogFileMode         = ffi::EVENT_TRACE_REAL_TIME_MODE;
+    session.properties.LoggerNameOffset    = ::std::mem::size_of::<ffi::EventTraceProperties>() as u32;
+
+    session
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    use std::os::windows::ffi::OsStrExt;
+
+    ::std::ffi::OsStr::new(s).encode_wide().chain(::std::iter::once(0)).collect()
+}
+
+#[cfg(windows)]
+fn win_error(status: u32) -> io::Error {
+    io::Error::from_raw_os_error(status as i32)
+}
+
+// Start the real-time session with process start events enabled, needs administrator
+// or Performance Log Users rights
+#[cfg(windows)]
+fn start_session() -> io::Result<ffi::TRACEHANDLE> {
+    let name        = wide(SESSION_NAME);
+    let mut session = session_properties();
+    let mut handle  = 0;
+
+    let mut status = unsafe { ffi::StartTraceW(&mut handle, name.as_ptr(), &mut session.properties) };
+    if status == ffi::ERROR_ALREADY_EXISTS {
+        // Left behind by a previous run
+        stop_session();
+        session = session_properties();
+        status  = unsafe { ffi::StartTraceW(&mut handle, name.as_ptr(), &mut session.properties) };
+    }
+    if status != ffi::ERROR_SUCCESS {
+        return Err(win_error(status));
+    }
+
+    let status = unsafe {
+        ffi::EnableTraceEx2(handle, &KERNEL_PROCESS_PROVIDER, ffi::EVENT_CONTROL_CODE_ENABLE_PROVIDER,
+                            ffi::TRACE_LEVEL_INFORMATION, WINEVENT_KEYWORD_PROCESS, 0, 0, ::std::ptr::null_mut())
+    };
+    if status != ffi::ERROR_SUCCESS {
+        stop_session();
+        return Err(win_error(status));
+    }
+
+    Ok(handle)
+}
+
+#[cfg(windows)]
+fn stop_session() {
+    let name        = wide(SESSION_NAME);
+    let mut session = session_properties();
+
+    unsafe { ffi::ControlTraceW(0, name.as_ptr(), &mut session.properties, ffi::EVENT_TRACE_CONTROL_STOP) };
+}
+
+// Whether process start events can be received, for the pre-flight check
+#[cfg(windows)]
+pub fn probe() -> io::Result<()> {
+    start_session()?;
+    stop_session();
+
+    Ok(())
+}
+
+// What the consumer thread needs to keep alive while processing the session
+#[cfg(windows)]
+struct Consumer {
+    handle:  ffi::TRACEHANDLE,
+    _name:   Vec<u16>,
+    logfile: Box<ffi::EventTraceLogfile>,
+}
+
+#[cfg(windows)]
+unsafe impl Send for Consumer {}
+
+// PIDs of the processes being started, as they are.
+// Events are processed by a background thread until the session is stopped.
+#[cfg(windows)]
+pub fn exec_events() -> io::Result<Receiver<u32>> {
+    use std::sync::mpsc::Sender;
+
+    start_session()?;
+
+    let (tx, rx)    = channel();
+    let mut name    = wide(SESSION_NAME);
+    let mut logfile = Box::new(unsafe { ::std::mem::zeroed::<ffi::EventTraceLogfile>() });
+    logfile.LoggerName          = name.as_mut_ptr();
+    logfile.ProcessTraceMode    = ffi::PROCESS_TRACE_MODE_REAL_TIME | ffi::PROCESS_TRACE_MODE_EVENT_RECORD;
+    logfile.EventRecordCallback = Some(on_event);
+    logfile.Context             = Box::into_raw(Box::new(tx)) as *mut _;
+
+    let handle = unsafe { ffi::OpenTraceW(&mut *logfile) };
+    // INVALID_PROCESSTRACE_HANDLE, as returned to 64 and 32 bit processes
+    if handle == !0 || handle == 0xFFFF_FFFF {
+        let why = io::Error::last_os_error();
+        unsafe { drop(Box::from_raw(logfile.Context as *mut Sender<u32>)) };
+        stop_session();
+        return Err(why);
+    }
+
+    let mut consumer = Consumer { handle: handle, _name: name, logfile: logfile };
+    thread::spawn(move || {
+        let status = unsafe { ffi::ProcessTrace(&mut consumer.handle, 1, ::std::ptr::null_mut(), ::std::ptr::null_mut()) };
+        if status != ffi::ERROR_SUCCESS {
+            error!("couldn't process the process events: {}", win_error(status));
+        }
+        unsafe {
+            ffi::CloseTrace(consumer.handle);
+            // Disconnects the receiver
+            drop(Box::from_raw(consumer.logfile.Context as *mut Sender<u32>));
+        }
+    });
+
+    Ok(rx)
+}
+
+// ProcessStart events carry the new process' ID first, the header's is the creator's
+#[cfg(windows)]
+unsafe extern "system" fn on_event(record: *mut ffi::EventRecord) {
+    use std::sync::mpsc::Sender;
+
+    let record = &*record;
+    if record.EventHeader.EventDescriptor.Id != PROCESS_START_EVENT || record.UserDataLength < 4 ||
+       record.UserContext.is_null() {
+        return;
+    }
+
+    let pid = ::std::ptr::read_unaligned(record.UserData as *const u32);
+    let _   = (*(record.UserContext as *const Sender<u32>)).send(pid);
+}
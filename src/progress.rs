@@ -0,0 +1,80 @@
+// Progress of the processes checked, drawn on stderr while a scan runs long. Thousands of processes
+// checked against digests and signatures take a while, a silent terminal looks like a hang.
+
+use std::io::{stderr, Write};
+use std::time::{Duration, Instant};
+
+use output;
+
+// Scans done sooner never show the bar
+const DELAY_MS: u64    = 1000;
+// Between two redraws
+const REDRAW_MS: u64   = 100;
+const BAR_WIDTH: usize = 30;
+
+pub struct Progress {
+    total  : usize,
+    done   : usize,
+    started: Instant,
+    // When the bar was drawn last, None while it isn't shown
+    drawn  : Option<Instant>,
+    // Length of the line drawn, blanked when the bar is cleared
+    width  : usize,
+    enabled: bool,
+}
+
+impl Progress {
+    // Only drawn on an interactive stderr, `enabled` is false for quiet and machine output.
+    // Log lines would scroll it away, verbose scans go without.
+    pub fn new(total: usize, enabled: bool) -> Progress {
+        Progress {
+            total:   total,
+            done:    0,
+            started: Instant::now(),
+            drawn:   None,
+            width:   0,
+            enabled: enabled && output::stderr_is_tty() && !log_enabled!(::log::LogLevel::Info),
+        }
+    }
+
+    // One more process checked
+    pub fn tick(&mut self) {
+        self.done += 1;
+        if !self.enabled || self.started.elapsed() < Duration::from_millis(DELAY_MS) {
+            return;
+        }
+        let recent = self.drawn.map_or(false, |drawn| drawn.elapsed() < Duration::from_millis(REDRAW_MS));
+        if !recent || self.done == self.total {
+            self.draw();
+        }
+    }
+
+    // Erase the bar before printing, the next tick draws it again
+    pub fn clear(&mut self) {
+        if self.drawn.take().is_some() {
+            eprint!("\r{}\r", " ".repeat(self.width));
+            let _ = stderr().flush();
+        }
+    }
+
+    fn draw(&mut self) {
+        let total  = self.total.max(1);
+        let filled = BAR_WIDTH * self.done.min(total) / total;
+        // Remaining processes at the pace so far
+        let elapsed = self.started.elapsed().as_secs();
+        let eta     = elapsed * (total - self.done.min(total)) as u64 / self.done.max(1) as u64;
+        let line    = format!("[{}{}] {}/{} {}% ETA {}:{:02}", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled),
+                              self.done, self.total, 100 * self.done.min(total) / total, eta / 60, eta % 60);
+
+        eprint!("\r{:width$}", line, width = self.width);
+        let _ = stderr().flush();
+        self.width = line.len();
+        self.drawn = Some(Instant::now());
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
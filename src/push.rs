@@ -0,0 +1,178 @@
+// Agent side of `bonomen server`: with `--push`, the JSON report of each scan is sent to the server.
+// Reports the server can't be reached for are spooled to disk and sent, oldest first, before the next one.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Local;
+use serde_json::Value;
+use ureq::{self, Agent};
+use ureq::tls::{Certificate, ClientCert, PrivateKey, RootCerts, TlsConfig};
+
+const TIMEOUT_SECS: u64 = 30;
+// Attempts at sending a report before spooling it, waiting twice as long after each failure
+const ATTEMPTS: u32 = 3;
+const FIRST_WAIT_SECS: u64 = 2;
+// Reports kept in the spool, the oldest are dropped beyond
+const MAX_SPOOLED: usize = 1000;
+
+pub struct Settings {
+    // Base URL of the server, e.g. https://bonomen-server:8443
+    pub url     : String,
+    // Sent as a bearer token when the server requires one
+    pub token   : Option<String>,
+    // Host name the server files the reports under, this host's name by default
+    pub agent_id: Option<String>,
+    // PEM certificates of the CA the server certificate is issued by, Mozilla's roots by default
+    pub ca      : Option<String>,
+    // PEM client certificate and private key, for servers started with --client-ca
+    pub cert    : Option<String>,
+    pub key     : Option<String>,
+    pub spool   : Option<PathBuf>,
+}
+
+enum Failure {
+    // The server rejected the report, sending it again won't help
+    Rejected(String),
+    Unreachable(String),
+}
+
+// Send `report`, and the spooled ones before it. Reports that couldn't be delivered are spooled when a spool
+// directory is set, otherwise lost.
+pub fn send(settings: &Settings, mut report: Value) -> Result<(), String> {
+    if let Some(ref agent_id) = settings.agent_id {
+        report["host"] = json!(agent_id);
+    }
+    let agent = agent(settings)?;
+    let url   = format!("{}/api/v1/reports", settings.url.trim_end_matches('/'));
+
+    if let Some(ref spool) = settings.spool {
+        for path in spooled(spool).map_err(|why| format!("couldn't read the spool {}: {}", spool.display(), why))? {
+            let body = match fs::read_to_string(&path) {
+                Ok(body) => body,
+                Err(why) => {
+                    warn!("couldn't read the spooled report {}: {}", path.display(), why);
+                    continue;
+                },
+            };
+            match post(&agent, &url, settings, &body) {
+                Ok(())                         => info!("Sent the spooled report {}", path.display()),
+                Err(Failure::Rejected(why))    => {
+                    warn!("{} rejected the spooled report {}: {}", url, path.display(), why)
+                },
+                Err(Failure::Unreachable(why)) => return spool_report(spool, &report, &why),
+            }
+            if let Err(why) = fs::remove_file(&path) {
+                warn!("couldn't remove the spooled report {}: {}", path.display(), why);
+            }
+        }
+    }
+
+    match post(&agent, &url, settings, &report.to_string()) {
+        Ok(())                         => Ok(()),
+        Err(Failure::Rejected(why))    => Err(format!("{} rejected the report: {}", url, why)),
+        Err(Failure::Unreachable(why)) => match settings.spool {
+            Some(ref spool) => spool_report(spool, &report, &why),
+            None            => Err(format!("couldn't reach {}: {}", url, why)),
+        },
+    }
+}
+
+fn agent(settings: &Settings) -> Result<Agent, String> {
+    let mut tls = TlsConfig::builder();
+    if let Some(ref ca) = settings.ca {
+        tls = tls.root_certs(RootCerts::new_with_certs(&read_certs(ca)?));
+    }
+    if let (Some(cert), Some(key)) = (settings.cert.as_ref(), settings.key.as_ref()) {
+        let pem = fs::read(key).map_err(|why| format!("couldn't read {}: {}", key, why))?;
+        let key = PrivateKey::from_pem(&pem).map_err(|why| format!("no private key in {}: {}", key, why))?;
+        tls = tls.client_cert(Some(ClientCert::new_with_certs(&read_certs(cert)?, key)));
+    }
+
+    Ok(Agent::config_builder()
+        .tls_config(tls.build())
+        .timeout_global(Some(Duration::from_secs(TIMEOUT_SECS)))
+        .build()
+        .into())
+}
+
+fn read_certs(path: &str) -> Result<Vec<Certificate<'static>>, String> {
+    let pem = fs::read(path).map_err(|why| format!("couldn't read {}: {}", path, why))?;
+    let certs: Vec<Certificate<'static>> = ureq::tls::parse_pem(&pem)
+        .filter_map(|item| match item {
+            Ok(ureq::tls::PemItem::Certificate(cert)) => Some(cert),
+            _                                         => None,
+        })
+        .collect();
+    if certs.is_empty() {
+        return Err(format!("no certificate in {}", path));
+    }
+
+    Ok(certs)
+}
+
+// POST `body`, trying again while the server can't be reached or is unavailable
+fn post(agent: &Agent, url: &str, settings: &Settings, body: &str) -> Result<(), Failure> {
+    let mut wait = Duration::from_secs(FIRST_WAIT_SECS);
+    let mut attempt = 1;
+    loop {
+        let mut request = agent.post(url).header("Content-Type", "application/json");
+        if let Some(ref token) = settings.token {
+            request = request.header("Authorization", &format!("Bearer {}", token));
+        }
+        let failure = match request.send(body) {
+            Ok(_)                                 => return Ok(()),
+            // Timeouts and overloaded or restarting servers are worth another try
+            Err(ureq::Error::StatusCode(status)) if status < 500 && status != 408 && status != 429 => {
+                Failure::Rejected(format!("HTTP status {}", status))
+            },
+            Err(why)                              => Failure::Unreachable(why.to_string()),
+        };
+        if let Failure::Unreachable(ref why) = failure {
+            if attempt < ATTEMPTS {
+                debug!("attempt {} at reaching {} failed: {}", attempt, url, why);
+                thread::sleep(wait);
+                wait *= 2;
+                attempt += 1;
+                continue;
+            }
+        }
+
+        return Err(failure);
+    }
+}
+
+// Reports of the spool, oldest first
+fn spooled(spool: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(spool) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .collect(),
+        Err(ref why) if why.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(why)    => return Err(why),
+    };
+    // Named after the time they were spooled
+    paths.sort();
+
+    Ok(paths)
+}
+
+fn spool_report(spool: &Path, report: &Value, why: &str) -> Result<(), String> {
+    let spool_err = |err: io::Error| format!("couldn't spool the report to {}: {}", spool.display(), err);
+    fs::create_dir_all(spool).map_err(spool_err)?;
+
+    let mut paths = spooled(spool).map_err(spool_err)?;
+    while paths.len() >= MAX_SPOOLED {
+        let oldest = paths.remove(0);
+        warn!("Spool {} full, dropping the report {}", spool.display(), oldest.display());
+        let _ = fs::remove_file(&oldest);
+    }
+    let path = spool.join(format!("{}.json", Local::now().format("%Y%m%dT%H%M%S%.6f")));
+    fs::write(&path, report.to_string()).map_err(spool_err)?;
+    warn!("Server unreachable ({}), report spooled to {}", why, path.display());
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+// Critical processes configuration of the monitor mode. It's kept from one scan to the next and
+// read again on SIGHUP or once one of its files changed, an invalid edit leaves the running one in place.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use error::ScanError;
+use types::Config;
+
+// How often the files are checked for changes while waiting for the next scan
+pub const POLL: Duration = Duration::from_secs(1);
+
+#[cfg(unix)]
+static HANGUP: AtomicBool = AtomicBool::new(false);
+
+pub struct Reloader {
+    // `-f` values
+    files  : Vec<String>,
+    // `--profile`
+    profile: Option<String>,
+    // Files they resolved to, the installed file may show up later
+    paths  : Vec<String>,
+    // Modification times of every file read
+    stamps : Vec<(PathBuf, Option<SystemTime>)>,
+    source : String,
+    config : Config,
+}
+
+impl Reloader {
+    pub fn load(files: &[&str], profile: Option<&str>) -> Result<Reloader, ScanError> {
+        let (source, config) = ::load_config(files, profile)?;
+        info!("{} critical processes loaded from {}", config.procs.len(), source);
+
+        Ok(Reloader {
+            files:   files.iter().map(|file| file.to_string()).collect(),
+            profile: profile.map(String::from),
+            paths:   ::config_paths(files),
+            stamps:  stamps(&config.files),
+            source:  source,
+            config:  config,
+        })
+    }
+
+    // Name of the files and their contents, for the next scan
+    pub fn current(&self) -> (String, Config) {
+        (self.source.clone(), self.config.clone())
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // Read the configuration again when asked to or once it changed, true when it was replaced
+    pub fn reload_if_changed(&mut self) -> bool {
+        let files: Vec<&str> = self.files.iter().map(|file| file.as_str()).collect();
+        let paths   = ::config_paths(&files);
+        let edited  = self.stamps.iter().any(|&(ref path, stamp)| modified(path) != stamp);
+        let changed = paths != self.paths || edited;
+        if !hangup() && !changed {
+            return false;
+        }
+        self.paths = paths;
+
+        match ::load_config(&files, self.profile.as_ref().map(|profile| profile.as_str())) {
+            Ok((source, config)) => {
+                info!("{} critical processes reloaded from {}", config.procs.len(), source);
+                self.stamps = stamps(&config.files);
+                self.source = source;
+                self.config = config;
+                true
+            },
+            Err(why)             => {
+                // Retried on the next change, e.g. once an editor finished writing
+                self.stamps = self.stamps.iter().map(|&(ref path, _)| (path.clone(), modified(path))).collect();
+                error!("Keeping the previous configuration, {}", why);
+                false
+            },
+        }
+    }
+}
+
+fn stamps(files: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    files.iter().map(|path| (path.clone(), modified(path))).collect()
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+// Reload on SIGHUP from now on, as `systemctl reload` sends
+#[cfg(unix)]
+pub fn reload_on_hangup() {
+    extern "C" fn on_hangup(_signal: ::libc::c_int) {
+        HANGUP.store(true, Ordering::SeqCst);
+    }
+
+    unsafe {
+        ::libc::signal(::libc::SIGHUP, on_hangup as extern "C" fn(::libc::c_int) as ::libc::sighandler_t);
+    }
+}
+
+#[cfg(unix)]
+fn hangup() -> bool {
+    HANGUP.swap(false, Ordering::SeqCst)
+}
+
+#[cfg(windows)]
+fn hangup() -> bool {
+    false
+}
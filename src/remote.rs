@@ -0,0 +1,33 @@
+// Collect the process list of another machine over SSH, detection runs locally
+
+use std::process::Command;
+
+use error::ScanError;
+use input;
+use types::ProcessInfo;
+
+// Command run on the remote host, its output is parsed like a captured `ps` listing
+const REMOTE_PS: &'static str = "ps -eo comm,pid,args";
+
+// Run `ps` on `host` through the local ssh client. Authentication, ports and jump
+// hosts come from the usual ssh configuration, `ssh_opts` are passed as `-o` options.
+pub fn collect(host: &str, ssh_opts: &[&str]) -> Result<Vec<ProcessInfo>, ScanError> {
+    let mut ssh = Command::new("ssh");
+    // Never stop to ask for a password, fail instead
+    ssh.arg("-o").arg("BatchMode=yes");
+    for opt in ssh_opts {
+        ssh.arg("-o").arg(opt);
+    }
+
+    info!("Collecting the process list of {}", host);
+    let output = match ssh.arg(host).arg(REMOTE_PS).output() {
+        Ok(output) => output,
+        Err(why)   => return Err(ScanError::Enum(format!("couldn't run ssh: {}", why.to_string()))),
+    };
+    if !output.status.success() {
+        return Err(ScanError::Enum(format!("{}: {}", host,
+                                           String::from_utf8_lossy(&output.stderr).trim())));
+    }
+
+    Ok(input::parse_ps(&String::from_utf8_lossy(&output.stdout)))
+}
@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use chrono::Local;
+use serde_json::Value;
+
+use misp;
+use sarif;
+use stix;
+use types::{Comparison, ScanReport, VtVerdict};
+
+// Write a full scan report to `path`.
+// `format` is "json", "csv", "sarif", "stix" or "misp", when absent it is deduced from the file extension.
+pub fn write_report(path: &str, format: Option<&str>, report: &ScanReport) -> io::Result<()> {
+    let lower  = path.to_lowercase();
+    let format = format.unwrap_or(if lower.ends_with(".csv") {
+        "csv"
+    } else if lower.ends_with(".sarif") || lower.ends_with(".sarif.json") {
+        "sarif"
+    } else if lower.ends_with(".stix.json") {
+        "stix"
+    } else if lower.ends_with(".misp.json") {
+        "misp"
+    } else {
+        "json"
+    });
+    let mut file = File::create(path)?;
+
+    match format {
+        "csv"   => write_csv(&mut file, report),
+        "sarif" => sarif::write(&mut file, report),
+        "stix"  => stix::write(&mut file, report),
+        "misp"  => misp::write(&mut file, report),
+        _       => write_json(&mut file, report),
+    }
+}
+
+pub fn comparison_json(c: &Comparison) -> Value {
+    json!({
+        "pid":        c.pid,
+        "name":       c.name,
+        "exe_path":   c.exe_path,
+        "critical":   c.crit_name,
+        "distance":   c.distance,
+        "threshold":  c.threshold,
+        "suspicious": c.suspicious,
+        "reason":     c.reason,
+        "severity":   c.severity.name(),
+        "module":     c.module,
+        "suspended":  c.suspended,
+        "explanation": c.explanation,
+        "score":      c.score,
+        "started":    c.started.map(|started| started.to_rfc3339()),
+        "environment": c.environment.iter().map(|&(ref name, ref value)| (name.clone(), json!(value)))
+            .collect::<::serde_json::Map<String, Value>>(),
+        "connections": c.connections.iter().map(|conn| json!({
+            "protocol": conn.proto,
+            "local":    conn.local.to_string(),
+            "remote":   conn.remote.to_string(),
+            "state":    conn.state,
+        })).collect::<Vec<_>>(),
+        "owner":      c.owner,
+        "container":  c.container.as_ref().map(|container| json!({
+            "id":        container.id,
+            "runtime":   container.runtime,
+            "pod_uid":   container.pod_uid,
+            "pod":       container.pod,
+            "namespace": container.namespace,
+        })),
+        "yara":       c.yara,
+        "virustotal": c.virustotal.as_ref().map(vt_json),
+        "executable": c.exe_info.as_ref().map(|exe_info| json!({
+            "modified": exe_info.modified.map(|modified| modified.to_rfc3339()),
+            "owner":    exe_info.owner,
+            "group":    exe_info.group,
+            "mode":     exe_info.mode.map(|mode| format!("{:04o}", mode & 0o7777)),
+            "version":  exe_info.version,
+            "company":  exe_info.company,
+        })),
+    })
+}
+
+pub fn vt_json(verdict: &VtVerdict) -> Value {
+    json!({
+        "sha256":    verdict.sha256,
+        "malicious": verdict.malicious,
+        "engines":   verdict.engines,
+    })
+}
+
+pub fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1e9
+}
+
+// Totals of a scan, detections are counted by reason
+pub fn summary_json(report: &ScanReport) -> Value {
+    json!({
+        "scanned":         report.scanned,
+        "compared":        report.compared,
+        "skipped":         report.skipped,
+        "name_only":       report.denied.iter()
+            .map(|&(pid, ref name)| json!({ "pid": pid, "name": name }))
+            .collect::<Vec<_>>(),
+        "detections":      report.detections_by_reason(),
+        "repeated":        report.repeated.len(),
+        "elapsed_seconds": seconds(report.elapsed),
+        "interrupted":     report.interrupted,
+    })
+}
+
+fn write_json<W: Write>(out: &mut W, report: &ScanReport) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &document(report))?;
+    writeln!(out)
+}
+
+// The JSON report of a scan, as report files hold it
+pub fn document(report: &ScanReport) -> Value {
+    json!({
+        "timestamp":   Local::now().to_rfc3339(),
+        "host":        report.host.clone().unwrap_or_else(hostname),
+        "scanned":     report.scanned,
+        "summary":     summary_json(report),
+        "comparisons": report.comparisons.iter().map(comparison_json).collect::<Vec<_>>(),
+        "detections":  report.detections.iter().map(comparison_json).collect::<Vec<_>>(),
+    })
+}
+
+// One row per comparison, scan metadata is repeated on each row
+fn write_csv<W: Write>(out: &mut W, report: &ScanReport) -> io::Result<()> {
+    let timestamp = Local::now().to_rfc3339();
+    let host      = report.host.clone().unwrap_or_else(hostname);
+
+    writeln!(out, "timestamp,host,scanned,pid,name,exe_path,critical,distance,threshold,suspicious,reason,severity,module,connections,container,executable,suspended,yara,virustotal,environment")?;
+    for c in report.detections.iter().chain(report.comparisons.iter()) {
+        let connections: Vec<String> = c.connections.iter().map(|conn| conn.to_string()).collect();
+        let container = c.container.as_ref().map(|container| container.to_string()).unwrap_or_default();
+        let exe_info  = c.exe_info.as_ref().map(|exe_info| exe_info.to_string()).unwrap_or_default();
+        let virustotal = c.virustotal.as_ref()
+            .map(|verdict| format!("{}/{}", verdict.malicious, verdict.engines))
+            .unwrap_or_default();
+        let environment: Vec<String> = c.environment.iter().map(|&(ref name, ref value)| format!("{}={}", name, value))
+            .collect();
+        writeln!(out, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                 timestamp, csv_field(&host), report.scanned, c.pid, csv_field(&c.name),
+                 csv_field(&c.exe_path), csv_field(&c.crit_name), c.distance, c.threshold,
+                 c.suspicious, c.reason, c.severity.name(), csv_field(&c.module),
+                 csv_field(&connections.join("; ")), csv_field(&container), csv_field(&exe_info),
+                 c.suspended, csv_field(&c.yara.join("; ")), virustotal, csv_field(&environment.join("; ")))?;
+    }
+
+    Ok(())
+}
+
+// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(unix)]
+pub fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let ret = unsafe { ::libc::gethostname(buf.as_mut_ptr() as *mut ::libc::c_char, buf.len()) };
+    if ret != 0 {
+        return String::from("unknown");
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(windows)]
+pub fn hostname() -> String {
+    ::std::env::var("COMPUTERNAME").unwrap_or_else(|_| String::from("unknown"))
+}
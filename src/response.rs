@@ -0,0 +1,67 @@
+// Response actions on suspicious processes. Suspended processes keep their memory,
+// responders can dump it for forensics and resume or kill them afterwards.
+
+use std::io;
+
+#[cfg(unix)]
+pub fn suspend(pid: u32) -> io::Result<()> {
+    signal(pid, ::libc::SIGSTOP)
+}
+
+#[cfg(unix)]
+pub fn resume(pid: u32) -> io::Result<()> {
+    signal(pid, ::libc::SIGCONT)
+}
+
+#[cfg(unix)]
+fn signal(pid: u32, signal: ::libc::c_int) -> io::Result<()> {
+    if unsafe { ::libc::kill(pid as ::libc::pid_t, signal) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+mod ffi {
+    use winapi::um::winnt::{HANDLE, LONG};
+
+    #[link(name = "ntdll")]
+    extern "system" {
+        pub fn NtSuspendProcess(process: HANDLE) -> LONG;
+        pub fn NtResumeProcess(process: HANDLE) -> LONG;
+    }
+}
+
+// Every thread of the process is suspended, each suspension needs its own resume
+#[cfg(windows)]
+pub fn suspend(pid: u32) -> io::Result<()> {
+    with_process(pid, |process| unsafe { ffi::NtSuspendProcess(process) })
+}
+
+#[cfg(windows)]
+pub fn resume(pid: u32) -> io::Result<()> {
+    with_process(pid, |process| unsafe { ffi::NtResumeProcess(process) })
+}
+
+#[cfg(windows)]
+fn with_process<F>(pid: u32, action: F) -> io::Result<()>
+    where F: FnOnce(::winapi::um::winnt::HANDLE) -> ::winapi::um::winnt::LONG {
+    use winapi::shared::minwindef::FALSE;
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winnt::PROCESS_SUSPEND_RESUME;
+
+    let process = unsafe { OpenProcess(PROCESS_SUSPEND_RESUME, FALSE, pid) };
+    if process.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let status = action(process);
+    unsafe { CloseHandle(process) };
+
+    if status < 0 {
+        return Err(io::Error::new(io::ErrorKind::Other, format!("NTSTATUS 0x{:08x}", status as u32)));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+// Whitelisting reviewed detections: the executable of each false positive of a report is added to the
+// line of the critical processes file defining its critical process, the rest of the file left untouched.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use whitelist;
+
+// Reasons a whitelisted executable no longer triggers
+const WHITELISTABLE: &'static [&'static str] = &["distance", "homoglyph", "wrong-path", "argv0",
+                                                 "impersonation-at-rest", "persistence", "service", "temp-path",
+                                                 "removable-path"];
+
+// A detection of a report, the executable to whitelist for the critical process
+pub struct Candidate {
+    pub pid      : u64,
+    pub name     : String,
+    pub exe_path : String,
+    pub crit_name: String,
+    pub reason   : String,
+}
+
+pub enum Outcome {
+    // Line number the path was added on
+    Added(PathBuf, usize),
+    Already(PathBuf, usize),
+    // No file defines the critical process, it comes from the embedded defaults
+    Undefined,
+}
+
+// Detections of a JSON report whitelisting would silence, once per critical process and executable,
+// and the number of those it wouldn't
+pub fn candidates(report_path: &str) -> io::Result<(Vec<Candidate>, usize)> {
+    let doc: Value = serde_json::from_reader(BufReader::new(File::open(report_path)?))?;
+    let detections = match doc.get("detections").and_then(|d| d.as_array()) {
+        Some(detections) => detections,
+        None             => return Err(io::Error::new(io::ErrorKind::InvalidData, "no detections in report")),
+    };
+
+    let mut found: Vec<Candidate> = Vec::new();
+    let mut others = 0;
+    for detection in detections {
+        let candidate = Candidate {
+            pid:       detection.get("pid").and_then(|v| v.as_u64()).unwrap_or(0),
+            name:      detection.get("name").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            exe_path:  detection.get("exe_path").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            crit_name: detection.get("critical").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            reason:    detection.get("reason").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+        };
+        // `;` separates the paths of a line
+        if !WHITELISTABLE.contains(&candidate.reason.as_str()) || candidate.exe_path.is_empty() ||
+            candidate.crit_name.is_empty() || candidate.exe_path.contains(';') {
+            others += 1;
+        } else if !found.iter().any(|c| c.crit_name == candidate.crit_name && c.exe_path == candidate.exe_path) {
+            found.push(candidate);
+        }
+    }
+
+    Ok((found, others))
+}
+
+// Add `exe_path` to the definition of `crit_name` in effect: the last one read from `files` and the files
+// they include, the way scans layer them. Lines of other profiles than `profile` don't count.
+pub fn add(files: &[String], profile: Option<&str>, crit_name: &str, exe_path: &str) -> io::Result<Outcome> {
+    let mut found = None;
+    for file in files {
+        if let Some(definition) = definition(Path::new(file), profile, crit_name, 0)? {
+            found = Some(definition);
+        }
+    }
+    let (file, index) = match found {
+        Some(found) => found,
+        None        => return Ok(Outcome::Undefined),
+    };
+
+    let contents = fs::read_to_string(&file)?;
+    let mut lines: Vec<&str> = contents.split_inclusive('\n').collect();
+    let line    = lines[index];
+    let content = line.trim_end_matches(|c| c == '\r' || c == '\n');
+    let already = content.split(';').skip(2)
+        .filter_map(|field| whitelist::Entry::parse(field).ok())
+        .any(|entry| entry.matches(&whitelist::normalize(exe_path)));
+    if already {
+        return Ok(Outcome::Already(file, index + 1));
+    }
+
+    // The line ending is kept
+    let edited = format!("{};{}{}", content, exe_path, &line[content.len() ..]);
+    lines[index] = &edited;
+    fs::write(&file, lines.concat())?;
+
+    Ok(Outcome::Added(file, index + 1))
+}
+
+// File and index of the last line defining `crit_name` that applies with `profile`, includes followed
+fn definition(path     : &Path,
+              profile  : Option<&str>,
+              crit_name: &str,
+              depth    : usize) -> io::Result<Option<(PathBuf, usize)>> {
+    let contents = fs::read_to_string(path)?;
+    let mut active = true;
+    let mut found  = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        if let Some(section) = ::profile_section(line) {
+            active = section.ok() == profile;
+        } else if let Some(included) = ::include_target(line) {
+            // Scans refuse deeper includes
+            if active && depth < ::MAX_INCLUDE_DEPTH {
+                let included = ::included_path(path, included);
+                if let Some(definition) = definition(&included, profile, crit_name, depth + 1)? {
+                    found = Some(definition);
+                }
+            }
+        } else if active && !line.trim_start().starts_with('#') && line.split(';').count() >= 3 &&
+            line.split(';').next().map(str::trim) == Some(crit_name) {
+            found = Some((path.to_path_buf(), index));
+        }
+    }
+
+    Ok(found)
+}
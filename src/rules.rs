@@ -0,0 +1,780 @@
+// Detection rules, each one a heuristic telling impersonating processes apart. Every rule
+// sees the same view of a process on every platform and may be disabled with `rule.NAME = off`.
+
+use std::collections::HashMap;
+#[cfg(windows)]
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local};
+
+#[cfg(windows)]
+use cancel;
+use confusables;
+#[cfg(unix)]
+use environ;
+use modules;
+#[cfg(windows)]
+use hollowing;
+#[cfg(windows)]
+use signature;
+use similarity::Similarity;
+use staging;
+use types::{Comparison, Container, ProcProps, ScanOptions, Severity};
+
+// A running process, as the rules see it
+pub struct Subject<'a> {
+    pub pid        : u32,
+    pub ppid       : Option<u32>,
+    // Name of the parent process, when it was collected too
+    pub parent     : Option<&'a str>,
+    pub started    : Option<DateTime<Local>>,
+    pub name       : &'a str,
+    // Executable path, or why it couldn't be read
+    pub exe_path   : &'a str,
+    pub exe_known  : bool,
+    // Set when the executable is gone, "deleted-exe" or "unresolved-exe"
+    pub exe_problem: Option<&'static str>,
+    // Name in argv[0] when it differs from the process name
+    pub argv0      : Option<&'a str>,
+    // Account the process runs as, when it was collected
+    pub owner      : Option<&'a str>,
+    // Number of threads and of open handles, Windows only
+    pub threads    : Option<u32>,
+    pub handles    : Option<u32>,
+    pub container  : Option<&'a Container>,
+    // Whether the name contains look-alike characters
+    pub homoglyph  : bool,
+    // Distance to every critical process, in order
+    pub distances  : &'a [f64],
+}
+
+impl<'a> Subject<'a> {
+    fn comparison(&self,
+                  crit_name : &str,
+                  distance  : f64,
+                  threshold : f64,
+                  suspicious: bool,
+                  reason    : &'static str,
+                  severity  : Severity) -> Comparison {
+        Comparison {
+            pid:        self.pid,
+            name:       self.name.to_string(),
+            exe_path:   self.exe_path.to_string(),
+            crit_name:  crit_name.to_string(),
+            distance:   distance,
+            threshold:  threshold,
+            suspicious: suspicious,
+            reason:     reason,
+            severity:   severity,
+            module:     String::new(),
+            connections: Vec::new(),
+            environment: Vec::new(),
+            container:  self.container.cloned(),
+            owner:      self.owner.map(String::from),
+            exe_info:   None,
+            yara:       Vec::new(),
+            virustotal: None,
+            suspended:  false,
+            explanation: Vec::new(),
+            score:      None,
+            started:    self.started,
+        }
+    }
+
+    // Critical processes with their distance and threshold
+    fn against<'b>(&'b self, context: &'b Context) -> impl Iterator<Item = (&'b ProcProps, f64, f64)> + 'b {
+        let normalized = context.algorithm.normalized();
+        context.crit_procs.iter().zip(self.distances.iter())
+            .map(move |(crit_proc, &distance)| {
+                (crit_proc, distance, crit_proc.threshold.limit(&crit_proc.name, normalized))
+            })
+    }
+
+    fn whitelisted(&self, crit_proc: &ProcProps, context: &Context) -> bool {
+        ::is_whitelisted_in(self.exe_path, self.container, crit_proc, context.options)
+    }
+}
+
+// What the processes are checked against
+pub struct Context<'a> {
+    pub crit_procs: &'a [ProcProps],
+    pub algorithm : &'a dyn Similarity,
+    pub options   : &'a ScanOptions,
+    // Instances of critical processes running more times than they may, by PID: the critical process
+    // and how many of them run
+    pub surplus   : HashMap<u32, (&'a ProcProps, usize)>,
+}
+
+// Instances beyond the `instances.NAME` count of their critical process among `subjects`. Those running
+// from a whitelisted path, then those of the lowest PIDs, are the expected ones. Containers run their own.
+pub fn surplus<'a>(subjects: &[Subject], context: &Context<'a>) -> HashMap<u32, (&'a ProcProps, usize)> {
+    let names       = context.options.names;
+    let mut surplus = HashMap::new();
+
+    for crit_proc in context.crit_procs.iter() {
+        let expected = match crit_proc.instances {
+            Some(expected) => expected,
+            None           => continue,
+        };
+        let crit_name = names.normalize(&crit_proc.name);
+        let mut running: HashMap<Option<String>, Vec<&Subject>> = HashMap::new();
+        for subject in subjects.iter().filter(|subject| names.normalize(subject.name) == crit_name) {
+            let container = subject.container.map(|container| container.to_string());
+            running.entry(container).or_insert_with(Vec::new).push(subject);
+        }
+
+        for (_, mut instances) in running.into_iter().filter(|&(_, ref instances)| instances.len() > expected) {
+            instances.sort_by_key(|subject| (!subject.whitelisted(crit_proc, context), subject.pid));
+            for subject in &instances[expected ..] {
+                surplus.insert(subject.pid, (crit_proc, instances.len()));
+            }
+        }
+    }
+
+    surplus
+}
+
+// Score a process needs to be alerted on, unless `alert_score` or `--alert-score` says otherwise
+pub const ALERT_SCORE: u32 = 50;
+
+pub trait DetectionRule: Sync {
+    // Name of the `rule.NAME` setting
+    fn name(&self) -> &'static str;
+
+    // Added to the score of the processes the rule finds suspicious, unless `weight.NAME` says otherwise.
+    // Strong signals weigh enough to alert alone, weak ones only together.
+    fn weight(&self) -> u32;
+
+    // Comparisons of the process, suspicious or not, recorded in order
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison>;
+}
+
+// Every rule, in the order their detections are reported
+pub fn registry() -> Vec<Box<dyn DetectionRule>> {
+    vec![
+        Box::new(DeletedExe),
+        Box::new(NameDistance),
+        Box::new(Invisible),
+        Box::new(WrongPath),
+        Box::new(Staging),
+        Box::new(WrongUser),
+        #[cfg(windows)]
+        Box::new(UnsignedBinary { signatures: Mutex::new(HashMap::new()) }),
+        #[cfg(windows)]
+        Box::new(Hollowing),
+        #[cfg(target_os = "linux")]
+        Box::new(ExeMaps),
+        Box::new(Module),
+        Box::new(Injection { preloaded: OnceLock::new() }),
+        Box::new(Argv0),
+        Box::new(OddParent),
+        Box::new(Instances),
+        Box::new(Counts),
+        Box::new(Recent),
+    ]
+}
+
+pub fn exists(name: &str) -> bool {
+    registry().iter().any(|rule| rule.name() == name)
+}
+
+// Weight of each rule, in order, the last `weight.NAME` setting of a rule wins
+pub fn weights(rules: &[Box<dyn DetectionRule>], settings: &[(String, u32)]) -> Vec<u32> {
+    rules.iter()
+        .map(|rule| {
+            settings.iter().rev().find(|&&(ref name, _)| name == rule.name()).map_or(rule.weight(), |&(_, w)| w)
+        })
+        .collect()
+}
+
+// Rules left enabled by the `rule.NAME = on|off` settings
+pub fn enabled(settings: &[(String, bool)]) -> Vec<Box<dyn DetectionRule>> {
+    registry().into_iter()
+        .filter(|rule| settings.iter().rev().find(|&&(ref name, _)| name == rule.name()).map_or(true, |&(_, on)| on))
+        .collect()
+}
+
+// Malware often deletes its binary once started, independent of its name
+struct DeletedExe;
+
+impl DetectionRule for DeletedExe {
+    fn name(&self) -> &'static str {
+        "deleted-exe"
+    }
+
+    fn weight(&self) -> u32 {
+        60
+    }
+
+    fn check(&self, subject: &Subject, _context: &Context) -> Vec<Comparison> {
+        subject.exe_problem.map(|problem| {
+            subject.comparison("", 0.0, 0.0, true, problem,
+                               ::detection_severity(Severity::Medium, problem, 0.0, 0.0, false))
+        }).into_iter().collect()
+    }
+}
+
+// Names close to a critical one, or spelled with look-alike characters
+struct NameDistance;
+
+impl DetectionRule for NameDistance {
+    fn name(&self) -> &'static str {
+        "name-distance"
+    }
+
+    fn weight(&self) -> u32 {
+        50
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let reason = if subject.homoglyph { "homoglyph" } else { "distance" };
+
+        subject.against(context)
+            .filter(|&(_, distance, _)| distance > 0.0 || subject.homoglyph)
+            .map(|(crit_proc, distance, limit)| {
+                let suspicious = distance <= limit && !subject.whitelisted(crit_proc, context);
+                subject.comparison(&crit_proc.name, distance, limit, suspicious, reason,
+                                   ::detection_severity(crit_proc.severity, reason, distance, limit,
+                                                        context.algorithm.normalized()))
+            })
+            .collect()
+    }
+}
+
+// Names padded with zero-width or control characters look like another and defeat both exact matches and edit
+// distances, no process has a reason to carry them. Reported against the closest critical process.
+struct Invisible;
+
+impl DetectionRule for Invisible {
+    fn name(&self) -> &'static str {
+        "invisible"
+    }
+
+    fn weight(&self) -> u32 {
+        100
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        if !confusables::has_invisible(subject.name) {
+            return Vec::new();
+        }
+
+        subject.against(context)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(::std::cmp::Ordering::Equal))
+            .map(|(crit_proc, distance, limit)| {
+                subject.comparison(&crit_proc.name, distance, limit, true, "invisible-chars",
+                                   ::detection_severity(crit_proc.severity, "invisible-chars", distance, limit,
+                                                        context.algorithm.normalized()))
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+// Critical names are only suspicious when running from a path that isn't whitelisted,
+// unknown executables can't be checked this way
+struct WrongPath;
+
+impl DetectionRule for WrongPath {
+    fn name(&self) -> &'static str {
+        "wrong-path"
+    }
+
+    fn weight(&self) -> u32 {
+        100
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        if subject.homoglyph {
+            return Vec::new();
+        }
+
+        subject.against(context)
+            .filter(|&(_, distance, _)| distance == 0.0)
+            .map(|(crit_proc, distance, limit)| {
+                let suspicious = subject.exe_known && !subject.whitelisted(crit_proc, context);
+                subject.comparison(&crit_proc.name, distance, limit, suspicious, "wrong-path",
+                                   ::detection_severity(crit_proc.severity, "wrong-path", distance, limit,
+                                                        context.algorithm.normalized()))
+            })
+            .collect()
+    }
+}
+
+// Critical names, or names close to one, running from a temporary directory, a download directory or removable
+// media. Critical processes are installed, where one runs from says more than how close its name is.
+struct Staging;
+
+impl DetectionRule for Staging {
+    fn name(&self) -> &'static str {
+        "staging"
+    }
+
+    fn weight(&self) -> u32 {
+        100
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let reason = match staging::category(subject.exe_path, context.options.local) {
+            Some(reason) if subject.exe_known => reason,
+            _                                 => return Vec::new(),
+        };
+
+        subject.against(context)
+            .filter(|&(crit_proc, distance, limit)| distance <= limit && !subject.whitelisted(crit_proc, context))
+            .map(|(crit_proc, distance, limit)| {
+                subject.comparison(&crit_proc.name, distance, limit, true, reason,
+                                   ::detection_severity(crit_proc.severity, reason, distance, limit,
+                                                        context.algorithm.normalized()))
+            })
+            .collect()
+    }
+}
+
+// Critical processes run as fixed accounts, `user.NAME = USER` settings: a postgres running as root or an
+// lsass.exe outside SYSTEM was started by someone else, whatever its path
+struct WrongUser;
+
+impl DetectionRule for WrongUser {
+    fn name(&self) -> &'static str {
+        "user"
+    }
+
+    fn weight(&self) -> u32 {
+        60
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let owner = match subject.owner {
+            Some(owner) if !subject.homoglyph => owner,
+            _                                 => return Vec::new(),
+        };
+
+        subject.against(context)
+            .filter(|&(crit_proc, distance, _)| {
+                distance == 0.0 && !crit_proc.users.is_empty() && !allowed_user(owner, &crit_proc.users)
+            })
+            .map(|(crit_proc, distance, limit)| {
+                subject.comparison(&crit_proc.name, distance, limit, true, "wrong-user",
+                                   ::detection_severity(crit_proc.severity, "wrong-user", distance, limit,
+                                                        context.algorithm.normalized()))
+            })
+            .collect()
+    }
+}
+
+// Whether `owner` is one of `users`. Windows accounts may be given without their domain, `SYSTEM` for
+// `NT AUTHORITY\SYSTEM`, and names are case insensitive there.
+#[cfg(unix)]
+fn allowed_user(owner: &str, users: &[String]) -> bool {
+    users.iter().any(|user| user == owner)
+}
+
+#[cfg(windows)]
+fn allowed_user(owner: &str, users: &[String]) -> bool {
+    let account = owner.rsplit('\\').next().unwrap_or(owner);
+    users.iter().any(|user| user.eq_ignore_ascii_case(owner) || user.eq_ignore_ascii_case(account))
+}
+
+// A binary carrying a critical name must be properly signed, even from a whitelisted path
+#[cfg(windows)]
+struct UnsignedBinary {
+    // Executables are shared by many processes, verify each one once
+    signatures: Mutex<HashMap<String, signature::SignatureState>>,
+}
+
+#[cfg(windows)]
+impl DetectionRule for UnsignedBinary {
+    fn name(&self) -> &'static str {
+        "unsigned-binary"
+    }
+
+    fn weight(&self) -> u32 {
+        60
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        if !context.options.verify_sigs || !subject.exe_known {
+            return Vec::new();
+        }
+
+        let mut found = Vec::new();
+        for (crit_proc, distance, limit) in subject.against(context) {
+            if distance > limit || !subject.whitelisted(crit_proc, context) {
+                continue;
+            }
+            let exe_path = subject.exe_path.to_string();
+            let state    = *self.signatures.lock().unwrap().entry(exe_path.clone())
+                .or_insert_with(|| {
+                    // Executables that can't be read in time are left unverified, not found unsigned
+                    cancel::with_timeout(move || signature::verify(&exe_path)).unwrap_or_else(|| {
+                        warn!("Verifying the signature of {} timed out", subject.exe_path);
+                        signature::SignatureState::Valid
+                    })
+                });
+            let reason = match state {
+                signature::SignatureState::Valid      => continue,
+                signature::SignatureState::Unsigned   => "unsigned",
+                signature::SignatureState::Invalid(_) => "bad-signature",
+            };
+            found.push(subject.comparison(&crit_proc.name, distance, limit, true, reason,
+                                          ::detection_severity(crit_proc.severity, reason, distance, limit,
+                                                               context.algorithm.normalized())));
+        }
+
+        found
+    }
+}
+
+// The code of a process replaced in memory once started from a legitimate executable, e.g. a
+// suspended svchost.exe unmapped and rewritten: only the PE headers in memory give it away
+#[cfg(windows)]
+struct Hollowing;
+
+#[cfg(windows)]
+impl DetectionRule for Hollowing {
+    fn name(&self) -> &'static str {
+        "hollowing"
+    }
+
+    fn weight(&self) -> u32 {
+        100
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        if !context.options.hollowing || !context.options.local || !subject.exe_known {
+            return Vec::new();
+        }
+        let crit_proc = match subject.against(context).find(|&(_, distance, _)| distance == 0.0) {
+            Some((crit_proc, _, _)) => crit_proc,
+            None                    => return Vec::new(),
+        };
+        let differences = match hollowing::differences(subject.pid, subject.exe_path) {
+            Ok(differences) => differences,
+            Err(why)        => {
+                debug!("Couldn't compare the image of {} ({}): {}", subject.name, subject.pid, why);
+                return Vec::new();
+            },
+        };
+        if differences.is_empty() {
+            return Vec::new();
+        }
+        for difference in &differences {
+            debug!("{} ({}) image: {}", subject.name, subject.pid, difference);
+        }
+
+        let severity = ::detection_severity(crit_proc.severity, "hollowed", 0.0, 0.0, false);
+        vec![subject.comparison(&crit_proc.name, 0.0, 0.0, true, "hollowed", severity)]
+    }
+}
+
+// /proc/<pid>/exe names the file the kernel started, the image actually running shows in
+// /proc/<pid>/maps: a critical process no longer mapping its executable runs something else
+#[cfg(target_os = "linux")]
+struct ExeMaps;
+
+#[cfg(target_os = "linux")]
+impl DetectionRule for ExeMaps {
+    fn name(&self) -> &'static str {
+        "exe-maps"
+    }
+
+    fn weight(&self) -> u32 {
+        60
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        if !context.options.local || !subject.exe_known || subject.exe_problem.is_some() {
+            return Vec::new();
+        }
+        let crit_proc = match subject.against(context).find(|&(_, distance, _)| distance == 0.0) {
+            Some((crit_proc, _, _)) => crit_proc,
+            None                    => return Vec::new(),
+        };
+
+        match modules::maps_executable(subject.pid, subject.exe_path) {
+            Ok(true)  => Vec::new(),
+            Ok(false) => {
+                let severity = ::detection_severity(crit_proc.severity, "exe-mismatch", 0.0, 0.0, false);
+                vec![subject.comparison(&crit_proc.name, 0.0, 0.0, true, "exe-mismatch", severity)]
+            },
+            Err(why)  => {
+                debug!("Couldn't read the mappings of {} ({}): {}", subject.name, subject.pid, why);
+                Vec::new()
+            },
+        }
+    }
+}
+
+// Libraries loaded from outside the system directories, by processes named as a critical one
+struct Module;
+
+impl DetectionRule for Module {
+    fn name(&self) -> &'static str {
+        "module"
+    }
+
+    fn weight(&self) -> u32 {
+        50
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let crit_proc = match subject.against(context).find(|&(_, distance, _)| distance == 0.0) {
+            Some((crit_proc, _, _)) if context.options.modules && !subject.homoglyph => crit_proc,
+            _                                                                         => return Vec::new(),
+        };
+        let loaded = match modules::loaded(subject.pid) {
+            Ok(loaded) => loaded,
+            Err(why)   => {
+                debug!("Couldn't read the modules of {} ({}): {}", subject.name, subject.pid, why);
+                return Vec::new();
+            },
+        };
+
+        let mut found = Vec::new();
+        for module in loaded {
+            trace!("{} ({}) module: {}", subject.name, subject.pid, module);
+            if !modules::nonstandard_path(&module) || modules::is_executable(&module, subject.exe_path) {
+                continue;
+            }
+
+            let severity   = ::detection_severity(crit_proc.severity, "module", 0.0, 0.0, false);
+            let mut comparison = subject.comparison(&crit_proc.name, 0.0, 0.0, true, "module", severity);
+            comparison.module = module;
+            found.push(comparison);
+        }
+
+        found
+    }
+}
+
+// Libraries forced into processes named as a critical one through the loader, by their environment
+// (LD_PRELOAD, LD_AUDIT) or by system-wide settings (/etc/ld.so.preload, AppInit_DLLs). An injected
+// critical process is suspicious whatever its name distance and path.
+struct Injection {
+    // Libraries the system loads into every process, read once per scan
+    preloaded: OnceLock<Vec<String>>,
+}
+
+impl DetectionRule for Injection {
+    fn name(&self) -> &'static str {
+        "injection"
+    }
+
+    fn weight(&self) -> u32 {
+        60
+    }
+
+    // The module is the injected library, prefixed with how it got in
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let crit_proc = match subject.against(context).find(|&(_, distance, _)| distance == 0.0) {
+            Some((crit_proc, _, _)) if context.options.local && !subject.homoglyph => crit_proc,
+            _                                                                       => return Vec::new(),
+        };
+
+        let mut injected = Vec::new();
+        #[cfg(unix)]
+        match environ::read(subject.pid) {
+            Ok(variables) => {
+                for (name, value) in variables.into_iter().filter(|&(ref name, _)| {
+                    modules::PRELOAD_VARIABLES.contains(&name.as_str())
+                }) {
+                    injected.extend(modules::library_list(&value).into_iter()
+                        .map(|library| format!("{}={}", name, library)));
+                }
+            },
+            Err(why)      => debug!("Couldn't read the environment of {} ({}): {}", subject.name, subject.pid, why),
+        }
+
+        // Statically linked processes don't load them, the process must map the library
+        let preloaded = self.preloaded.get_or_init(modules::preloaded);
+        if !preloaded.is_empty() {
+            let loaded = modules::loaded(subject.pid).map_err(|why| {
+                debug!("Couldn't read the modules of {} ({}): {}", subject.name, subject.pid, why);
+            });
+            // Unreadable modules leave the library loaded as far as we know
+            let maps = |library: &str| {
+                loaded.as_ref().map_or(true, |loaded| loaded.iter().any(|module| modules::is_library(module, library)))
+            };
+            for library in preloaded.iter().filter(|library| maps(library)) {
+                injected.push(format!("{}: {}", modules::PRELOAD_SOURCE, library));
+            }
+        }
+
+        injected.into_iter()
+            .map(|library| {
+                let severity       = ::detection_severity(crit_proc.severity, "injection", 0.0, 0.0, false);
+                let mut comparison = subject.comparison(&crit_proc.name, 0.0, 0.0, true, "injection", severity);
+                comparison.module  = library;
+                comparison
+            })
+            .collect()
+    }
+}
+
+// argv[0] is freely chosen by whoever starts the process, a critical
+// name there must still be backed by a whitelisted executable
+struct Argv0;
+
+impl DetectionRule for Argv0 {
+    fn name(&self) -> &'static str {
+        "argv0"
+    }
+
+    fn weight(&self) -> u32 {
+        60
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let argv0 = match subject.argv0 {
+            Some(argv0) if subject.exe_known => context.options.names.normalize(&confusables::skeleton(argv0)),
+            _                                => return Vec::new(),
+        };
+
+        subject.against(context)
+            .filter(|&(crit_proc, _, _)| {
+                argv0 == context.options.names.normalize(&crit_proc.name) && !subject.whitelisted(crit_proc, context)
+            })
+            .map(|(crit_proc, _, limit)| {
+                subject.comparison(&crit_proc.name, 0.0, limit, true, "argv0",
+                                   ::detection_severity(crit_proc.severity, "argv0", 0.0, limit,
+                                                        context.algorithm.normalized()))
+            })
+            .collect()
+    }
+}
+
+// Shells and script interpreters, services aren't started from them
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh", "csh", "tcsh", "fish", "busybox", "python", "python2",
+                          "python3", "perl", "ruby", "node", "php", "cmd.exe", "powershell.exe", "pwsh.exe",
+                          "wscript.exe", "cscript.exe", "mshta.exe"];
+
+// Critical processes are started by init or the service manager, one started from a shell or
+// script interpreter was launched by hand or by a dropper. A weak signal on its own.
+struct OddParent;
+
+impl DetectionRule for OddParent {
+    fn name(&self) -> &'static str {
+        "odd-parent"
+    }
+
+    fn weight(&self) -> u32 {
+        25
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let names      = context.options.names;
+        let from_shell = subject.parent.map_or(false, |parent| {
+            SHELLS.iter().any(|shell| names.normalize(shell) == names.normalize(parent))
+        });
+        if !from_shell {
+            return Vec::new();
+        }
+
+        subject.against(context)
+            .filter(|&(_, distance, limit)| distance <= limit)
+            .map(|(crit_proc, distance, limit)| {
+                subject.comparison(&crit_proc.name, distance, limit, true, "odd-parent",
+                                   ::detection_severity(crit_proc.severity, "odd-parent", distance, limit,
+                                                        context.algorithm.normalized()))
+            })
+            .collect()
+    }
+}
+
+// Singleton critical processes running more times than `instances.NAME` allows. A second lsass.exe
+// is a masquerade whatever its name and path, which the other rules find right.
+struct Instances;
+
+impl DetectionRule for Instances {
+    fn name(&self) -> &'static str {
+        "instances"
+    }
+
+    fn weight(&self) -> u32 {
+        60
+    }
+
+    // Distance and threshold are the number of instances running and expected
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        context.surplus.get(&subject.pid).map(|&(crit_proc, running)| {
+            let expected = crit_proc.instances.unwrap_or(0);
+            subject.comparison(&crit_proc.name, running as f64, expected as f64, true, "duplicate",
+                               ::detection_severity(crit_proc.severity, "duplicate", 0.0, 0.0,
+                                                    context.algorithm.normalized()))
+        }).into_iter().collect()
+    }
+}
+
+// Decoys named like a critical process seldom behave like it: an svchost.exe with one thread and five
+// handles hosts no service. `threads.NAME` and `handles.NAME` settings give the ranges expected.
+struct Counts;
+
+impl DetectionRule for Counts {
+    fn name(&self) -> &'static str {
+        "counts"
+    }
+
+    fn weight(&self) -> u32 {
+        50
+    }
+
+    // Distance and threshold are the count and the bound of the range it crosses
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let crit_proc = match subject.against(context).find(|&(_, distance, _)| distance == 0.0) {
+            Some((crit_proc, _, _)) if !subject.homoglyph => crit_proc,
+            _                                             => return Vec::new(),
+        };
+
+        let mut found = Vec::new();
+        for &(count, range, reason) in &[(subject.threads, crit_proc.threads, "thread-count"),
+                                         (subject.handles, crit_proc.handles, "handle-count")] {
+            let (count, (min, max)) = match (count, range) {
+                (Some(count), Some(range)) => (count, range),
+                _                          => continue,
+            };
+            if count < min || count > max {
+                let bound = if count < min { min } else { max };
+                found.push(subject.comparison(&crit_proc.name, count as f64, bound as f64, true, reason,
+                                              ::detection_severity(crit_proc.severity, reason, 0.0, 0.0, false)));
+            }
+        }
+
+        found
+    }
+}
+
+// Critical processes started recently on a system up for long, system daemons start along with it.
+// A weak signal on its own, updates restart services too.
+struct Recent;
+
+impl DetectionRule for Recent {
+    fn name(&self) -> &'static str {
+        "recent"
+    }
+
+    fn weight(&self) -> u32 {
+        25
+    }
+
+    fn check(&self, subject: &Subject, context: &Context) -> Vec<Comparison> {
+        let (started, booted) = match (subject.started, context.options.booted) {
+            (Some(started), Some(booted)) => (started, booted),
+            _                             => return Vec::new(),
+        };
+        let (within, after_boot) = context.options.recent;
+        if Local::now() - started > within || started - booted < after_boot {
+            return Vec::new();
+        }
+
+        subject.against(context)
+            .filter(|&(_, distance, limit)| distance <= limit)
+            .map(|(crit_proc, distance, limit)| {
+                subject.comparison(&crit_proc.name, distance, limit, true, "recent",
+                                   ::detection_severity(crit_proc.severity, "recent", distance, limit,
+                                                        context.algorithm.normalized()))
+            })
+            .collect()
+    }
+}
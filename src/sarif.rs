@@ -0,0 +1,193 @@
+// SARIF 2.1.0 logs of a scan, for code scanning and security dashboards. Every detection reason is
+// a rule and every detection a result located at the executable, or the module, it was found for.
+
+use std::io::{self, Write};
+
+use serde_json::Value;
+
+use report;
+use types::{Comparison, ScanReport, Severity};
+
+const SCHEMA: &'static str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+// Detection reasons and what they stand for
+const RULES: &'static [(&'static str, &'static str)] = &[
+    ("distance",              "Process name close to the name of a critical process"),
+    ("homoglyph",             "Process name spelling a critical process with look-alike characters"),
+    ("wrong-path",            "Critical process running from an executable that isn't whitelisted"),
+    ("deleted-exe",           "Process whose executable was deleted while it runs"),
+    ("unresolved-exe",        "Process whose executable can't be found"),
+    ("argv0",                 "Process posing as a critical process in argv[0]"),
+    ("module",                "Critical process loading a module from a temporary or user directory"),
+    ("injection",             "Critical process with a library injected through the loader"),
+    ("unsigned",              "Critical process running from an unsigned executable"),
+    ("bad-signature",         "Critical process running from an executable whose signature doesn't verify"),
+    ("odd-parent",            "Critical process started from a shell or script interpreter"),
+    ("duplicate",             "More instances of a critical process than expected"),
+    ("recent",                "Critical process started long after boot"),
+    ("hollowed",              "Critical process whose image in memory doesn't match its executable"),
+    ("exe-mismatch",          "Critical process whose executable isn't among its memory mappings"),
+    ("wrong-user",            "Critical process running as a user it isn't expected to run as"),
+    ("thread-count",          "Critical process running a number of threads outside the expected range"),
+    ("handle-count",          "Critical process holding a number of handles outside the expected range"),
+    ("impersonation-at-rest", "Command in PATH shadowing or imitating a critical process"),
+    ("invisible-chars",       "Process name hiding zero-width or control characters"),
+    ("persistence",           "Autostart entry starting a program imitating a critical process"),
+    ("service",               "Installed service imitating a critical process by its name or program"),
+    ("kernel-module",         "Loaded kernel module or driver imitating an expected one"),
+    ("temp-path",             "Critical process running from a temporary or download directory"),
+    ("removable-path",        "Critical process running from removable media"),
+];
+
+pub fn write<W: Write>(out: &mut W, report: &ScanReport) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &log(report))?;
+    writeln!(out)
+}
+
+pub fn log(report: &ScanReport) -> Value {
+    let host = report.host.clone().unwrap_or_else(report::hostname);
+
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name":           "BONOMEN",
+                    "version":        crate_version!(),
+                    "informationUri": "https://github.com/0xcpu/bonomen",
+                    "rules":          RULES.iter().map(|&(id, description)| json!({
+                        "id":               id,
+                        "shortDescription": { "text": description },
+                    })).collect::<Vec<_>>(),
+                },
+            },
+            "results": report.detections.iter().map(|c| result(c, &host)).collect::<Vec<_>>(),
+        }],
+    })
+}
+
+fn result(c: &Comparison, host: &str) -> Value {
+    let path = if c.module.is_empty() { &c.exe_path } else { &c.module };
+    let mut result = json!({
+        "ruleId":  c.reason,
+        "level":   level(c.severity),
+        "message": { "text": message(c) },
+        // The same process detected again on a later scan is the same finding
+        "partialFingerprints": {
+            "bonomenDetection/v1": format!("{}:{}:{}:{}", host, c.reason, c.crit_name, path),
+        },
+        "properties": {
+            "host":      host,
+            "pid":       c.pid,
+            "name":      c.name,
+            "critical":  c.crit_name,
+            "distance":  c.distance,
+            "threshold": c.threshold,
+            "severity":  c.severity.name(),
+            "score":     c.score,
+            "started":   c.started.map(|started| started.to_rfc3339()),
+            "container": c.container.as_ref().map(|container| container.to_string()),
+            "environment": c.environment.iter().map(|&(ref name, ref value)| (name.clone(), json!(value)))
+                .collect::<serde_json::Map<String, Value>>(),
+            "yara":      c.yara,
+            "virustotal": c.virustotal.as_ref().map(report::vt_json),
+        },
+    });
+    if !path.is_empty() {
+        result["locations"] = json!([{
+            "physicalLocation": { "artifactLocation": { "uri": file_uri(path) } },
+        }]);
+    }
+
+    result
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Critical | Severity::High => "error",
+        Severity::Medium                    => "warning",
+        Severity::Low                       => "note",
+    }
+}
+
+// The detection as the text output words it
+pub fn message(c: &Comparison) -> String {
+    match c.reason {
+        "wrong-path" | "deleted-exe" | "unresolved-exe" => {
+            format!("{} ({}) running from {}", c.name, c.pid, c.exe_path)
+        },
+        "argv0"                                         => {
+            format!("{} ({}) poses as {} in argv[0], running from {}", c.name, c.pid, c.crit_name, c.exe_path)
+        },
+        "module"                                        => format!("{} ({}) loads {}", c.name, c.pid, c.module),
+        "injection"                                     => format!("{} ({}) has {} injected", c.name, c.pid, c.module),
+        "duplicate"                                     => {
+            format!("{} ({}) is one of {} instances of {}, {} expected", c.name, c.pid, c.distance, c.crit_name,
+                    c.threshold)
+        },
+        "recent"                                        => format!("{} ({}) started long after boot", c.name, c.pid),
+        "odd-parent"                                    => {
+            format!("{} ({}) started from a shell or script interpreter", c.name, c.pid)
+        },
+        "hollowed"                                      => {
+            format!("{} ({}) runs an image in memory other than its executable {}", c.name, c.pid, c.exe_path)
+        },
+        "exe-mismatch"                                  => {
+            format!("{} ({}) doesn't map its executable {}", c.name, c.pid, c.exe_path)
+        },
+        "wrong-user"                                    => {
+            format!("{} ({}) runs as {}, a user {} isn't expected to run as", c.name, c.pid,
+                    c.owner.as_ref().map_or("?", |owner| owner.as_str()), c.crit_name)
+        },
+        "thread-count"                                  => {
+            format!("{} ({}) runs {} threads, outside the range expected of {}", c.name, c.pid, c.distance,
+                    c.crit_name)
+        },
+        "handle-count"                                  => {
+            format!("{} ({}) has {} handles open, outside the range expected of {}", c.name, c.pid, c.distance,
+                    c.crit_name)
+        },
+        "impersonation-at-rest"                         => {
+            format!("{} in PATH imitates {}, distance {}", c.exe_path, c.crit_name, c.distance)
+        },
+        "invisible-chars"                               => {
+            format!("{} ({}) hides invisible or control characters in its name, closest to {}",
+                    c.name.escape_debug(), c.pid, c.crit_name)
+        },
+        "persistence"                                   => {
+            format!("{} starts {}, imitating {}, distance {}", c.module, c.exe_path, c.crit_name, c.distance)
+        },
+        "service"                                       => {
+            format!("service {} runs {}, imitating {}, distance {}", c.name, c.exe_path, c.crit_name, c.distance)
+        },
+        "temp-path"                                     => {
+            format!("{} ({}) runs from the temporary or download directory {}, imitating {}", c.name, c.pid,
+                    c.exe_path, c.crit_name)
+        },
+        "removable-path"                                => {
+            format!("{} ({}) runs from removable media {}, imitating {}", c.name, c.pid, c.exe_path, c.crit_name)
+        },
+        "kernel-module"                                 => {
+            format!("kernel module {} imitates {}, distance {}", c.name, c.crit_name, c.distance)
+        },
+        _                                               => {
+            format!("{} ({}) resembles {}, distance {}, threshold {}", c.name, c.pid, c.crit_name, c.distance,
+                    c.threshold)
+        },
+    }
+}
+
+// file:// URI of an absolute path, `C:\Windows` becomes file:///C:/Windows
+fn file_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = String::from(if path.starts_with('/') { "file://" } else { "file:///" });
+    for c in path.chars() {
+        match c {
+            ' ' | '#' | '%' | '?' => uri.push_str(&format!("%{:02X}", c as u32)),
+            c                     => uri.push(c),
+        }
+    }
+
+    uri
+}
@@ -0,0 +1,154 @@
+// Cron expressions of the monitor mode: minute, hour, day of month, month and day of week,
+// each one `*`, a value, a range `a-b` or a list of them, optionally stepped with `/n`
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use sha2::{Digest, Sha256};
+
+const MONTHS: [&str; 12]  = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+// Runs more than this far apart are never found, e.g. `0 0 30 2 *`
+const SEARCH_YEARS: i32 = 5;
+
+pub struct Schedule {
+    minutes : u64,
+    hours   : u64,
+    days    : u64,
+    months  : u64,
+    weekdays: u64,
+    // Restricting both days of the month and of the week matches either of them
+    any_day : bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Schedule, String> {
+        let expression = match expression.trim() {
+            "@hourly"               => "0 * * * *",
+            "@daily" | "@midnight"  => "0 0 * * *",
+            "@weekly"               => "0 0 * * 0",
+            "@monthly"              => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression              => expression,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("expected 5 fields, minute hour day month weekday: {}", expression));
+        }
+
+        // Sunday is both 0 and 7
+        let weekdays = parse_field(fields[4], 0, 7, &WEEKDAYS)?;
+        let weekdays = (weekdays | (weekdays >> 7)) & 0x7f;
+
+        Ok(Schedule {
+            minutes:  parse_field(fields[0], 0, 59, &[])?,
+            hours:    parse_field(fields[1], 0, 23, &[])?,
+            days:     parse_field(fields[2], 1, 31, &[])?,
+            months:   parse_field(fields[3], 1, 12, &MONTHS)?,
+            weekdays: weekdays,
+            any_day:  !fields[2].starts_with('*') && !fields[4].starts_with('*'),
+        })
+    }
+
+    // First run strictly after `after`, None when the expression never matches
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = start + Duration::days(366 * SEARCH_YEARS as i64);
+        let mut t = start;
+
+        while t < limit {
+            if !contains(self.months, t.month()) {
+                t = first_of_next_month(t.date())?;
+            } else if !self.day_matches(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !contains(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if !contains(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else if let Some(run) = Local.from_local_datetime(&t).earliest() {
+                return Some(run);
+            } else {
+                // Skipped by a daylight saving time change
+                t += Duration::minutes(1);
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day     = contains(self.days, date.day());
+        let weekday = contains(self.weekdays, date.weekday().num_days_from_sunday());
+
+        if self.any_day { day || weekday } else { day && weekday }
+    }
+}
+
+// Delay of up to `jitter` added to every scheduled run, always the same on a host so that
+// a fleet sharing one schedule spreads its scans while each host keeps a regular pace
+pub fn host_jitter(host: &str, jitter: Duration) -> Duration {
+    if jitter <= Duration::zero() {
+        return Duration::zero();
+    }
+    let digest = Sha256::digest(host.as_bytes());
+    let mut seed = [0u8; 8];
+    seed.copy_from_slice(&digest[.. 8]);
+
+    Duration::milliseconds((u64::from_be_bytes(seed) % jitter.num_milliseconds() as u64) as i64)
+}
+
+fn contains(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+fn first_of_next_month(date: NaiveDate) -> Option<NaiveDateTime> {
+    let (year, month) = if date.month() == 12 { (date.year() + 1, 1) } else { (date.year(), date.month() + 1) };
+    NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+// Set of the values of one field, as bits
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let mut set = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(pos) => match part[pos + 1 ..].parse::<u32>() {
+                Ok(step) if step > 0 => (&part[.. pos], step),
+                _                    => return Err(format!("invalid step: {}", part)),
+            },
+            None      => (part, 1),
+        };
+        let (first, last) = if range == "*" {
+            (min, max)
+        } else if let Some(pos) = range.find('-') {
+            (field_value(&range[.. pos], min, max, names)?, field_value(&range[pos + 1 ..], min, max, names)?)
+        } else {
+            let first = field_value(range, min, max, names)?;
+            // `a/n` runs from a to the end of the field
+            (first, if step > 1 { max } else { first })
+        };
+        if first > last {
+            return Err(format!("invalid range: {}", part));
+        }
+
+        let mut current = first;
+        while current <= last {
+            set |= 1 << current;
+            current += step;
+        }
+    }
+
+    Ok(set)
+}
+
+fn field_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32, String> {
+    let lower = value.to_lowercase();
+    if let Some(index) = names.iter().position(|name| *name == lower) {
+        // Months are numbered from 1, weekdays from 0
+        return Ok(index as u32 + if names.len() == 12 { 1 } else { 0 });
+    }
+
+    match value.parse::<u32>() {
+        Ok(number) if min <= number && number <= max => Ok(number),
+        _                                            => Err(format!("{} isn't between {} and {}", value, min, max)),
+    }
+}
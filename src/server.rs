@@ -0,0 +1,380 @@
+// Fleet view for teams without a SIEM: `bonomen server` accepts the JSON reports of remote BONOMEN agents
+// over HTTPS, from agents holding a client certificate when given a CA, records them in a history database
+// and serves what every host found as JSON, and as a summary page.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::str;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::pki_types::pem::PemObject;
+use rustls::server::WebPkiClientVerifier;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use history;
+use whitelist;
+
+pub const DEFAULT_LISTEN: &'static str = "0.0.0.0:8443";
+const TIMEOUT_SECS: u64 = 30;
+// Largest report accepted, reports with every comparison of a busy host stay well below
+const MAX_BODY: usize = 64 * 1024 * 1024;
+// Longest request or header line and most header lines, read before clients are known to hold the token
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+// Connections served at once, others are closed right away
+const MAX_CONNECTIONS: usize = 64;
+// Detections on the summary page
+const RECENT: usize = 50;
+
+pub struct Settings {
+    // ADDRESS:PORT to listen on
+    pub listen   : String,
+    // PEM certificate chain and private key of the server
+    pub cert     : String,
+    pub key      : String,
+    // PEM certificates of the CA agents' client certificates must be issued by
+    pub client_ca: Option<String>,
+    // Bearer token every request must carry
+    pub token    : Option<String>,
+    pub db       : String,
+}
+
+struct Request {
+    method    : String,
+    path      : String,
+    query     : Vec<(String, String)>,
+    // Carries the bearer token of the server, if it has one. The body of others isn't read.
+    authorized: bool,
+    body      : Vec<u8>,
+}
+
+struct Response {
+    status      : &'static str,
+    content_type: &'static str,
+    body        : String,
+}
+
+impl Response {
+    fn json(status: &'static str, body: &Value) -> Response {
+        Response {
+            status:       status,
+            content_type: "application/json",
+            body:         body.to_string(),
+        }
+    }
+
+    fn error(status: &'static str, why: &str) -> Response {
+        Response::json(status, &json!({ "error": why }))
+    }
+}
+
+// Serve until the process is stopped, each connection on its own thread
+pub fn run(settings: Settings) -> Result<(), String> {
+    let config   = Arc::new(tls_config(&settings)?);
+    let listener = TcpListener::bind(&settings.listen)
+        .map_err(|why| format!("couldn't listen on {}: {}", settings.listen, why))?;
+    if settings.client_ca.is_none() && settings.token.is_none() {
+        warn!("No --client-ca or --token, anyone reaching {} can submit and read reports", settings.listen);
+    }
+    info!("Accepting reports on https://{}/api/v1/reports", settings.listen);
+    let settings = Arc::new(settings);
+    let serving  = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(why)   => {
+                debug!("couldn't accept a connection: {}", why);
+                continue;
+            },
+        };
+        let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+        if serving.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            serving.fetch_sub(1, Ordering::SeqCst);
+            warn!("Connection from {} closed, {} connections are served already", peer, MAX_CONNECTIONS);
+            continue;
+        }
+        let config   = config.clone();
+        let settings = settings.clone();
+        let serving  = serving.clone();
+        thread::spawn(move || {
+            if let Err(why) = serve(stream, config, &settings, &peer) {
+                debug!("request from {} failed: {}", peer, why);
+            }
+            serving.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+
+    Ok(())
+}
+
+fn tls_config(settings: &Settings) -> Result<ServerConfig, String> {
+    let certs = read_certs(&settings.cert)?;
+    let key   = PrivateKeyDer::from_pem_file(&settings.key)
+        .map_err(|why| format!("couldn't read the private key {}: {}", settings.key, why))?;
+
+    let builder = ServerConfig::builder();
+    let builder = match settings.client_ca {
+        Some(ref client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(client_ca)? {
+                roots.add(cert).map_err(|why| format!("invalid CA certificate in {}: {}", client_ca, why))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots)).build()
+                .map_err(|why| format!("couldn't use the CA certificates of {}: {}", client_ca, why))?;
+            builder.with_client_cert_verifier(verifier)
+        },
+        None                => builder.with_no_client_auth(),
+    };
+
+    builder.with_single_cert(certs, key).map_err(|why| format!("invalid certificate or key: {}", why))
+}
+
+fn read_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|why| format!("couldn't read the certificates of {}: {}", path, why))?;
+    if certs.is_empty() {
+        return Err(format!("no certificate in {}", path));
+    }
+
+    Ok(certs)
+}
+
+fn serve(stream: TcpStream, config: Arc<ServerConfig>, settings: &Settings, peer: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(TIMEOUT_SECS)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(TIMEOUT_SECS)))?;
+    let conn = ServerConnection::new(config).map_err(|why| io::Error::new(io::ErrorKind::Other, why))?;
+    let mut tls = StreamOwned::new(conn, stream);
+
+    let response = match read_request(&mut BufReader::new(&mut tls), settings.token.as_ref()) {
+        Ok(request) => route(&request, settings, peer),
+        Err(why)    => Response::error("400 Bad Request", &why.to_string()),
+    };
+    write!(tls, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+           response.status, response.content_type, response.body.len(), response.body)?;
+    tls.conn.send_close_notify();
+    tls.flush()
+}
+
+// A line of at most MAX_LINE bytes
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String) -> io::Result<usize> {
+    let read = reader.by_ref().take(MAX_LINE as u64 + 1).read_line(line)?;
+    if line.len() > MAX_LINE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "request line or header too long"));
+    }
+
+    Ok(read)
+}
+
+// Compared in constant time, hashes hide the length of the token too
+fn same_secret(expected: &str, given: &str) -> bool {
+    let (expected, given) = (Sha256::digest(expected.as_bytes()), Sha256::digest(given.as_bytes()));
+
+    expected.iter().zip(given.iter()).fold(0, |differ, (a, b)| differ | (a ^ b)) == 0
+}
+
+fn read_request<R: BufRead>(reader: &mut R, expected: Option<&String>) -> io::Result<Request> {
+    let invalid = |why: &str| io::Error::new(io::ErrorKind::InvalidData, why.to_string());
+
+    let mut line = String::new();
+    read_line(reader, &mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().ok_or_else(|| invalid("empty request"))?.to_string();
+    let target = parts.next().ok_or_else(|| invalid("no request target"))?.to_string();
+
+    let mut length = 0;
+    let mut token  = None;
+    for count in 0 .. {
+        line.clear();
+        if read_line(reader, &mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if count == MAX_HEADERS {
+            return Err(invalid("too many headers"));
+        }
+        let mut header = line.splitn(2, ':');
+        let name = header.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            length = header.next().unwrap_or("").trim().parse::<usize>()
+                .map_err(|_| invalid("invalid Content-Length"))?;
+        } else if name.eq_ignore_ascii_case("authorization") {
+            let value = header.next().unwrap_or("").trim();
+            if value.len() > 7 && value.get(.. 7).map_or(false, |prefix| prefix.eq_ignore_ascii_case("bearer ")) {
+                token = Some(value[7 ..].trim().to_string());
+            }
+        }
+    }
+    let authorized = match (expected, token) {
+        (Some(expected), Some(token)) => same_secret(expected, &token),
+        (Some(_), None)               => false,
+        (None, _)                     => true,
+    };
+    if length > MAX_BODY {
+        return Err(invalid("report too large"));
+    }
+    // Nothing is allocated for clients without the token
+    let mut body = vec![0; if authorized { length } else { 0 }];
+    reader.read_exact(&mut body)?;
+
+    let (path, query) = match target.find('?') {
+        Some(pos) => (&target[.. pos], &target[pos + 1 ..]),
+        None      => (target.as_str(), ""),
+    };
+
+    Ok(Request {
+        method:     method,
+        path:       path.to_string(),
+        query:      query.split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let mut pair = pair.splitn(2, '=');
+                (decode(pair.next().unwrap_or("")), decode(pair.next().unwrap_or("")))
+            })
+            .collect(),
+        authorized: authorized,
+        body:       body,
+    })
+}
+
+fn route(request: &Request, settings: &Settings, peer: &str) -> Response {
+    if !request.authorized {
+        warn!("Request from {} refused, wrong or missing token", peer);
+        return Response::error("401 Unauthorized", "wrong or missing bearer token");
+    }
+    let db = settings.db.as_str();
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/api/v1/reports")    => submit(request, settings, peer),
+        ("GET", "/api/v1/hosts")       => match history::hosts(db) {
+            Ok(hosts) => Response::json("200 OK", &hosts.iter().map(history::Host::to_json).collect()),
+            Err(why)  => Response::error("500 Internal Server Error", &why.to_string()),
+        },
+        ("GET", "/api/v1/detections")  => match detections(request, db) {
+            Ok(records) => Response::json("200 OK", &records.iter().map(history::Record::to_json).collect()),
+            Err(why)    => Response::error("400 Bad Request", &why),
+        },
+        ("GET", "/")                   => summary(db),
+        (_, "/api/v1/reports") | (_, "/api/v1/hosts") | (_, "/api/v1/detections") | (_, "/") => {
+            Response::error("405 Method Not Allowed", "method not allowed")
+        },
+        _                              => Response::error("404 Not Found", "not found"),
+    }
+}
+
+// A JSON report of an agent, as `bonomen scan --report` writes it
+fn submit(request: &Request, settings: &Settings, peer: &str) -> Response {
+    let report: Value = match serde_json::from_slice(&request.body) {
+        Ok(report) => report,
+        Err(why)   => return Response::error("400 Bad Request", &format!("invalid JSON report: {}", why)),
+    };
+    if !report["host"].is_string() || !report["detections"].is_array() {
+        return Response::error("400 Bad Request", "not a BONOMEN report: host or detections missing");
+    }
+
+    match history::record(&settings.db, &report) {
+        Ok(detections) => {
+            info!("Report of {} from {}: {} detections", report["host"].as_str().unwrap_or(""), peer, detections);
+            Response::json("201 Created", &json!({ "recorded": detections }))
+        },
+        Err(why)       => {
+            error!("couldn't record the report from {}: {}", peer, why);
+            Response::error("500 Internal Server Error", &why.to_string())
+        },
+    }
+}
+
+// Detections matching the `host`, `rule`, `name`, `since` and `until` parameters, as `bonomen history` filters them
+fn detections(request: &Request, db: &str) -> Result<Vec<history::Record>, String> {
+    let param = |name: &str| request.query.iter().find(|&&(ref key, _)| key == name).map(|&(_, ref value)| value);
+    let time  = |name: &str| -> Result<_, String> {
+        match param(name) {
+            Some(time) => ::history_time(time).map(Some).map_err(|why| why.to_string()),
+            None       => Ok(None),
+        }
+    };
+    let filter = history::Filter {
+        since:  time("since")?,
+        until:  time("until")?,
+        host:   param("host").cloned(),
+        reason: param("rule").cloned(),
+    };
+    let name = match param("name") {
+        Some(name) => Some(whitelist::Entry::parse(name)?),
+        None       => None,
+    };
+
+    let records = history::query(db, &filter).map_err(|why| why.to_string())?;
+
+    Ok(records.into_iter().filter(|record| name.as_ref().map_or(true, |name| name.matches(&record.name))).collect())
+}
+
+// Every host and the latest detections
+fn summary(db: &str) -> Response {
+    let (hosts, records) = match (history::hosts(db), history::query(db, &history::Filter::default())) {
+        (Ok(hosts), Ok(records)) => (hosts, records),
+        (Err(why), _) | (_, Err(why)) => return Response::error("500 Internal Server Error", &why.to_string()),
+    };
+
+    let mut page = String::from("<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>BONOMEN</title></head>\n");
+    page.push_str("<body>\n");
+    page.push_str(&format!("<h1>BONOMEN: {} hosts</h1>\n<table border=\"1\">\n", hosts.len()));
+    page.push_str("<tr><th>Host</th><th>Last report</th><th>Reports</th><th>Detections in the last report</th>\
+                   <th>Detections</th></tr>\n");
+    for host in &hosts {
+        page.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                               escape(&host.host), host.last_scan.format("%Y-%m-%d %H:%M:%S"), host.scans,
+                               host.last_detections, host.detections));
+    }
+    page.push_str("</table>\n<h2>Latest detections</h2>\n<table border=\"1\">\n");
+    page.push_str("<tr><th>Time</th><th>Host</th><th>Process</th><th>Executable</th><th>Reason</th>\
+                   <th>Critical process</th><th>Severity</th></tr>\n");
+    for record in records.iter().rev().take(RECENT) {
+        page.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{} ({})</td><td>{}</td><td>{}</td><td>{}</td>\
+                                <td>{}</td></tr>\n",
+                               record.time.format("%Y-%m-%d %H:%M:%S"), escape(&record.host), escape(&record.name),
+                               record.pid, escape(&record.exe_path), escape(&record.reason),
+                               escape(&record.crit_name), escape(&record.severity)));
+    }
+    page.push_str("</table>\n</body></html>\n");
+
+    Response {
+        status:       "200 OK",
+        content_type: "text/html; charset=utf-8",
+        body:         page,
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Percent-decoded query string component, `+` standing for a space
+fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+'                        => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match str::from_utf8(&bytes[i + 1 .. i + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    },
+                    None       => decoded.push(b'%'),
+                }
+            },
+            byte                        => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
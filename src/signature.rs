@@ -0,0 +1,107 @@
+// Authenticode verification of executables through WinVerifyTrust
+
+use std::mem::size_of;
+use std::ptr;
+
+use winapi::shared::minwindef::DWORD;
+use winapi::um::winnt::{HANDLE, LONG, LPCWSTR};
+use winapi::shared::guiddef::GUID;
+use winapi::shared::windef::HWND;
+
+// {00AAC56B-CD44-11d0-8CC2-00C04FC295EE}
+const WINTRUST_ACTION_GENERIC_VERIFY_V2: GUID = GUID {
+    Data1: 0x00aac56b,
+    Data2: 0xcd44,
+    Data3: 0x11d0,
+    Data4: [0x8c, 0xc2, 0x00, 0xc0, 0x4f, 0xc2, 0x95, 0xee],
+};
+
+const WTD_UI_NONE: DWORD                 = 2;
+const WTD_REVOKE_NONE: DWORD             = 0;
+const WTD_CHOICE_FILE: DWORD             = 1;
+const WTD_STATEACTION_VERIFY: DWORD      = 1;
+const WTD_STATEACTION_CLOSE: DWORD       = 2;
+const TRUST_E_NOSIGNATURE: LONG          = 0x800B0100u32 as LONG;
+const TRUST_E_SUBJECT_FORM_UNKNOWN: LONG = 0x800B0003u32 as LONG;
+const TRUST_E_PROVIDER_UNKNOWN: LONG     = 0x800B0001u32 as LONG;
+
+#[repr(C)]
+struct WintrustFileInfo {
+    cb_struct:        DWORD,
+    pcwsz_file_path:  LPCWSTR,
+    h_file:           HANDLE,
+    pg_known_subject: *const GUID,
+}
+
+#[repr(C)]
+struct WintrustData {
+    cb_struct:              DWORD,
+    p_policy_callback_data: *mut ::std::os::raw::c_void,
+    p_sip_client_data:      *mut ::std::os::raw::c_void,
+    dw_ui_choice:           DWORD,
+    fdw_revocation_checks:  DWORD,
+    dw_union_choice:        DWORD,
+    p_file:                 *mut WintrustFileInfo,
+    dw_state_action:        DWORD,
+    h_wvt_state_data:       HANDLE,
+    pwsz_url_reference:     *mut u16,
+    dw_prov_flags:          DWORD,
+    dw_ui_context:          DWORD,
+    p_signature_settings:   *mut ::std::os::raw::c_void,
+}
+
+#[link(name = "wintrust")]
+extern "system" {
+    fn WinVerifyTrust(hwnd: HWND, pg_action_id: *mut GUID, p_wvt_data: *mut ::std::os::raw::c_void) -> LONG;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SignatureState {
+    Valid,
+    Unsigned,
+    // WinVerifyTrust error code
+    Invalid(i32),
+}
+
+// Check the embedded Authenticode signature of an executable
+pub fn verify(path: &str) -> SignatureState {
+    let mut wide_path: Vec<u16> = path.encode_utf16().collect();
+    wide_path.push(0);
+
+    let mut file_info = WintrustFileInfo {
+        cb_struct:        size_of::<WintrustFileInfo>() as DWORD,
+        pcwsz_file_path:  wide_path.as_ptr(),
+        h_file:           ptr::null_mut(),
+        pg_known_subject: ptr::null(),
+    };
+    let mut data = WintrustData {
+        cb_struct:              size_of::<WintrustData>() as DWORD,
+        p_policy_callback_data: ptr::null_mut(),
+        p_sip_client_data:      ptr::null_mut(),
+        dw_ui_choice:           WTD_UI_NONE,
+        fdw_revocation_checks:  WTD_REVOKE_NONE,
+        dw_union_choice:        WTD_CHOICE_FILE,
+        p_file:                 &mut file_info,
+        dw_state_action:        WTD_STATEACTION_VERIFY,
+        h_wvt_state_data:       ptr::null_mut(),
+        pwsz_url_reference:     ptr::null_mut(),
+        dw_prov_flags:          0,
+        dw_ui_context:          0,
+        p_signature_settings:   ptr::null_mut(),
+    };
+    let mut action = WINTRUST_ACTION_GENERIC_VERIFY_V2;
+
+    let status = unsafe {
+        let status = WinVerifyTrust(ptr::null_mut(), &mut action, &mut data as *mut _ as *mut _);
+        // Release the state data allocated by the verification
+        data.dw_state_action = WTD_STATEACTION_CLOSE;
+        WinVerifyTrust(ptr::null_mut(), &mut action, &mut data as *mut _ as *mut _);
+        status
+    };
+
+    match status {
+        0 => SignatureState::Valid,
+        TRUST_E_NOSIGNATURE | TRUST_E_SUBJECT_FORM_UNKNOWN | TRUST_E_PROVIDER_UNKNOWN => SignatureState::Unsigned,
+        _ => SignatureState::Invalid(status),
+    }
+}
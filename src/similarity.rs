@@ -0,0 +1,76 @@
+use strsim::{damerau_levenshtein, jaro_winkler, normalized_damerau_levenshtein, osa_distance};
+
+// Names accepted by `--algorithm` and the `algorithm` setting
+pub const ALGORITHMS: &'static [&'static str] = &["damerau-levenshtein", "osa", "jaro-winkler", "ratio"];
+
+// A way of measuring how far apart two process names are.
+// Edit distance algorithms return a number of edits, normalized algorithms
+// return a dissimilarity percentage between 0 and 100.
+// Identical names are always at distance 0.
+pub trait Similarity: Sync {
+    fn distance(&self, a: &str, b: &str) -> f64;
+
+    // True if distances are percentages rather than a number of edits
+    fn normalized(&self) -> bool {
+        false
+    }
+}
+
+// Insertions, deletions, substitutions and transpositions of adjacent characters
+pub struct DamerauLevenshtein;
+
+// Like Damerau-Levenshtein, but a substring can't be edited more than once
+pub struct Osa;
+
+// Jaro-Winkler, favors names sharing a common prefix
+pub struct JaroWinkler;
+
+// Damerau-Levenshtein distance relative to the length of the longest name
+pub struct Ratio;
+
+impl Similarity for DamerauLevenshtein {
+    fn distance(&self, a: &str, b: &str) -> f64 {
+        damerau_levenshtein(a, b) as f64
+    }
+}
+
+impl Similarity for Osa {
+    fn distance(&self, a: &str, b: &str) -> f64 {
+        osa_distance(a, b) as f64
+    }
+}
+
+impl Similarity for JaroWinkler {
+    fn distance(&self, a: &str, b: &str) -> f64 {
+        percent(1.0 - jaro_winkler(a, b))
+    }
+
+    fn normalized(&self) -> bool {
+        true
+    }
+}
+
+impl Similarity for Ratio {
+    fn distance(&self, a: &str, b: &str) -> f64 {
+        percent(1.0 - normalized_damerau_levenshtein(a, b))
+    }
+
+    fn normalized(&self) -> bool {
+        true
+    }
+}
+
+// Turn a 0..1 dissimilarity into a percentage rounded to two decimals
+fn percent(dissimilarity: f64) -> f64 {
+    (dissimilarity * 10000.0).round() / 100.0
+}
+
+pub fn from_name(name: &str) -> Option<Box<dyn Similarity>> {
+    match name {
+        "damerau-levenshtein" => Some(Box::new(DamerauLevenshtein)),
+        "osa"                 => Some(Box::new(Osa)),
+        "jaro-winkler"        => Some(Box::new(JaroWinkler)),
+        "ratio"               => Some(Box::new(Ratio)),
+        _                     => None,
+    }
+}
@@ -0,0 +1,102 @@
+// Locations programs are dropped into and run from rather than installed to: temporary directories, memory
+// backed file systems, downloads and removable media. Critical processes never run from them.
+
+use std::sync::OnceLock;
+
+#[cfg(unix)]
+const TEMP_PREFIXES: &'static [&'static str] = &["/tmp/", "/var/tmp/", "/dev/shm/", "/run/shm/", "/private/tmp/",
+                                                 "/private/var/tmp/", "/private/var/folders/"];
+#[cfg(unix)]
+const TEMP_FRAGMENTS: &'static [&'static str] = &["/Downloads/"];
+// Where desktops and udisks mount USB drives
+#[cfg(unix)]
+const REMOVABLE_PREFIXES: &'static [&'static str] = &["/media/", "/run/media/", "/Volumes/"];
+
+#[cfg(windows)]
+const TEMP_FRAGMENTS: &'static [&'static str] = &["\\appdata\\local\\temp\\", "\\windows\\temp\\", "\\downloads\\"];
+
+// "temp-path" for temporary and download directories, "removable-path" for removable media. The file systems and
+// drives of the system are only looked into for `local` scans, listings come from another machine.
+#[cfg(unix)]
+pub fn category(path: &str, local: bool) -> Option<&'static str> {
+    if TEMP_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) ||
+        TEMP_FRAGMENTS.iter().any(|fragment| path.contains(fragment)) ||
+        (local && on_tmpfs(path)) {
+        Some("temp-path")
+    } else if REMOVABLE_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+        Some("removable-path")
+    } else {
+        None
+    }
+}
+
+#[cfg(windows)]
+pub fn category(path: &str, local: bool) -> Option<&'static str> {
+    let lower = path.to_lowercase();
+    let temp  = local && ["TEMP", "TMP"].iter()
+        .filter_map(|var| ::std::env::var(var).ok())
+        .any(|dir| !dir.is_empty() && lower.starts_with(&dir.to_lowercase()));
+
+    if temp || TEMP_FRAGMENTS.iter().any(|fragment| lower.contains(fragment)) {
+        Some("temp-path")
+    } else if local && removable_drive(path) {
+        Some("removable-path")
+    } else {
+        None
+    }
+}
+
+// Mount points of the system and whether they are memory backed, read once
+#[cfg(unix)]
+static MOUNTS: OnceLock<Vec<(String, bool)>> = OnceLock::new();
+
+// Whether the file system `path` is on, the mount point closest to it, lives in memory. The root file system of
+// live systems does, and isn't telling.
+#[cfg(unix)]
+fn on_tmpfs(path: &str) -> bool {
+    let mounts = MOUNTS.get_or_init(|| {
+        // `device mount_point type options dump pass`, spaces in the mount point escaped as \040
+        ::std::fs::read_to_string("/proc/self/mounts").unwrap_or_default().lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace().skip(1);
+                match (fields.next(), fields.next()) {
+                    (Some(mount_point), Some(fs_type)) => {
+                        Some((mount_point.replace("\\040", " "), fs_type == "tmpfs" || fs_type == "ramfs"))
+                    },
+                    _                                  => None,
+                }
+            })
+            .collect()
+    });
+
+    mounts.iter()
+        .filter(|&&(ref mount_point, _)| {
+            mount_point != "/" && path.starts_with(mount_point.as_str()) &&
+                path[mount_point.len() ..].starts_with('/')
+        })
+        .max_by_key(|&&(ref mount_point, _)| mount_point.len())
+        .map_or(false, |&(_, in_memory)| in_memory)
+}
+
+// Drive letters of removable drives, by their root
+#[cfg(windows)]
+static REMOVABLE: OnceLock<::std::sync::Mutex<::std::collections::HashMap<String, bool>>> = OnceLock::new();
+
+#[cfg(windows)]
+fn removable_drive(path: &str) -> bool {
+    use winapi::um::fileapi::GetDriveTypeW;
+    use winapi::um::winbase::DRIVE_REMOVABLE;
+
+    // `X:\`, UNC and device paths aren't on a drive of their own
+    let root = match path.get(.. 3) {
+        Some(root) if root.ends_with(":\\") => root.to_uppercase(),
+        _                                   => return false,
+    };
+    let drives = REMOVABLE.get_or_init(Default::default);
+    let mut drives = drives.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    *drives.entry(root.clone()).or_insert_with(|| {
+        let wide: Vec<u16> = root.encode_utf16().chain(Some(0)).collect();
+        unsafe { GetDriveTypeW(wide.as_ptr()) == DRIVE_REMOVABLE }
+    })
+}
@@ -0,0 +1,148 @@
+// STIX 2.1 bundles of the detections of a scan, for threat intelligence platforms. Each detection is an
+// observed-data object referring to the process, its executable and the directory it is in.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{SecondsFormat, Utc};
+use serde_json::Value;
+use uuid::Uuid;
+
+use hash;
+use report;
+use sarif;
+use types::ScanReport;
+
+// Namespace of the identifiers of cyber-observable objects, from the STIX 2.1 specification
+const SCO_NAMESPACE: Uuid = Uuid::from_u128(0x00abedb4_aa42_466c_9c01_fed23315a9b7);
+
+pub fn write<W: Write>(out: &mut W, report: &ScanReport) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &bundle(report))?;
+    writeln!(out)
+}
+
+pub fn bundle(report: &ScanReport) -> Value {
+    let host     = report.host.clone().unwrap_or_else(report::hostname);
+    let now      = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+    let identity = format!("identity--{}", Uuid::new_v4());
+
+    let mut objects = vec![json!({
+        "type":           "identity",
+        "spec_version":   "2.1",
+        "id":             identity,
+        "created":        now,
+        "modified":       now,
+        "name":           format!("BONOMEN on {}", host),
+        "identity_class": "system",
+    })];
+    let mut seen = HashSet::new();
+    for c in &report.detections {
+        let mut refs = Vec::new();
+        let image = file(&c.exe_path, c.exe_info.is_some(), &mut objects, &mut seen, &mut refs);
+        if c.pid != 0 {
+            let mut process = json!({
+                "type":         "process",
+                "spec_version": "2.1",
+                // Processes have no identifying properties, their identifiers are random
+                "id":           format!("process--{}", Uuid::new_v4()),
+                "pid":          c.pid,
+            });
+            if let Some(image) = image {
+                process["image_ref"] = json!(image);
+            }
+            if let Some(started) = c.started {
+                let started = started.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true);
+                process["created_time"] = json!(started);
+            }
+            refs.insert(0, process["id"].clone());
+            objects.push(process);
+        }
+        if !c.module.is_empty() {
+            file(&c.module, c.exe_info.is_some(), &mut objects, &mut seen, &mut refs);
+        }
+        if refs.is_empty() {
+            continue;
+        }
+
+        objects.push(json!({
+            "type":                "observed-data",
+            "spec_version":        "2.1",
+            "id":                  format!("observed-data--{}", Uuid::new_v4()),
+            "created_by_ref":      identity,
+            "created":             now,
+            "modified":            now,
+            "first_observed":      now,
+            "last_observed":       now,
+            "number_observed":     1,
+            "object_refs":         refs,
+            "labels":              ["bonomen", c.reason],
+            "x_bonomen_host":      host,
+            "x_bonomen_name":      c.name,
+            "x_bonomen_reason":    c.reason,
+            "x_bonomen_critical":  c.crit_name,
+            "x_bonomen_severity":  c.severity.name(),
+            "x_bonomen_distance":  c.distance,
+            "x_bonomen_threshold": c.threshold,
+            "x_bonomen_message":   sarif::message(c),
+        }));
+    }
+
+    json!({
+        "type":    "bundle",
+        "id":      format!("bundle--{}", Uuid::new_v4()),
+        "objects": objects,
+    })
+}
+
+// Add the file and directory objects of `path`, once each, and return the identifier of the file.
+// Files are hashed when they are on this machine.
+fn file(path   : &str,
+        local  : bool,
+        objects: &mut Vec<Value>,
+        seen   : &mut HashSet<String>,
+        refs   : &mut Vec<Value>) -> Option<String> {
+    let name = Path::new(path).file_name()?.to_string_lossy().into_owned();
+    let mut contributing = json!({ "name": name });
+    let mut directory_id = None;
+    if let Some(parent) = Path::new(path).parent().map(|parent| parent.to_string_lossy().into_owned()) {
+        let directory = sco("directory", &json!({ "path": parent }));
+        if seen.insert(directory["id"].as_str().unwrap_or("").to_string()) {
+            objects.push(directory.clone());
+        }
+        contributing["parent_directory_ref"] = directory["id"].clone();
+        directory_id = Some(directory["id"].clone());
+    }
+    if local {
+        if let Ok(sha256) = hash::sha256_file(path) {
+            contributing["hashes"] = json!({ "SHA-256": sha256 });
+        }
+    }
+
+    let file = sco("file", &contributing);
+    let id   = file["id"].as_str().unwrap_or("").to_string();
+    if seen.insert(id.clone()) {
+        objects.push(file);
+    }
+    refs.push(json!(id));
+    refs.extend(directory_id);
+
+    Some(id)
+}
+
+// Cyber-observable object of `kind` with the properties identifying it, its identifier derived from them
+fn sco(kind: &str, contributing: &Value) -> Value {
+    // serde_json sorts keys, as the canonical form the identifier is computed over requires
+    let id = Uuid::new_v5(&SCO_NAMESPACE, contributing.to_string().as_bytes());
+
+    let mut object = json!({
+        "type":         kind,
+        "spec_version": "2.1",
+        "id":           format!("{}--{}", kind, id),
+    });
+    if let (Some(object), Some(properties)) = (object.as_object_mut(), contributing.as_object()) {
+        object.extend(properties.clone());
+    }
+
+    object
+}
@@ -0,0 +1,97 @@
+// Running as a supervised systemd service: unit file generation and the sd_notify protocol
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+// Unit running `monitor` with the given arguments, restarted by systemd if it stops
+// pinging the watchdog for `watchdog` seconds
+pub fn unit(exe: &Path, monitor_args: &[String], watchdog: u64) -> String {
+    let mut exec_start = quote(&exe.to_string_lossy());
+    exec_start.push_str(" monitor");
+    for arg in monitor_args {
+        exec_start.push(' ');
+        exec_start.push_str(&quote(arg));
+    }
+
+    format!("[Unit]\n\
+             Description=BONOMEN critical process impersonation monitor\n\
+             After=local-fs.target network.target\n\
+             \n\
+             [Service]\n\
+             Type=notify\n\
+             ExecStart={}\n\
+             ExecReload=/bin/kill -HUP $MAINPID\n\
+             WatchdogSec={}\n\
+             Restart=on-failure\n\
+             \n\
+             [Install]\n\
+             WantedBy=multi-user.target\n",
+            exec_start, watchdog)
+}
+
+// systemd splits ExecStart on whitespace, quote arguments that contain any
+fn quote(arg: &str) -> String {
+    if arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+pub fn install(path: &str, unit: &str) -> io::Result<()> {
+    File::create(path)?.write_all(unit.as_bytes())
+}
+
+// Send a state to the service manager, e.g. "READY=1" or "WATCHDOG=1".
+// Does nothing when not started by systemd.
+pub fn notify(state: &str) {
+    let socket_path = match env::var("NOTIFY_SOCKET") {
+        Ok(path) => path,
+        Err(_)   => return,
+    };
+
+    let sent = UnixDatagram::unbound().and_then(|socket| {
+        if socket_path.starts_with('@') {
+            send_abstract(&socket, &socket_path[1 ..], state)
+        } else {
+            socket.send_to(state.as_bytes(), &socket_path).map(|_| ())
+        }
+    });
+    if let Err(why) = sent {
+        debug!("sd_notify {} failed: {}", state, why);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn send_abstract(socket: &UnixDatagram, name: &str, state: &str) -> io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    let addr = SocketAddr::from_abstract_name(name.as_bytes())?;
+    socket.send_to_addr(state.as_bytes(), &addr).map(|_| ())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_abstract(_socket: &UnixDatagram, _name: &str, _state: &str) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "abstract sockets are only supported on Linux"))
+}
+
+// How often to ping the watchdog, half of the interval systemd expects
+pub fn watchdog_interval() -> Option<Duration> {
+    // The watchdog may be meant for another process of the service
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok() != Some(process::id()) {
+            return None;
+        }
+    }
+
+    env::var("WATCHDOG_USEC").ok()
+        .and_then(|usec| usec.parse::<u64>().ok())
+        .filter(|&usec| usec > 0)
+        .map(|usec| Duration::from_micros(usec / 2))
+}
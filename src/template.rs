@@ -0,0 +1,106 @@
+// Detection lines formatted by `--template`, e.g. "{pid} {name} {path} matched {crit} (d={distance})".
+// Placeholders name fields of the detection, `{{` and `}}` are literal braces and `\t` a tab.
+
+use chrono::Local;
+
+use types::Comparison;
+
+// Fields a template may use
+pub const FIELDS: &'static [&'static str] = &["pid", "name", "path", "crit", "distance", "threshold", "reason",
+                                              "severity", "score", "module", "owner", "container", "started", "age"];
+
+enum Part {
+    Text(String),
+    Field(&'static str),
+}
+
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    // Unknown placeholders and unbalanced braces are refused, a typo would print nothing
+    pub fn parse(text: &str) -> Result<Template, String> {
+        let mut parts   = Vec::new();
+        let mut literal = String::new();
+        let mut chars   = text.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                },
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                },
+                '\\' if chars.peek() == Some(&'t') => {
+                    chars.next();
+                    literal.push('\t');
+                },
+                '{'                                => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        return Err(format!("unterminated placeholder {{{}", name));
+                    }
+                    let field = FIELDS.iter().find(|&&field| field == name.trim())
+                        .ok_or_else(|| format!("unknown placeholder {{{}}}, use one of {}", name, FIELDS.join(", ")))?;
+                    if !literal.is_empty() {
+                        parts.push(Part::Text(literal.split_off(0)));
+                    }
+                    parts.push(Part::Field(field));
+                },
+                '}'                                => return Err(String::from("unmatched }, write }} for a brace")),
+                _                                  => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Text(literal));
+        }
+
+        Ok(Template { parts: parts })
+    }
+
+    // The line of a detection, fields it doesn't have are left empty
+    pub fn render(&self, c: &Comparison) -> String {
+        let mut line = String::new();
+        for part in &self.parts {
+            match *part {
+                Part::Text(ref text) => line.push_str(text),
+                Part::Field(field)   => line.push_str(&field_value(c, field)),
+            }
+        }
+
+        line
+    }
+}
+
+fn field_value(c: &Comparison, field: &str) -> String {
+    match field {
+        "pid"       => c.pid.to_string(),
+        "name"      => c.name.clone(),
+        "path"      => c.exe_path.clone(),
+        "crit"      => c.crit_name.clone(),
+        "distance"  => c.distance.to_string(),
+        "threshold" => c.threshold.to_string(),
+        "reason"    => c.reason.to_string(),
+        "severity"  => c.severity.name().to_string(),
+        "score"     => c.score.map(|score| score.to_string()).unwrap_or_default(),
+        "module"    => c.module.clone(),
+        "owner"     => c.owner.clone().unwrap_or_default(),
+        "container" => c.container.as_ref().map(|container| container.to_string()).unwrap_or_default(),
+        "started"   => c.started.map(|started| started.to_rfc3339()).unwrap_or_default(),
+        "age"       => c.started.map(|started| ::format_age((Local::now() - started).num_seconds()))
+            .unwrap_or_default(),
+        _           => String::new(),
+    }
+}
@@ -0,0 +1,93 @@
+// Counts of the detections recorded with --db for `bonomen trends`: by reason, by critical process and by day or
+// week, telling what scans catch and how that changes over time.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde_json::Value;
+
+use history::Record;
+
+pub const PERIODS: &'static [&'static str] = &["day", "week"];
+
+pub struct Trends {
+    pub detections : usize,
+    pub hosts      : usize,
+    // Most frequent first
+    pub reasons    : Vec<(String, usize)>,
+    // Critical processes imitated, None for detections not tied to one such as deleted executables
+    pub critical   : Vec<(Option<String>, usize)>,
+    // First day of every period from the first detection to the last, oldest first, those without any included
+    pub periods    : Vec<(NaiveDate, usize)>,
+    pub weekly     : bool,
+}
+
+// Most frequent first, then by name
+fn ranked<K: Ord>(counts: HashMap<K, usize>) -> Vec<(K, usize)> {
+    let mut ranked: Vec<(K, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    ranked
+}
+
+// Weeks start on Monday, as ISO 8601 has it
+fn period_start(date: NaiveDate, weekly: bool) -> NaiveDate {
+    if weekly { date - Duration::days(date.weekday().num_days_from_monday() as i64) } else { date }
+}
+
+// Trends of `records`, counted in local days or weeks
+pub fn count(records: &[Record], weekly: bool) -> Trends {
+    let mut reasons  = HashMap::new();
+    let mut critical = HashMap::new();
+    let mut periods  = HashMap::new();
+    let mut hosts    = HashSet::new();
+
+    for record in records {
+        *reasons.entry(record.reason.clone()).or_insert(0) += 1;
+        let crit_name = if record.crit_name.is_empty() { None } else { Some(record.crit_name.clone()) };
+        *critical.entry(crit_name).or_insert(0) += 1;
+        *periods.entry(period_start(record.time.date_naive(), weekly)).or_insert(0) += 1;
+        hosts.insert(record.host.as_str());
+    }
+
+    let step      = Duration::days(if weekly { 7 } else { 1 });
+    let mut every = Vec::new();
+    if let (Some(&first), Some(&last)) = (periods.keys().min(), periods.keys().max()) {
+        let mut start = first;
+        while start <= last {
+            every.push((start, periods.get(&start).cloned().unwrap_or(0)));
+            start += step;
+        }
+    }
+
+    Trends {
+        detections: records.len(),
+        hosts:      hosts.len(),
+        reasons:    ranked(reasons),
+        critical:   ranked(critical),
+        periods:    every,
+        weekly:     weekly,
+    }
+}
+
+impl Trends {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "detections":  self.detections,
+            "hosts":       self.hosts,
+            "period":      if self.weekly { "week" } else { "day" },
+            "by_reason":   self.reasons.iter().map(|&(ref reason, count)| json!({
+                "reason":     reason,
+                "detections": count,
+            })).collect::<Vec<_>>(),
+            "by_critical": self.critical.iter().map(|&(ref crit_name, count)| json!({
+                "critical":   crit_name,
+                "detections": count,
+            })).collect::<Vec<_>>(),
+            "by_period":   self.periods.iter().map(|&(start, count)| json!({
+                "start":      start.format("%Y-%m-%d").to_string(),
+                "detections": count,
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
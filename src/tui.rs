@@ -0,0 +1,384 @@
+// Interactive dashboard: a live table of the scanned processes and their closest critical
+// process, the detections, and the details of a selected process
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+use clap::ArgMatches;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use distcache::DistanceCache;
+use error::ScanError;
+use output::Output;
+use types::{Comparison, ScanReport, Severity};
+
+const KEYS: &'static str = " q quit | ↑↓ PgUp PgDn move | Tab switch pane | Enter details | r rescan";
+
+// A scanned process, its detections and its comparisons, closest first
+struct Process {
+    pid:         u32,
+    name:        String,
+    exe_path:    String,
+    detections:  Vec<Comparison>,
+    comparisons: Vec<Comparison>,
+}
+
+impl Process {
+    fn closest(&self) -> Option<&Comparison> {
+        self.detections.first().or(self.comparisons.first())
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        self.detections.iter().map(|detection| detection.severity).max()
+    }
+}
+
+#[derive(PartialEq)]
+enum Focus {
+    Processes,
+    Detections,
+}
+
+struct Dashboard {
+    processes:  Vec<Process>,
+    // Process and detection index of every detection, in the order listed
+    detections: Vec<(usize, usize)>,
+    table:      TableState,
+    list:       ListState,
+    focus:      Focus,
+    details:    bool,
+    status:     String,
+    error:      Option<String>,
+}
+
+impl Dashboard {
+    fn new() -> Dashboard {
+        Dashboard {
+            processes:  Vec::new(),
+            detections: Vec::new(),
+            table:      TableState::default(),
+            list:       ListState::default(),
+            focus:      Focus::Processes,
+            details:    false,
+            status:     String::new(),
+            error:      None,
+        }
+    }
+
+    // Replace the processes with those of a new scan, keeping the selection on the same process
+    fn update(&mut self, report: ScanReport) {
+        let selected = self.selected().map(|process| (process.pid, process.name.clone()));
+
+        self.status = format!("{} processes, {} detections, {} skipped, scanned at {} in {:.3}s",
+                              report.scanned, report.detections.len(), report.skipped,
+                              Local::now().format("%H:%M:%S"), ::report::seconds(report.elapsed));
+        self.error  = None;
+
+        let mut processes: BTreeMap<(u32, String, String), Process> = BTreeMap::new();
+        for (comparison, detected) in report.detections.into_iter().map(|c| (c, true))
+            .chain(report.comparisons.into_iter().map(|c| (c, false))) {
+            let process = processes.entry((comparison.pid, comparison.name.clone(), comparison.exe_path.clone()))
+                .or_insert_with(|| Process {
+                    pid:         comparison.pid,
+                    name:        comparison.name.clone(),
+                    exe_path:    comparison.exe_path.clone(),
+                    detections:  Vec::new(),
+                    comparisons: Vec::new(),
+                });
+            if detected {
+                process.detections.push(comparison);
+            } else {
+                process.comparisons.push(comparison);
+            }
+        }
+
+        let mut processes: Vec<Process> = processes.into_values().collect();
+        for process in processes.iter_mut() {
+            process.detections.sort_by(|a, b| b.severity.cmp(&a.severity).then(by_distance(a, b)));
+            process.comparisons.sort_by(by_distance);
+        }
+        // Suspicious processes first, the most severe on top, then the closest names
+        processes.sort_by(|a, b| {
+            b.severity().cmp(&a.severity())
+                .then_with(|| match (a.closest(), b.closest()) {
+                    (Some(a), Some(b)) => by_distance(a, b),
+                    _                  => Ordering::Equal,
+                })
+                .then(a.pid.cmp(&b.pid))
+        });
+
+        self.detections = processes.iter().enumerate()
+            .flat_map(|(index, process)| (0 .. process.detections.len()).map(move |detection| (index, detection)))
+            .collect();
+        self.processes  = processes;
+
+        let index = selected
+            .and_then(|(pid, name)| self.processes.iter().position(|p| p.pid == pid && p.name == name))
+            .unwrap_or(0);
+        self.table.select(if self.processes.is_empty() { None } else { Some(index) });
+        self.list.select(if self.detections.is_empty() {
+            None
+        } else {
+            Some(self.list.selected().unwrap_or(0).min(self.detections.len() - 1))
+        });
+    }
+
+    fn selected(&self) -> Option<&Process> {
+        match self.focus {
+            Focus::Processes  => self.table.selected().and_then(|index| self.processes.get(index)),
+            Focus::Detections => self.list.selected()
+                .and_then(|index| self.detections.get(index))
+                .and_then(|&(process, _)| self.processes.get(process)),
+        }
+    }
+
+    fn scroll(&mut self, by: isize) {
+        let (len, selected) = match self.focus {
+            Focus::Processes  => (self.processes.len(), self.table.selected()),
+            Focus::Detections => (self.detections.len(), self.list.selected()),
+        };
+        if len == 0 {
+            return;
+        }
+
+        let index = (selected.unwrap_or(0) as isize).saturating_add(by).max(0).min(len as isize - 1) as usize;
+        match self.focus {
+            Focus::Processes  => self.table.select(Some(index)),
+            Focus::Detections => self.list.select(Some(index)),
+        }
+    }
+}
+
+fn by_distance(a: &Comparison, b: &Comparison) -> Ordering {
+    a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal)
+}
+
+fn severity_color(severity: Severity) -> Color {
+    match severity {
+        Severity::Critical => Color::LightRed,
+        Severity::High     => Color::Red,
+        Severity::Medium   => Color::Yellow,
+        Severity::Low      => Color::Cyan,
+    }
+}
+
+fn terminal_error(why: io::Error) -> ScanError {
+    ScanError::Config(format!("terminal error: {}", why))
+}
+
+// Scan every `interval` and on demand until the user quits
+pub fn run(matches: &ArgMatches, interval: Duration) -> Result<(), ScanError> {
+    let mut setup = ::scan_setup(matches, None, Some(&Arc::new(DistanceCache::new())), None, None)?;
+    // Every comparison is kept to show the closest critical process of each process
+    setup.options.silent      = true;
+    setup.options.report_dist = Some(f64::INFINITY);
+    // Every rescan would suspend the same processes again
+    if setup.options.suspend {
+        warn!("Processes aren't suspended from the dashboard, --suspend is ignored");
+        setup.options.suspend = false;
+    }
+
+    let mut output    = Output::new(false);
+    let mut dashboard = Dashboard::new();
+    dashboard.update(scan(matches, &setup, &mut output)?);
+
+    let mut terminal = ratatui::try_init().map_err(terminal_error)?;
+    let result       = event_loop(&mut terminal, &mut dashboard, matches, &setup, &mut output, interval);
+    ratatui::restore();
+
+    result
+}
+
+fn scan(matches: &ArgMatches, setup: &::ScanSetup, output: &mut Output) -> Result<ScanReport, ScanError> {
+    let start = Instant::now();
+    let mut r = ::check_processes(matches, setup, output)?;
+    r.elapsed = start.elapsed();
+
+    Ok(r)
+}
+
+fn event_loop(terminal : &mut DefaultTerminal,
+              dashboard: &mut Dashboard,
+              matches  : &ArgMatches,
+              setup    : &::ScanSetup,
+              output   : &mut Output,
+              interval : Duration) -> Result<(), ScanError> {
+    let mut next_scan = Instant::now() + interval;
+
+    loop {
+        terminal.draw(|frame| draw(frame, dashboard)).map_err(terminal_error)?;
+
+        let now     = Instant::now();
+        let timeout = if next_scan > now { next_scan - now } else { Duration::from_secs(0) };
+        let mut rescan = timeout == Duration::from_secs(0);
+
+        if event::poll(timeout).map_err(terminal_error)? {
+            if let Event::Key(key) = event::read().map_err(terminal_error)? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q')                     => return Ok(()),
+                    KeyCode::Esc if dashboard.details      => dashboard.details = false,
+                    KeyCode::Esc                           => return Ok(()),
+                    KeyCode::Enter                         => dashboard.details = !dashboard.details,
+                    KeyCode::Tab                           => {
+                        dashboard.focus = if dashboard.focus == Focus::Processes {
+                            Focus::Detections
+                        } else {
+                            Focus::Processes
+                        };
+                    },
+                    KeyCode::Up | KeyCode::Char('k')       => dashboard.scroll(-1),
+                    KeyCode::Down | KeyCode::Char('j')     => dashboard.scroll(1),
+                    KeyCode::PageUp                        => dashboard.scroll(-20),
+                    KeyCode::PageDown                      => dashboard.scroll(20),
+                    KeyCode::Home | KeyCode::Char('g')     => dashboard.scroll(isize::MIN),
+                    KeyCode::End | KeyCode::Char('G')      => dashboard.scroll(isize::MAX),
+                    KeyCode::Char('r')                     => rescan = true,
+                    _                                      => (),
+                }
+            }
+        }
+
+        if rescan {
+            // A failed scan keeps the last results on screen
+            match scan(matches, setup, output) {
+                Ok(report) => dashboard.update(report),
+                Err(why)   => dashboard.error = Some(why.to_string()),
+            }
+            next_scan = Instant::now() + interval;
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, dashboard: &mut Dashboard) {
+    let [header, body, footer] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)])
+        .areas(frame.area());
+    let [processes, detections] = Layout::vertical([Constraint::Percentage(65), Constraint::Percentage(35)])
+        .areas(body);
+
+    let status = match dashboard.error {
+        Some(ref why) => Line::styled(format!(" BONOMEN | {}", why), Style::default().fg(Color::Red)),
+        None          => Line::from(format!(" BONOMEN | {}", dashboard.status)),
+    };
+    frame.render_widget(Paragraph::new(status).style(Style::default().add_modifier(Modifier::BOLD)), header);
+    frame.render_widget(Paragraph::new(KEYS).style(Style::default().add_modifier(Modifier::DIM)), footer);
+
+    draw_processes(frame, dashboard, processes);
+    draw_detections(frame, dashboard, detections);
+
+    if dashboard.details {
+        draw_details(frame, dashboard);
+    }
+}
+
+fn pane_block(title: &'static str, focused: bool) -> Block<'static> {
+    let block = Block::bordered().title(title);
+    if focused { block.border_style(Style::default().fg(Color::Green)) } else { block }
+}
+
+fn draw_processes(frame: &mut Frame, dashboard: &mut Dashboard, area: Rect) {
+    let rows: Vec<Row> = dashboard.processes.iter().map(|process| {
+        let closest = process.closest();
+        let row     = Row::new(vec![
+            Cell::from(process.pid.to_string()),
+            Cell::from(process.name.clone()),
+            Cell::from(closest.map(|c| c.crit_name.clone()).unwrap_or_default()),
+            Cell::from(closest.map(|c| c.distance.to_string()).unwrap_or_default()),
+            Cell::from(closest.map(|c| c.threshold.to_string()).unwrap_or_default()),
+            Cell::from(process.severity().map(|s| s.name()).unwrap_or("")),
+            Cell::from(process.exe_path.clone()),
+        ]);
+        match process.severity() {
+            Some(severity) => row.style(Style::default().fg(severity_color(severity))),
+            None           => row,
+        }
+    }).collect();
+
+    let widths = [Constraint::Length(8), Constraint::Length(20), Constraint::Length(20), Constraint::Length(9),
+                  Constraint::Length(10), Constraint::Length(9), Constraint::Min(10)];
+    let table = Table::new(rows, widths)
+        .header(Row::new(vec!["PID", "Name", "Closest match", "Distance", "Threshold", "Severity", "Path"])
+                .style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(pane_block("Processes", dashboard.focus == Focus::Processes))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    frame.render_stateful_widget(table, area, &mut dashboard.table);
+}
+
+fn describe(detection: &Comparison) -> String {
+    format!("[{}] {}: {} ({}) <-> {}, distance {}, {}", detection.severity.name(), detection.reason,
+            detection.name, detection.pid, detection.crit_name, detection.distance, detection.exe_path)
+}
+
+fn draw_detections(frame: &mut Frame, dashboard: &mut Dashboard, area: Rect) {
+    let items: Vec<ListItem> = dashboard.detections.iter().map(|&(process, detection)| {
+        let detection = &dashboard.processes[process].detections[detection];
+        ListItem::new(describe(detection)).style(Style::default().fg(severity_color(detection.severity)))
+    }).collect();
+
+    let list = List::new(items)
+        .block(pane_block("Detections", dashboard.focus == Focus::Detections))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+    frame.render_stateful_widget(list, area, &mut dashboard.list);
+}
+
+// Everything known about the selected process, over the panes
+fn draw_details(frame: &mut Frame, dashboard: &Dashboard) {
+    let process = match dashboard.selected() {
+        Some(process) => process,
+        None          => return,
+    };
+
+    let mut lines = vec![
+        Line::from(format!("PID:        {}", process.pid)),
+        Line::from(format!("Name:       {}", process.name)),
+        Line::from(format!("Executable: {}", process.exe_path)),
+        Line::from(""),
+    ];
+    if process.detections.is_empty() {
+        lines.push(Line::from("No detections"));
+    }
+    for detection in &process.detections {
+        lines.push(Line::styled(describe(detection), Style::default().fg(severity_color(detection.severity))));
+        if !detection.module.is_empty() {
+            lines.push(Line::from(format!("    module: {}", detection.module)));
+        }
+        if let Some(ref container) = detection.container {
+            lines.push(Line::from(format!("    in {}", container)));
+        }
+        if let Some(ref exe_info) = detection.exe_info {
+            lines.push(Line::from(format!("    executable: {}", exe_info)));
+        }
+        if detection.suspended {
+            lines.push(Line::from("    suspended"));
+        }
+        for connection in &detection.connections {
+            lines.push(Line::from(format!("    connection: {}", connection)));
+        }
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::styled("Closest critical processes", Style::default().add_modifier(Modifier::BOLD)));
+    for comparison in process.comparisons.iter().take(10) {
+        lines.push(Line::from(format!("    {:<24} distance {:<8} threshold {}",
+                                      comparison.crit_name, comparison.distance, comparison.threshold)));
+    }
+
+    let area = frame.area();
+    let area = Rect::new(area.width / 10, area.height / 6, area.width * 8 / 10, area.height * 2 / 3);
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines)
+                        .block(Block::bordered().title("Details (Esc to close)"))
+                        .wrap(Wrap { trim: false }), area);
+}
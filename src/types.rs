@@ -1,11 +1,422 @@
+#[derive(Clone)]
 pub struct ProcProps {
-    pub name     : ::std::string::String,
-    pub threshold: u32,
-    pub whitelist: Vec<::std::string::String>,
+    pub name         : ::std::string::String,
+    pub threshold    : Threshold,
+    pub whitelist    : Vec<::whitelist::Entry>,
+    // `container:PATH` and `container=SCOPE:PATH` entries, only trusted inside containers
+    pub in_containers: Vec<::whitelist::ContainerEntry>,
+    // Base severity of detections against this process, `severity.NAME = LEVEL` settings
+    pub severity     : Severity,
+    // How many may run at once, `instances.NAME = N` settings, unlimited when unset
+    pub instances    : Option<usize>,
+    // Accounts it may run as, `user.NAME = USER` settings, any when empty
+    pub users        : Vec<::std::string::String>,
+    // Inclusive ranges of threads and open handles, `threads.NAME` and `handles.NAME` settings
+    pub threads      : Option<(u32, u32)>,
+    pub handles      : Option<(u32, u32)>,
 }
 
-#[cfg(windows)]
-pub struct WinProc {
-    pub name:     ::std::string::String,
+// How alarming a detection is, ordered from least to most
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+pub const SEVERITIES: &'static [&'static str] = &["low", "medium", "high", "critical"];
+
+impl Severity {
+    pub fn from_name(name: &str) -> Option<Severity> {
+        match name {
+            "low"      => Some(Severity::Low),
+            "medium"   => Some(Severity::Medium),
+            "high"     => Some(Severity::High),
+            "critical" => Some(Severity::Critical),
+            _          => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Severity::Low      => "low",
+            Severity::Medium   => "medium",
+            Severity::High     => "high",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+// How process names are normalized before being compared
+#[derive(Clone, Copy, PartialEq)]
+pub enum NameMatching {
+    Exact,
+    // Windows file names are case insensitive, `SvcHost.EXE` is `svchost.exe`
+    IgnoreCase,
+    // Also leave a trailing `.exe` out, `svchost` is `svchost.exe`
+    IgnoreExtension,
+}
+
+impl NameMatching {
+    pub fn from_name(name: &str) -> Option<NameMatching> {
+        match name {
+            "exact"            => Some(NameMatching::Exact),
+            "ignore-case"      => Some(NameMatching::IgnoreCase),
+            "ignore-extension" => Some(NameMatching::IgnoreExtension),
+            _                  => None,
+        }
+    }
+
+    // Names are compared as is on Unix, case insensitively on Windows
+    pub fn platform_default() -> NameMatching {
+        if cfg!(windows) { NameMatching::IgnoreCase } else { NameMatching::Exact }
+    }
+
+    pub fn normalize(&self, name: &str) -> ::std::string::String {
+        match *self {
+            NameMatching::Exact           => name.to_string(),
+            NameMatching::IgnoreCase      => name.to_lowercase(),
+            NameMatching::IgnoreExtension => {
+                let name = name.to_lowercase();
+                if name.ends_with(".exe") { name[.. name.len() - 4].to_string() } else { name }
+            },
+        }
+    }
+}
+
+// How executable paths are compared with whitelisted paths and directories
+#[derive(Clone, Copy)]
+pub struct PathMatching {
+    // Windows paths are case insensitive, `C:\WINDOWS\system32` is `C:\Windows\System32`
+    pub ignore_case: bool,
+    // Edits tolerated in the directories of a path, never in its file name
+    pub distance   : u32,
+}
+
+impl PathMatching {
+    // Paths are compared as is on Unix, case insensitively on Windows
+    pub fn platform_default() -> PathMatching {
+        PathMatching { ignore_case: cfg!(windows), distance: 0 }
+    }
+}
+
+// Maximum distance between a process name and a critical process name
+#[derive(Clone)]
+pub enum Threshold {
+    // In the unit of the similarity algorithm
+    Absolute(u32),
+    // Percentage of the critical process name length, e.g. `15%`
+    Percent(u32),
+}
+
+impl Threshold {
+    // Largest distance still considered suspicious for `crit_name`.
+    // `normalized` algorithms already measure distances as percentages.
+    pub fn limit(&self, crit_name: &str, normalized: bool) -> f64 {
+        match *self {
+            Threshold::Absolute(n)              => n as f64,
+            Threshold::Percent(p) if normalized => p as f64,
+            Threshold::Percent(p)               => p as f64 * crit_name.chars().count() as f64 / 100.0,
+        }
+    }
+}
+
+// Contents of a critical processes file
+#[derive(Clone)]
+pub struct Config {
+    pub procs     : Vec<ProcProps>,
+    pub algorithm : Option<::std::string::String>,
+    // `ignore = NAME` settings, known benign running processes
+    pub ignore    : Vec<::whitelist::Entry>,
+    // `severity.NAME = LEVEL` settings, applied once the whole file is read
+    pub severities: Vec<(::std::string::String, Severity)>,
+    // `instances.NAME = N` settings, applied along with the severities
+    pub instances : Vec<(::std::string::String, usize)>,
+    // `user.NAME = USER, ...` settings, applied along with the severities
+    pub users     : Vec<(::std::string::String, Vec<::std::string::String>)>,
+    // `threads.NAME = MIN-MAX` and `handles.NAME = MIN-MAX` settings, applied along with the severities
+    pub threads   : Vec<(::std::string::String, (u32, u32))>,
+    pub handles   : Vec<(::std::string::String, (u32, u32))>,
+    // `kernel_module.NAME = THRESHOLD` settings, the kernel modules expected to be loaded
+    pub kernel_modules: Vec<(::std::string::String, Threshold)>,
+    // `names = MATCHING` setting
+    pub names     : Option<NameMatching>,
+    // `paths = exact|ignore-case` and `path_distance = N` settings
+    pub ignore_path_case: Option<bool>,
+    pub path_distance   : Option<u32>,
+    // `rule.NAME = on|off` settings, the last one of a rule wins
+    pub rules     : Vec<(::std::string::String, bool)>,
+    // `weight.NAME = N` settings, the last one of a rule wins
+    pub weights   : Vec<(::std::string::String, u32)>,
+    // `alert_score = N` setting
+    pub alert_score: Option<u32>,
+    // `recent_start = DURATION` and `recent_uptime = DURATION` settings
+    pub recent_start : Option<::std::time::Duration>,
+    pub recent_uptime: Option<::std::time::Duration>,
+    // `smtp.NAME = VALUE` settings, emailing detections
+    pub mail      : ::mail::Settings,
+    // Every file read, included ones too
+    pub files     : Vec<::std::path::PathBuf>,
+    // `--profile`, whose `[profile.NAME]` sections apply
+    pub profile   : Option<::std::string::String>,
+    // Every `[profile.NAME]` section read
+    pub profiles  : Vec<::std::string::String>,
+}
+
+// A running process, collected on this system or read from a captured listing.
+// An empty `exe_path` means the executable is unknown, as do the other missing fields.
+#[derive(Clone)]
+pub struct ProcessInfo {
+    pub pid        : u32,
+    pub name       : ::std::string::String,
+    pub exe_path   : ::std::string::String,
+    // Set when the executable is gone while the process runs, "deleted-exe" or "unresolved-exe"
+    pub exe_problem: Option<&'static str>,
+    // Set when the executable couldn't be read without privileges, Unix only
+    pub exe_denied : bool,
+    pub ppid       : Option<u32>,
+    // UID of the owner, Unix only
+    pub user       : Option<u32>,
+    // Name of the account the process runs as, e.g. `root` or `NT AUTHORITY\SYSTEM`
+    pub owner      : Option<::std::string::String>,
+    // Number of threads and of open handles, Windows only
+    pub threads    : Option<u32>,
+    pub handles    : Option<u32>,
+    pub cmdline    : Option<Vec<::std::string::String>>,
+    pub started    : Option<::chrono::DateTime<::chrono::Local>>,
+}
+
+// Result of comparing one running process against one critical process
+pub struct Comparison {
+    pub pid        : u32,
+    pub name       : ::std::string::String,
+    pub exe_path   : ::std::string::String,
+    pub crit_name  : ::std::string::String,
+    pub distance   : f64,
+    pub threshold  : f64,
+    pub suspicious : bool,
+    pub reason     : &'static str,
+    pub severity   : Severity,
+    // Module loaded from a non-standard path, only set for `module` detections
+    pub module     : ::std::string::String,
+    // Network connections of the process, only gathered for detections with `--connections`
+    pub connections: Vec<Connection>,
+    // Environment variables of the process, only gathered for detections with `--env`
+    pub environment: Vec<(::std::string::String, ::std::string::String)>,
+    // Container the process runs in, Linux only
+    pub container  : Option<Container>,
+    // Account the process runs as, when it was collected
+    pub owner      : Option<::std::string::String>,
+    // Metadata of the executable, only gathered for detections of local processes
+    pub exe_info   : Option<FileInfo>,
+    // YARA rules matching the executable, or module, of a detection with `--yara`
+    pub yara       : Vec<::std::string::String>,
+    // VirusTotal verdict on the executable of a detection with `--vt-api-key`
+    pub virustotal : Option<VtVerdict>,
+    // Stopped with `--suspend`, until `bonomen resume`
+    pub suspended  : bool,
+    // Rule, distance, threshold and whitelist entries behind a detection, only gathered with `--explain`
+    pub explanation: Vec<::std::string::String>,
+    // Weights of the rules finding the process suspicious added up, unset for files checked at rest
+    pub score      : Option<u32>,
+    // Start time of the process, when it was collected
+    pub started    : Option<::chrono::DateTime<::chrono::Local>>,
+}
+
+// Metadata of an executable file, owner and permissions on Unix, version resource on Windows
+pub struct FileInfo {
+    pub modified: Option<::chrono::DateTime<::chrono::Local>>,
+    pub owner   : Option<::std::string::String>,
+    pub group   : Option<::std::string::String>,
+    pub mode    : Option<u32>,
+    pub version : Option<::std::string::String>,
+    pub company : Option<::std::string::String>,
+}
+
+impl ::std::fmt::Display for FileInfo {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let mut parts = Vec::new();
+        match (&self.owner, &self.group) {
+            (&Some(ref owner), &Some(ref group)) => parts.push(format!("owner {}:{}", owner, group)),
+            (&Some(ref owner), &None)            => parts.push(format!("owner {}", owner)),
+            _                                    => (),
+        }
+        if let Some(mode) = self.mode {
+            parts.push(format!("mode {:04o}", mode & 0o7777));
+        }
+        if let Some(modified) = self.modified {
+            parts.push(format!("modified {}", modified.format("%Y-%m-%d %H:%M:%S")));
+        }
+        if let Some(ref version) = self.version {
+            parts.push(format!("version {}", version));
+        }
+        if let Some(ref company) = self.company {
+            parts.push(format!("company {}", company));
+        }
+
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+// VirusTotal verdict on an executable, no engines when VirusTotal never saw the file
+#[derive(Clone)]
+pub struct VtVerdict {
+    pub sha256   : ::std::string::String,
+    pub malicious: u32,
+    pub engines  : u32,
+}
+
+// A container found in the cgroup of a process, with its Kubernetes pod when it belongs to one
+#[derive(Clone)]
+pub struct Container {
+    pub id       : ::std::string::String,
+    pub runtime  : &'static str,
+    pub pod_uid  : Option<::std::string::String>,
+    pub pod      : Option<::std::string::String>,
+    pub namespace: Option<::std::string::String>,
+}
+
+impl Container {
+    // First 12 characters of the ID, as printed by docker and crictl
+    pub fn short_id(&self) -> &str {
+        &self.id[.. ::std::cmp::min(12, self.id.len())]
+    }
+}
+
+impl ::std::fmt::Display for Container {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match (&self.namespace, &self.pod, &self.pod_uid) {
+            (&Some(ref namespace), &Some(ref pod), _) => write!(f, "pod {}/{}, ", namespace, pod)?,
+            (_, &Some(ref pod), _)                    => write!(f, "pod {}, ", pod)?,
+            (_, _, &Some(ref pod_uid))                => write!(f, "pod {}, ", pod_uid)?,
+            _                                         => (),
+        }
+        write!(f, "{} container {}", self.runtime, self.short_id())
+    }
+}
+
+// An open socket of a process
+pub struct Connection {
+    pub proto : &'static str,
+    pub local : ::std::net::SocketAddr,
+    pub remote: ::std::net::SocketAddr,
+    pub state : &'static str,
+}
+
+impl ::std::fmt::Display for Connection {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{} {} -> {} {}", self.proto, self.local, self.remote, self.state)
+    }
+}
+
+// Options controlling how a scan runs and what it prints
+pub struct ScanOptions {
+    // Only print detections, one per line, without colors
+    pub quiet       : bool,
+    // Print nothing, the report is presented by the caller
+    pub silent      : bool,
+    // Keep non suspicious comparisons up to this distance in the report
+    pub report_dist : Option<f64>,
+    // Running processes excluded from any comparison
+    pub ignore      : Vec<::whitelist::Entry>,
+    // Detections below this severity are neither printed nor reported
+    pub min_severity: Severity,
+    // When not empty, only running processes with a matching name are checked
+    pub only        : Vec<::whitelist::Entry>,
+    // Inclusive range of checked PIDs
+    pub pid_range   : Option<(u32, u32)>,
+    // When not empty, only processes owned by one of these users are checked
+    #[cfg(unix)]
+    pub users       : Vec<u32>,
+    // List the network connections of suspicious processes
+    pub connections : bool,
+    // Environment variables shown for suspicious processes with `--env`
+    pub environ     : Option<::environ::Settings>,
+    // Format of detection lines given with `--template`, instead of the text or quiet output
+    pub template    : Option<::template::Template>,
+    // Inspect the modules loaded into processes carrying a critical name
+    pub modules     : bool,
+    // Look for commands in PATH that shadow or imitate critical processes
+    pub path_hijack : bool,
+    // Look for autostart entries starting programs named like critical processes
+    pub persistence : bool,
+    // Look for installed services named like critical processes or running look-alike programs
+    pub services    : bool,
+    // Expected kernel modules loaded ones are compared against, with `--kernel-modules`
+    pub kernel_modules: Option<Vec<(::std::string::String, Threshold)>>,
+    // Processes are those of this system, their executables can be inspected
+    pub local       : bool,
+    // Suspend the processes detected, for forensics
+    pub suspend     : bool,
+    // Tell why each detection is suspicious
+    pub explain     : bool,
+    // Files of installed packages, whitelisted when intact, with `--trust-packages`
+    #[cfg(target_os = "linux")]
+    pub packages    : Option<::packages::Database>,
+    // Rules matched against the executables of detections, with `--yara`
+    #[cfg(feature = "yara")]
+    pub yara        : Option<::yara::Rules>,
+    // Looks up the executables of detections, with `--vt-api-key`
+    pub virustotal  : Option<::virustotal::Client>,
+    // Normalization of process names before comparing them
+    pub names       : NameMatching,
+    // Comparison of executable paths with whitelisted ones
+    pub paths       : PathMatching,
+    // Processes already alerted on, their detections aren't printed again
+    pub suppress    : ::std::collections::HashSet<::dedup::Key>,
+    // Only these processes are checked, those started since the last check
+    pub pids        : Option<Vec<u32>>,
+    // Distances measured by previous scans of the monitor mode
+    pub distances   : Option<::std::sync::Arc<::distcache::DistanceCache>>,
+    // Closest critical processes logged per process in verbose output, 0 for all
+    pub top         : usize,
+    // Boot time of the system the processes run on, only known for local scans
+    pub booted      : Option<::chrono::DateTime<::chrono::Local>>,
+    // Processes started within the first duration, at least the second one after boot, are recent
+    pub recent      : (::chrono::Duration, ::chrono::Duration),
+    #[cfg(windows)]
+    pub verify_sigs : bool,
+    // Compare the PE headers in memory of critical processes with their executable
+    #[cfg(windows)]
+    pub hollowing   : bool,
+}
+
+// Everything observed during a single scan
+pub struct ScanReport {
+    // Scanned machine when it isn't the local one
+    pub host       : Option<::std::string::String>,
+    pub scanned    : usize,
+    pub skipped    : usize,
+    // Processes whose executable couldn't be read without privileges, only checked by name
+    pub denied     : Vec<(u32, ::std::string::String)>,
+    // Name comparisons performed, running processes times critical processes
+    pub compared   : usize,
+    pub elapsed    : ::std::time::Duration,
+    pub comparisons: Vec<Comparison>,
+    pub detections : Vec<Comparison>,
+    // Detections of processes already alerted on by a previous scan of the monitor mode
+    pub repeated   : Vec<Comparison>,
+    // Stopped by Ctrl-C, only `scanned` processes were checked
+    pub interrupted: bool,
+}
+
+impl ScanReport {
+    // Number of detections for each reason
+    pub fn detections_by_reason(&self) -> ::std::collections::BTreeMap<&'static str, usize> {
+        let mut counts = ::std::collections::BTreeMap::new();
+        for detection in &self.detections {
+            *counts.entry(detection.reason).or_insert(0) += 1;
+        }
+
+        counts
+    }
+}
+
+// A running process as recorded in a baseline
+pub struct BaselineEntry {
+    pub pid     : u32,
+    pub name    : ::std::string::String,
     pub exe_path: ::std::string::String,
+    pub hash    : Option<::std::string::String>,
 }
@@ -0,0 +1,85 @@
+// Download signed critical processes definitions and install them as the system-wide file
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+use error::ScanError;
+
+// Download the definitions at `url` and their detached ed25519 signature at `sig_url`
+// (hex encoded), check them against `public_key` and write them to `dest`.
+// Returns the number of critical processes installed.
+pub fn run(url: &str, sig_url: &str, public_key: &str, dest: &Path) -> Result<usize, ScanError> {
+    let key       = parse_key(public_key)?;
+    let procs     = download(url)?;
+    let signature = download(sig_url)?;
+
+    let signature = decode_hex(&String::from_utf8_lossy(&signature), 64)
+        .ok_or_else(|| ScanError::Update(format!("{}: expected a hex encoded ed25519 signature", sig_url)))?;
+    let mut sig_bytes = [0; 64];
+    sig_bytes.copy_from_slice(&signature);
+
+    if key.verify(&procs, &Signature::from_bytes(&sig_bytes)).is_err() {
+        return Err(ScanError::Update(format!("bad signature for {}", url)));
+    }
+
+    // Never install a file the scanner would refuse to load
+    let config = ::parse_procs(&procs[..], url)?;
+
+    install(&procs, dest).map_err(|why| ScanError::Update(format!("couldn't install {}: {}", dest.display(), why)))?;
+
+    Ok(config.procs.len())
+}
+
+pub fn parse_key(public_key: &str) -> Result<VerifyingKey, ScanError> {
+    let key_bytes = key_bytes(public_key, "public key")?;
+
+    VerifyingKey::from_bytes(&key_bytes).map_err(|why| ScanError::Config(format!("invalid public key: {}", why)))
+}
+
+// A key is given as 64 hex characters, or as a file holding them
+pub fn key_bytes(key: &str, what: &str) -> Result<[u8; 32], ScanError> {
+    let hex_key = if Path::new(key).is_file() {
+        let mut content = String::new();
+        File::open(key)
+            .and_then(|mut f| f.read_to_string(&mut content))
+            .map_err(|why| ScanError::Config(format!("couldn't read {}: {}", key, why)))?;
+        content
+    } else {
+        key.to_string()
+    };
+
+    let bytes = match decode_hex(&hex_key, 32) {
+        Some(bytes) => bytes,
+        None        => return Err(ScanError::Config(format!("the {} must be 32 hex encoded bytes", what))),
+    };
+    let mut key_bytes = [0; 32];
+    key_bytes.copy_from_slice(&bytes);
+
+    Ok(key_bytes)
+}
+
+pub fn decode_hex(text: &str, len: usize) -> Option<Vec<u8>> {
+    hex::decode(text.trim()).ok().filter(|bytes| bytes.len() == len)
+}
+
+fn download(url: &str) -> Result<Vec<u8>, ScanError> {
+    info!("Downloading {}", url);
+
+    ureq::get(url).call()
+        .and_then(|mut response| response.body_mut().read_to_vec())
+        .map_err(|why| ScanError::Update(format!("couldn't download {}: {}", url, why)))
+}
+
+// Write next to the destination then rename, so a scan never reads a partial file
+fn install(content: &[u8], dest: &Path) -> ::std::io::Result<()> {
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let tmp = dest.with_extension("tmp");
+    File::create(&tmp)?.write_all(content)?;
+    fs::rename(&tmp, dest)
+}
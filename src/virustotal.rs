@@ -0,0 +1,158 @@
+// VirusTotal lookups of the executables of detections with `--vt-api-key`: the SHA-256 of each is looked up
+// and the detection annotated with how many engines flag it. Answers are cached on disk for a day, and
+// requests spaced to stay within the rate of the API key.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde_json::{self, Value};
+use ureq;
+
+use types::VtVerdict;
+
+const API: &'static str = "https://www.virustotal.com/api/v3/files/";
+// Hours a cached answer stays valid, engines keep updating their verdicts
+const CACHE_HOURS: i64 = 24;
+// Requests per minute of public API keys
+pub const DEFAULT_RATE: u32 = 4;
+
+pub struct Client {
+    key  : String,
+    cache: Option<PathBuf>,
+    // Time between two requests
+    gap  : Duration,
+    state: Mutex<State>,
+}
+
+struct State {
+    verdicts: HashMap<String, (VtVerdict, DateTime<Utc>)>,
+    last    : Option<Instant>,
+    // Quota exhausted, no more requests until the next scan
+    limited : bool,
+}
+
+impl Client {
+    // `per_minute` requests at most, answers cached in `cache` when given
+    pub fn new(key: &str, cache: Option<PathBuf>, per_minute: u32) -> Client {
+        let verdicts = cache.as_ref().map(|cache| load(cache)).unwrap_or_default();
+
+        Client {
+            key:   key.to_string(),
+            cache: cache,
+            gap:   Duration::from_millis(60_000 / u64::from(per_minute.max(1))),
+            state: Mutex::new(State {
+                verdicts: verdicts,
+                last:     None,
+                limited:  false,
+            }),
+        }
+    }
+
+    // Verdict on the file of SHA-256 `sha256`, from the cache when recent enough
+    pub fn lookup(&self, sha256: &str) -> Result<VtVerdict, String> {
+        let mut state = self.state.lock().map_err(|_| String::from("lookup state poisoned"))?;
+        if let Some(&(ref verdict, checked)) = state.verdicts.get(sha256) {
+            if Utc::now().signed_duration_since(checked).num_hours() < CACHE_HOURS {
+                return Ok(verdict.clone());
+            }
+        }
+        if state.limited {
+            return Err(String::from("request quota exhausted"));
+        }
+        if let Some(wait) = state.last.and_then(|last| self.gap.checked_sub(last.elapsed())) {
+            debug!("waiting {}s for the VirusTotal rate limit", wait.as_secs());
+            thread::sleep(wait);
+        }
+        state.last = Some(Instant::now());
+
+        let verdict = match ureq::get(&format!("{}{}", API, sha256)).header("x-apikey", &self.key).call() {
+            Ok(mut response) => {
+                let body = response.body_mut().read_to_string().map_err(|why| why.to_string())?;
+                let doc: Value = serde_json::from_str(&body).map_err(|why| why.to_string())?;
+                let stats = &doc["data"]["attributes"]["last_analysis_stats"];
+                let count = |key: &str| stats[key].as_u64().unwrap_or(0) as u32;
+                VtVerdict {
+                    sha256:    sha256.to_string(),
+                    malicious: count("malicious"),
+                    engines:   ["malicious", "suspicious", "undetected", "harmless"].iter().map(|key| count(key)).sum(),
+                }
+            },
+            // Never submitted to VirusTotal
+            Err(ureq::Error::StatusCode(404)) => VtVerdict {
+                sha256:    sha256.to_string(),
+                malicious: 0,
+                engines:   0,
+            },
+            Err(ureq::Error::StatusCode(429)) => {
+                state.limited = true;
+                warn!("VirusTotal quota exhausted, no more lookups in this scan");
+                return Err(String::from("request quota exhausted"));
+            },
+            Err(why)                          => return Err(why.to_string()),
+        };
+
+        state.verdicts.insert(sha256.to_string(), (verdict.clone(), Utc::now()));
+        if let Some(ref cache) = self.cache {
+            if let Err(why) = save(cache, &state.verdicts) {
+                warn!("couldn't write the VirusTotal cache {}: {}", cache.display(), why);
+            }
+        }
+
+        Ok(verdict)
+    }
+}
+
+// Per-user cache file
+pub fn default_cache() -> Option<PathBuf> {
+    let base = if cfg!(windows) {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))
+    };
+
+    base.map(|base| base.join("bonomen").join("virustotal.json"))
+}
+
+fn load(path: &Path) -> HashMap<String, (VtVerdict, DateTime<Utc>)> {
+    let doc: Value = match fs::read_to_string(path).map(|text| serde_json::from_str(&text)) {
+        Ok(Ok(doc)) => doc,
+        Ok(Err(why)) => {
+            warn!("ignoring the invalid VirusTotal cache {}: {}", path.display(), why);
+            return HashMap::new();
+        },
+        Err(_)       => return HashMap::new(),
+    };
+
+    doc.as_object().into_iter().flat_map(|entries| entries.iter())
+        .filter_map(|(sha256, entry)| {
+            let checked = DateTime::parse_from_rfc3339(entry["checked"].as_str()?).ok()?.with_timezone(&Utc);
+            Some((sha256.clone(), (VtVerdict {
+                sha256:    sha256.clone(),
+                malicious: entry["malicious"].as_u64()? as u32,
+                engines:   entry["engines"].as_u64()? as u32,
+            }, checked)))
+        })
+        .collect()
+}
+
+fn save(path: &Path, verdicts: &HashMap<String, (VtVerdict, DateTime<Utc>)>) -> ::std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let doc: serde_json::Map<String, Value> = verdicts.iter()
+        .map(|(sha256, &(ref verdict, checked))| (sha256.clone(), json!({
+            "malicious": verdict.malicious,
+            "engines":   verdict.engines,
+            "checked":   checked.to_rfc3339(),
+        })))
+        .collect();
+
+    fs::write(path, Value::Object(doc).to_string())
+}
@@ -0,0 +1,59 @@
+// Push detections to an HTTP endpoint (Slack, PagerDuty, SOAR platforms, ...)
+
+use chrono::Local;
+use serde_json::Value;
+
+use report;
+use types::ScanReport;
+
+// POST the detections of `report` to `url` as JSON, one request per detection or a single
+// one holding all of them when `batch` is set. `headers` are "Name: value" strings, e.g.
+// for auth tokens. Returns the number of requests that failed along with the last error.
+pub fn send(url: &str, headers: &[&str], batch: bool, report: &ScanReport) -> Result<(), (usize, String)> {
+    let timestamp = Local::now().to_rfc3339();
+    let host      = report.host.clone().unwrap_or_else(report::hostname);
+
+    let payloads: Vec<Value> = if batch {
+        vec![json!({
+            "timestamp":  timestamp,
+            "host":       host,
+            "scanned":    report.scanned,
+            "summary":    report::summary_json(report),
+            "detections": report.detections.iter().map(report::comparison_json).collect::<Vec<_>>(),
+        })]
+    } else {
+        report.detections.iter().map(|d| json!({
+            "timestamp": timestamp,
+            "host":      host,
+            "detection": report::comparison_json(d),
+        })).collect()
+    };
+
+    let mut failed   = 0;
+    let mut last_err = String::new();
+    for payload in payloads {
+        debug!("POST {}: {}", url, payload);
+        if let Err(why) = post(url, headers, &payload.to_string()) {
+            failed  += 1;
+            last_err = why;
+        }
+    }
+
+    if failed > 0 {
+        Err((failed, last_err))
+    } else {
+        Ok(())
+    }
+}
+
+fn post(url: &str, headers: &[&str], body: &str) -> Result<(), String> {
+    let mut request = ureq::post(url).header("Content-Type", "application/json");
+    for header in headers {
+        match header.find(':') {
+            Some(idx) => request = request.header(header[.. idx].trim(), header[idx + 1 ..].trim()),
+            None      => return Err(format!("invalid header, expected \"Name: value\": {}", header)),
+        }
+    }
+
+    request.send(body).map(|_| ()).map_err(|why| why.to_string())
+}
@@ -0,0 +1,311 @@
+use std::cmp::min;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use glob::{MatchOptions, Pattern};
+use regex::Regex;
+
+use types::{Container, PathMatching};
+
+// Paths compared as written
+const EXACT: PathMatching = PathMatching { ignore_case: false, distance: 0 };
+
+// A whitelisted executable path of a critical process, or an ignored process name.
+// Entries prefixed with `re:` are regular expressions, entries containing
+// one of `*?[` are glob patterns, entries ending with a path separator are trusted
+// directories, any other entry is an exact path.
+// Paths are compared once `.` and `..` are resolved, and match through the symbolic
+// links they lead through on this system too, e.g. `/bin/login` on merged /usr systems.
+// Scans may compare them case insensitively, or with a few edits in their directories,
+// see `matches_with`.
+#[derive(Clone)]
+pub enum Entry {
+    Exact     { path: String, resolved: Option<String> },
+    // Executables directly inside, not in subdirectories
+    Directory { path: String, resolved: Option<String> },
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+impl Entry {
+    pub fn parse(entry: &str) -> Result<Entry, String> {
+        Entry::parse_on(entry, true)
+    }
+
+    // Paths of another file system, e.g. inside containers, aren't resolved
+    fn parse_on(entry: &str, local: bool) -> Result<Entry, String> {
+        let resolved = if local && Path::new(entry).is_absolute() { resolve(entry) } else { None };
+
+        if entry.starts_with("re:") {
+            Regex::new(&entry[3..])
+                .map(Entry::Regex)
+                .map_err(|why| format!("invalid regex {}: {}", entry, why))
+        } else if entry.contains(|c| c == '*' || c == '?' || c == '[') {
+            Pattern::new(entry)
+                .map(Entry::Glob)
+                .map_err(|why| format!("invalid glob {}: {}", entry, why))
+        } else if entry.ends_with('/') || entry.ends_with('\\') {
+            Ok(Entry::Directory {
+                path:     normalize(entry),
+                resolved: resolved.map(|resolved| format!("{}{}", resolved, separator(entry))),
+            })
+        } else {
+            Ok(Entry::Exact { path: normalize(entry), resolved: resolved })
+        }
+    }
+
+    // `proc_path` is expected to be normalized, or resolved for local processes
+    pub fn matches(&self, proc_path: &str) -> bool {
+        self.matches_with(proc_path, EXACT)
+    }
+
+    // Ignoring case, globs match any case too. The file name of exact paths and of files in directories must be
+    // the same, their directories may be `paths.distance` edits away. 8.3 short names such as `PROGRA~1` are only
+    // spelled out for paths of this system, by the file system: anyone may create `C:\PROGRA~7\`.
+    pub fn matches_with(&self, proc_path: &str, paths: PathMatching) -> bool {
+        match *self {
+            Entry::Exact { ref path, ref resolved }     => {
+                same_path(path, proc_path, paths) ||
+                    resolved.as_ref().map_or(false, |resolved| same_path(resolved, proc_path, paths))
+            },
+            Entry::Directory { ref path, ref resolved } => {
+                in_directory(proc_path, path, paths) ||
+                    resolved.as_ref().map_or(false, |resolved| in_directory(proc_path, resolved, paths))
+            },
+            // `*` must not match across directories
+            Entry::Glob(ref pattern)                    => pattern.matches_with(proc_path, MatchOptions {
+                case_sensitive:              !paths.ignore_case,
+                require_literal_separator:   true,
+                require_literal_leading_dot: false,
+            }),
+            Entry::Regex(ref regex)                     => regex.is_match(proc_path),
+        }
+    }
+
+    // The exact path or directory, None for patterns
+    pub fn path(&self) -> Option<&str> {
+        match *self {
+            Entry::Exact { ref path, .. } | Entry::Directory { ref path, .. } => Some(path),
+            _                                                                 => None,
+        }
+    }
+}
+
+// `.` and `..` components resolved and repeated separators collapsed, without looking at the file system.
+// The root, drive or UNC prefix is kept, `..` never climbs above it.
+pub fn normalize(path: &str) -> String {
+    let sep  = separator(path);
+    let lead = path.len() - path.trim_start_matches(sep).len();
+    let mut parts: Vec<&str> = Vec::new();
+    let mut root = 0;
+
+    for part in path.split(sep).filter(|part| !part.is_empty() && *part != ".") {
+        if part != ".." {
+            if parts.is_empty() && part.ends_with(':') {
+                root = 1;
+            }
+            parts.push(part);
+        } else if parts.len() > root {
+            parts.pop();
+        }
+    }
+
+    let mut normalized: String = (0 .. min(lead, 2)).map(|_| sep).collect();
+    normalized.push_str(&parts.join(&sep.to_string()));
+    if path.ends_with(sep) && !parts.is_empty() {
+        normalized.push(sep);
+    }
+
+    normalized
+}
+
+// The path a local file is reached through, symbolic links included, else its normalized form
+pub fn canonical(path: &str) -> String {
+    resolve(path).unwrap_or_else(|| normalize(path))
+}
+
+fn resolve(path: &str) -> Option<String> {
+    let resolved = match fs::canonicalize(path) {
+        Ok(resolved) => resolved.to_string_lossy().into_owned(),
+        // Files that can't be opened may still be there under a short name
+        Err(_)       => return long_name(path).map(|long| normalize(&long)),
+    };
+
+    // Windows returns extended-length paths
+    Some(if resolved.starts_with(r"\\?\UNC\") {
+        format!(r"\\{}", &resolved[8 ..])
+    } else if resolved.starts_with(r"\\?\") {
+        resolved[4 ..].to_string()
+    } else {
+        resolved
+    })
+}
+
+// `PROGRA~1` and the other 8.3 short names of an existing path spelled out
+#[cfg(windows)]
+fn long_name(path: &str) -> Option<String> {
+    use winapi::um::fileapi::GetLongPathNameW;
+
+    if !path.contains('~') {
+        return None;
+    }
+    let wide: Vec<u16> = path.encode_utf16().chain(Some(0)).collect();
+    let mut long       = vec![0u16; 32768];
+    let length = unsafe { GetLongPathNameW(wide.as_ptr(), long.as_mut_ptr(), long.len() as u32) } as usize;
+    if length == 0 || length >= long.len() {
+        return None;
+    }
+
+    Some(String::from_utf16_lossy(&long[.. length]))
+}
+
+#[cfg(unix)]
+fn long_name(_path: &str) -> Option<String> {
+    None
+}
+
+fn separator(path: &str) -> char {
+    if path.contains('\\') { '\\' } else { '/' }
+}
+
+// `path` is a file of `directory`, which ends with a separator
+fn directly_in(path: &str, directory: &str) -> bool {
+    path.len() > directory.len() && path.starts_with(directory) &&
+        !path[directory.len() ..].contains(separator(directory))
+}
+
+// `proc_path` is the whitelisted path, as `paths` compares them
+fn same_path(whitelisted: &str, proc_path: &str, paths: PathMatching) -> bool {
+    if whitelisted == proc_path {
+        return true;
+    }
+    if !paths.ignore_case && paths.distance == 0 {
+        return false;
+    }
+
+    let sep = separator(whitelisted);
+    match (whitelisted.rfind(sep), proc_path.rfind(sep)) {
+        (Some(end), Some(proc_end)) => {
+            same_name(&whitelisted[end + 1 ..], &proc_path[proc_end + 1 ..], paths) &&
+                same_directory(&whitelisted[.. end], &proc_path[.. proc_end], sep, paths)
+        },
+        _                           => false,
+    }
+}
+
+// `path` is a file of `directory`, as `paths` compares them
+fn in_directory(path: &str, directory: &str, paths: PathMatching) -> bool {
+    if directly_in(path, directory) {
+        return true;
+    }
+    if !paths.ignore_case && paths.distance == 0 {
+        return false;
+    }
+
+    let sep = separator(directory);
+    match path.rfind(sep) {
+        Some(end) if end + 1 < path.len() => {
+            same_directory(directory.trim_end_matches(sep), &path[.. end], sep, paths)
+        },
+        _                                 => false,
+    }
+}
+
+// The same directories one by one, those differing at most `paths.distance` edits away altogether
+fn same_directory(whitelisted: &str, directory: &str, sep: char, paths: PathMatching) -> bool {
+    let whitelisted: Vec<&str> = whitelisted.split(sep).collect();
+    let directory  : Vec<&str> = directory.split(sep).collect();
+    if whitelisted.len() != directory.len() {
+        return false;
+    }
+
+    let mut edits = 0;
+    for (expected, found) in whitelisted.iter().zip(directory.iter()) {
+        if !same_name(expected, found, paths) {
+            edits += if paths.ignore_case {
+                ::strsim::damerau_levenshtein(&expected.to_lowercase(), &found.to_lowercase())
+            } else {
+                ::strsim::damerau_levenshtein(expected, found)
+            };
+            if edits > paths.distance as usize {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+fn same_name(expected: &str, found: &str, paths: PathMatching) -> bool {
+    expected == found || (paths.ignore_case && expected.to_lowercase() == found.to_lowercase())
+}
+
+// As written in the critical processes file
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Entry::Exact { ref path, .. } | Entry::Directory { ref path, .. } => write!(f, "{}", path),
+            Entry::Glob(ref pattern)                                          => write!(f, "{}", pattern.as_str()),
+            Entry::Regex(ref regex)                                           => write!(f, "re:{}", regex.as_str()),
+        }
+    }
+}
+
+// A whitelisted executable path only trusted inside containers: `container:PATH` for any
+// container, `container=SCOPE:PATH` for containers whose ID, short ID or `NAMESPACE/POD`
+// matches SCOPE, itself an exact name, glob or `re:` regular expression
+#[derive(Clone)]
+pub struct ContainerEntry {
+    scope: Option<Entry>,
+    path : Entry,
+}
+
+impl ContainerEntry {
+    // None when `entry` isn't container-scoped
+    pub fn parse(entry: &str) -> Option<Result<ContainerEntry, String>> {
+        let (scope, path) = if entry.starts_with("container:") {
+            (None, &entry[10..])
+        } else if entry.starts_with("container=") {
+            match entry[10..].find(':') {
+                Some(pos) => (Some(&entry[10 .. 10 + pos]), &entry[11 + pos..]),
+                None      => return Some(Err(format!("missing path in {}", entry))),
+            }
+        } else {
+            return None;
+        };
+
+        Some(Entry::parse_on(path, false).and_then(|path| {
+            let scope = match scope {
+                Some(scope) => Some(Entry::parse(scope)?),
+                None        => None,
+            };
+            Ok(ContainerEntry { scope: scope, path: path })
+        }))
+    }
+
+    pub fn matches(&self, proc_path: &str, container: &Container) -> bool {
+        let in_scope = match self.scope {
+            Some(ref scope) => {
+                let pod = match (&container.namespace, &container.pod) {
+                    (&Some(ref namespace), &Some(ref pod)) => Some(format!("{}/{}", namespace, pod)),
+                    _                                      => None,
+                };
+                scope.matches(&container.id) || scope.matches(container.short_id()) ||
+                    pod.map_or(false, |pod| scope.matches(&pod))
+            },
+            None            => true,
+        };
+
+        in_scope && self.path.matches(proc_path)
+    }
+}
+
+impl fmt::Display for ContainerEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.scope {
+            Some(ref scope) => write!(f, "container={}:{}", scope, self.path),
+            None            => write!(f, "container:{}", self.path),
+        }
+    }
+}
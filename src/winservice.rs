@@ -0,0 +1,261 @@
+// Running as a native Windows service, detections are written to the Application event log
+
+use std::cmp::min;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::iter::once;
+use std::os::windows::ffi::OsStrExt;
+use std::ptr;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use chrono::Local;
+
+use winapi::shared::minwindef::{BOOL, DWORD, LPVOID, WORD};
+use winapi::um::winnt::{HANDLE, LPCWSTR, PSID};
+
+use windows_service::service::{ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl,
+                               ServiceExitCode, ServiceInfo, ServiceStartType, ServiceState, ServiceStatus,
+                               ServiceType};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_dispatcher;
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+
+use error::ScanError;
+use output::Output;
+use types::Comparison;
+
+pub const SERVICE_NAME: &'static str = "bonomen";
+const DISPLAY_NAME: &'static str     = "BONOMEN critical process impersonation monitor";
+const EVENT_SOURCE: &'static str     = "BONOMEN";
+
+const EVENTLOG_ERROR_TYPE: WORD       = 0x0001;
+const EVENTLOG_WARNING_TYPE: WORD     = 0x0002;
+const EVENTLOG_INFORMATION_TYPE: WORD = 0x0004;
+const EVENT_ID: DWORD                 = 1;
+
+#[link(name = "advapi32")]
+extern "system" {
+    fn RegisterEventSourceW(lpUNCServerName: LPCWSTR, lpSourceName: LPCWSTR) -> HANDLE;
+    fn ReportEventW(hEventLog: HANDLE, wType: WORD, wCategory: WORD, dwEventID: DWORD, lpUserSid: PSID,
+                    wNumStrings: WORD, dwDataSize: DWORD, lpStrings: *mut LPCWSTR, lpRawData: LPVOID) -> BOOL;
+    fn DeregisterEventSource(hEventLog: HANDLE) -> BOOL;
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn wide(s: &str) -> Vec<u16> {
+    OsStr::new(s).encode_wide().chain(once(0)).collect()
+}
+
+fn service_error(what: &str, why: ::windows_service::Error) -> ScanError {
+    ScanError::Config(format!("couldn't {} the {} service: {}", what, SERVICE_NAME, why))
+}
+
+// Register the service, started at boot with `service run` and the given arguments
+pub fn install(run_args: Vec<OsString>) -> Result<(), ScanError> {
+    let exe = match env::current_exe() {
+        Ok(exe)  => exe,
+        Err(why) => return Err(ScanError::Config(format!("couldn't locate the bonomen executable: {}", why))),
+    };
+
+    let manager = ServiceManager::local_computer(None::<&str>,
+                                                 ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)
+        .map_err(|why| service_error("install", why))?;
+
+    let mut launch_arguments = vec![OsString::from("service"), OsString::from("run")];
+    launch_arguments.extend(run_args);
+
+    let info = ServiceInfo {
+        name:             OsString::from(SERVICE_NAME),
+        display_name:     OsString::from(DISPLAY_NAME),
+        service_type:     ServiceType::OWN_PROCESS,
+        start_type:       ServiceStartType::AutoStart,
+        error_control:    ServiceErrorControl::Normal,
+        executable_path:  exe,
+        launch_arguments: launch_arguments,
+        dependencies:     vec![],
+        account_name:     None,
+        account_password: None,
+    };
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .map_err(|why| service_error("install", why))?;
+    service.set_description("Scans running processes for impersonations of critical system processes")
+        .map_err(|why| service_error("install", why))
+}
+
+pub fn uninstall() -> Result<(), ScanError> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(|why| service_error("uninstall", why))?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::DELETE)
+        .map_err(|why| service_error("uninstall", why))?;
+
+    service.delete().map_err(|why| service_error("uninstall", why))
+}
+
+pub fn start() -> Result<(), ScanError> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(|why| service_error("start", why))?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::START)
+        .map_err(|why| service_error("start", why))?;
+
+    service.start(&[] as &[&OsStr]).map_err(|why| service_error("start", why))
+}
+
+pub fn stop() -> Result<(), ScanError> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .map_err(|why| service_error("stop", why))?;
+    let service = manager.open_service(SERVICE_NAME, ServiceAccess::STOP)
+        .map_err(|why| service_error("stop", why))?;
+
+    service.stop().map(|_| ()).map_err(|why| service_error("stop", why))
+}
+
+// Hand the process over to the service control manager, returns once the service stopped.
+// Fails when not started by the service control manager.
+pub fn run() -> Result<(), ScanError> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+        .map_err(|why| service_error("run", why))
+}
+
+// The arguments given to `sc start` are ignored, the service is configured through
+// the command line registered by `install`
+fn service_main(_arguments: Vec<OsString>) {
+    let (shutdown_tx, shutdown_rx) = mpsc::channel();
+    let handler = move |control| match control {
+        ServiceControl::Stop        => {
+            let _ = shutdown_tx.send(());
+            ServiceControlHandlerResult::NoError
+        },
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _                           => ServiceControlHandlerResult::NotImplemented,
+    };
+    let status_handle = match service_control_handler::register(SERVICE_NAME, handler) {
+        Ok(handle) => handle,
+        Err(_)     => return,
+    };
+
+    let status = |state, exit_code| ServiceStatus {
+        service_type:      ServiceType::OWN_PROCESS,
+        current_state:     state,
+        controls_accepted: if state == ServiceState::Running { ServiceControlAccept::STOP } else { ServiceControlAccept::empty() },
+        exit_code:         ServiceExitCode::Win32(exit_code),
+        checkpoint:        0,
+        wait_hint:         Duration::default(),
+        process_id:        None,
+    };
+    let _ = status_handle.set_service_status(status(ServiceState::Running, 0));
+
+    let exit_code = match monitor(&shutdown_rx) {
+        Ok(())   => 0,
+        Err(why) => {
+            report_event(EVENTLOG_ERROR_TYPE, &why.to_string());
+            why.exit_code() as DWORD
+        },
+    };
+
+    let _ = status_handle.set_service_status(status(ServiceState::Stopped, exit_code));
+}
+
+// Scan every `--interval` seconds until the service is stopped
+fn monitor(shutdown: &mpsc::Receiver<()>) -> Result<(), ScanError> {
+    let matches = ::cli::build("").get_matches_from_safe(env::args_os())
+        .map_err(|why| ScanError::Config(why.message))?;
+    let matches = match matches.subcommand_matches("service").and_then(|m| m.subcommand_matches("run")) {
+        Some(matches) => matches.clone(),
+        None          => return Err(ScanError::Config(String::from("not started with `service run`"))),
+    };
+
+    ::setup_threads(&matches)?;
+    let interval = match matches.value_of("interval").unwrap_or("60").parse::<u64>() {
+        Ok(interval) => Duration::from_secs(interval),
+        Err(_)       => return Err(ScanError::Config(String::from("invalid interval"))),
+    };
+
+    // Nobody reads the output of a service
+    let mut terminal = Output::new(false);
+    report_event(EVENTLOG_INFORMATION_TYPE, "BONOMEN service started");
+
+    let files: Vec<&str> = matches.values_of("file").map(|v| v.collect()).unwrap_or_default();
+    let mut config = ::reload::Reloader::load(&files, matches.value_of("profile"))?;
+    let status     = Arc::new(Mutex::new(::control::Status::new(config.source().to_string())));
+    if let Err(why) = ::control::serve(::control::DEFAULT_PATH, status.clone()) {
+        report_event(EVENTLOG_WARNING_TYPE, &format!("`bonomen status` won't reach the service: {}", why));
+    }
+
+    let mut alerted = ::dedup::Alerted::new(::realert_after(&matches)?);
+    let distances   = Arc::new(::distcache::DistanceCache::new());
+    'scans: loop {
+        let r = ::scan_once(&matches, &mut terminal, Some(&alerted), Some(&distances), Some(config.current()))?;
+        alerted.update(&r);
+        if let Ok(mut status) = status.lock() {
+            status.record(&r, Local::now() + ::chrono::Duration::seconds(interval.as_secs() as i64));
+        }
+        for detection in &r.detections {
+            report_event(EVENTLOG_WARNING_TYPE, &describe(detection));
+        }
+
+        // Scan right away once the configuration changed
+        let deadline = Instant::now() + interval;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            match shutdown.recv_timeout(min(deadline - now, ::reload::POLL)) {
+                Err(mpsc::RecvTimeoutError::Timeout) => (),
+                _                                    => break 'scans,
+            }
+            if config.reload_if_changed() {
+                if let Ok(mut status) = status.lock() {
+                    status.set_config(config.source().to_string());
+                }
+                break;
+            }
+        }
+    }
+
+    report_event(EVENTLOG_INFORMATION_TYPE, "BONOMEN service stopped");
+
+    Ok(())
+}
+
+fn describe(detection: &Comparison) -> String {
+    let mut message = format!("Suspicious process {} (PID {}) running from {}\n\
+                               Impersonates: {}\nReason: {}\nSeverity: {}\nDistance: {}",
+                              detection.name, detection.pid, detection.exe_path, detection.crit_name,
+                              detection.reason, detection.severity.name(), detection.distance);
+    if !detection.module.is_empty() {
+        message.push_str(&format!("\nModule: {}", detection.module));
+    }
+    if let Some(ref exe_info) = detection.exe_info {
+        message.push_str(&format!("\nExecutable: {}", exe_info));
+    }
+    if detection.suspended {
+        message.push_str("\nSuspended: yes");
+    }
+    for connection in &detection.connections {
+        message.push_str(&format!("\nConnection: {}", connection));
+    }
+
+    message
+}
+
+// The source isn't registered with a message file, Event Viewer shows the message
+// as the event's insertion string
+fn report_event(event_type: WORD, message: &str) {
+    let source  = wide(EVENT_SOURCE);
+    let message = wide(message);
+
+    unsafe {
+        let log = RegisterEventSourceW(ptr::null(), source.as_ptr());
+        if log.is_null() {
+            return;
+        }
+
+        let mut strings = [message.as_ptr()];
+        ReportEventW(log, event_type, 0, EVENT_ID, ptr::null_mut(),
+                     1, 0, strings.as_mut_ptr(), ptr::null_mut());
+        DeregisterEventSource(log);
+    }
+}
@@ -0,0 +1,44 @@
+// YARA rules matched against the executables of detections with `--yara`, so that a suspicious name comes
+// with on-disk signature hits in the same pass. Built with the `yara` feature, which links libyara.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use yara::{Compiler, Rules};
+
+// Seconds libyara may spend on a file
+const SCAN_TIMEOUT_SECS: i32 = 10;
+
+// Compile the rules of `path`, a rules file or a directory of .yar and .yara files
+pub fn load(path: &str) -> Result<Rules, String> {
+    let files: Vec<PathBuf> = if Path::new(path).is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(path)
+            .map_err(|why| format!("couldn't read the YARA rules directory {}: {}", path, why))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.extension().map_or(false, |ext| ext == "yar" || ext == "yara"))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![PathBuf::from(path)]
+    };
+    if files.is_empty() {
+        return Err(format!("no .yar or .yara rules in {}", path));
+    }
+
+    let mut compiler = Compiler::new().map_err(|why| format!("couldn't set up YARA: {}", why))?;
+    for file in &files {
+        compiler = compiler.add_rules_file(file)
+            .map_err(|why| format!("invalid YARA rules in {}: {}", file.display(), why))?;
+    }
+    info!("YARA rules compiled from {} files", files.len());
+
+    compiler.compile_rules().map_err(|why| format!("couldn't compile the YARA rules: {}", why))
+}
+
+// Names of the rules matching the file at `path`
+pub fn matches(rules: &Rules, path: &str) -> Result<Vec<String>, String> {
+    rules.scan_file(path, SCAN_TIMEOUT_SECS)
+        .map(|matched| matched.iter().map(|rule| rule.identifier.to_string()).collect())
+        .map_err(|why| why.to_string())
+}
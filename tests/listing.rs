@@ -0,0 +1,204 @@
+// Detections on the process listings of tests/fixtures, checked the way --input-procs scans them so that
+// the detection logic runs without the processes of the machine the tests run on.
+
+extern crate serde_json;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+use serde_json::Value;
+
+fn fixture(name: &str) -> String {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name).to_string_lossy().into_owned()
+}
+
+// Exit code and JSON report of a scan of `listing` against the critical processes fixture
+fn scan(listing: &str, extra: &[&str]) -> (i32, Value) {
+    let report = env::temp_dir().join(format!("bonomen-test-{}-{}.json", listing, process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_bonomen"))
+        .args(["scan", "-q", "--no-color", "-f", &fixture("critical.txt"), "--input-procs", &fixture(listing)])
+        .arg("--report").arg(&report)
+        .args(extra)
+        .output()
+        .expect("couldn't run bonomen");
+    let doc = serde_json::from_str(&fs::read_to_string(&report).expect("no report written")).expect("invalid report");
+    let _ = fs::remove_file(&report);
+
+    (output.status.code().unwrap_or(-1), doc)
+}
+
+fn detections(doc: &Value) -> Vec<(u64, String, String)> {
+    doc["detections"].as_array().expect("no detections in the report").iter()
+        .map(|c| (c["pid"].as_u64().unwrap_or(0),
+                  c["name"].as_str().unwrap_or("").to_string(),
+                  c["reason"].as_str().unwrap_or("").to_string()))
+        .collect()
+}
+
+#[test]
+fn flags_wrong_path_and_lookalike_name() {
+    let (code, doc) = scan("masquerade.txt", &[]);
+
+    assert_eq!(code, 1);
+    assert_eq!(doc["scanned"], 5);
+    assert_eq!(detections(&doc), vec![
+        (4242, String::from("sshd"),  String::from("wrong-path")),
+        (4242, String::from("sshd"),  String::from("temp-path")),
+        (4243, String::from("sshdd"), String::from("distance")),
+    ]);
+}
+
+#[test]
+fn clean_listing_has_no_detections() {
+    let (code, doc) = scan("clean.json", &[]);
+
+    assert_eq!(code, 0);
+    assert_eq!(doc["scanned"], 4);
+    assert!(detections(&doc).is_empty());
+}
+
+#[test]
+fn match_limits_the_checked_processes() {
+    let (_, doc) = scan("masquerade.txt", &["--match", "sshd"]);
+
+    assert_eq!(detections(&doc), vec![
+        (4242, String::from("sshd"), String::from("wrong-path")),
+        (4242, String::from("sshd"), String::from("temp-path")),
+    ]);
+}
+
+#[test]
+fn flags_critical_process_running_as_another_user() {
+    let (_, doc) = scan("owners.json", &[]);
+
+    assert_eq!(detections(&doc), vec![(5150, String::from("cron"), String::from("wrong-user"))]);
+    assert_eq!(doc["detections"][0]["owner"], "www-data");
+}
+
+#[test]
+fn flags_thread_and_handle_counts_outside_their_range() {
+    let (_, doc) = scan("counts.json", &[]);
+
+    assert_eq!(detections(&doc), vec![
+        (5150, String::from("cron"), String::from("handle-count")),
+        (5151, String::from("cron"), String::from("thread-count")),
+    ]);
+}
+
+#[test]
+fn refuses_critical_processes_file_modified_since_signed() {
+    let private_key = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+    let public_key  = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+    let file = env::temp_dir().join(format!("bonomen-test-signed-{}.txt", process::id()));
+    fs::copy(fixture("critical.txt"), &file).expect("couldn't copy the critical processes fixture");
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_bonomen")).args(args).output().expect("couldn't run bonomen").status.code()
+    };
+    let file_arg = file.to_string_lossy().into_owned();
+    let scan = ["scan", "-q", "-f", &file_arg, "--input-procs", &fixture("clean.json"), "--config-key", public_key];
+
+    assert_eq!(run(&["config", "sign", &file_arg, "-k", private_key]), Some(0));
+    assert_eq!(run(&scan), Some(0));
+    fs::write(&file, "ignore = sshdd\n").expect("couldn't modify the critical processes file");
+    assert_eq!(run(&scan), Some(2));
+
+    let _ = fs::remove_file(&file);
+    let _ = fs::remove_file(file.with_extension("txt.sig"));
+}
+
+#[test]
+fn template_formats_detection_lines() {
+    let output = Command::new(env!("CARGO_BIN_EXE_bonomen"))
+        .args(["scan", "-q", "-f", &fixture("critical.txt"), "--input-procs", &fixture("masquerade.txt")])
+        .args(["--template", "{pid}\\t{name} matched {crit} ({reason})"])
+        .output()
+        .expect("couldn't run bonomen");
+
+    assert_eq!(String::from_utf8_lossy(&output.stdout),
+               "4242\tsshd matched sshd (wrong-path)\n4242\tsshd matched sshd (temp-path)\n\
+                4243\tsshdd matched sshd (distance)\n");
+}
+
+#[test]
+fn normalizes_unicode_names_and_flags_invisible_characters() {
+    let (_, doc) = scan("unicode.json", &[]);
+
+    assert_eq!(detections(&doc).into_iter().map(|(pid, _, reason)| (pid, reason)).collect::<Vec<_>>(), vec![
+        (6001, String::from("invisible-chars")),
+        (6002, String::from("homoglyph")),
+        (6003, String::from("homoglyph")),
+        (6004, String::from("homoglyph")),
+    ]);
+}
+
+#[test]
+fn flags_critical_names_running_from_temporary_or_removable_locations() {
+    let (_, doc) = scan("staging.json", &[]);
+
+    assert_eq!(detections(&doc).into_iter().filter(|&(_, _, ref reason)| reason.ends_with("-path"))
+                   .collect::<Vec<_>>(), vec![
+        (7001, String::from("crond"), String::from("temp-path")),
+        (7002, String::from("sshd"),  String::from("wrong-path")),
+        (7002, String::from("sshd"),  String::from("removable-path")),
+        (7003, String::from("cron"),  String::from("wrong-path")),
+        (7003, String::from("cron"),  String::from("temp-path")),
+    ]);
+    // High, whatever the distance of crond alone would give
+    let crond = doc["detections"].as_array().unwrap().iter().find(|c| c["reason"] == "temp-path")
+        .expect("no temp-path detection");
+    assert_eq!(crond["severity"], "high");
+}
+
+#[cfg(unix)]
+#[test]
+fn api_scans_the_processes_requested() {
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use std::time::Duration;
+
+    let socket  = env::temp_dir().join(format!("bonomen-test-api-{}.sock", process::id()));
+    let control = env::temp_dir().join(format!("bonomen-test-control-{}.sock", process::id()));
+    let mut monitor = Command::new(env!("CARGO_BIN_EXE_bonomen"))
+        .args(["monitor", "-q", "-f", &fixture("critical.txt"), "--input-procs", &fixture("masquerade.txt")])
+        .arg("--api").arg(&socket)
+        .arg("--control").arg(&control)
+        .stdout(process::Stdio::null())
+        .spawn()
+        .expect("couldn't run bonomen");
+    let mut stream = (0 .. 50).filter_map(|_| {
+        thread::sleep(Duration::from_millis(100));
+        UnixStream::connect(&socket).ok()
+    }).next().expect("the monitor doesn't listen for API requests");
+
+    stream.write_all(b"{\"request\": \"scan\", \"pids\": [4243]}\n").expect("couldn't send the request");
+    let mut answer = String::new();
+    BufReader::new(&stream).read_line(&mut answer).expect("no answer");
+    let _ = monitor.kill();
+    let _ = monitor.wait();
+    let _ = fs::remove_file(&socket);
+    let _ = fs::remove_file(&control);
+
+    // The first scan of the monitor, run before any request, already alerted on it
+    let doc: Value = serde_json::from_str(&answer).expect("invalid answer");
+    assert_eq!(doc["scanned"], 1);
+    assert!(detections(&doc).is_empty());
+    assert_eq!(doc["repeated"][0]["pid"], 4243);
+    assert_eq!(doc["repeated"][0]["reason"], "distance");
+}
+
+#[test]
+fn matches_whitelisted_paths_ignoring_case_and_directory_edits() {
+    let (_, doc) = scan("windows.json", &["-f", &fixture("windows.txt")]);
+
+    // 8003 is a directory edit away, the file name of 8005 never is. Short names of listings aren't taken for
+    // the directories they look like: 8006 and 8007 may run from anywhere.
+    assert_eq!(detections(&doc), vec![
+        (8004, String::from("svchost.exe"), String::from("wrong-path")),
+        (8005, String::from("svch0st.exe"), String::from("distance")),
+        (8006, String::from("MsMpEng.exe"), String::from("wrong-path")),
+        (8007, String::from("svchost.exe"), String::from("wrong-path")),
+    ]);
+}